@@ -1,7 +1,9 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::json;
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 
@@ -12,6 +14,9 @@ use tokio::io::AsyncWriteExt;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Print progress and results as JSON lines on stdout, for scripts and CI; implies --yes
+    #[arg(long, global = true)]
+    json: bool,
 }
 
 #[derive(Subcommand)]
@@ -29,6 +34,12 @@ enum Commands {
         /// Skip confirmation prompts
         #[arg(short, long)]
         yes: bool,
+        /// Only fetch this quantization's weights, e.g. q4k (configs and shared weights are always fetched)
+        #[arg(long, conflicts_with = "files")]
+        quant: Option<String>,
+        /// Only fetch these weights files, comma-separated (configs and shared weights are always fetched)
+        #[arg(long, value_delimiter = ',')]
+        files: Vec<String>,
     },
     /// Download all available models
     DownloadAll {
@@ -38,6 +49,9 @@ enum Commands {
         /// Skip confirmation prompts
         #[arg(short, long)]
         yes: bool,
+        /// Only fetch this quantization's weights for models that offer it
+        #[arg(long)]
+        quant: Option<String>,
     },
     /// Show model information
     Info {
@@ -45,6 +59,12 @@ enum Commands {
         #[arg(value_enum)]
         model: ModelChoice,
     },
+    /// Re-check downloaded files, e.g. after restoring them from antivirus quarantine
+    Verify {
+        /// Model to verify
+        #[arg(value_enum)]
+        model: ModelChoice,
+    },
 }
 
 // Available AI models for the mistral.rs Tauri demo
@@ -69,6 +89,12 @@ struct ModelInfo {
     directory: &'static str,
     format: &'static str,
     size_estimate: &'static str,
+    license: &'static str,
+    // Requires accepting the license terms on Hugging Face before files can be fetched
+    gated: bool,
+    // Base model to take tokenizer_config.json (and so the chat template) from when `repo` only
+    // has weights; a failure here doesn't fail the download
+    chat_template_repo: Option<&'static str>,
 }
 
 struct ModelFile {
@@ -80,13 +106,102 @@ struct ModelFile {
 
 const BASE_DIR: &str = "src-tauri/models";
 
+// Which weights files to fetch; all of a model's quantizations when both are empty
+struct Selection {
+    quant: Option<String>,
+    files: Vec<String>,
+}
+
+// The app reads the chat template from this file when it sits beside the model
+const CHAT_TEMPLATE_FILE: &str = "tokenizer_config.json";
+
+// Sizes of completed downloads, kept in each model directory for `verify`
+const MANIFEST_FILE: &str = ".download_manifest.json";
+
+// Expected SHA-256 of each downloaded weights file, from Hugging Face's LFS metadata. The app
+// reads this file too, to check models in discover_models.
+const CHECKSUM_FILE: &str = ".download_checksums.json";
+
+// Read size when hashing files
+const HASH_BUFFER_BYTES: usize = 8 * 1024 * 1024;
+
+// How often the partially written file is checked for disappearance
+const PRESENCE_CHECK_INTERVAL: u64 = 256 * 1024 * 1024;
+
+// Downloads are written to "<file>.part" and renamed once complete; a leftover one is resumed
+const PARTIAL_SUFFIX: &str = ".part";
+
+// Tries per file; the wait between tries doubles from RETRY_BASE_DELAY
+const MAX_ATTEMPTS: u32 = 5;
+const RETRY_BASE_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+
+// Windows error codes antivirus software produces when it blocks or removes a file
+const ERROR_SHARING_VIOLATION: i32 = 32;
+const ERROR_LOCK_VIOLATION: i32 = 33;
+const ERROR_VIRUS_INFECTED: i32 = 225;
+const ERROR_VIRUS_DELETED: i32 = 226;
+
+#[derive(Debug, thiserror::Error)]
+enum IntegrityError {
+    // The file vanished, got locked or became unreadable while we owned it
+    #[error("QuarantineSuspected: {path} {reason}")]
+    QuarantineSuspected { path: String, reason: String },
+    #[error("{path} is {actual} bytes but {expected} bytes were downloaded")]
+    SizeMismatch { path: String, expected: u64, actual: u64 },
+    #[error("{path} has SHA-256 {actual}, but Hugging Face lists {expected}")]
+    ChecksumMismatch { path: String, expected: String, actual: String },
+}
+
+// A failure that another attempt might get past
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct TransientError(String);
+
+// Set by --json: human-readable output moves to stderr and stdout carries one JSON object per line
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+// Human-readable output, kept off stdout in JSON mode
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if json_output() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+// A machine-readable event on stdout; nothing without --json
+fn emit(event: &str, fields: serde_json::Value) {
+    if !json_output() {
+        return;
+    }
+    let mut line = json!({ "event": event });
+    if let (Some(line), serde_json::Value::Object(fields)) = (line.as_object_mut(), fields) {
+        line.extend(fields);
+    }
+    println!("{}", line);
+}
+
+// The name a model has on the command line, e.g. "mistral-gguf"
+fn choice_name(choice: &ModelChoice) -> String {
+    choice
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_else(|| format!("{:?}", choice))
+}
+
 // Copy .env file for HuggingFace token access
 async fn copy_env_file() -> Result<()> {
     let _ = fs::remove_file(".env").await;
     
     if Path::new("../.env").exists() {
         fs::copy("../.env", ".env").await?;
-        println!("📄 Copied .env file from parent directory");
+        say!("📄 Copied .env file from parent directory");
     }
     
     Ok(())
@@ -99,34 +214,53 @@ async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
     
     let cli = Cli::parse();
+    JSON_OUTPUT.store(cli.json, Ordering::Relaxed);
     let models = get_model_info();
 
-    match cli.command {
+    // Scripts can't answer prompts, so JSON mode never asks
+    let result = match cli.command {
         Commands::List => {
             print_header();
             list_models(&models);
+            Ok(())
         }
-        Commands::Download { model, force, yes } => {
+        Commands::Download { model, force, yes, quant, files } => {
             print_header();
-            download_model(&models, &model, force, yes).await?;
+            let selection = Selection { quant, files };
+            download_model(&models, &model, &selection, force, yes || cli.json).await
         }
-        Commands::DownloadAll { force, yes } => {
+        Commands::DownloadAll { force, yes, quant } => {
             print_header();
-            download_all_models(&models, force, yes).await?;
+            let selection = Selection { quant, files: Vec::new() };
+            download_all_models(&models, &selection, force, yes || cli.json).await
         }
         Commands::Info { model } => {
             print_header();
             show_model_info(&models, &model);
+            Ok(())
         }
-    }
+        Commands::Verify { model } => {
+            print_header();
+            verify_model(&models, &model).await
+        }
+    };
 
-    Ok(())
+    if let Err(e) = &result {
+        let kind = match e.downcast_ref::<IntegrityError>() {
+            Some(IntegrityError::QuarantineSuspected { .. }) => "quarantine_suspected",
+            Some(IntegrityError::SizeMismatch { .. }) => "size_mismatch",
+            Some(IntegrityError::ChecksumMismatch { .. }) => "checksum_mismatch",
+            None => "error",
+        };
+        emit("error", json!({ "kind": kind, "message": e.to_string() }));
+    }
+    result
 }
 
 fn print_header() {
-    println!("🦀 Tauri Mistral Chat - AI Model Downloader");
-    println!("═══════════════════════════════════════════");
-    println!();
+    say!("🦀 Tauri Mistral Chat - AI Model Downloader");
+    say!("═══════════════════════════════════════════");
+    say!();
 }
 
 // Defines available AI models with download information
@@ -142,6 +276,9 @@ fn get_model_info() -> HashMap<ModelChoice, ModelInfo> {
             directory: "mistral-gguf",
             format: "GGUF",
             size_estimate: "~4.4GB",
+            license: "apache-2.0",
+            gated: false,
+            chat_template_repo: Some("mistralai/Mistral-7B-Instruct-v0.1"),
             files: vec![
                 ModelFile {
                     filename: "mistral-7b-instruct-v0.1.Q4_K_M.gguf",
@@ -162,6 +299,9 @@ fn get_model_info() -> HashMap<ModelChoice, ModelInfo> {
             directory: "llama-vision",
             format: "UQFF",
             size_estimate: "12-17GB",
+            license: "llama3.2",
+            gated: false,
+            chat_template_repo: None,
             files: vec![
                 // Configuration files required for UQFF models
                 ModelFile {
@@ -235,6 +375,9 @@ fn get_model_info() -> HashMap<ModelChoice, ModelInfo> {
             directory: "gemma-3n-e2b",
             format: "UQFF",
             size_estimate: "~8GB",
+            license: "gemma",
+            gated: true,
+            chat_template_repo: None,
             files: vec![
                 ModelFile {
                     filename: "config.json",
@@ -297,6 +440,9 @@ fn get_model_info() -> HashMap<ModelChoice, ModelInfo> {
             directory: "smollm3-3b",
             format: "UQFF",
             size_estimate: "~1-3GB",
+            license: "apache-2.0",
+            gated: false,
+            chat_template_repo: None,
             files: vec![
                 ModelFile {
                     filename: "config.json",
@@ -348,108 +494,243 @@ fn get_model_info() -> HashMap<ModelChoice, ModelInfo> {
 }
 
 fn list_models(models: &HashMap<ModelChoice, ModelInfo>) {
-    println!("📋 Available Models:");
-    println!();
+    say!("📋 Available Models:");
+    say!();
 
     for (choice, info) in models {
-        let status_icon = if model_exists(info) { "✅" } else { "⬜" };
-        println!("  {} {:?}", status_icon, choice);
-        println!("     📝 {}", info.name);
-        println!("     📄 {}", info.description);
-        println!("     📊 Format: {} | Size: {}", info.format, info.size_estimate);
-        println!("     🔗 {}", info.repo);
-        println!();
+        emit("model", model_json(choice, info));
+        let installed = installed_quantizations(info);
+        // 🟨 marks a model with only some of its quantizations downloaded
+        let status_icon = if !model_exists(info) {
+            "⬜"
+        } else if installed.len() < quantizations(info).len() {
+            "🟨"
+        } else {
+            "✅"
+        };
+        say!("  {} {:?}", status_icon, choice);
+        say!("     📝 {}", info.name);
+        say!("     📄 {}", info.description);
+        say!("     📊 Format: {} | Size: {} | License: {}", info.format, info.size_estimate, info.license);
+        if quantizations(info).len() > 1 {
+            say!("     🎚️  Quantizations: {}", quantizations(info).join(", "));
+            if !installed.is_empty() {
+                say!("     📥 Downloaded: {}", installed.join(", "));
+            }
+        }
+        say!("     🔗 {}", info.repo);
+        if info.gated {
+            say!("     🔒 Gated: accept the terms on Hugging Face and set HF_TOKEN first");
+        }
+        say!();
     }
 
-    println!("💡 Usage examples:");
-    println!("   cargo run --example download_models download mistral-gguf");
-    println!("   cargo run --example download_models download gemma3n-e2b");
-    println!("   cargo run --example download_models info llama-vision");
-    println!("   cargo run --example download_models download-all");
+    say!("💡 Usage examples:");
+    say!("   cargo run --example download_models download mistral-gguf");
+    say!("   cargo run --example download_models download gemma3n-e2b");
+    say!("   cargo run --example download_models download llama-vision --quant q4k");
+    say!("   cargo run --example download_models info llama-vision");
+    say!("   cargo run --example download_models download-all");
+}
+
+// A model's catalog entry and whether it is downloaded, as emitted by `list` and `info`
+fn model_json(choice: &ModelChoice, info: &ModelInfo) -> serde_json::Value {
+    json!({
+        "model": choice_name(choice),
+        "name": info.name,
+        "format": info.format,
+        "size_estimate": info.size_estimate,
+        "license": info.license,
+        "gated": info.gated,
+        "repo": info.repo,
+        "directory": Path::new(BASE_DIR).join(info.directory),
+        "downloaded": model_exists(info),
+        "quantizations": quantizations(info),
+        "installed_quantizations": installed_quantizations(info),
+    })
 }
 
 fn show_model_info(models: &HashMap<ModelChoice, ModelInfo>, choice: &ModelChoice) {
     if let Some(info) = models.get(choice) {
-        println!("📋 Model Information: {:?}", choice);
-        println!();
-        println!("  📝 Name: {}", info.name);
-        println!("  📄 Description: {}", info.description);
-        println!("  🔗 Repository: {}", info.repo);
-        println!("  📊 Format: {}", info.format);
-        println!("  💾 Estimated Size: {}", info.size_estimate);
-        println!("  📁 Local Directory: {}/{}", BASE_DIR, info.directory);
-        println!();
+        let mut model = model_json(choice, info);
+        model["files"] = info
+            .files
+            .iter()
+            .map(|file| json!({ "filename": file.filename, "size": file.size, "url": file.url }))
+            .collect();
+        emit("model", model);
+        say!("📋 Model Information: {:?}", choice);
+        say!();
+        say!("  📝 Name: {}", info.name);
+        say!("  📄 Description: {}", info.description);
+        say!("  🔗 Repository: {}", info.repo);
+        say!("  📊 Format: {}", info.format);
+        say!("  💾 Estimated Size: {}", info.size_estimate);
+        say!("  ⚖️  License: {}", info.license);
+        say!("  🔒 Gated: {}", if info.gated { "yes (requires accepting terms and an HF token)" } else { "no" });
+        say!("  📁 Local Directory: {}/{}", BASE_DIR, info.directory);
+        say!();
         
         let status = if model_exists(info) { "✅ Downloaded" } else { "⬜ Not Downloaded" };
-        println!("  Status: {}", status);
-        println!();
+        say!("  Status: {}", status);
+        let installed = installed_quantizations(info);
+        if !installed.is_empty() {
+            say!("  Quantizations downloaded: {} of {}", installed.join(", "), quantizations(info).join(", "));
+        }
+        say!();
         
-        println!("  📦 Files to download:");
+        say!("  📦 Files to download:");
         for file in &info.files {
-            println!("     • {} ({})", file.filename, file.size);
-            println!("       {}", file.description);
+            say!("     • {} ({})", file.filename, file.size);
+            say!("       {}", file.description);
+        }
+        if let Some(repo) = info.chat_template_repo {
+            say!("     • {} (chat template from {})", CHAT_TEMPLATE_FILE, repo);
         }
     }
 }
 
-// Check if all required model files are already downloaded
+// Quantization a weights file holds, e.g. "q4k" for "smollm33b-q4k-0.uqff"; None for files every
+// quantization needs (configs, tokenizer, residual weights)
+fn file_quantization(filename: &str) -> Option<String> {
+    if let Some(stem) = filename.strip_suffix(".gguf") {
+        return stem.rsplit('.').next().map(|q| q.to_lowercase());
+    }
+    let stem = filename.strip_suffix(".uqff")?;
+    // Sharded files end in "-<shard number>"
+    let stem = match stem.rsplit_once('-') {
+        Some((rest, shard)) if shard.chars().all(|c| c.is_ascii_digit()) => rest,
+        _ => stem,
+    };
+    stem.rsplit_once('-').map(|(_, q)| q.to_lowercase())
+}
+
+fn quantizations(info: &ModelInfo) -> Vec<String> {
+    let mut quantizations: Vec<String> = Vec::new();
+    for quant in info.files.iter().filter_map(|file| file_quantization(file.filename)) {
+        if !quantizations.contains(&quant) {
+            quantizations.push(quant);
+        }
+    }
+    quantizations
+}
+
+// The files a download fetches: everything shared, plus the selected weights
+fn selected_files<'a>(info: &'a ModelInfo, selection: &Selection) -> Result<Vec<&'a ModelFile>> {
+    if let Some(quant) = &selection.quant {
+        let quant = quant.to_lowercase();
+        if !quantizations(info).contains(&quant) {
+            anyhow::bail!("{} has no {} quantization (available: {})", info.name, quant, quantizations(info).join(", "));
+        }
+    }
+    for name in &selection.files {
+        if !info.files.iter().any(|file| name == file.filename) {
+            anyhow::bail!("{} has no file named {}", info.name, name);
+        }
+    }
+    Ok(info
+        .files
+        .iter()
+        .filter(|file| match file_quantization(file.filename) {
+            None => true,
+            Some(_) if !selection.files.is_empty() => selection.files.iter().any(|name| name == file.filename),
+            Some(quant) => selection.quant.as_ref().map_or(true, |selected| selected.eq_ignore_ascii_case(&quant)),
+        })
+        .collect())
+}
+
+// Quantizations whose weights are all downloaded
+fn installed_quantizations(info: &ModelInfo) -> Vec<String> {
+    let model_dir = Path::new(BASE_DIR).join(info.directory);
+    quantizations(info)
+        .into_iter()
+        .filter(|quant| {
+            info.files
+                .iter()
+                .filter(|file| file_quantization(file.filename).as_ref() == Some(quant))
+                .all(|file| model_dir.join(file.filename).exists())
+        })
+        .collect()
+}
+
+// A model is usable once its shared files and at least one complete quantization are downloaded
 fn model_exists(info: &ModelInfo) -> bool {
     let model_dir = Path::new(BASE_DIR).join(info.directory);
     if !model_dir.exists() {
         return false;
     }
-    
-    info.files.iter().all(|file| {
-        model_dir.join(file.filename).exists()
-    })
+    let shared = info
+        .files
+        .iter()
+        .filter(|file| file_quantization(file.filename).is_none())
+        .all(|file| model_dir.join(file.filename).exists());
+    shared && (quantizations(info).is_empty() || !installed_quantizations(info).is_empty())
 }
 
 // Download individual model with all required files
 async fn download_model(
     models: &HashMap<ModelChoice, ModelInfo>,
     choice: &ModelChoice,
+    selection: &Selection,
     force: bool,
     skip_confirmation: bool,
 ) -> Result<()> {
     if let Some(info) = models.get(choice) {
-        println!("🎯 Selected Model: {}", info.name);
-        println!("📄 {}", info.description);
-        println!("📊 Total estimated size: {}", info.size_estimate);
-        println!();
+        say!("🎯 Selected Model: {}", info.name);
+        say!("📄 {}", info.description);
+        say!("📊 Total estimated size: {}", info.size_estimate);
+        say!("⚖️  License: {}", info.license);
+        say!();
 
+        let model = choice_name(choice);
+        if info.gated && hf_token().is_none() {
+            say!("🔒 This model includes files from a gated repository.");
+            say!("   1. Open https://huggingface.co/{} and accept the license terms", info.repo);
+            say!("   2. Create a token at https://huggingface.co/settings/tokens");
+            say!("   3. Add HF_TOKEN=<your token> to .env and re-run this command");
+            anyhow::bail!("HF_TOKEN is required to download {}", info.name);
+        }
+
+        let files = selected_files(info, selection)?;
         let model_dir = Path::new(BASE_DIR).join(info.directory);
         
-        // Check if model already exists
-        if !force && model_exists(info) {
-            println!("✅ Model already exists at: {:?}", model_dir);
+        // Check if the selected files already exist
+        if !force && files.iter().all(|file| model_dir.join(file.filename).exists()) {
+            say!("✅ Model already exists at: {:?}", model_dir);
             
             if !skip_confirmation {
-                println!("🔄 Re-download? (y/N): ");
+                say!("🔄 Re-download? (y/N): ");
                 let mut input = String::new();
                 std::io::stdin().read_line(&mut input)?;
                 
                 if !input.trim().to_lowercase().starts_with('y') {
-                    println!("🚀 Using existing model.");
+                    say!("🚀 Using existing model.");
                     return Ok(());
                 }
             } else {
-                println!("🚀 Using existing model (use --force to re-download).");
+                say!("🚀 Using existing model (use --force to re-download).");
+                emit("model_skipped", json!({ "model": model, "reason": "already downloaded", "directory": model_dir }));
                 return Ok(());
             }
         }
 
         // Confirm download with user
         if !skip_confirmation {
-            println!("⚠️  This will download {} files totaling approximately {}.", info.files.len(), info.size_estimate);
-            println!("📁 Files will be saved to: {:?}", model_dir);
-            println!();
-            println!("🤔 Do you want to proceed? (y/N): ");
+            if files.len() < info.files.len() {
+                let sizes: Vec<&str> = files.iter().map(|file| file.size).collect();
+                say!("⚠️  This will download {} of {} files ({}).", files.len(), info.files.len(), sizes.join(" + "));
+            } else {
+                say!("⚠️  This will download {} files totaling approximately {}.", info.files.len(), info.size_estimate);
+            }
+            say!("📁 Files will be saved to: {:?}", model_dir);
+            say!();
+            say!("🤔 Do you want to proceed? (y/N): ");
             
             let mut input = String::new();
             std::io::stdin().read_line(&mut input)?;
             
             if !input.trim().to_lowercase().starts_with('y') {
-                println!("❌ Download cancelled.");
+                say!("❌ Download cancelled.");
                 return Ok(());
             }
         }
@@ -457,26 +738,64 @@ async fn download_model(
         fs::create_dir_all(&model_dir).await?;
         
         // Download all required files
-        println!("📥 Starting download...");
-        println!();
+        say!("📥 Starting download...");
+        say!();
         
-        for (i, file) in info.files.iter().enumerate() {
-            println!("📦 Downloading file {} of {}: {}", i + 1, info.files.len(), file.filename);
-            println!("📝 {}", file.description);
+        // Expected hashes, fetched once per repo the files come from
+        let mut checksums: HashMap<String, HashMap<String, String>> = HashMap::new();
+        for (i, file) in files.iter().enumerate() {
+            say!("📦 Downloading file {} of {}: {}", i + 1, files.len(), file.filename);
+            emit("file_started", json!({ "model": model, "file": file.filename, "index": i + 1, "total": files.len() }));
+            say!("📝 {}", file.description);
             
             let file_path = model_dir.join(file.filename);
-            download_file(file.url, &file_path).await?;
+            let size = match download_file(file.url, &file_path).await {
+                Ok(size) => size,
+                Err(e) => {
+                    if let Some(IntegrityError::QuarantineSuspected { path, .. }) = e.downcast_ref::<IntegrityError>() {
+                        print_quarantine_help(path, choice);
+                    }
+                    return Err(e);
+                }
+            };
+            record_download(&model_dir, file.filename, size).await?;
+
+            let mut sha256 = None;
+            if let Some(repo) = repo_from_url(file.url) {
+                if !checksums.contains_key(&repo) {
+                    let listed = expected_checksums(&repo).await.unwrap_or_else(|e| {
+                        say!("⚠️  Could not fetch checksums for {} ({}); skipping SHA-256 verification", repo, e);
+                        HashMap::new()
+                    });
+                    checksums.insert(repo.clone(), listed);
+                }
+                if let Some(expected) = checksums[&repo].get(file.filename) {
+                    verify_checksum(&file_path, expected).await.inspect_err(|_| {
+                        // A corrupt file must not pass for a finished download on the next run
+                        let _ = std::fs::remove_file(&file_path);
+                    })?;
+                    record_checksum(&model_dir, file.filename, expected).await?;
+                    sha256 = Some(expected.clone());
+                }
+            }
             
-            println!("✅ Downloaded: {}", file.filename);
-            println!();
+            say!("✅ Downloaded: {}", file.filename);
+            emit("file_done", json!({ "model": model, "file": file.filename, "bytes": size, "sha256": sha256 }));
+            say!();
+        }
+
+        if let Some(repo) = info.chat_template_repo {
+            download_chat_template(repo, &model_dir, force).await;
         }
         
-        println!("🎉 Model download complete!");
-        println!("📁 Location: {:?}", model_dir);
-        println!("🚀 You can now use this model in your Tauri app!");
+        say!("🎉 Model download complete!");
+        emit("model_done", json!({ "model": model, "directory": model_dir }));
+        say!("📁 Location: {:?}", model_dir);
+        say!("🚀 You can now use this model in your Tauri app!");
         
     } else {
-        println!("❌ Model not found: {:?}", choice);
+        say!("❌ Model not found: {:?}", choice);
+        anyhow::bail!("Model not found: {}", choice_name(choice));
     }
     
     Ok(())
@@ -485,19 +804,20 @@ async fn download_model(
 // Download all available models for the demo
 async fn download_all_models(
     models: &HashMap<ModelChoice, ModelInfo>,
+    selection: &Selection,
     force: bool,
     skip_confirmation: bool,
 ) -> Result<()> {
-    println!("🎯 Download All Models");
-    println!();
+    say!("🎯 Download All Models");
+    say!();
     
     let total_models = models.len();
     let existing_count = models.values().filter(|info| model_exists(info)).count();
     
-    println!("📊 Summary:");
-    println!("   Total models: {}", total_models);
-    println!("   Already downloaded: {}", existing_count);
-    println!("   To download: {}", total_models - existing_count);
+    say!("📊 Summary:");
+    say!("   Total models: {}", total_models);
+    say!("   Already downloaded: {}", existing_count);
+    say!("   To download: {}", total_models - existing_count);
     
     // Calculate estimated total download size
     let total_size: f64 = models.values()
@@ -514,18 +834,18 @@ async fn download_all_models(
         })
         .sum();
     
-    println!("   Estimated download size: ~{:.1}GB", total_size);
-    println!();
+    say!("   Estimated download size: ~{:.1}GB", total_size);
+    say!();
 
     if !skip_confirmation {
-        println!("⚠️  This is a large download that may take significant time and bandwidth.");
-        println!("🤔 Do you want to proceed with downloading all models? (y/N): ");
+        say!("⚠️  This is a large download that may take significant time and bandwidth.");
+        say!("🤔 Do you want to proceed with downloading all models? (y/N): ");
         
         let mut input = String::new();
         std::io::stdin().read_line(&mut input)?;
         
         if !input.trim().to_lowercase().starts_with('y') {
-            println!("❌ Download cancelled.");
+            say!("❌ Download cancelled.");
             return Ok(());
         }
     }
@@ -534,37 +854,364 @@ async fn download_all_models(
     let choices = vec![ModelChoice::MistralGguf, ModelChoice::LlamaVision, ModelChoice::Gemma3nE2b, ModelChoice::SmolLm3];
     
     for (i, choice) in choices.iter().enumerate() {
-        println!("🚀 Downloading model {} of {}", i + 1, total_models);
-        download_model(models, choice, force, true).await?;
-        println!();
+        say!("🚀 Downloading model {} of {}", i + 1, total_models);
+        // Models without the requested quantization get all of theirs
+        let offered = models.get(choice).is_some_and(|info| {
+            selection.quant.as_ref().map_or(true, |quant| quantizations(info).contains(&quant.to_lowercase()))
+        });
+        let selection = if offered { selection } else { &Selection { quant: None, files: Vec::new() } };
+        download_model(models, choice, selection, force, true).await?;
+        say!();
     }
     
-    println!("🎉 All models downloaded successfully!");
-    println!("🚀 Your Tauri app is now ready with all available AI models!");
+    say!("🎉 All models downloaded successfully!");
+    say!("🚀 Your Tauri app is now ready with all available AI models!");
     
     Ok(())
 }
 
-// Downloads individual file with progress tracking
-async fn download_file(url: &str, file_path: &Path) -> Result<()> {
+// Fetches the base model's tokenizer_config.json into the model directory. Base repos are often
+// gated while the quantized ones aren't, so a failure only means the app falls back to its
+// bundled templates.
+async fn download_chat_template(repo: &str, model_dir: &Path, force: bool) {
+    let file_path = model_dir.join(CHAT_TEMPLATE_FILE);
+    if !force && file_path.exists() {
+        return;
+    }
+    say!("📦 Downloading the chat template from {}", repo);
+    let url = format!("https://huggingface.co/{}/resolve/main/{}", repo, CHAT_TEMPLATE_FILE);
+    match download_file(&url, &file_path).await {
+        Ok(size) => {
+            if let Err(e) = record_download(model_dir, CHAT_TEMPLATE_FILE, size).await {
+                say!("⚠️  Failed to record {}: {}", CHAT_TEMPLATE_FILE, e);
+            }
+            say!("✅ Downloaded: {}", CHAT_TEMPLATE_FILE);
+        }
+        Err(e) => {
+            let _ = fs::remove_file(partial_path(&file_path)).await;
+            say!("⚠️  Could not fetch the chat template ({}); the app will use its bundled template", e);
+        }
+    }
+    say!();
+}
+
+// HF access token from .env / the environment, used for gated repositories
+fn hf_token() -> Option<String> {
+    ["HF_TOKEN", "HUGGING_FACE_HUB_TOKEN"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .map(|token| token.trim().to_string())
+        .find(|token| !token.is_empty())
+}
+
+// Extracts "owner/name" from a huggingface.co resolve URL
+fn repo_from_url(url: &str) -> Option<String> {
+    let path = url.strip_prefix("https://huggingface.co/")?;
+    let mut parts = path.split('/');
+    Some(format!("{}/{}", parts.next()?, parts.next()?))
+}
+
+// Antivirus tools tend to surface as these errors when they grab a file mid-write
+fn is_quarantine_error(e: &std::io::Error) -> bool {
+    matches!(e.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::PermissionDenied)
+        || matches!(
+            e.raw_os_error(),
+            Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION | ERROR_VIRUS_INFECTED | ERROR_VIRUS_DELETED)
+        )
+}
+
+fn integrity_error(file_path: &Path, e: std::io::Error) -> anyhow::Error {
+    if is_quarantine_error(&e) {
+        IntegrityError::QuarantineSuspected {
+            path: file_path.display().to_string(),
+            reason: format!("became inaccessible while downloading ({})", e),
+        }
+        .into()
+    } else {
+        e.into()
+    }
+}
+
+// Confirms the file is still there, readable and as large as what was written
+async fn check_file(file_path: &Path, expected: u64) -> Result<()> {
+    let path = file_path.display().to_string();
+    let metadata = match fs::metadata(file_path).await {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(IntegrityError::QuarantineSuspected { path, reason: "disappeared after being written".to_string() }.into());
+        }
+        Err(e) => return Err(integrity_error(file_path, e)),
+    };
+    // A quarantined file is often replaced by an empty or truncated stub
+    if metadata.len() != expected {
+        if metadata.len() < expected {
+            return Err(IntegrityError::QuarantineSuspected {
+                path,
+                reason: format!("shrank to {} of {} bytes", metadata.len(), expected),
+            }
+            .into());
+        }
+        return Err(IntegrityError::SizeMismatch { path, expected, actual: metadata.len() }.into());
+    }
+    // Locked files can still be stat'ed, so make sure they can actually be opened
+    fs::File::open(file_path).await.map_err(|e| integrity_error(file_path, e))?;
+    Ok(())
+}
+
+fn print_quarantine_help(path: &str, choice: &ModelChoice) {
+    let model = choice_name(choice);
+    say!("🛡️  {} looks like it was quarantined or locked by antivirus software.", path);
+    say!("   Large model files are a common false positive.");
+    if cfg!(windows) {
+        say!("   1. Open Windows Security > Virus & threat protection > Protection history");
+        say!("   2. Restore the file (or allow it), then add {} as an exclusion", BASE_DIR);
+    } else {
+        say!("   1. Check your antivirus quarantine and restore the file");
+        say!("   2. Exclude {} from real-time scanning", BASE_DIR);
+    }
+    say!("   3. Run `cargo run --example download_models verify {}` to re-check the files", model);
+    say!("      (or download again with --force if the file can't be restored)");
+}
+
+async fn load_manifest(model_dir: &Path) -> HashMap<String, u64> {
+    fs::read_to_string(model_dir.join(MANIFEST_FILE))
+        .await
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+async fn record_download(model_dir: &Path, filename: &str, size: u64) -> Result<()> {
+    let mut manifest = load_manifest(model_dir).await;
+    manifest.insert(filename.to_string(), size);
+    fs::write(model_dir.join(MANIFEST_FILE), serde_json::to_string_pretty(&manifest)?).await?;
+    Ok(())
+}
+
+// SHA-256 of every LFS file in a repo, as listed by the Hugging Face tree API. Small files kept in
+// git have no SHA-256 there and aren't listed.
+async fn expected_checksums(repo: &str) -> Result<HashMap<String, String>> {
+    let mut request = reqwest::Client::new().get(format!("https://huggingface.co/api/models/{}/tree/main", repo));
+    if let Some(token) = hf_token() {
+        request = request.bearer_auth(token);
+    }
+    let body = request.send().await?.error_for_status()?.text().await?;
+    let entries: Vec<serde_json::Value> = serde_json::from_str(&body)?;
+    Ok(entries
+        .iter()
+        .filter_map(|entry| Some((entry["path"].as_str()?.to_string(), entry["lfs"]["oid"].as_str()?.to_string())))
+        .collect())
+}
+
+async fn sha256_file(file_path: &Path) -> Result<String> {
+    use sha2::{Digest, Sha256};
+    use tokio::io::AsyncReadExt;
+
+    let mut file = fs::File::open(file_path).await.map_err(|e| integrity_error(file_path, e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_BUFFER_BYTES];
+    loop {
+        let read = file.read(&mut buffer).await.map_err(|e| integrity_error(file_path, e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+async fn verify_checksum(file_path: &Path, expected: &str) -> Result<()> {
+    say!("🔐 Checking the SHA-256 of {}...", file_path.display());
+    let actual = sha256_file(file_path).await?;
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(IntegrityError::ChecksumMismatch {
+            path: file_path.display().to_string(),
+            expected: expected.to_string(),
+            actual,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+async fn load_checksums(model_dir: &Path) -> HashMap<String, String> {
+    fs::read_to_string(model_dir.join(CHECKSUM_FILE))
+        .await
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+async fn record_checksum(model_dir: &Path, filename: &str, sha256: &str) -> Result<()> {
+    let mut checksums = load_checksums(model_dir).await;
+    checksums.insert(filename.to_string(), sha256.to_string());
+    fs::write(model_dir.join(CHECKSUM_FILE), serde_json::to_string_pretty(&checksums)?).await?;
+    Ok(())
+}
+
+// Re-checks every file against the sizes (and SHA-256, where known) recorded at download time
+async fn verify_model(models: &HashMap<ModelChoice, ModelInfo>, choice: &ModelChoice) -> Result<()> {
+    let Some(info) = models.get(choice) else {
+        say!("❌ Model not found: {:?}", choice);
+        anyhow::bail!("Model not found: {}", choice_name(choice));
+    };
+    let model = choice_name(choice);
+    let model_dir = Path::new(BASE_DIR).join(info.directory);
+    let manifest = load_manifest(&model_dir).await;
+    let checksums = load_checksums(&model_dir).await;
+    let mut failures = 0;
+
+    say!("🔍 Verifying {}...", info.name);
+    for file in &info.files {
+        let file_path = model_dir.join(file.filename);
+        let Some(&expected) = manifest.get(file.filename) else {
+            let status = if file_path.exists() { "present, but not recorded by this downloader" } else { "not downloaded" };
+            say!("   ⬜ {}: {}", file.filename, status);
+            let status = if file_path.exists() { "unrecorded" } else { "missing" };
+            emit("file_checked", json!({ "model": model, "file": file.filename, "status": status }));
+            continue;
+        };
+        let checked = match (check_file(&file_path, expected).await, checksums.get(file.filename)) {
+            (Ok(()), Some(sha256)) => verify_checksum(&file_path, sha256).await,
+            (result, _) => result,
+        };
+        match checked {
+            Ok(()) => {
+                say!("   ✅ {} ({} bytes)", file.filename, expected);
+                emit("file_checked", json!({ "model": model, "file": file.filename, "status": "ok", "bytes": expected }));
+            }
+            Err(e) => {
+                failures += 1;
+                say!("   ❌ {}: {}", file.filename, e);
+                emit("file_checked", json!({ "model": model, "file": file.filename, "status": "failed", "error": e.to_string() }));
+                if let Some(IntegrityError::QuarantineSuspected { path, .. }) = e.downcast_ref::<IntegrityError>() {
+                    print_quarantine_help(path, choice);
+                }
+            }
+        }
+    }
+
+    emit("verify_done", json!({ "model": model, "failures": failures }));
+    if failures > 0 {
+        anyhow::bail!("{} file(s) of {} failed verification", failures, info.name);
+    }
+    say!("🎉 All recorded files are intact.");
+    Ok(())
+}
+
+// Downloads individual file with progress tracking, returning the number of bytes written.
+// Transient failures are retried with backoff, each retry resuming the partial file.
+async fn download_file(url: &str, file_path: &Path) -> Result<u64> {
+    let mut attempt = 1;
+    loop {
+        match download_attempt(url, file_path).await {
+            Ok(size) => return Ok(size),
+            Err(e) if attempt < MAX_ATTEMPTS && is_transient(&e) => {
+                let delay = RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+                say!("⚠️  {} (attempt {} of {}), retrying in {}s", e, attempt, MAX_ATTEMPTS, delay.as_secs());
+                emit("retry", json!({ "file": file_path, "attempt": attempt, "max_attempts": MAX_ATTEMPTS, "delay_secs": delay.as_secs(), "error": e.to_string() }));
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Where a file is written until it is complete
+fn partial_path(file_path: &Path) -> PathBuf {
+    let mut name = file_path.as_os_str().to_owned();
+    name.push(PARTIAL_SUFFIX);
+    PathBuf::from(name)
+}
+
+// Dropped connections, timeouts, rate limits and server errors are worth another try
+fn is_transient(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<TransientError>().is_some() || e.downcast_ref::<reqwest::Error>().is_some()
+}
+
+// Start offset and total size from a "bytes start-end/total" Content-Range header
+fn content_range(response: &reqwest::Response) -> Option<(u64, u64)> {
+    let value = response.headers().get(reqwest::header::CONTENT_RANGE)?.to_str().ok()?;
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+    Some((start.parse().ok()?, total.parse().ok()?))
+}
+
+// Throws away a partial file that can't be resumed, so the next attempt starts over
+async fn discard_partial(partial: &Path, reason: &str) -> anyhow::Error {
+    let _ = fs::remove_file(partial).await;
+    TransientError(format!("discarded the partial download of {}: {}", partial.display(), reason)).into()
+}
+
+async fn download_attempt(url: &str, file_path: &Path) -> Result<u64> {
+    let partial = partial_path(file_path);
+    let resume_from = fs::metadata(&partial).await.map(|m| m.len()).unwrap_or(0);
+
     let client = reqwest::Client::new();
-    let response = client.get(url).send().await?;
+    let mut request = client.get(url);
+    let token = hf_token();
+    if let Some(token) = &token {
+        request = request.bearer_auth(token);
+    }
+    if resume_from > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", resume_from));
+    }
+    let response = request.send().await?;
     
-    if !response.status().is_success() {
-        anyhow::bail!("Failed to download file: HTTP {}", response.status());
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        let repo = repo_from_url(url).unwrap_or_else(|| url.to_string());
+        say!("🔒 {} is a gated repository (HTTP {}).", repo, status.as_u16());
+        if token.is_none() {
+            say!("   Create a token at https://huggingface.co/settings/tokens and add HF_TOKEN=<token> to .env");
+        } else {
+            say!("   Your token does not have access yet. Open https://huggingface.co/{} while logged in and accept the license terms", repo);
+        }
+        anyhow::bail!("Access to {} was denied; accept the model terms and retry", repo);
+    }
+    // The partial file is at least as long as the real one, so it can't be a prefix of it
+    if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        return Err(discard_partial(&partial, "it is longer than the file on the server").await);
+    }
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        return Err(TransientError(format!("HTTP {} from {}", status.as_u16(), url)).into());
+    }
+    if !status.is_success() {
+        anyhow::bail!("Failed to download file: HTTP {}", status);
     }
 
-    let total_size = response.content_length().unwrap_or(0);
-    let mut file = fs::File::create(file_path).await?;
-    let mut downloaded = 0u64;
+    let (mut file, mut downloaded, total_size) = if status == reqwest::StatusCode::PARTIAL_CONTENT {
+        let Some((start, total)) = content_range(&response) else {
+            return Err(discard_partial(&partial, "the server's Content-Range was unreadable").await);
+        };
+        if start != resume_from {
+            return Err(discard_partial(&partial, "the server resumed at the wrong offset").await);
+        }
+        say!("↩️  Resuming at {:.1} MB", resume_from as f64 / (1024.0 * 1024.0));
+        let file = fs::OpenOptions::new().append(true).open(&partial).await.map_err(|e| integrity_error(&partial, e))?;
+        (file, resume_from, total)
+    } else {
+        // A plain 200 sends the whole file, whether or not a range was asked for
+        let file = fs::File::create(&partial).await.map_err(|e| integrity_error(&partial, e))?;
+        (file, 0, response.content_length().unwrap_or(0))
+    };
+    let mut next_check = downloaded + PRESENCE_CHECK_INTERVAL;
     let mut stream = response.bytes_stream();
     
     use futures::StreamExt;
     
     while let Some(chunk) = stream.next().await {
         let chunk = chunk?;
-        file.write_all(&chunk).await?;
+        file.write_all(&chunk).await.map_err(|e| integrity_error(&partial, e))?;
         downloaded += chunk.len() as u64;
+
+        // Writes to an open handle can keep succeeding after the file was removed from disk
+        if downloaded >= next_check {
+            file.flush().await.map_err(|e| integrity_error(&partial, e))?;
+            check_file(&partial, downloaded).await?;
+            next_check += PRESENCE_CHECK_INTERVAL;
+        }
         
         // Show progress every 100MB or at completion
         if downloaded % (100 * 1024 * 1024) == 0 || downloaded == total_size {
@@ -573,11 +1220,39 @@ async fn download_file(url: &str, file_path: &Path) -> Result<()> {
             } else {
                 0
             };
-            println!("📈 Progress: {:.1} MB ({}%)", 
+            say!("📈 Progress: {:.1} MB ({}%)", 
                 downloaded as f64 / (1024.0 * 1024.0), progress);
+            emit("progress", json!({ "file": file_path, "bytes": downloaded, "total_bytes": total_size, "percent": progress }));
         }
     }
 
-    file.flush().await?;
-    Ok(())
+    file.flush().await.map_err(|e| integrity_error(&partial, e))?;
+    drop(file);
+
+    if total_size > 0 && downloaded > total_size {
+        return Err(discard_partial(&partial, &format!("{} bytes arrived for a {} byte file", downloaded, total_size)).await);
+    }
+    if total_size > 0 && downloaded < total_size {
+        return Err(TransientError(format!("the connection closed at {} of {} bytes", downloaded, total_size)).into());
+    }
+    fs::rename(&partial, file_path).await.map_err(|e| integrity_error(file_path, e))?;
+
+    // With the chaos feature, CHAOS_CORRUPT_DOWNLOAD=1 truncates the file to exercise the integrity checks
+    #[cfg(feature = "chaos")]
+    if std::env::var("CHAOS_CORRUPT_DOWNLOAD").is_ok_and(|v| v == "1") {
+        say!("💥 Chaos: truncating {} to simulate a corrupted download", file_path.display());
+        fs::OpenOptions::new().write(true).open(file_path).await?.set_len(downloaded / 2).await?;
+    }
+
+    // Scanners usually act when the file is closed, so give them a moment before checking
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    let checked = check_file(file_path, downloaded).await;
+    // A file that grew after it was written is corrupt rather than quarantined, so don't keep it
+    if let Err(e) = &checked {
+        if let Some(IntegrityError::SizeMismatch { .. }) = e.downcast_ref::<IntegrityError>() {
+            let _ = fs::remove_file(file_path).await;
+        }
+    }
+    checked?;
+    Ok(downloaded)
 } 
\ No newline at end of file
@@ -1,13 +1,40 @@
 use anyhow::Result;
+use clap::{Parser, ValueEnum};
 use mistralrs::{
     GgufModelBuilder, TextModelBuilder, PagedAttentionMetaBuilder, RequestBuilder, TextMessageRole, TextMessages, IsqType,
 };
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs;
+use tauri_mistral_chat_lib::bench;
+
+#[derive(Parser)]
+#[command(name = "hello_world")]
+#[command(about = "Tests the local models, or benchmarks each one at every quantization")]
+struct Cli {
+    /// Load each local model at each available quantization and time a fixed prompt set
+    #[arg(long)]
+    bench: bool,
+    /// Table format of the benchmark results
+    #[arg(long, value_enum, default_value = "markdown")]
+    format: TableFormat,
+    /// Write the benchmark table to this file instead of stdout
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TableFormat {
+    Csv,
+    Markdown,
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+    if cli.bench {
+        return run_benchmark(cli.format, cli.output).await;
+    }
     println!("🦀 Local Mistral Model Testing for Tauri Desktop App");
     println!("This example tests all models found in the models directory");
 
@@ -98,6 +125,47 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+// Benchmarks every local model at every quantization, using the app's own discovery and loaders
+async fn run_benchmark(format: TableFormat, output: Option<PathBuf>) -> Result<()> {
+    let models_base_path = "models";
+    let variants = bench::variants(models_base_path).map_err(anyhow::Error::msg)?;
+    if variants.is_empty() {
+        anyhow::bail!("No local models found in {}/", models_base_path);
+    }
+
+    println!("⏱️  Benchmarking {} model/quantization pair(s) with {} prompts each:", variants.len(), bench::PROMPTS.len());
+    for variant in &variants {
+        println!("  • {} ({})", variant.model_id, variant.quantization);
+    }
+    println!();
+
+    let results = bench::run(models_base_path, |result| match &result.error {
+        Some(error) => println!("❌ {} ({}): {}", result.model_id, result.quantization, error),
+        None => println!(
+            "✅ {} ({}): loaded in {} ms, {:.1} tok/s",
+            result.model_id,
+            result.quantization,
+            result.load_ms.unwrap_or_default(),
+            result.completion_tok_per_sec.unwrap_or_default()
+        ),
+    })
+    .await
+    .map_err(anyhow::Error::msg)?;
+
+    let table = match format {
+        TableFormat::Csv => bench::to_csv(&results),
+        TableFormat::Markdown => bench::to_markdown(&results),
+    };
+    match output {
+        Some(path) => {
+            fs::write(&path, table)?;
+            println!("📄 Results written to {}", path.display());
+        }
+        None => println!("\n{}", table),
+    }
+    Ok(())
+}
+
 // Discovers all supported model formats in the models directory
 fn discover_models(base_path: &str) -> Result<Vec<(String, String, String)>> {
     let mut models = Vec::new();
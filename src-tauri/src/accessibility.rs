@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+// Screen-reader friendly post-processing of finished responses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccessibilityConfig {
+    pub enabled: bool,
+    // Rewrite Markdown tables as "Row 1: Name: Alice; Age: 30" lines
+    pub linearize_tables: bool,
+    // Announce code blocks with their language and length instead of bare fences
+    pub announce_code: bool,
+    // Split sentences longer than this many words; 0 disables
+    pub max_sentence_words: usize,
+}
+
+impl Default for AccessibilityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            linearize_tables: true,
+            announce_code: true,
+            max_sentence_words: 25,
+        }
+    }
+}
+
+impl AccessibilityConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.max_sentence_words > 0 && self.max_sentence_words < 5 {
+            errors.push(format!(
+                "accessibility.max_sentence_words = {} is too short to read naturally; use at least 5 or 0 to disable",
+                self.max_sentence_words
+            ));
+        }
+        errors
+    }
+}
+
+// Formats a finished response when accessibility mode is on; None means "leave as is"
+pub fn apply(text: &str) -> Option<String> {
+    let options = config::get().accessibility;
+    options.enabled.then(|| format(text, &options))
+}
+
+pub fn format(text: &str, options: &AccessibilityConfig) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut output: Vec<String> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start();
+
+        if let Some(lang) = trimmed.strip_prefix("```") {
+            let end = lines[i + 1..]
+                .iter()
+                .position(|l| l.trim_start().starts_with("```"))
+                .map(|p| i + 1 + p)
+                .unwrap_or(lines.len());
+            let code = &lines[i + 1..end];
+
+            if options.announce_code {
+                let lang = lang.trim();
+                let kind = if lang.is_empty() { "Code block".to_string() } else { format!("{} code block", lang) };
+                output.push(format!("{}, {} line{}:", kind, code.len(), if code.len() == 1 { "" } else { "s" }));
+                output.extend(code.iter().map(|l| l.to_string()));
+                output.push("End of code block.".to_string());
+            } else {
+                output.extend(lines[i..end.min(lines.len() - 1) + 1].iter().map(|l| l.to_string()));
+            }
+            i = end + 1;
+            continue;
+        }
+
+        if options.linearize_tables && is_table_row(trimmed) && lines.get(i + 1).is_some_and(|l| is_separator_row(l.trim())) {
+            let headers = table_cells(trimmed);
+            let mut rows = Vec::new();
+            i += 2;
+            while i < lines.len() && is_table_row(lines[i].trim_start()) {
+                rows.push(table_cells(lines[i].trim_start()));
+                i += 1;
+            }
+            output.extend(linearize_table(&headers, &rows));
+            continue;
+        }
+
+        let plain = strip_inline_markup(line);
+        output.push(if options.max_sentence_words > 0 {
+            split_long_sentences(&plain, options.max_sentence_words)
+        } else {
+            plain
+        });
+        i += 1;
+    }
+
+    output.join("\n")
+}
+
+fn is_table_row(line: &str) -> bool {
+    line.starts_with('|') && line.matches('|').count() >= 2
+}
+
+fn is_separator_row(line: &str) -> bool {
+    is_table_row(line) && line.chars().all(|c| matches!(c, '|' | '-' | ':' | ' '))
+}
+
+fn table_cells(line: &str) -> Vec<String> {
+    line.trim()
+        .trim_matches('|')
+        .split('|')
+        .map(|cell| strip_inline_markup(cell.trim()))
+        .collect()
+}
+
+fn linearize_table(headers: &[String], rows: &[Vec<String>]) -> Vec<String> {
+    let mut output = vec![format!(
+        "Table with {} row{} and {} column{}: {}.",
+        rows.len(),
+        if rows.len() == 1 { "" } else { "s" },
+        headers.len(),
+        if headers.len() == 1 { "" } else { "s" },
+        headers.join(", ")
+    )];
+
+    for (index, row) in rows.iter().enumerate() {
+        let cells: Vec<String> = headers
+            .iter()
+            .zip(row.iter())
+            .map(|(header, value)| format!("{}: {}", header, value))
+            .collect();
+        output.push(format!("Row {}: {}.", index + 1, cells.join("; ")));
+    }
+
+    output
+}
+
+// Drops emphasis, inline code ticks and heading markers that screen readers read aloud
+fn strip_inline_markup(line: &str) -> String {
+    let line = if line.trim_start().starts_with('#') {
+        line.trim_start().trim_start_matches('#').trim_start()
+    } else {
+        line
+    };
+    line.replace("**", "").replace("__", "").replace('`', "")
+}
+
+// Breaks sentences over the word limit at the last comma or semicolon, or hard at the limit
+fn split_long_sentences(line: &str, max_words: usize) -> String {
+    let mut sentences = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+
+    for word in line.split_whitespace() {
+        current.push(word.to_string());
+        let ends_sentence = word.ends_with(['.', '!', '?']);
+
+        if ends_sentence {
+            sentences.push(current.join(" "));
+            current.clear();
+        } else if current.len() >= max_words {
+            let cut = current
+                .iter()
+                .rposition(|w| w.ends_with([',', ';']))
+                .filter(|&pos| pos + 1 >= max_words / 2)
+                .map(|pos| pos + 1)
+                .unwrap_or(current.len());
+            let rest = current.split_off(cut);
+            let mut head = current.join(" ");
+            head = head.trim_end_matches([',', ';']).to_string();
+            head.push('.');
+            sentences.push(head);
+            current = rest;
+            if let Some(first) = current.first_mut() {
+                *first = capitalize(first);
+            }
+        }
+    }
+    if !current.is_empty() {
+        sentences.push(current.join(" "));
+    }
+
+    // Keep the indentation of nested list items, which split_whitespace drops
+    let indent: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+    format!("{}{}", indent, sentences.join(" "))
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+#[tauri::command]
+pub fn get_accessibility_options() -> AccessibilityConfig {
+    config::get().accessibility
+}
+
+#[tauri::command]
+pub fn set_accessibility_options(options: AccessibilityConfig, app: tauri::AppHandle) -> Result<AccessibilityConfig, String> {
+    let config = config::update(&app, |config| config.accessibility = options)?;
+    Ok(config.accessibility)
+}
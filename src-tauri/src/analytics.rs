@@ -0,0 +1,162 @@
+// Opt-in usage counters kept in the local database; there is no network code here and the
+// only way out is a user-triggered export. Build without the "analytics" feature to drop storage.
+
+use serde::Serialize;
+
+use crate::config;
+
+#[cfg(feature = "analytics")]
+use crate::dates::format_day;
+#[cfg(feature = "analytics")]
+use crate::db_writer;
+#[cfg(feature = "analytics")]
+use crate::history::{db_err, with_db};
+
+// Everything that can be counted; adding a variant is the only way to collect something new
+#[derive(Debug, Clone, Copy)]
+pub enum UsageEvent<'a> {
+    GenerationCompleted { model_id: &'a str, tokens: usize },
+    GenerationCancelled { model_id: &'a str },
+    GenerationFailed { model_id: &'a str },
+    SlashCommandUsed { command: &'a str },
+}
+
+impl UsageEvent<'_> {
+    // (event name, subject, tokens) as stored in the usage_stats table
+    #[cfg_attr(not(feature = "analytics"), allow(dead_code))]
+    fn key(&self) -> (&'static str, &str, usize) {
+        match *self {
+            UsageEvent::GenerationCompleted { model_id, tokens } => ("generation_completed", model_id, tokens),
+            UsageEvent::GenerationCancelled { model_id } => ("generation_cancelled", model_id, 0),
+            UsageEvent::GenerationFailed { model_id } => ("generation_failed", model_id, 0),
+            UsageEvent::SlashCommandUsed { command } => ("slash_command_used", command, 0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageRow {
+    pub day: String,
+    pub event: String,
+    pub subject: String,
+    pub count: u64,
+    pub tokens: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageExport {
+    pub enabled: bool,
+    pub generated_at_ms: u64,
+    pub rows: Vec<UsageRow>,
+}
+
+#[cfg(feature = "analytics")]
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS usage_stats (
+    day INTEGER NOT NULL,
+    event TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    count INTEGER NOT NULL DEFAULT 0,
+    tokens INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (day, event, subject)
+);
+";
+
+// Creates the usage table next to the chat history
+pub fn init() -> Result<(), String> {
+    #[cfg(feature = "analytics")]
+    with_db(|conn| conn.execute_batch(SCHEMA).map_err(db_err))?;
+    Ok(())
+}
+
+// Bumps a daily counter when the user has opted in; failures are logged and otherwise ignored
+pub fn record(event: UsageEvent) {
+    if !config::get().analytics.enabled {
+        return;
+    }
+
+    #[cfg(feature = "analytics")]
+    {
+        let (name, subject, tokens) = event.key();
+        let subject = subject.to_string();
+        let day = crate::streaming::now_ms() / 86_400_000;
+        db_writer::submit("usage stats", move |conn| {
+            conn.execute(
+                "INSERT INTO usage_stats (day, event, subject, count, tokens) VALUES (?1, ?2, ?3, 1, ?4)
+                 ON CONFLICT(day, event, subject) DO UPDATE SET count = count + 1, tokens = tokens + excluded.tokens",
+                rusqlite::params![day as i64, name, subject, tokens as i64],
+            )
+            .map(|_| ())
+            .map_err(db_err)
+        });
+    }
+
+    #[cfg(not(feature = "analytics"))]
+    let _ = event;
+}
+
+#[cfg(feature = "analytics")]
+fn load_rows() -> Result<Vec<UsageRow>, String> {
+    with_db(|conn| {
+        let mut stmt = conn
+            .prepare("SELECT day, event, subject, count, tokens FROM usage_stats ORDER BY day, event, subject")
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(UsageRow {
+                    day: format_day(row.get::<_, i64>(0)?),
+                    event: row.get(1)?,
+                    subject: row.get(2)?,
+                    count: row.get::<_, i64>(3)? as u64,
+                    tokens: row.get::<_, i64>(4)? as u64,
+                })
+            })
+            .map_err(db_err)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+    })
+}
+
+#[cfg(feature = "analytics")]
+fn clear_rows() -> Result<(), String> {
+    with_db(|conn| conn.execute("DELETE FROM usage_stats", []).map(|_| ()).map_err(db_err))
+}
+
+#[cfg(not(feature = "analytics"))]
+fn load_rows() -> Result<Vec<UsageRow>, String> {
+    Err("Local analytics is not included in this build".to_string())
+}
+
+#[cfg(not(feature = "analytics"))]
+fn clear_rows() -> Result<(), String> {
+    Err("Local analytics is not included in this build".to_string())
+}
+
+// Returns the aggregated stats, optionally also writing them to a local JSON file
+#[tauri::command]
+pub fn export_usage_stats(path: Option<String>) -> Result<UsageExport, String> {
+    crate::kiosk::ensure_unlocked("Exporting usage stats")?;
+    let export = UsageExport {
+        enabled: config::get().analytics.enabled,
+        generated_at_ms: crate::streaming::now_ms(),
+        rows: load_rows()?,
+    };
+
+    if let Some(path) = path {
+        let contents = serde_json::to_string_pretty(&export).map_err(|e| e.to_string())?;
+        std::fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+
+    Ok(export)
+}
+
+#[tauri::command]
+pub fn clear_usage_stats() -> Result<(), String> {
+    clear_rows()
+}
+
+// Opts in or out; opting out keeps existing stats until they are cleared
+#[tauri::command]
+pub fn set_usage_stats_enabled(enabled: bool, app: tauri::AppHandle) -> Result<(), String> {
+    config::update(&app, |config| config.analytics.enabled = enabled)?;
+    Ok(())
+}
@@ -0,0 +1,321 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::history::{db_err, new_id, with_db};
+use crate::streaming::now_ms;
+use crate::{context_packs, db_writer, keep_alive, kiosk, models, system_prompt, warm_prefix};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS assistants (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    system_prompt TEXT NOT NULL,
+    context_pack_id TEXT REFERENCES context_packs(id) ON DELETE SET NULL,
+    model_id TEXT,
+    created_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS builtin_assistants (
+    key TEXT PRIMARY KEY,
+    assistant_id TEXT REFERENCES assistants(id) ON DELETE SET NULL
+);
+CREATE TABLE IF NOT EXISTS conversation_assistants (
+    conversation_id TEXT PRIMARY KEY REFERENCES conversations(id) ON DELETE CASCADE,
+    assistant_id TEXT NOT NULL REFERENCES assistants(id) ON DELETE CASCADE
+);
+";
+
+// Starter assistants added on first run, so new users get presets without any download
+const BUILTIN_TEMPLATES: &str = include_str!("../templates/assistants.json");
+
+#[derive(Debug, Deserialize)]
+struct Template {
+    key: String,
+    name: String,
+    system_prompt: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Assistant {
+    pub id: String,
+    pub name: String,
+    pub system_prompt: String,
+    pub context_pack_id: Option<String>,
+    // The model whose prefix cache is warmed; falls back to models.default_model
+    pub model_id: Option<String>,
+    pub created_at: u64,
+}
+
+impl Assistant {
+    fn warm_model(&self) -> Option<String> {
+        self.model_id.clone().or_else(keep_alive::default_model)
+    }
+}
+
+pub fn init() -> Result<(), String> {
+    with_db(|conn| {
+        conn.execute_batch(SCHEMA).map_err(db_err)?;
+        install_templates(conn, false)
+    })
+}
+
+fn templates() -> Result<Vec<Template>, String> {
+    serde_json::from_str(BUILTIN_TEMPLATES).map_err(|e| format!("Invalid built-in templates: {}", e))
+}
+
+// Adds the templates that were never installed. A built-in the user deleted keeps its row with a
+// NULL assistant_id, so it only comes back through reset_builtin_templates, which also restores
+// the original prompts of edited ones.
+fn install_templates(conn: &mut Connection, reset: bool) -> Result<(), String> {
+    let tx = conn.transaction().map_err(db_err)?;
+    for template in templates()? {
+        let installed: Option<Option<String>> = tx
+            .query_row(
+                "SELECT assistant_id FROM builtin_assistants WHERE key = ?1",
+                params![template.key],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_err)?;
+        let existing = match installed {
+            Some(existing) if reset => existing,
+            Some(_) => continue,
+            None => None,
+        };
+
+        let assistant_id = match existing {
+            Some(id) => {
+                tx.execute(
+                    "UPDATE assistants SET name = ?2, system_prompt = ?3, context_pack_id = NULL, model_id = NULL WHERE id = ?1",
+                    params![id, template.name, template.system_prompt],
+                )
+                .map_err(db_err)?;
+                Some(id)
+            }
+            None => {
+                let id = new_id();
+                let inserted = tx
+                    .execute(
+                        "INSERT OR IGNORE INTO assistants (id, name, system_prompt, created_at) VALUES (?1, ?2, ?3, ?4)",
+                        params![id, template.name, template.system_prompt, now_ms() as i64],
+                    )
+                    .map_err(db_err)?;
+                if inserted == 0 {
+                    println!("Warning: Skipped built-in assistant \"{}\": an assistant with that name exists", template.name);
+                }
+                (inserted > 0).then_some(id)
+            }
+        };
+        tx.execute(
+            "INSERT INTO builtin_assistants (key, assistant_id) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET assistant_id = excluded.assistant_id",
+            params![template.key, assistant_id],
+        )
+        .map_err(db_err)?;
+    }
+    tx.commit().map_err(db_err)
+}
+
+const COLUMNS: &str = "id, name, system_prompt, context_pack_id, model_id, created_at";
+
+fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Assistant> {
+    Ok(Assistant {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        system_prompt: row.get(2)?,
+        context_pack_id: row.get(3)?,
+        model_id: row.get(4)?,
+        created_at: row.get::<_, i64>(5)? as u64,
+    })
+}
+
+pub(crate) fn assistant_by_id(conn: &Connection, assistant_id: &str) -> Result<Assistant, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM assistants WHERE id = ?1 OR name = ?1", COLUMNS),
+        params![assistant_id],
+        from_row,
+    )
+    .optional()
+    .map_err(db_err)?
+    .ok_or_else(|| format!("Assistant not found: {}", assistant_id))
+}
+
+fn all_assistants(conn: &Connection) -> Result<Vec<Assistant>, String> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM assistants ORDER BY name", COLUMNS))
+        .map_err(db_err)?;
+    let rows = stmt.query_map([], from_row).map_err(db_err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+}
+
+// The assistant's system prompt followed by its pack's fixed context
+fn system_prompt(conn: &Connection, assistant: &Assistant) -> Result<String, String> {
+    let Some(pack_id) = &assistant.context_pack_id else {
+        return Ok(assistant.system_prompt.clone());
+    };
+    let context = context_packs::fixed_context(conn, pack_id)?;
+    if context.is_empty() {
+        return Ok(assistant.system_prompt.clone());
+    }
+    Ok(format!("{}\n\nReference material:\n\n{}", assistant.system_prompt, context.trim_end()))
+}
+
+// Id of the assistant the conversation runs under, if any
+pub(crate) fn assistant_id_for(conn: &Connection, conversation_id: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT assistant_id FROM conversation_assistants WHERE conversation_id = ?1",
+        params![conversation_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(db_err)
+}
+
+// System prompt of the assistant the conversation runs under, if any
+pub fn system_prompt_for(conversation_id: &str) -> Option<String> {
+    let result = with_db(|conn| match assistant_id_for(conn, conversation_id)? {
+        Some(id) => system_prompt(conn, &assistant_by_id(conn, &id)?).map(Some),
+        None => Ok(None),
+    });
+    result.unwrap_or_else(|e| {
+        println!("Warning: Failed to load the assistant of conversation {}: {}", conversation_id, e);
+        None
+    })
+}
+
+// Runs an assistant's prompt prefix through its model in the background. The KV state lives in
+// mistral.rs's in-memory prefix cache, so this is repeated whenever the model is loaded again.
+fn spawn_warm(assistant: Assistant, model_id: String, app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let prefix = match with_db(|conn| system_prompt(conn, &assistant)) {
+            Ok(prompt) => system_prompt::resolve(None, &model_id, Some(&prompt)).prompt,
+            Err(e) => {
+                println!("Warning: Failed to build the prompt of assistant {}: {}", assistant.name, e);
+                return;
+            }
+        };
+        match warm_prefix(&model_id, &prefix, &app).await {
+            Ok(()) => println!("Cached the prompt of assistant {} on {}", assistant.name, model_id),
+            Err(e) => println!("Warning: Failed to cache the prompt of assistant {}: {}", assistant.name, e),
+        }
+    });
+}
+
+// Called by the model manager after a load, for the assistants that use the model
+pub fn warm_for_model(model_id: &str, app: &tauri::AppHandle) {
+    // The kiosk prompt replaces every assistant's, so there is nothing worth caching
+    if kiosk::system_prompt().is_some() {
+        return;
+    }
+    let assistants = match with_db(|conn| all_assistants(conn)) {
+        Ok(assistants) => assistants,
+        Err(e) => {
+            println!("Warning: Failed to list assistants to cache for {}: {}", model_id, e);
+            return;
+        }
+    };
+    for assistant in assistants {
+        if assistant.warm_model().as_deref() == Some(model_id) {
+            spawn_warm(assistant, model_id.to_string(), app.clone());
+        }
+    }
+}
+
+// Creates an assistant; its prompt is cached right away when its model is already loaded,
+// otherwise on the model's next load
+#[tauri::command]
+pub async fn create_assistant(
+    name: String,
+    system_prompt: String,
+    context_pack_id: Option<String>,
+    model_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<Assistant, String> {
+    kiosk::ensure_unlocked("Assistants")?;
+    if name.trim().is_empty() {
+        return Err("Assistant name must not be empty".to_string());
+    }
+    if system_prompt.trim().is_empty() {
+        return Err("Assistant system prompt must not be empty".to_string());
+    }
+    let assistant = db_writer::write(move |conn| {
+        let context_pack_id = match &context_pack_id {
+            Some(pack_id) => Some(context_packs::pack_by_id(conn, pack_id)?.id),
+            None => None,
+        };
+        let id = new_id();
+        conn.execute(
+            "INSERT INTO assistants (id, name, system_prompt, context_pack_id, model_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id, name.trim(), system_prompt.trim(), context_pack_id, model_id, now_ms() as i64],
+        )
+        .map_err(db_err)?;
+        assistant_by_id(conn, &id)
+    })
+    .await?;
+
+    if let Some(model_id) = assistant.warm_model() {
+        if models::manager(&app).is_loaded(&model_id).await {
+            spawn_warm(assistant.clone(), model_id, app);
+        }
+    }
+    Ok(assistant)
+}
+
+// Restores the starter assistants, undoing edits and deletions; conversations using them keep them
+#[tauri::command]
+pub async fn reset_builtin_templates() -> Result<Vec<Assistant>, String> {
+    kiosk::ensure_unlocked("Assistants")?;
+    with_db(|conn| {
+        install_templates(conn, true)?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM assistants WHERE id IN (SELECT assistant_id FROM builtin_assistants) ORDER BY name",
+                COLUMNS
+            ))
+            .map_err(db_err)?;
+        let rows = stmt.query_map([], from_row).map_err(db_err)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+    })
+}
+
+#[tauri::command]
+pub async fn list_assistants() -> Result<Vec<Assistant>, String> {
+    with_db(|conn| all_assistants(conn))
+}
+
+#[tauri::command]
+pub async fn delete_assistant(assistant_id: String) -> Result<(), String> {
+    db_writer::write(move |conn| {
+        let assistant = assistant_by_id(conn, &assistant_id)?;
+        conn.execute("DELETE FROM assistants WHERE id = ?1", params![assistant.id])
+            .map_err(db_err)?;
+        Ok(())
+    })
+    .await
+}
+
+// Runs the conversation under an assistant's prompt; None returns it to the default persona
+#[tauri::command]
+pub async fn set_conversation_assistant(conversation_id: String, assistant_id: Option<String>) -> Result<Option<Assistant>, String> {
+    db_writer::write(move |conn| match assistant_id {
+        Some(assistant_id) => {
+            let assistant = assistant_by_id(conn, &assistant_id)?;
+            conn.execute(
+                "INSERT INTO conversation_assistants (conversation_id, assistant_id) VALUES (?1, ?2)
+                 ON CONFLICT(conversation_id) DO UPDATE SET assistant_id = excluded.assistant_id",
+                params![conversation_id, assistant.id],
+            )
+            .map_err(db_err)?;
+            Ok(Some(assistant))
+        }
+        None => {
+            conn.execute(
+                "DELETE FROM conversation_assistants WHERE conversation_id = ?1",
+                params![conversation_id],
+            )
+            .map_err(db_err)?;
+            Ok(None)
+        }
+    })
+    .await
+}
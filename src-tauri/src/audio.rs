@@ -0,0 +1,25 @@
+// Audio attachments for models that take audio (Gemma 3n). ai_chat receives the recording as base64,
+// like an image; it is checked to be WAV or MP3 here and decoded to samples by mistral.rs.
+
+use mistralrs::AudioInput;
+
+// Container formats accepted in audio_data, told apart by their first bytes
+fn format_of(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WAVE" {
+        return Some("wav");
+    }
+    // An ID3 tag, or a bare MPEG audio frame header
+    if bytes.starts_with(b"ID3") || (bytes.len() >= 2 && bytes[0] == 0xFF && bytes[1] & 0xE0 == 0xE0) {
+        return Some("mp3");
+    }
+    None
+}
+
+pub fn decode(audio_base64: &str) -> Result<AudioInput, String> {
+    use base64::Engine;
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(audio_base64)
+        .map_err(|e| format!("Failed to decode base64 audio: {}", e))?;
+    let format = format_of(&bytes).ok_or_else(|| "Audio must be a WAV or MP3 file".to_string())?;
+    AudioInput::from_bytes(&bytes).map_err(|e| format!("Failed to read {} audio: {}", format, e))
+}
@@ -0,0 +1,125 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config;
+use crate::db_writer;
+use crate::history::{db_err, with_db};
+use crate::streaming::now_ms;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    command TEXT NOT NULL,
+    source TEXT NOT NULL,
+    payload_bytes INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    outcome TEXT NOT NULL,
+    detail TEXT,
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_audit_log_created ON audit_log(created_at);
+";
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+const DEFAULT_LIST_LIMIT: usize = 200;
+
+// A failing audit write is reported once rather than on every command
+static WRITE_FAILED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    // Record every command with its duration and outcome in the history database
+    pub enabled: bool,
+    // Entries older than this are removed when the database is opened; 0 keeps them forever
+    pub retention_days: u64,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            retention_days: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub command: String,
+    // Window label for IPC calls, "remote:<client>" for the WebSocket channel
+    pub source: String,
+    pub payload_bytes: u64,
+    pub duration_ms: u64,
+    // "ok", "error", "rejected", "dispatched" or "unknown"
+    pub outcome: String,
+    pub detail: Option<String>,
+    pub created_at: u64,
+}
+
+pub fn init() -> Result<(), String> {
+    with_db(|conn| {
+        conn.execute_batch(SCHEMA).map_err(db_err)?;
+        let retention_days = config::get().audit.retention_days;
+        if retention_days > 0 {
+            let cutoff = now_ms().saturating_sub(retention_days * DAY_MS);
+            conn.execute("DELETE FROM audit_log WHERE created_at < ?1", params![cutoff as i64])
+                .map_err(db_err)?;
+        }
+        Ok(())
+    })
+}
+
+pub fn record(command: &str, source: &str, payload_bytes: usize, duration_ms: u64, outcome: &str, detail: Option<&str>) {
+    if !config::get().audit.enabled {
+        return;
+    }
+    let (command, source, outcome, detail) = (command.to_string(), source.to_string(), outcome.to_string(), detail.map(str::to_string));
+    let created_at = now_ms() as i64;
+    db_writer::submit("the audit log", move |conn| {
+        let result = conn.execute(
+            "INSERT INTO audit_log (command, source, payload_bytes, duration_ms, outcome, detail, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![command, source, payload_bytes as i64, duration_ms as i64, outcome, detail, created_at],
+        );
+        // Reported once, rather than on every command while the table is unwritable
+        if let Err(e) = result {
+            if !WRITE_FAILED.swap(true, Ordering::SeqCst) {
+                println!("Warning: Failed to write the audit log: {}", db_err(e));
+            }
+        }
+        Ok(())
+    });
+}
+
+// Most recent entries first, optionally only those of one command
+#[tauri::command]
+pub async fn list_audit_log(command: Option<String>, limit: Option<usize>) -> Result<Vec<AuditEntry>, String> {
+    let limit = limit.unwrap_or(DEFAULT_LIST_LIMIT) as i64;
+    with_db(|conn| {
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, command, source, payload_bytes, duration_ms, outcome, detail, created_at FROM audit_log
+                 WHERE ?1 IS NULL OR command = ?1 ORDER BY id DESC LIMIT ?2",
+            )
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map(params![command, limit], |row| {
+                Ok(AuditEntry {
+                    id: row.get(0)?,
+                    command: row.get(1)?,
+                    source: row.get(2)?,
+                    payload_bytes: row.get::<_, i64>(3)? as u64,
+                    duration_ms: row.get::<_, i64>(4)? as u64,
+                    outcome: row.get(5)?,
+                    detail: row.get(6)?,
+                    created_at: row.get::<_, i64>(7)? as u64,
+                })
+            })
+            .map_err(db_err)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+    })
+}
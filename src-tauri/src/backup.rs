@@ -0,0 +1,435 @@
+// Zip archives of the active profile's history database, config.toml and attachment files. Model
+// weights are never included; they can be downloaded again. Archives are written on demand by backup_now and on a
+// schedule, and restore_backup checks an archive completely before replacing anything.
+
+use rusqlite::{params, Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::config::{self, CONFIG_FILE};
+use crate::history::{self, db_err, with_db, ATTACHMENTS_DIR, HISTORY_DB_FILE};
+use crate::provenance::APP_VERSION;
+use crate::streaming::now_ms;
+use crate::{kiosk, migrations, profiles};
+
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+const MANIFEST_FILE: &str = "manifest.json";
+const ARCHIVE_PREFIX: &str = "backup-";
+const HOUR_MS: u64 = 60 * 60 * 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupConfig {
+    // Write an archive every interval_hours while the app runs
+    pub scheduled: bool,
+    pub interval_hours: u64,
+    // Scheduled and backup_now archives kept in `dir`; older ones are removed
+    pub keep: usize,
+    // Defaults to "backups" in the profile's data directory
+    pub dir: Option<String>,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            scheduled: true,
+            interval_hours: 24,
+            keep: 7,
+            dir: None,
+        }
+    }
+}
+
+impl BackupConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.interval_hours == 0 {
+            errors.push("backup.interval_hours must be at least 1".to_string());
+        }
+        if self.keep == 0 {
+            errors.push("backup.keep must be at least 1".to_string());
+        }
+        errors
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    app_version: String,
+    profile: String,
+    created_at: u64,
+    // Schema version of the database, so newer archives aren't restored into older builds
+    db_version: u32,
+    files: Vec<ManifestFile>,
+    // Whether the attachments folder is archived; older archives leave it out, and restoring them
+    // keeps the current one
+    #[serde(default)]
+    attachments: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestFile {
+    name: String,
+    size_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupInfo {
+    pub path: String,
+    pub size_bytes: u64,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreResult {
+    pub restored_from: String,
+    // Archive of the state that was replaced, in case the restore wasn't wanted
+    pub previous_state: BackupInfo,
+}
+
+fn default_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    match config::get().backup.dir {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => Ok(profiles::data_dir(app)?.join(migrations::BACKUP_DIR)),
+    }
+}
+
+fn info(path: &Path, created_at: u64) -> BackupInfo {
+    BackupInfo {
+        path: path.to_string_lossy().to_string(),
+        size_bytes: std::fs::metadata(path).map_or(0, |m| m.len()),
+        created_at,
+    }
+}
+
+// Archives in a directory, newest first; the creation time is part of the file name
+fn archives_in(dir: &Path) -> Vec<BackupInfo> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut archives: Vec<BackupInfo> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let created_at = name.strip_prefix(ARCHIVE_PREFIX)?.strip_suffix(".zip")?.parse().ok()?;
+            Some(info(&entry.path(), created_at))
+        })
+        .collect();
+    archives.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    archives
+}
+
+fn rotate(dir: &Path, keep: usize) {
+    for old in archives_in(dir).into_iter().skip(keep) {
+        if let Err(e) = std::fs::remove_file(&old.path) {
+            println!("Warning: Failed to remove old backup {}: {}", old.path, e);
+        }
+    }
+}
+
+fn add_file(zip: &mut ZipWriter<File>, name: &str, source: &mut impl Read) -> Result<(), String> {
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file(name, options).map_err(|e| format!("Failed to add {} to the backup: {}", name, e))?;
+    std::io::copy(source, zip).map_err(|e| format!("Failed to add {} to the backup: {}", name, e))?;
+    Ok(())
+}
+
+// Archive entry name of an attachment file
+fn attachment_entry(file_name: &str) -> String {
+    format!("{}/{}", ATTACHMENTS_DIR, file_name)
+}
+
+// The attachment files, opened before the manifest is written so a file deleted meanwhile can still
+// be read, with their sizes
+fn open_attachments(data_dir: &Path) -> Result<Vec<(String, File, u64)>, String> {
+    let dir = data_dir.join(ATTACHMENTS_DIR);
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+    let mut files = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        let Ok(file) = File::open(&path) else {
+            // Deleted since the directory was listed
+            continue;
+        };
+        let size = file.metadata().map_err(|e| format!("Failed to read {}: {}", path.display(), e))?.len();
+        files.push((attachment_entry(&entry.file_name().to_string_lossy()), file, size));
+    }
+    Ok(files)
+}
+
+// Writes the manifest, a database snapshot, config.toml and the attachment files to `partial`
+fn write_zip(partial: &Path, snapshot: &Path, data_dir: &Path, created_at: u64) -> Result<(), String> {
+    // VACUUM INTO gives a consistent copy while the app keeps using the database
+    let db_version: u32 = with_db(|conn| {
+        conn.execute("VACUUM INTO ?1", params![snapshot.to_string_lossy()])
+            .map_err(db_err)?;
+        conn.query_row("PRAGMA user_version", [], |row| row.get(0)).map_err(db_err)
+    })?;
+    let settings = match config::path().filter(|p| p.exists()) {
+        Some(path) => std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+        None => toml::to_string_pretty(&config::get()).map_err(|e| e.to_string())?.into_bytes(),
+    };
+
+    let mut attachments = open_attachments(data_dir)?;
+
+    let db_size = std::fs::metadata(snapshot).map_err(|e| e.to_string())?.len();
+    let mut files = vec![
+        ManifestFile { name: HISTORY_DB_FILE.to_string(), size_bytes: db_size },
+        ManifestFile { name: CONFIG_FILE.to_string(), size_bytes: settings.len() as u64 },
+    ];
+    files.extend(attachments.iter().map(|(name, _, size)| ManifestFile { name: name.clone(), size_bytes: *size }));
+    let manifest = Manifest {
+        app_version: APP_VERSION.to_string(),
+        profile: profiles::active_id(),
+        created_at,
+        db_version,
+        files,
+        attachments: true,
+    };
+    let manifest = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+
+    let file = File::create(partial).map_err(|e| format!("Failed to create {}: {}", partial.display(), e))?;
+    let mut zip = ZipWriter::new(file);
+    add_file(&mut zip, MANIFEST_FILE, &mut manifest.as_slice())?;
+    let mut db = File::open(snapshot).map_err(|e| e.to_string())?;
+    add_file(&mut zip, HISTORY_DB_FILE, &mut db)?;
+    add_file(&mut zip, CONFIG_FILE, &mut settings.as_slice())?;
+    for (name, file, _) in &mut attachments {
+        add_file(&mut zip, name, file)?;
+    }
+    let file = zip.finish().map_err(|e| format!("Failed to finish the backup: {}", e))?;
+    file.sync_all().map_err(|e| e.to_string())
+}
+
+fn write_archive(dir: &Path, data_dir: &Path) -> Result<BackupInfo, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let created_at = now_ms();
+    let path = dir.join(format!("{}{}.zip", ARCHIVE_PREFIX, created_at));
+    // Written under a temporary name so an interrupted backup never looks complete
+    let partial = dir.join(format!(".{}{}.zip.partial", ARCHIVE_PREFIX, created_at));
+    let snapshot = dir.join(format!(".{}{}.db", ARCHIVE_PREFIX, created_at));
+
+    let result = write_zip(&partial, &snapshot, data_dir, created_at)
+        .and_then(|_| std::fs::rename(&partial, &path).map_err(|e| format!("Failed to write {}: {}", path.display(), e)));
+    let _ = std::fs::remove_file(&snapshot);
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+    println!("Wrote backup {}", path.display());
+    Ok(info(&path, created_at))
+}
+
+// Reads an entry completely; the zip reader fails on a CRC mismatch once the end is reached
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>, String> {
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|_| format!("The backup has no {}", name))?;
+    let mut contents = Vec::new();
+    entry
+        .read_to_end(&mut contents)
+        .map_err(|e| format!("{} in the backup is corrupt: {}", name, e))?;
+    Ok(contents)
+}
+
+// What restore_backup puts in place once an archive has been checked
+struct Staged {
+    settings: Vec<u8>,
+    // Whether the archive carries the attachments folder, staged next to the database
+    attachments: bool,
+}
+
+// Checks every part of an archive and stages its database at `staged_db` and its attachment files in
+// `staged_attachments`. Nothing live is touched.
+fn verify(path: &Path, staged_db: &Path, staged_attachments: &Path) -> Result<Staged, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("{} is not a backup archive: {}", path.display(), e))?;
+    let manifest: Manifest = serde_json::from_slice(&read_entry(&mut archive, MANIFEST_FILE)?)
+        .map_err(|e| format!("The backup manifest is invalid: {}", e))?;
+    if manifest.db_version > migrations::latest_version() {
+        return Err(format!(
+            "The backup was made by a newer version of the app ({}) and can't be restored by this one",
+            manifest.app_version
+        ));
+    }
+
+    let mut settings = None;
+    for expected in &manifest.files {
+        let contents = read_entry(&mut archive, &expected.name)?;
+        if contents.len() as u64 != expected.size_bytes {
+            return Err(format!(
+                "{} in the backup is {} bytes, but the manifest records {}",
+                expected.name,
+                contents.len(),
+                expected.size_bytes
+            ));
+        }
+        match expected.name.as_str() {
+            HISTORY_DB_FILE => std::fs::write(staged_db, &contents).map_err(|e| e.to_string())?,
+            CONFIG_FILE => settings = Some(contents),
+            name => {
+                if let Some(file_name) = name.strip_prefix(ATTACHMENTS_DIR).and_then(|rest| rest.strip_prefix('/')) {
+                    // A bare file name, so an archive can't write outside the attachments folder
+                    if Path::new(file_name).file_name().and_then(|n| n.to_str()) != Some(file_name) {
+                        return Err(format!("The backup has an invalid attachment name: {}", name));
+                    }
+                    std::fs::create_dir_all(staged_attachments).map_err(|e| e.to_string())?;
+                    std::fs::write(staged_attachments.join(file_name), &contents).map_err(|e| e.to_string())?;
+                }
+            }
+        }
+    }
+    let settings = settings.ok_or_else(|| format!("The backup has no {}", CONFIG_FILE))?;
+    let text = std::str::from_utf8(&settings).map_err(|_| format!("{} in the backup is not text", CONFIG_FILE))?;
+    config::parse(text).map_err(|e| format!("{} in the backup is invalid: {}", CONFIG_FILE, e))?;
+
+    let conn = Connection::open_with_flags(staged_db, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("The database in the backup can't be opened: {}", e))?;
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| format!("The database in the backup can't be checked: {}", e))?;
+    if integrity != "ok" {
+        return Err(format!("The database in the backup is damaged: {}", integrity));
+    }
+    conn.query_row("SELECT COUNT(*) FROM conversations", [], |row| row.get::<_, i64>(0))
+        .map_err(|_| "The database in the backup has no chat history".to_string())?;
+    Ok(Staged { settings, attachments: manifest.attachments })
+}
+
+// Background task that writes an archive every backup.interval_hours
+pub fn spawn_scheduler(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(SCHEDULER_INTERVAL).await;
+            let settings = config::get().backup;
+            if !settings.scheduled {
+                continue;
+            }
+            let dir = match default_dir(&app) {
+                Ok(dir) => dir,
+                Err(e) => {
+                    println!("Warning: Failed to resolve the backup directory: {}", e);
+                    continue;
+                }
+            };
+            let last = archives_in(&dir).first().map_or(0, |b| b.created_at);
+            if now_ms().saturating_sub(last) < settings.interval_hours * HOUR_MS {
+                continue;
+            }
+            let archived = profiles::data_dir(&app).and_then(|data_dir| write_archive(&dir, &data_dir));
+            match archived {
+                Ok(_) => rotate(&dir, settings.keep),
+                Err(e) => println!("Warning: Scheduled backup failed: {}", e),
+            }
+        }
+    });
+}
+
+// Swaps the staged attachment files in for the current ones. The current folder is only removed once
+// the staged one is in place; the previous state's archive holds its files either way.
+fn restore_attachments(data_dir: &Path, staged: &Path) {
+    let live = data_dir.join(ATTACHMENTS_DIR);
+    let replaced = data_dir.join(format!(".replaced-{}-{}", now_ms(), ATTACHMENTS_DIR));
+    if live.exists() {
+        if let Err(e) = std::fs::rename(&live, &replaced) {
+            println!("Warning: Failed to restore attachments, keeping the current ones: {}", e);
+            return;
+        }
+    }
+    // An archive made with no attachments stages no folder
+    let restored = if staged.exists() { std::fs::rename(staged, &live) } else { std::fs::create_dir_all(&live) };
+    match restored {
+        Ok(()) => {
+            let _ = std::fs::remove_dir_all(&replaced);
+        }
+        Err(e) => {
+            println!("Warning: Failed to restore attachments, keeping the current ones: {}", e);
+            let _ = std::fs::rename(&replaced, &live);
+        }
+    }
+}
+
+// Writes an archive to target_dir, or to the backup directory (where old archives are rotated out)
+#[tauri::command]
+pub async fn backup_now(target_dir: Option<String>, app: tauri::AppHandle) -> Result<BackupInfo, String> {
+    kiosk::ensure_unlocked("Backups")?;
+    let data_dir = profiles::data_dir(&app)?;
+    match target_dir {
+        Some(dir) => write_archive(Path::new(&dir), &data_dir),
+        None => {
+            let dir = default_dir(&app)?;
+            let backup = write_archive(&dir, &data_dir)?;
+            rotate(&dir, config::get().backup.keep);
+            Ok(backup)
+        }
+    }
+}
+
+// Archives in the backup directory, newest first
+#[tauri::command]
+pub fn list_backups(app: tauri::AppHandle) -> Result<Vec<BackupInfo>, String> {
+    Ok(archives_in(&default_dir(&app)?))
+}
+
+// Replaces the active profile's database, config.toml and attachment files with an archive's. The
+// archive is fully checked first, and the current state is backed up before anything is replaced.
+#[tauri::command]
+pub async fn restore_backup(path: String, app: tauri::AppHandle) -> Result<RestoreResult, String> {
+    kiosk::ensure_unlocked("Restoring backups")?;
+    let data_dir = profiles::data_dir(&app)?;
+    let started = now_ms();
+    let staged_db = data_dir.join(format!(".restore-{}.db", started));
+    let staged_attachments = data_dir.join(format!(".restore-{}-{}", started, ATTACHMENTS_DIR));
+    let discard_staged = || {
+        let _ = std::fs::remove_file(&staged_db);
+        let _ = std::fs::remove_dir_all(&staged_attachments);
+    };
+    let staged = match verify(Path::new(&path), &staged_db, &staged_attachments) {
+        Ok(staged) => staged,
+        Err(e) => {
+            discard_staged();
+            return Err(e);
+        }
+    };
+    let previous_state = match write_archive(&default_dir(&app)?, &data_dir) {
+        Ok(backup) => backup,
+        Err(e) => {
+            discard_staged();
+            return Err(format!("Not restoring, since the current state couldn't be backed up first: {}", e));
+        }
+    };
+
+    history::close()?;
+    let db_path = data_dir.join(HISTORY_DB_FILE);
+    for suffix in ["-wal", "-shm"] {
+        let _ = std::fs::remove_file(data_dir.join(format!("{}{}", HISTORY_DB_FILE, suffix)));
+    }
+    let replaced = std::fs::rename(&staged_db, &db_path).map_err(|e| format!("Failed to replace {}: {}", db_path.display(), e));
+    if replaced.is_ok() && staged.attachments {
+        restore_attachments(&data_dir, &staged_attachments);
+    }
+    let _ = std::fs::remove_dir_all(&staged_attachments);
+    if let Some(config_path) = config::path() {
+        // The config watcher picks the restored settings up like any other edit
+        if let Err(e) = std::fs::write(&config_path, &staged.settings) {
+            println!("Warning: Failed to restore {}: {}", config_path.display(), e);
+        }
+    }
+    // Reopened even when the rename failed, so history keeps working on the old database
+    crate::open_stores(&app)?;
+    replaced?;
+
+    println!("Restored backup {}", path);
+    Ok(RestoreResult { restored_from: path, previous_state })
+}
@@ -0,0 +1,262 @@
+// Benchmark matrix for the hello_world example: every local model at every quantization it can load
+// with, timed on a fixed prompt set. Models are found, grouped and held the way the app does it, so
+// the numbers match what a chat would see.
+
+use mistralrs::{
+    GgufModelBuilder, IsqType, RequestBuilder, TextMessageRole, TextMessages, UqffTextModelBuilder,
+    UqffVisionModelBuilder, VisionModelBuilder,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use sysinfo::System;
+
+use crate::models::{LoadedModel, ModelManager};
+use crate::{discover_local_models, get_uqff_files, hardware, isq, prompt_format, provenance};
+
+pub const PROMPTS: [&str; 3] = [
+    "Hello! Please respond with just 'Hi there!' to confirm you're working.",
+    "Explain in three sentences what a Rust lifetime is.",
+    "Write a Rust function that reverses the words in a sentence.",
+];
+
+// Tokens generated per prompt
+const MAX_TOKENS: usize = 128;
+
+// Levels tried for models that are quantized in place (ISQ) as they load
+const ISQ_LEVELS: [IsqType; 3] = [IsqType::Q4K, IsqType::Q5K, IsqType::Q8_0];
+
+#[derive(Debug, Clone)]
+enum Weights {
+    Gguf(String),
+    Uqff(Vec<PathBuf>),
+    Isq(IsqType),
+}
+
+// One model at one quantization
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub model_id: String,
+    pub quantization: String,
+    model_dir: String,
+    model_type: String,
+    weights: Weights,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchResult {
+    pub model_id: String,
+    pub quantization: String,
+    pub load_ms: Option<u64>,
+    pub prompt_tok_per_sec: Option<f64>,
+    pub completion_tok_per_sec: Option<f64>,
+    // Growth of the process's resident memory from before the load to after the last prompt
+    pub memory_mb: Option<u64>,
+    pub error: Option<String>,
+}
+
+// Every variant under `base_path`, in the order discover_models lists the models
+pub fn variants(base_path: &str) -> Result<Vec<Variant>, String> {
+    let discovered = discover_local_models(base_path).map_err(|e| format!("Failed to discover local models: {}", e))?;
+    let mut variants = Vec::new();
+    for (model_dir, model_file, model_type) in discovered {
+        let model_id = if model_dir.is_empty() {
+            format!("local-{}", model_file.replace(".gguf", ""))
+        } else {
+            format!("local-{}", model_dir)
+        };
+        let variant = |quantization: String, weights: Weights| Variant {
+            model_id: model_id.clone(),
+            quantization,
+            model_dir: model_dir.clone(),
+            model_type: model_type.clone(),
+            weights,
+        };
+        match model_type.as_str() {
+            // Each GGUF file is its own model and quantization
+            "gguf" | "gguf-vision" => {
+                let quantization = provenance::gguf_quantization(&model_file).unwrap_or_else(|| model_file.clone());
+                variants.push(variant(quantization, Weights::Gguf(model_file.clone())));
+            }
+            "smollm3" | "llama-uqff" | "llama-uqff-vision" => {
+                let path = Path::new(base_path).join(&model_dir);
+                let mut by_quantization: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+                for file in get_uqff_files(&path.to_string_lossy())? {
+                    let quantization = provenance::uqff_quantization(&file.to_string_lossy()).unwrap_or_default();
+                    by_quantization.entry(quantization).or_default().push(file);
+                }
+                for (quantization, files) in by_quantization {
+                    variants.push(variant(format!("UQFF {}", quantization), Weights::Uqff(files)));
+                }
+            }
+            _ => {
+                for level in ISQ_LEVELS {
+                    variants.push(variant(format!("ISQ {}", isq::name(level)), Weights::Isq(level)));
+                }
+            }
+        }
+    }
+    Ok(variants)
+}
+
+// Builds a variant with the builder the app's loader uses for its model type
+async fn load(base_path: &str, variant: &Variant) -> Result<mistralrs::Model, String> {
+    let model_path = Path::new(base_path).join(&variant.model_dir);
+    let path = model_path.to_string_lossy().to_string();
+    let force_cpu = hardware::use_cpu();
+    let built = match (&variant.weights, variant.model_type.as_str()) {
+        (Weights::Gguf(file), _) => {
+            let mut builder = GgufModelBuilder::new(format!("{}/", path), vec![file.clone()]);
+            if let Some(template) = prompt_format::template_file(&model_path) {
+                builder = builder.with_chat_template(template.to_string_lossy());
+            }
+            if force_cpu {
+                builder = builder.with_force_cpu();
+            }
+            builder.build().await
+        }
+        (Weights::Uqff(files), "llama-uqff-vision") => {
+            let (level, _) = isq::for_model(&variant.model_id, IsqType::Q5_0);
+            let mut builder = UqffVisionModelBuilder::new(&path, files.clone()).into_inner().with_isq(level);
+            if force_cpu {
+                builder = builder.with_force_cpu();
+            }
+            builder.build().await
+        }
+        (Weights::Uqff(files), model_type) => {
+            let mut builder = UqffTextModelBuilder::new(&path, files.clone()).into_inner();
+            // Llama UQFF weights are quantized again as they load; SmolLM3's are used as they are
+            if model_type == "llama-uqff" {
+                builder = builder.with_isq(isq::for_model(&variant.model_id, IsqType::Q5_0).0);
+            }
+            if force_cpu {
+                builder = builder.with_force_cpu();
+            }
+            builder.build().await
+        }
+        (Weights::Isq(level), _) => {
+            let mut builder = VisionModelBuilder::new(&path).with_isq(*level);
+            if force_cpu {
+                builder = builder.with_force_cpu();
+            }
+            builder.build().await
+        }
+    };
+    built.map_err(|e: anyhow::Error| format!("Failed to load: {}", e))
+}
+
+fn resident_mb(system: &mut System) -> Option<u64> {
+    let pid = sysinfo::get_current_pid().ok()?;
+    system.refresh_process(pid);
+    system.process(pid).map(|process| process.memory() / (1024 * 1024))
+}
+
+async fn measure(base_path: &str, variant: &Variant, manager: &ModelManager, system: &mut System) -> BenchResult {
+    let mut result = BenchResult {
+        model_id: variant.model_id.clone(),
+        quantization: variant.quantization.clone(),
+        load_ms: None,
+        prompt_tok_per_sec: None,
+        completion_tok_per_sec: None,
+        memory_mb: None,
+        error: None,
+    };
+    let memory_before = resident_mb(system);
+
+    let started = Instant::now();
+    let model = match load(base_path, variant).await {
+        Ok(model) => Arc::new(model),
+        Err(e) => {
+            result.error = Some(e);
+            return result;
+        }
+    };
+    result.load_ms = Some(started.elapsed().as_millis() as u64);
+    manager.lock().await.insert(
+        variant.model_id.clone(),
+        LoadedModel { model: model.clone(), last_used: Instant::now(), size_bytes: None },
+    );
+
+    let (mut prompt_rates, mut completion_rates) = (Vec::new(), Vec::new());
+    for prompt in PROMPTS {
+        let request = RequestBuilder::from(TextMessages::new().add_message(TextMessageRole::User, prompt))
+            .set_sampler_max_len(MAX_TOKENS);
+        match model.send_chat_request(request).await {
+            Ok(response) => {
+                prompt_rates.push(response.usage.avg_prompt_tok_per_sec as f64);
+                completion_rates.push(response.usage.avg_compl_tok_per_sec as f64);
+            }
+            Err(e) => {
+                result.error = Some(format!("Generation failed: {}", e));
+                break;
+            }
+        }
+    }
+    let average = |rates: &[f64]| (!rates.is_empty()).then(|| rates.iter().sum::<f64>() / rates.len() as f64);
+    result.prompt_tok_per_sec = average(&prompt_rates);
+    result.completion_tok_per_sec = average(&completion_rates);
+    result.memory_mb = memory_before.zip(resident_mb(system)).map(|(before, after)| after.saturating_sub(before));
+
+    // Only one variant is loaded at a time, so each starts from the same baseline
+    drop(model);
+    manager.clear().await;
+    result
+}
+
+// Runs every variant in turn; `on_result` sees each result as soon as it is measured
+pub async fn run(base_path: &str, mut on_result: impl FnMut(&BenchResult)) -> Result<Vec<BenchResult>, String> {
+    let manager = ModelManager::default();
+    let mut system = System::new();
+    let mut results = Vec::new();
+    for variant in variants(base_path)? {
+        let result = measure(base_path, &variant, &manager, &mut system).await;
+        on_result(&result);
+        results.push(result);
+    }
+    Ok(results)
+}
+
+fn cell(value: Option<f64>) -> String {
+    value.map(|v| format!("{:.1}", v)).unwrap_or_default()
+}
+
+fn row(result: &BenchResult) -> [String; 7] {
+    [
+        result.model_id.clone(),
+        result.quantization.clone(),
+        result.load_ms.map(|ms| ms.to_string()).unwrap_or_default(),
+        cell(result.prompt_tok_per_sec),
+        cell(result.completion_tok_per_sec),
+        result.memory_mb.map(|mb| mb.to_string()).unwrap_or_default(),
+        result.error.clone().unwrap_or_default(),
+    ]
+}
+
+const HEADER: [&str; 7] = ["model", "quantization", "load_ms", "prompt_tok_s", "completion_tok_s", "memory_mb", "error"];
+
+pub fn to_csv(results: &[BenchResult]) -> String {
+    let quote = |field: &str| {
+        if field.contains([',', '"', '\n']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    };
+    let mut csv = HEADER.join(",") + "\n";
+    for result in results {
+        let fields: Vec<String> = row(result).iter().map(|field| quote(field)).collect();
+        csv.push_str(&(fields.join(",") + "\n"));
+    }
+    csv
+}
+
+pub fn to_markdown(results: &[BenchResult]) -> String {
+    let mut table = format!("| {} |\n|{}\n", HEADER.join(" | "), "---|".repeat(HEADER.len()));
+    for result in results {
+        let fields: Vec<String> = row(result).iter().map(|field| field.replace('|', "\\|").replace('\n', " ")).collect();
+        table.push_str(&format!("| {} |\n", fields.join(" | ")));
+    }
+    table
+}
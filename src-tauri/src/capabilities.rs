@@ -0,0 +1,74 @@
+// What a local model can do, read from its own metadata instead of guessed from folder names: the
+// architecture and context length from config.json or the GGUF header, and whether it takes images
+// or audio.
+// A model is a vision model when its architecture is a vision architecture in the compatibility
+// table, or its config.json has a vision_config; "llama" in a name says nothing either way.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::compat;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Capabilities {
+    // general.architecture for GGUF, config.json's first "architectures" entry otherwise
+    pub architecture: Option<String>,
+    pub vision: bool,
+    // Multimodal configs with an audio_config (Gemma 3n) take audio as well
+    pub audio: bool,
+    // Trained context length in tokens, when the metadata has one
+    pub context_length: Option<u64>,
+}
+
+// From a GGUF file's header. The GGUF loader only builds text models, so a GGUF file is only a
+// vision model if the compatibility table says its architecture is one.
+pub fn from_gguf(path: &Path) -> Capabilities {
+    let metadata = match compat::gguf_file_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            println!("Warning: Failed to detect the capabilities of {}: {}", path.display(), e);
+            return Capabilities::default();
+        }
+    };
+    let architecture = metadata.strings.get("general.architecture").cloned();
+    let context_length = architecture
+        .as_ref()
+        .and_then(|architecture| metadata.integers.get(&format!("{}.context_length", architecture)).copied());
+    let vision = architecture
+        .as_deref()
+        .and_then(|architecture| compat::lookup(architecture, "gguf"))
+        .is_some_and(|support| support.vision);
+    Capabilities { architecture, vision, audio: false, context_length }
+}
+
+// From a model directory's config.json; empty when there is none (e.g. SmolLM3's UQFF files)
+pub fn from_config(dir: &Path) -> Capabilities {
+    let Some(config) = std::fs::read_to_string(dir.join("config.json"))
+        .ok()
+        .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok())
+    else {
+        return Capabilities::default();
+    };
+    let architecture = config["architectures"][0].as_str().map(str::to_string);
+    // Multimodal configs keep the language model's settings under text_config
+    let context_length = config["max_position_embeddings"]
+        .as_u64()
+        .or_else(|| config["text_config"]["max_position_embeddings"].as_u64());
+    let vision = config.get("vision_config").is_some()
+        || architecture
+            .as_deref()
+            .and_then(|architecture| compat::lookup(architecture, "uqff"))
+            .is_some_and(|support| support.vision);
+    let audio = config.get("audio_config").is_some();
+    Capabilities { architecture, vision, audio, context_length }
+}
+
+// Capabilities of a model found by discover_local_models
+pub fn of_local(base_path: &str, model_dir: &str, model_file: &str, model_type: &str) -> Capabilities {
+    let dir = Path::new(base_path).join(model_dir);
+    if model_type.starts_with("gguf") {
+        from_gguf(&dir.join(model_file))
+    } else {
+        from_config(&dir)
+    }
+}
@@ -0,0 +1,382 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::compat;
+use crate::config;
+use crate::hf_cache;
+use crate::kiosk;
+use crate::presentation;
+use crate::provenance;
+use crate::streaming;
+use crate::trace;
+
+// Header bytes requested first when previewing a GGUF file; doubled while the wanted keys are missing
+const INITIAL_HEADER_BYTES: u64 = 1024 * 1024;
+const MAX_HEADER_BYTES: u64 = 16 * 1024 * 1024;
+
+const HEADER_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Header previews by catalog directory; the files behind a catalog entry never change
+static GGUF_PREVIEWS: Mutex<Option<HashMap<String, GgufPreview>>> = Mutex::new(None);
+
+// Known downloadable models, keyed by the directory the downloader places them in
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogEntry {
+    pub directory: &'static str,
+    pub repo: &'static str,
+    // SPDX-style identifier or the license name used on the Hugging Face model card
+    pub license: &'static str,
+    // Whether any file comes from a repo that requires accepting terms on Hugging Face
+    pub gated: bool,
+    // Repos the files are fetched from; can differ from `repo` for base tokenizer/config files
+    pub source_repos: &'static [&'static str],
+    // Total size of the files the downloader fetches, as listed on Hugging Face
+    pub download_bytes: u64,
+    // The weights file in `repo`, for GGUF models whose header can be previewed
+    pub gguf_file: Option<&'static str>,
+    // Base model whose tokenizer_config.json the downloader saves beside weights-only repos, so the
+    // chat template travels with the model. Best effort: without it the bundled templates apply
+    pub chat_template_repo: Option<&'static str>,
+}
+
+pub const CATALOG: &[CatalogEntry] = &[
+    CatalogEntry {
+        directory: "mistral-gguf",
+        repo: "TheBloke/Mistral-7B-Instruct-v0.1-GGUF",
+        license: "apache-2.0",
+        gated: false,
+        source_repos: &["TheBloke/Mistral-7B-Instruct-v0.1-GGUF", "mistralai/Mistral-7B-Instruct-v0.1"],
+        download_bytes: 4_368_439_000,
+        gguf_file: Some("mistral-7b-instruct-v0.1.Q4_K_M.gguf"),
+        chat_template_repo: Some("mistralai/Mistral-7B-Instruct-v0.1"),
+    },
+    CatalogEntry {
+        directory: "llama-vision",
+        repo: "EricB/Llama-3.2-11B-Vision-Instruct-UQFF",
+        license: "llama3.2",
+        gated: false,
+        source_repos: &["EricB/Llama-3.2-11B-Vision-Instruct-UQFF"],
+        download_bytes: 23_790_000_000,
+        gguf_file: None,
+        chat_template_repo: None,
+    },
+    CatalogEntry {
+        directory: "gemma-3n-e2b",
+        repo: "EricB/gemma-3n-E2B-it-UQFF",
+        license: "gemma",
+        gated: true,
+        source_repos: &["EricB/gemma-3n-E2B-it-UQFF", "google/gemma-3n-E2B-it"],
+        download_bytes: 10_823_000_000,
+        gguf_file: None,
+        chat_template_repo: None,
+    },
+    CatalogEntry {
+        directory: "smollm3-3b",
+        repo: "EricB/SmolLM3-3B-UQFF",
+        license: "apache-2.0",
+        gated: false,
+        source_repos: &["EricB/SmolLM3-3B-UQFF", "HuggingFaceTB/SmolLM3-3B"],
+        download_bytes: 9_918_000_000,
+        gguf_file: None,
+        chat_template_repo: None,
+    },
+];
+
+// Models streamed from Hugging Face at load time instead of being downloaded first
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteModel {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub model_type: &'static str,
+    pub repo: &'static str,
+    pub license: &'static str,
+    // The loader refuses to start without a Hugging Face token
+    pub needs_token: bool,
+}
+
+pub const REMOTE_MODELS: &[RemoteModel] = &[
+    RemoteModel {
+        id: "mistral-7b-remote",
+        name: "Mistral 7B Instruct (remote)",
+        description: "Q4_K_M GGUF fetched from Hugging Face on first load",
+        model_type: "remote-gguf",
+        repo: "TheBloke/Mistral-7B-Instruct-v0.1-GGUF",
+        license: "apache-2.0",
+        needs_token: true,
+    },
+    RemoteModel {
+        id: "smollm3-remote",
+        name: "SmolLM3 3B (remote)",
+        description: "Fetched from Hugging Face on first load and quantized to Q8_0 in memory",
+        model_type: "remote-text",
+        repo: "HuggingFaceTB/SmolLM3-3B",
+        license: "apache-2.0",
+        needs_token: false,
+    },
+];
+
+// Offline when configured, when the Hugging Face libraries are told to be, or in presentation mode
+pub fn is_offline() -> bool {
+    config::get().models.offline
+        || presentation::is_active()
+        || std::env::var("HF_HUB_OFFLINE").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+// Why a remote model can't be loaded right now, if it can't
+pub fn remote_unavailable_reason(model: &RemoteModel, app: &tauri::AppHandle) -> Option<String> {
+    if is_offline() {
+        return Some("Offline mode is on".to_string());
+    }
+    if model.needs_token && hf_token(app).is_none() {
+        return Some("Needs a Hugging Face token: set HF_TOKEN in .env or run `huggingface-cli login`".to_string());
+    }
+    None
+}
+
+// Decimal units, matching the sizes Hugging Face shows
+pub fn format_size(bytes: u64) -> String {
+    if bytes >= 1_000_000_000 {
+        format!("{:.2} GB", bytes as f64 / 1e9)
+    } else {
+        format!("{:.1} MB", bytes as f64 / 1e6)
+    }
+}
+
+pub fn lookup(directory: &str) -> Option<&'static CatalogEntry> {
+    CATALOG.iter().find(|entry| entry.directory.eq_ignore_ascii_case(directory))
+}
+
+// Outcome of probing a Hugging Face repo before downloading from it
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelAccess {
+    pub repo: String,
+    // "ok", "needs_token", "needs_terms", "not_found" or "error"
+    pub status: String,
+    pub has_token: bool,
+    pub message: String,
+    pub terms_url: Option<String>,
+}
+
+// Reads the HF token from the environment or the `huggingface-cli login` token file
+pub fn hf_token(app: &tauri::AppHandle) -> Option<String> {
+    for var in ["HF_TOKEN", "HUGGING_FACE_HUB_TOKEN"] {
+        if let Ok(token) = std::env::var(var) {
+            if !token.trim().is_empty() {
+                return Some(token.trim().to_string());
+            }
+        }
+    }
+
+    let (home, _) = hf_cache::resolve(app).ok()?;
+    std::fs::read_to_string(home.join("token"))
+        .ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+// Turns the HTTP status of a gated-repo probe into guidance for the user
+fn classify(repo: &str, status: u16, has_token: bool) -> ModelAccess {
+    let terms_url = format!("https://huggingface.co/{}", repo);
+    let (status, message, terms_url) = match status {
+        200..=399 => ("ok", "Repository is accessible".to_string(), None),
+        401 if !has_token => (
+            "needs_token",
+            format!(
+                "{} is gated. Create a token at https://huggingface.co/settings/tokens and set HF_TOKEN in .env (or run `huggingface-cli login`)",
+                repo
+            ),
+            Some(terms_url),
+        ),
+        401 | 403 => (
+            "needs_terms",
+            format!(
+                "Your Hugging Face token cannot access {}. Open {} while logged in, accept the license terms, then retry",
+                repo, terms_url
+            ),
+            Some(terms_url),
+        ),
+        404 => ("not_found", format!("Repository {} was not found", repo), None),
+        other => ("error", format!("Hugging Face returned HTTP {} for {}", other, repo), None),
+    };
+
+    ModelAccess {
+        repo: repo.to_string(),
+        status: status.to_string(),
+        has_token,
+        message,
+        terms_url,
+    }
+}
+
+// Probes a repo's config.json so gated downloads fail with instructions instead of a raw 403
+#[tauri::command]
+pub async fn check_model_access(repo: String, app: tauri::AppHandle) -> Result<ModelAccess, String> {
+    kiosk::ensure_unlocked("Model downloads")?;
+    let token = hf_token(&app);
+    let url = format!("https://huggingface.co/{}/resolve/main/config.json", repo);
+
+    let mut request = reqwest::Client::new().head(&url);
+    if let Some(token) = &token {
+        request = request.bearer_auth(token);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach Hugging Face: {}", e))?;
+
+    // GGUF-only repos have no config.json, which is fine as long as the repo itself is reachable
+    let mut status = response.status().as_u16();
+    if status == 404 {
+        let mut request = reqwest::Client::new().head(format!("https://huggingface.co/api/models/{}", repo));
+        if let Some(token) = &token {
+            request = request.bearer_auth(token);
+        }
+        if let Ok(response) = request.send().await {
+            status = response.status().as_u16();
+        }
+    }
+
+    Ok(classify(&repo, status, token.is_some()))
+}
+
+#[tauri::command]
+pub fn list_model_catalog() -> Vec<CatalogEntry> {
+    CATALOG.to_vec()
+}
+
+// What a catalog GGUF file's header says, read before the file is downloaded
+#[derive(Debug, Clone, Serialize)]
+pub struct GgufPreview {
+    pub directory: String,
+    pub file: String,
+    pub architecture: Option<String>,
+    pub name: Option<String>,
+    pub context_length: Option<u64>,
+    // From general.file_type, else from the file name
+    pub quantization: Option<String>,
+    pub header_bytes: u64,
+}
+
+// llama.cpp's general.file_type values for the common quantizations
+fn file_type_name(file_type: u64) -> Option<&'static str> {
+    Some(match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        30 => "IQ4_XS",
+        32 => "BF16",
+        _ => return None,
+    })
+}
+
+// The first `bytes` bytes of a file in a Hugging Face repo, via an HTTP range request
+async fn fetch_prefix(client: &reqwest::Client, url: &str, bytes: u64, token: Option<&str>) -> Result<Vec<u8>, String> {
+    let mut request = client.get(url).header(reqwest::header::RANGE, format!("bytes=0-{}", bytes - 1));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let mut response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach Hugging Face: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Hugging Face returned HTTP {} for {}", response.status().as_u16(), url));
+    }
+    // A server that ignores the range would send the whole file, so stop reading at the limit
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| format!("Failed to read {}: {}", url, e))? {
+        data.extend_from_slice(&chunk);
+        if data.len() as u64 >= bytes {
+            data.truncate(bytes as usize);
+            break;
+        }
+    }
+    Ok(data)
+}
+
+// Fetches growing prefixes of a GGUF file until its metadata holds the context length, returning
+// the metadata and how many bytes that took
+async fn fetch_header(url: &str, file: &str, request_id: &str, app: &tauri::AppHandle) -> Result<(compat::GgufMetadata, u64), String> {
+    let client = reqwest::Client::builder()
+        .timeout(HEADER_FETCH_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let token = hf_token(app);
+    let mut bytes = INITIAL_HEADER_BYTES;
+    loop {
+        trace::log(request_id, format!("Fetching the first {} of {}", format_size(bytes), url));
+        let data = fetch_prefix(&client, url, bytes, token.as_deref()).await?;
+        let metadata = compat::read_gguf_metadata(&mut Cursor::new(&data), file)?;
+        let architecture = metadata.strings.get("general.architecture");
+        let has_context = architecture.is_some_and(|arch| metadata.integers.contains_key(&format!("{}.context_length", arch)));
+        if !metadata.truncated || has_context || bytes >= MAX_HEADER_BYTES {
+            return Ok((metadata, data.len() as u64));
+        }
+        bytes *= 2;
+    }
+}
+
+// Reads a catalog GGUF model's architecture, context length and quantization from the start of
+// the file on Hugging Face, without downloading the weights
+#[tauri::command]
+pub async fn preview_catalog_model(directory: String, app: tauri::AppHandle) -> Result<GgufPreview, String> {
+    let entry = lookup(&directory).ok_or_else(|| format!("{} is not in the model catalog", directory))?;
+    let file = entry
+        .gguf_file
+        .ok_or_else(|| format!("{} isn't a GGUF model, so there is no header to preview", entry.directory))?;
+    if let Some(preview) = GGUF_PREVIEWS.lock().unwrap().as_ref().and_then(|previews| previews.get(entry.directory)) {
+        return Ok(preview.clone());
+    }
+    kiosk::ensure_unlocked("Model downloads")?;
+    if is_offline() {
+        return Err("Offline mode is on".to_string());
+    }
+
+    let url = format!("https://huggingface.co/{}/resolve/main/{}", entry.repo, file);
+    let request_id = streaming::new_request_id();
+    trace::begin(&request_id, "download");
+    let (metadata, header_bytes) = fetch_header(&url, file, &request_id, &app)
+        .await
+        .map_err(|e| trace::fail(&request_id, &e))?;
+
+    let architecture = metadata.strings.get("general.architecture").cloned();
+    let preview = GgufPreview {
+        directory: entry.directory.to_string(),
+        file: file.to_string(),
+        context_length: architecture
+            .as_ref()
+            .and_then(|arch| metadata.integers.get(&format!("{}.context_length", arch)).copied()),
+        architecture,
+        name: metadata.strings.get("general.name").cloned(),
+        quantization: metadata
+            .integers
+            .get("general.file_type")
+            .and_then(|&file_type| file_type_name(file_type))
+            .map(str::to_string)
+            .or_else(|| provenance::gguf_quantization(file)),
+        header_bytes,
+    };
+    GGUF_PREVIEWS
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(entry.directory.to_string(), preview.clone());
+    Ok(preview)
+}
@@ -0,0 +1,90 @@
+// Deterministic failure injection for exercising error paths and UI states. Only builds with the
+// "chaos" feature honour it; otherwise the hooks do nothing and the commands refuse.
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "chaos")]
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Faults {
+    // Extra delay before every model load
+    pub slow_load_ms: Option<u64>,
+    // Model loads fail as if the backend ran out of memory
+    pub load_out_of_memory: bool,
+    // Model loads fail as if the downloaded weights were corrupted
+    pub corrupt_download: bool,
+    // Streams are cut off after this many chunks
+    pub disconnect_after_tokens: Option<usize>,
+}
+
+#[cfg(feature = "chaos")]
+static FAULTS: Mutex<Faults> = Mutex::new(Faults {
+    slow_load_ms: None,
+    load_out_of_memory: false,
+    corrupt_download: false,
+    disconnect_after_tokens: None,
+});
+
+#[cfg(feature = "chaos")]
+fn current() -> Faults {
+    FAULTS.lock().unwrap().clone()
+}
+
+#[cfg(not(feature = "chaos"))]
+fn current() -> Faults {
+    Faults::default()
+}
+
+// Called by the model manager before it loads a model
+pub async fn before_load(model_id: &str) -> Result<(), String> {
+    let faults = current();
+    if let Some(ms) = faults.slow_load_ms {
+        println!("Chaos: delaying load of {} by {}ms", model_id, ms);
+        tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
+    }
+    if faults.load_out_of_memory {
+        return Err(format!("Failed to load {}: out of memory (injected)", model_id));
+    }
+    if faults.corrupt_download {
+        return Err(format!("Failed to load {}: the downloaded weights are corrupted (injected)", model_id));
+    }
+    Ok(())
+}
+
+// Called for every streamed chunk with the number of chunks received so far
+pub fn on_chunk(streamed: usize) -> Result<(), String> {
+    match current().disconnect_after_tokens {
+        Some(limit) if streamed >= limit => Err(format!("Stream disconnected after {} tokens (injected)", streamed)),
+        _ => Ok(()),
+    }
+}
+
+// Replaces the active faults; they stay in effect until cleared
+#[tauri::command]
+pub fn inject_faults(faults: Faults) -> Result<Faults, String> {
+    #[cfg(feature = "chaos")]
+    {
+        println!("Chaos: injecting {:?}", faults);
+        *FAULTS.lock().unwrap() = faults.clone();
+        Ok(faults)
+    }
+    #[cfg(not(feature = "chaos"))]
+    {
+        let _ = faults;
+        Err("Failure injection is not included in this build; build with --features chaos".to_string())
+    }
+}
+
+#[tauri::command]
+pub fn clear_faults() {
+    #[cfg(feature = "chaos")]
+    {
+        *FAULTS.lock().unwrap() = Faults::default();
+    }
+}
+
+#[tauri::command]
+pub fn get_faults() -> Faults {
+    current()
+}
@@ -0,0 +1,95 @@
+// SHA-256 checks of downloaded model files against the hashes the downloader recorded from
+// Hugging Face's LFS metadata, so a corrupt file shows up in the model list instead of as a
+// "Failed to build model" error at load time. Hashing a multi-GB file takes a while, so a result is
+// remembered until the file's size or modification time changes.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// Written by `cargo run --example download_models` into each model directory
+pub const CHECKSUM_FILE: &str = ".download_checksums.json";
+
+const HASH_BUFFER_BYTES: usize = 8 * 1024 * 1024;
+
+// File -> (size, modified, matched) of files already hashed
+static VERIFIED: Mutex<Option<HashMap<PathBuf, (u64, SystemTime, bool)>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChecksumReport {
+    pub model_id: String,
+    // Files with a recorded checksum
+    pub checked: usize,
+    // One line per missing or mismatched file
+    pub failures: Vec<String>,
+}
+
+fn sha256_file(path: &Path) -> Result<String, String> {
+    use sha2::{Digest, Sha256};
+
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_BUFFER_BYTES];
+    loop {
+        let read = file.read(&mut buffer).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+// Whether a file matches its recorded hash, reusing the answer while the file is unchanged
+fn matches(path: &Path, expected: &str) -> Result<bool, String> {
+    let metadata = std::fs::metadata(path).map_err(|e| format!("{} is missing: {}", path.display(), e))?;
+    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    let known = VERIFIED.lock().unwrap().as_ref().and_then(|verified| verified.get(path).copied());
+    if let Some((size, at, matched)) = known {
+        if size == metadata.len() && at == modified {
+            return Ok(matched);
+        }
+    }
+
+    println!("Checking the SHA-256 of {}", path.display());
+    let matched = sha256_file(path)?.eq_ignore_ascii_case(expected);
+    VERIFIED
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(path.to_path_buf(), (metadata.len(), modified, matched));
+    Ok(matched)
+}
+
+// Problems with the files of a model directory; empty when every recorded file matches or the
+// directory has no checksums (e.g. models copied in by hand)
+pub fn failures(model_dir: &Path) -> (usize, Vec<String>) {
+    let Some(expected): Option<HashMap<String, String>> = std::fs::read_to_string(model_dir.join(CHECKSUM_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+    else {
+        return (0, Vec::new());
+    };
+    let failures = expected
+        .iter()
+        .filter_map(|(file, sha256)| match matches(&model_dir.join(file), sha256) {
+            Ok(true) => None,
+            Ok(false) => Some(format!("{} doesn't match its SHA-256; download it again", file)),
+            Err(e) => Some(e),
+        })
+        .collect();
+    (expected.len(), failures)
+}
+
+// Hashes a downloaded model's files against the checksums recorded when it was downloaded
+#[tauri::command]
+pub async fn verify_model_checksums(model_id: String, app: tauri::AppHandle) -> Result<ChecksumReport, String> {
+    let dir = crate::local_model_dir(&model_id, &app).ok_or_else(|| format!("{} is not a downloaded model directory", model_id))?;
+    let (checked, failures) = tauri::async_runtime::spawn_blocking(move || failures(&dir))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(ChecksumReport { model_id, checked, failures })
+}
@@ -0,0 +1,119 @@
+use serde::Serialize;
+
+use crate::history::{self, with_db};
+use crate::priority::Priority;
+use crate::{generate_text, kiosk, sampling};
+
+// A fenced code block inside a message; its id is "<message_id>:<index>"
+#[derive(Debug, Clone, Serialize)]
+pub struct CodeBlock {
+    pub block_id: String,
+    pub language: Option<String>,
+    pub code: String,
+}
+
+// Tests generated for a code block, kept separate from the original response
+#[derive(Debug, Clone, Serialize)]
+pub struct TestScaffold {
+    pub block_id: String,
+    pub language: Option<String>,
+    pub code: String,
+    // The model's full reply, in case it explained how to run the tests
+    pub raw_response: String,
+}
+
+// Finds ``` fenced blocks; an unterminated final fence runs to the end of the text
+pub fn extract(text: &str) -> Vec<(Option<String>, String)> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(Option<String>, Vec<&str>)> = None;
+
+    for line in text.lines() {
+        if let Some(info) = line.trim_start().strip_prefix("```") {
+            match current.take() {
+                Some((lang, lines)) => blocks.push((lang, lines.join("\n"))),
+                None => {
+                    let info = info.trim();
+                    current = Some(((!info.is_empty()).then(|| info.to_string()), Vec::new()));
+                }
+            }
+        } else if let Some((_, lines)) = current.as_mut() {
+            lines.push(line);
+        }
+    }
+    if let Some((lang, lines)) = current {
+        blocks.push((lang, lines.join("\n")));
+    }
+
+    blocks
+}
+
+fn parse_block_id(block_id: &str) -> Result<(&str, usize), String> {
+    let (message_id, index) = block_id
+        .rsplit_once(':')
+        .ok_or_else(|| format!("Invalid block id: {}", block_id))?;
+    let index = index.parse().map_err(|_| format!("Invalid block id: {}", block_id))?;
+    Ok((message_id, index))
+}
+
+fn block_by_id(block_id: &str) -> Result<CodeBlock, String> {
+    let (message_id, index) = parse_block_id(block_id)?;
+    let content = with_db(|conn| history::message_content(conn, message_id))?;
+    let (language, code) = extract(&content)
+        .into_iter()
+        .nth(index)
+        .ok_or_else(|| format!("Message {} has no code block {}", message_id, index))?;
+    Ok(CodeBlock {
+        block_id: block_id.to_string(),
+        language,
+        code,
+    })
+}
+
+#[tauri::command]
+pub fn list_code_blocks(message_id: String) -> Result<Vec<CodeBlock>, String> {
+    let content = with_db(|conn| history::message_content(conn, &message_id))?;
+    Ok(extract(&content)
+        .into_iter()
+        .enumerate()
+        .map(|(index, (language, code))| CodeBlock {
+            block_id: format!("{}:{}", message_id, index),
+            language,
+            code,
+        })
+        .collect())
+}
+
+// Asks the model for unit tests covering one extracted code block
+#[tauri::command]
+pub async fn generate_tests(block_id: String, model_id: String, app: tauri::AppHandle) -> Result<TestScaffold, String> {
+    kiosk::ensure_unlocked("Test generation")?;
+    let block = block_by_id(&block_id)?;
+    let language = block.language.clone().unwrap_or_else(|| "its language".to_string());
+
+    let prompt = format!(
+        "Write unit tests for the following code using the standard test framework for {lang}. \
+         Cover normal cases, edge cases and error handling. Reply with a single fenced code block \
+         containing only the tests.\n\n```{fence}\n{code}\n```",
+        lang = language,
+        fence = block.language.as_deref().unwrap_or(""),
+        code = block.code,
+    );
+
+    // Low temperature keeps the tests close to the code instead of inventing behaviour
+    let (_, sampling) = sampling::resolve_profile(Some("precise"), None)
+        .or_else(|_| sampling::resolve_profile(None, None))?;
+    let response = generate_text(&model_id, &prompt, &sampling, Priority::Interactive, &app).await?;
+
+    let (language, code) = extract(&response)
+        .into_iter()
+        .next()
+        .map(|(lang, code)| (lang.or(block.language.clone()), code))
+        .unwrap_or_else(|| (block.language.clone(), response.trim().to_string()));
+
+    Ok(TestScaffold {
+        block_id,
+        language,
+        code,
+        raw_response: response,
+    })
+}
@@ -0,0 +1,264 @@
+// Which model architectures the bundled mistral.rs can run, per file format and backend. Local
+// models whose architecture isn't in the table are listed as incompatible by discover_models and
+// refused before loading, instead of failing deep inside the loader with an opaque error.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+use crate::hardware;
+
+const ALL_BACKENDS: &[&str] = &["cpu", "metal", "cuda"];
+
+// Only the first keys are read; general.* and the architecture's keys come before the tokenizer's
+const MAX_GGUF_KEYS: u64 = 64;
+const MAX_GGUF_STRING: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Support {
+    // general.architecture for GGUF, the config.json architecture for safetensors/UQFF
+    pub architecture: &'static str,
+    // "gguf" or "uqff"
+    pub format: &'static str,
+    pub backends: &'static [&'static str],
+    pub vision: bool,
+}
+
+const fn text(architecture: &'static str, format: &'static str) -> Support {
+    Support { architecture, format, backends: ALL_BACKENDS, vision: false }
+}
+
+const fn vision(architecture: &'static str) -> Support {
+    Support { architecture, format: "uqff", backends: ALL_BACKENDS, vision: true }
+}
+
+// Matches the mistral.rs revision in Cargo.toml; update alongside it
+pub const COMPATIBILITY: &[Support] = &[
+    // The GGUF loader only has text models
+    text("llama", "gguf"),
+    text("mistral", "gguf"),
+    text("phi2", "gguf"),
+    text("phi3", "gguf"),
+    text("starcoder2", "gguf"),
+    text("qwen2", "gguf"),
+    text("qwen3", "gguf"),
+    text("LlamaForCausalLM", "uqff"),
+    text("MistralForCausalLM", "uqff"),
+    text("MixtralForCausalLM", "uqff"),
+    text("GemmaForCausalLM", "uqff"),
+    text("Gemma2ForCausalLM", "uqff"),
+    text("Phi3ForCausalLM", "uqff"),
+    text("Qwen2ForCausalLM", "uqff"),
+    text("Qwen3ForCausalLM", "uqff"),
+    text("Starcoder2ForCausalLM", "uqff"),
+    text("SmolLM3ForCausalLM", "uqff"),
+    vision("MllamaForConditionalGeneration"),
+    vision("Gemma3ForConditionalGeneration"),
+    vision("Gemma3nForConditionalGeneration"),
+    vision("Qwen2VLForConditionalGeneration"),
+    vision("Qwen2_5_VLForConditionalGeneration"),
+    vision("Idefics3ForConditionalGeneration"),
+    vision("LlavaForConditionalGeneration"),
+    vision("LlavaNextForConditionalGeneration"),
+    vision("Phi3VForCausalLM"),
+    vision("Mistral3ForConditionalGeneration"),
+];
+
+pub fn current_backend() -> &'static str {
+    if hardware::use_cpu() {
+        "cpu"
+    } else {
+        hardware::accelerated_backend()
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_string(reader: &mut impl Read) -> std::io::Result<String> {
+    let len = read_u64(reader)?;
+    if len > MAX_GGUF_STRING {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "oversized string"));
+    }
+    let mut buf = vec![0; len as usize];
+    reader.read_exact(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).to_string())
+}
+
+// Skips a metadata value of the given GGUF type
+fn skip_value<R: Read + Seek>(reader: &mut R, value_type: u32) -> std::io::Result<()> {
+    let size = match value_type {
+        0 | 1 | 7 => 1,
+        2 | 3 => 2,
+        4..=6 => 4,
+        10..=12 => 8,
+        8 => {
+            let len = read_u64(reader)?;
+            reader.seek_relative(len as i64)?;
+            return Ok(());
+        }
+        9 => {
+            let element_type = read_u32(reader)?;
+            let count = read_u64(reader)?;
+            for _ in 0..count {
+                skip_value(reader, element_type)?;
+            }
+            return Ok(());
+        }
+        _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "unknown value type")),
+    };
+    reader.seek_relative(size)
+}
+
+// String and integer values among the first keys of a GGUF header; arrays and floats are skipped
+#[derive(Debug, Default)]
+pub struct GgufMetadata {
+    pub strings: HashMap<String, String>,
+    pub integers: HashMap<String, u64>,
+    // The data ran out before all of the first keys were read (e.g. a partial download)
+    pub truncated: bool,
+}
+
+fn read_integer(reader: &mut impl Read, value_type: u32) -> std::io::Result<Option<u64>> {
+    let mut buf = [0; 8];
+    let (size, signed) = match value_type {
+        0 => (1, false),
+        1 => (1, true),
+        2 => (2, false),
+        3 => (2, true),
+        4 => (4, false),
+        5 => (4, true),
+        10 => (8, false),
+        11 => (8, true),
+        _ => unreachable!("not an integer type: {}", value_type),
+    };
+    reader.read_exact(&mut buf[..size])?;
+    let value = u64::from_le_bytes(buf);
+    // Negative values never describe sizes or types, so they are dropped
+    let negative = signed && buf[size - 1] & 0x80 != 0;
+    Ok((!negative).then_some(value))
+}
+
+fn read_entry<R: Read + Seek>(reader: &mut R, metadata: &mut GgufMetadata) -> std::io::Result<()> {
+    let key = read_string(reader)?;
+    let value_type = read_u32(reader)?;
+    if value_type == 8 {
+        metadata.strings.insert(key, read_string(reader)?);
+    } else if matches!(value_type, 0..=5 | 10 | 11) {
+        if let Some(value) = read_integer(reader, value_type)? {
+            metadata.integers.insert(key, value);
+        }
+    } else {
+        skip_value(reader, value_type)?;
+    }
+    Ok(())
+}
+
+// Parses a GGUF header from any reader; `source` names it in errors
+pub fn read_gguf_metadata<R: Read + Seek>(reader: &mut R, source: &str) -> Result<GgufMetadata, String> {
+    let describe = |e: std::io::Error| format!("Failed to read the GGUF header of {}: {}", source, e);
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic).map_err(describe)?;
+    if &magic != b"GGUF" {
+        return Err(format!("{} is not a GGUF file", source));
+    }
+    let version = read_u32(reader).map_err(describe)?;
+    if version < 2 {
+        return Err(format!("{} uses GGUF version {}, which is no longer supported", source, version));
+    }
+    let _tensor_count = read_u64(reader).map_err(describe)?;
+    let key_count = read_u64(reader).map_err(describe)?;
+
+    let mut metadata = GgufMetadata::default();
+    for _ in 0..key_count.min(MAX_GGUF_KEYS) {
+        match read_entry(reader, &mut metadata) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                metadata.truncated = true;
+                break;
+            }
+            Err(e) => return Err(describe(e)),
+        }
+    }
+    Ok(metadata)
+}
+
+pub fn gguf_file_metadata(path: &Path) -> Result<GgufMetadata, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to read the GGUF header of {}: {}", path.display(), e))?;
+    read_gguf_metadata(&mut BufReader::new(file), &path.display().to_string())
+}
+
+// A string value from a GGUF file's metadata, or None when the key isn't among the first keys
+pub fn gguf_metadata_string(path: &Path, wanted: &str) -> Result<Option<String>, String> {
+    Ok(gguf_file_metadata(path)?.strings.remove(wanted))
+}
+
+// general.architecture from a GGUF file's header
+pub fn gguf_architecture(path: &Path) -> Result<String, String> {
+    gguf_metadata_string(path, "general.architecture")?
+        .ok_or_else(|| format!("{} has no general.architecture", path.display()))
+}
+
+// The first entry of config.json's "architectures"
+fn config_architecture(dir: &Path) -> Option<String> {
+    let contents = std::fs::read_to_string(dir.join("config.json")).ok()?;
+    let config: serde_json::Value = serde_json::from_str(&contents).ok()?;
+    config.get("architectures")?.get(0)?.as_str().map(str::to_string)
+}
+
+pub fn lookup(architecture: &str, format: &str) -> Option<&'static Support> {
+    COMPATIBILITY
+        .iter()
+        .find(|s| s.format == format && s.architecture.eq_ignore_ascii_case(architecture))
+}
+
+// Why the current build can't run a discovered local model, or None when it can (or when its
+// architecture can't be determined, in which case loading is left to try)
+pub fn incompatibility(base_path: &str, model_dir: &str, model_file: &str, model_type: &str) -> Option<String> {
+    let dir = Path::new(base_path).join(model_dir);
+    let backend = current_backend();
+    let (architecture, format) = match model_type {
+        "matformer" => return Some("MatFormer text models aren't supported by this build".to_string()),
+        "gguf" | "gguf-vision" => match gguf_architecture(&dir.join(model_file)) {
+            Ok(architecture) => (architecture, "gguf"),
+            Err(e) => return Some(e),
+        },
+        _ => (config_architecture(&dir)?, "uqff"),
+    };
+
+    let Some(support) = lookup(&architecture, format) else {
+        let formats: Vec<&str> = COMPATIBILITY
+            .iter()
+            .filter(|s| s.architecture.eq_ignore_ascii_case(&architecture))
+            .map(|s| s.format)
+            .collect();
+        return Some(if formats.is_empty() {
+            format!("The {} architecture isn't supported by this build of mistral.rs", architecture)
+        } else {
+            format!("The {} architecture is only supported as {}, not {}", architecture, formats.join(", "), format)
+        });
+    };
+    if model_type == "gguf-vision" && !support.vision {
+        return Some(format!("{} GGUF files load as text-only models; image input isn't supported", architecture));
+    }
+    if !support.backends.contains(&backend) {
+        return Some(format!("{} models can't run on the {} backend", architecture, backend));
+    }
+    None
+}
+
+#[tauri::command]
+pub fn get_compatibility_table() -> &'static [Support] {
+    COMPATIBILITY
+}
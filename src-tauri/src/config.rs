@@ -0,0 +1,564 @@
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, RwLock};
+use tauri::Emitter;
+
+use crate::accessibility::AccessibilityConfig;
+use crate::audit::AuditConfig;
+use crate::backup::BackupConfig;
+use crate::context_window::ContextWindowConfig;
+use crate::digest::DigestConfig;
+use crate::documents::DocumentsConfig;
+use crate::embeddings::EmbeddingsConfig;
+use crate::explain::ExplainConfig;
+use crate::hardware::BenchmarkConfig;
+use crate::hf_cache;
+use crate::image_input::ImageInputConfig;
+use crate::isq;
+use crate::history::CancelBehavior;
+use crate::kiosk::KioskConfig;
+use crate::language::LanguageConfig;
+use crate::middleware::MiddlewareConfig;
+use crate::mock::MockConfig;
+use crate::presentation::PresentationConfig;
+use crate::priority::PriorityConfig;
+use crate::profiles;
+use crate::recap::RecapConfig;
+use crate::replay::ReplayConfig;
+use crate::response_style::ResponseStyleConfig;
+use crate::sampling::{self, SamplingConfig, TokenRules};
+use crate::slash_commands::SlashCommands;
+use crate::system_prompt::SystemPromptConfig;
+use crate::telemetry::TelemetryConfig;
+use crate::tools::ToolsConfig;
+use crate::transcript::TranscriptConfig;
+use crate::vision_cache::VisionCacheConfig;
+use crate::watchdog::WatchdogConfig;
+use crate::web::WebConfig;
+
+pub const CONFIG_FILE: &str = "config.toml";
+pub const CONFIG_CHANGED_EVENT: &str = "config_changed";
+pub const CONFIG_ERROR_EVENT: &str = "config_error";
+
+static CONFIG: OnceLock<RwLock<AppConfig>> = OnceLock::new();
+// Replaced when switching profiles
+static CONFIG_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+// Keeps the file watcher alive for the lifetime of the app
+static WATCHER: Mutex<Option<RecommendedWatcher>> = Mutex::new(None);
+
+// Typed schema for config.toml; unknown keys are rejected so typos surface as errors
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub models: ModelsConfig,
+    pub keep_alive: KeepAliveConfig,
+    pub server: ServerConfig,
+    pub limits: LimitsConfig,
+    pub safe_mode: SafeModeConfig,
+    pub hf_cache: HfCacheConfig,
+    pub memory: MemoryConfig,
+    pub generation: GenerationConfig,
+    pub slash_commands: SlashCommands,
+    pub analytics: AnalyticsConfig,
+    pub accessibility: AccessibilityConfig,
+    pub kiosk: KioskConfig,
+    pub benchmark: BenchmarkConfig,
+    pub web: WebConfig,
+    pub digest: DigestConfig,
+    pub tools: ToolsConfig,
+    pub language: LanguageConfig,
+    pub priority: PriorityConfig,
+    pub transcripts: TranscriptConfig,
+    pub replay: ReplayConfig,
+    pub mock: MockConfig,
+    pub telemetry: TelemetryConfig,
+    pub context_window: ContextWindowConfig,
+    pub middleware: MiddlewareConfig,
+    pub audit: AuditConfig,
+    pub recap: RecapConfig,
+    pub backup: BackupConfig,
+    pub vision_cache: VisionCacheConfig,
+    pub system_prompt: SystemPromptConfig,
+    pub explain: ExplainConfig,
+    pub response_style: ResponseStyleConfig,
+    pub documents: DocumentsConfig,
+    pub embeddings: EmbeddingsConfig,
+    pub watchdog: WatchdogConfig,
+    pub image_input: ImageInputConfig,
+    pub presentation: PresentationConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnalyticsConfig {
+    // Local usage counters are only collected after the user opts in
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelsConfig {
+    // Directories searched (in order) for local models
+    pub dirs: Vec<String>,
+    // Model loaded at startup and exempt from idle unload when configured
+    pub default_model: Option<String>,
+    // List the catalog's remote models in discover_models
+    pub show_remote: bool,
+    // Never reach Hugging Face for weights; remote models are listed as unavailable
+    pub offline: bool,
+    // Hash downloaded files in discover_models and list corrupt models as unavailable. The first
+    // check of a multi-GB model takes a while; later ones are cached until the files change
+    pub verify_checksums: bool,
+    // Load SmolLM3 from Hugging Face when its local UQFF files can't be loaded, instead of failing
+    pub allow_remote_fallback: bool,
+    // Model id -> ISQ type, e.g. "Q4K"; models not listed use their loader's default
+    pub isq: HashMap<String, String>,
+    // Model id -> UQFF quantization to load when its folder holds several, e.g. "Q8_0"
+    pub uqff_quantization: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeepAliveConfig {
+    // Minutes without requests before a model is unloaded; 0 disables
+    pub idle_minutes: u64,
+    pub keep_default_loaded: bool,
+    pub per_model_minutes: HashMap<String, u64>,
+    // Before another model loads, least recently used models are unloaded to stay within these;
+    // 0 disables either limit
+    pub max_loaded_models: usize,
+    pub max_loaded_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub enabled: bool,
+    pub bind: String,
+    // Accept remote clients on the /ws channel (requires remote_token)
+    pub remote_control: bool,
+    pub remote_token: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub max_message_chars: usize,
+    pub max_image_bytes: usize,
+    // Images one vision request may send, new and stored ones together
+    pub max_images_per_request: usize,
+    pub max_audio_bytes: usize,
+    // Extra limits for individual models, keyed by model id
+    pub per_model: HashMap<String, ModelLimits>,
+}
+
+// Per-model caps; the GPU and batch settings take effect the next time the model is loaded
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelLimits {
+    // Prompt plus max_tokens must fit in this many (estimated) tokens
+    pub max_context_tokens: Option<usize>,
+    // Share of GPU memory the KV cache may use, 0.0 - 1.0
+    pub max_gpu_memory_fraction: Option<f32>,
+    // Most sequences the engine schedules at once
+    pub max_batch_size: Option<usize>,
+}
+
+impl LimitsConfig {
+    pub fn for_model(&self, model_id: &str) -> ModelLimits {
+        self.per_model.get(model_id).cloned().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SafeModeConfig {
+    pub force: bool,
+    pub crash_threshold: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HfCacheConfig {
+    // Overrides HF_HOME for remote model downloads; None keeps the system default
+    pub home: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MemoryConfig {
+    // Below this much available system memory the low-memory handler steps in; 0 disables it
+    pub critical_available_mb: u64,
+    pub check_interval_secs: u64,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            critical_available_mb: 1024,
+            check_interval_secs: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenerationConfig {
+    pub default_profile: String,
+    pub profiles: BTreeMap<String, SamplingConfig>,
+    // Conversation id -> profile name
+    pub conversation_profiles: HashMap<String, String>,
+    // Conversation id -> banned phrases and logit bias added to its profile's
+    pub conversation_token_rules: HashMap<String, TokenRules>,
+    // Balance code fences and strip dangerous HTML from streamed chunks before they reach the webview
+    pub sanitize_markdown: bool,
+    // Emit "prefill" progress events while prompts at least this long (in estimated tokens) are processed; 0 disables
+    pub prefill_progress_min_tokens: usize,
+    // What happens to the partial answer of a cancelled request
+    pub on_cancel: CancelBehavior,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            default_profile: "balanced".to_string(),
+            profiles: sampling::default_profiles(),
+            conversation_profiles: HashMap::new(),
+            conversation_token_rules: HashMap::new(),
+            sanitize_markdown: true,
+            prefill_progress_min_tokens: 1024,
+            on_cancel: CancelBehavior::default(),
+        }
+    }
+}
+
+impl Default for ModelsConfig {
+    fn default() -> Self {
+        Self {
+            dirs: vec![
+                "models".to_string(),           // When running from src-tauri directory (most common)
+                "../models".to_string(),        // When running from target directory
+                "src-tauri/models".to_string(), // When running from project root
+            ],
+            default_model: None,
+            show_remote: true,
+            offline: false,
+            verify_checksums: false,
+            allow_remote_fallback: false,
+            isq: HashMap::new(),
+            uqff_quantization: HashMap::new(),
+        }
+    }
+}
+
+impl Default for KeepAliveConfig {
+    fn default() -> Self {
+        Self {
+            idle_minutes: 15,
+            keep_default_loaded: true,
+            per_model_minutes: HashMap::new(),
+            max_loaded_models: 2,
+            max_loaded_bytes: 0,
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: "127.0.0.1:8765".to_string(),
+            remote_control: false,
+            remote_token: None,
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_message_chars: 32_000,
+            max_image_bytes: 20 * 1024 * 1024,
+            max_images_per_request: 4,
+            max_audio_bytes: 25 * 1024 * 1024,
+            per_model: HashMap::new(),
+        }
+    }
+}
+
+impl Default for SafeModeConfig {
+    fn default() -> Self {
+        Self {
+            force: false,
+            crash_threshold: 3,
+        }
+    }
+}
+
+impl AppConfig {
+    // Semantic checks that serde can't express, reported all at once
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+
+        if self.models.dirs.is_empty() {
+            errors.push("models.dirs must list at least one directory".to_string());
+        }
+        if self.models.dirs.iter().any(|d| d.trim().is_empty()) {
+            errors.push("models.dirs must not contain empty paths".to_string());
+        }
+        errors.extend(isq::validate(&self.models.isq));
+        if self.keep_alive.idle_minutes > 7 * 24 * 60 {
+            errors.push(format!(
+                "keep_alive.idle_minutes = {} is longer than a week; use 0 to disable idle unload",
+                self.keep_alive.idle_minutes
+            ));
+        }
+        if self.server.bind.parse::<SocketAddr>().is_err() {
+            errors.push(format!(
+                "server.bind = \"{}\" is not a valid address (expected e.g. \"127.0.0.1:8765\")",
+                self.server.bind
+            ));
+        }
+        if self.server.remote_token.as_deref().is_some_and(|t| t.len() < 16) {
+            errors.push("server.remote_token must be at least 16 characters long".to_string());
+        }
+        if self.limits.max_message_chars == 0 {
+            errors.push("limits.max_message_chars must be greater than 0".to_string());
+        }
+        if self.limits.max_image_bytes == 0 {
+            errors.push("limits.max_image_bytes must be greater than 0".to_string());
+        }
+        if self.limits.max_images_per_request == 0 {
+            errors.push("limits.max_images_per_request must be greater than 0".to_string());
+        }
+        if self.limits.max_audio_bytes == 0 {
+            errors.push("limits.max_audio_bytes must be greater than 0".to_string());
+        }
+        for (model_id, limits) in &self.limits.per_model {
+            if limits.max_context_tokens == Some(0) {
+                errors.push(format!("limits.per_model.{}.max_context_tokens must be greater than 0", model_id));
+            }
+            if limits.max_gpu_memory_fraction.is_some_and(|f| f <= 0.0 || f > 1.0) {
+                errors.push(format!("limits.per_model.{}.max_gpu_memory_fraction must be in (0, 1]", model_id));
+            }
+            if limits.max_batch_size == Some(0) {
+                errors.push(format!("limits.per_model.{}.max_batch_size must be at least 1", model_id));
+            }
+        }
+        if self.hf_cache.home.as_deref().is_some_and(|h| h.trim().is_empty()) {
+            errors.push("hf_cache.home must not be empty; remove the key to use the default cache".to_string());
+        }
+        if self.memory.check_interval_secs == 0 {
+            errors.push("memory.check_interval_secs must be at least 1".to_string());
+        }
+        if !self.generation.profiles.contains_key(&self.generation.default_profile) {
+            errors.push(format!(
+                "generation.default_profile = \"{}\" does not match any [generation.profiles] entry",
+                self.generation.default_profile
+            ));
+        }
+        for (conversation_id, profile) in &self.generation.conversation_profiles {
+            if !self.generation.profiles.contains_key(profile) {
+                errors.push(format!(
+                    "generation.conversation_profiles: conversation {} uses unknown profile \"{}\"",
+                    conversation_id, profile
+                ));
+            }
+        }
+        for (name, profile) in &self.generation.profiles {
+            errors.extend(profile.validate(&format!("generation.profiles.{}", name)));
+        }
+        for (conversation_id, rules) in &self.generation.conversation_token_rules {
+            errors.extend(rules.validate(&format!("generation.conversation_token_rules.{}", conversation_id)));
+        }
+        errors.extend(self.slash_commands.validate());
+        errors.extend(self.accessibility.validate());
+        errors.extend(self.kiosk.validate());
+        errors.extend(self.web.validate());
+        errors.extend(self.digest.validate());
+        errors.extend(self.tools.validate());
+        errors.extend(self.language.validate());
+        errors.extend(self.transcripts.validate());
+        errors.extend(self.mock.validate());
+        errors.extend(self.telemetry.validate());
+        errors.extend(self.context_window.validate());
+        errors.extend(self.middleware.validate());
+        errors.extend(self.recap.validate());
+        errors.extend(self.backup.validate());
+        errors.extend(self.explain.validate());
+        errors.extend(self.response_style.validate());
+        errors.extend(self.documents.validate());
+        errors.extend(self.embeddings.validate());
+        errors.extend(self.watchdog.validate());
+        errors.extend(self.image_input.validate());
+        errors.extend(self.presentation.validate());
+        if self.safe_mode.crash_threshold == 0 {
+            errors.push("safe_mode.crash_threshold must be at least 1".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+}
+
+// Parses and validates config file contents, with TOML syntax errors pointing at the offending line
+pub fn parse(contents: &str) -> Result<AppConfig, String> {
+    let config: AppConfig = toml::from_str(contents).map_err(|e| e.to_string())?;
+    config.validate()?;
+    Ok(config)
+}
+
+fn load_from(path: &Path) -> Result<AppConfig, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse(&contents).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+}
+
+fn save_to(path: &Path, config: &AppConfig) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    let contents = toml::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+    std::fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn cell() -> &'static RwLock<AppConfig> {
+    CONFIG.get_or_init(|| RwLock::new(AppConfig::default()))
+}
+
+// Current configuration snapshot
+pub fn get() -> AppConfig {
+    cell().read().unwrap().clone()
+}
+
+// Loads the active profile's config.toml (creating it with defaults) and starts watching it
+pub fn init(app: &tauri::AppHandle) {
+    let config_dir = match profiles::config_dir(app) {
+        Ok(dir) => dir,
+        Err(e) => {
+            println!("Warning: {}, using default config", e);
+            return;
+        }
+    };
+    let path = config_dir.join(CONFIG_FILE);
+    *CONFIG_PATH.write().unwrap() = Some(path.clone());
+
+    // Start from defaults so nothing carries over from a previously active profile
+    *cell().write().unwrap() = AppConfig::default();
+    if path.exists() {
+        match load_from(&path) {
+            Ok(config) => *cell().write().unwrap() = config,
+            Err(e) => println!("Warning: {}. Falling back to defaults", e),
+        }
+    } else if let Err(e) = save_to(&path, &AppConfig::default()) {
+        println!("Warning: {}", e);
+    }
+    hf_cache::apply(&get());
+
+    println!("Using config file: {}", path.display());
+    watch(app, config_dir, path);
+}
+
+fn watch(app: &tauri::AppHandle, config_dir: PathBuf, path: PathBuf) {
+    let handle = app.clone();
+    let watched_path = path.clone();
+
+    let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            if event.paths.iter().any(|p| p.file_name() == watched_path.file_name()) {
+                reload(&handle, &watched_path);
+            }
+        }
+    });
+
+    match watcher {
+        Ok(mut watcher) => {
+            // Watch the directory so editors that replace the file atomically are picked up
+            if let Err(e) = watcher.watch(&config_dir, RecursiveMode::NonRecursive) {
+                println!("Warning: Failed to watch {}: {}", config_dir.display(), e);
+                return;
+            }
+            // Replacing the previous watcher stops it
+            *WATCHER.lock().unwrap() = Some(watcher);
+        }
+        Err(e) => println!("Warning: Failed to create config watcher: {}", e),
+    }
+}
+
+// Re-reads the file after an edit; invalid files keep the previous config and report why
+fn reload(app: &tauri::AppHandle, path: &Path) {
+    if !path.exists() {
+        return;
+    }
+
+    match load_from(path) {
+        Ok(config) => {
+            let mut current = cell().write().unwrap();
+            if *current == config {
+                return;
+            }
+            *current = config.clone();
+            drop(current);
+            hf_cache::apply(&config);
+
+            println!("Config reloaded from {}", path.display());
+            if let Err(e) = app.emit(CONFIG_CHANGED_EVENT, &config) {
+                println!("Warning: Failed to emit config_changed event: {}", e);
+            }
+        }
+        Err(e) => {
+            println!("Warning: {}", e);
+            let _ = app.emit(CONFIG_ERROR_EVENT, &e);
+        }
+    }
+}
+
+// Applies a change, validates it, persists it to config.toml and notifies the frontend
+pub fn update<F>(app: &tauri::AppHandle, change: F) -> Result<AppConfig, String>
+where
+    F: FnOnce(&mut AppConfig),
+{
+    // Kiosk terminals are configured by editing config.toml directly
+    if get().kiosk.enabled {
+        return Err("Settings are locked in kiosk mode".to_string());
+    }
+
+    let mut config = get();
+    change(&mut config);
+    config.validate()?;
+
+    if let Some(path) = CONFIG_PATH.read().unwrap().as_ref() {
+        save_to(path, &config)?;
+    }
+    *cell().write().unwrap() = config.clone();
+    hf_cache::apply(&config);
+
+    if let Err(e) = app.emit(CONFIG_CHANGED_EVENT, &config) {
+        println!("Warning: Failed to emit config_changed event: {}", e);
+    }
+    Ok(config)
+}
+
+#[tauri::command]
+pub fn get_config() -> AppConfig {
+    get()
+}
+
+// The active profile's config.toml
+pub fn path() -> Option<PathBuf> {
+    CONFIG_PATH.read().unwrap().clone()
+}
+
+#[tauri::command]
+pub fn get_config_path() -> Option<String> {
+    CONFIG_PATH.read().unwrap().as_ref().map(|p| p.to_string_lossy().to_string())
+}
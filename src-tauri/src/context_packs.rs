@@ -0,0 +1,454 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::db_writer;
+use crate::estimate::estimate_tokens;
+use crate::history::{db_err, new_id, with_db};
+use crate::kiosk;
+use crate::streaming::now_ms;
+
+// Token budget used when a pack doesn't set one
+const DEFAULT_TOKEN_BUDGET: usize = 2048;
+
+// Characters per indexed chunk; paragraphs are kept whole where possible
+const CHUNK_CHARS: usize = 1500;
+
+// Files larger than this are skipped when indexing folders
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+pub(crate) const MAX_FILES_PER_FOLDER: usize = 500;
+
+// Directories that hold build output or dependencies rather than project sources
+const SKIPPED_DIRS: [&str; 5] = ["node_modules", "target", "dist", "build", "__pycache__"];
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS context_packs (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    token_budget INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    refreshed_at INTEGER
+);
+CREATE TABLE IF NOT EXISTS context_pack_sources (
+    pack_id TEXT NOT NULL REFERENCES context_packs(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    location TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS context_pack_chunks (
+    pack_id TEXT NOT NULL REFERENCES context_packs(id) ON DELETE CASCADE,
+    source TEXT NOT NULL,
+    content TEXT NOT NULL,
+    tokens INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_context_pack_chunks ON context_pack_chunks(pack_id);
+CREATE TABLE IF NOT EXISTS conversation_context_packs (
+    conversation_id TEXT PRIMARY KEY REFERENCES conversations(id) ON DELETE CASCADE,
+    pack_id TEXT NOT NULL REFERENCES context_packs(id) ON DELETE CASCADE
+);
+";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackSource {
+    // "file", "folder" or "url"
+    pub kind: String,
+    pub location: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextPack {
+    pub id: String,
+    pub name: String,
+    pub token_budget: usize,
+    pub sources: Vec<PackSource>,
+    pub chunk_count: usize,
+    pub total_tokens: usize,
+    pub created_at: u64,
+    pub refreshed_at: Option<u64>,
+}
+
+pub(crate) struct Chunk {
+    pub source: String,
+    pub content: String,
+    pub tokens: usize,
+}
+
+pub fn init() -> Result<(), String> {
+    with_db(|conn| conn.execute_batch(SCHEMA).map_err(db_err))
+}
+
+fn sources_for(conn: &Connection, pack_id: &str) -> Result<Vec<PackSource>, String> {
+    let mut stmt = conn
+        .prepare("SELECT kind, location FROM context_pack_sources WHERE pack_id = ?1")
+        .map_err(db_err)?;
+    let rows = stmt
+        .query_map(params![pack_id], |row| {
+            Ok(PackSource {
+                kind: row.get(0)?,
+                location: row.get(1)?,
+            })
+        })
+        .map_err(db_err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+}
+
+pub(crate) fn pack_by_id(conn: &Connection, pack_id: &str) -> Result<ContextPack, String> {
+    let pack = conn
+        .query_row(
+            "SELECT p.id, p.name, p.token_budget, p.created_at, p.refreshed_at,
+                    (SELECT COUNT(*) FROM context_pack_chunks c WHERE c.pack_id = p.id),
+                    (SELECT COALESCE(SUM(tokens), 0) FROM context_pack_chunks c WHERE c.pack_id = p.id)
+             FROM context_packs p WHERE p.id = ?1 OR p.name = ?1",
+            params![pack_id],
+            |row| {
+                Ok(ContextPack {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    token_budget: row.get::<_, i64>(2)? as usize,
+                    sources: Vec::new(),
+                    chunk_count: row.get::<_, i64>(5)? as usize,
+                    total_tokens: row.get::<_, i64>(6)? as usize,
+                    created_at: row.get::<_, i64>(3)? as u64,
+                    refreshed_at: row.get::<_, Option<i64>>(4)?.map(|t| t as u64),
+                })
+            },
+        )
+        .optional()
+        .map_err(db_err)?
+        .ok_or_else(|| format!("Context pack not found: {}", pack_id))?;
+
+    Ok(ContextPack {
+        sources: sources_for(conn, &pack.id)?,
+        ..pack
+    })
+}
+
+// Splits text into roughly CHUNK_CHARS pieces along paragraph boundaries
+pub(crate) fn chunk_text(source: &str, text: &str) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    let mut flush = |current: &mut String| {
+        let content = current.trim().to_string();
+        if !content.is_empty() {
+            chunks.push(Chunk {
+                source: source.to_string(),
+                tokens: estimate_tokens(&content),
+                content,
+            });
+        }
+        current.clear();
+    };
+
+    for paragraph in text.split("\n\n") {
+        if !current.is_empty() && current.len() + paragraph.len() > CHUNK_CHARS {
+            flush(&mut current);
+        }
+        // Paragraphs longer than a chunk are hard-split on character boundaries
+        let mut rest = paragraph;
+        while rest.len() > CHUNK_CHARS {
+            let split = (0..=CHUNK_CHARS).rev().find(|&i| rest.is_char_boundary(i)).unwrap_or(0);
+            current.push_str(&rest[..split]);
+            flush(&mut current);
+            rest = &rest[split..];
+        }
+        current.push_str(rest);
+        current.push_str("\n\n");
+    }
+    flush(&mut current);
+
+    chunks
+}
+
+pub(crate) fn read_text_file(path: &Path) -> Option<String> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if metadata.len() > MAX_FILE_BYTES {
+        return None;
+    }
+    // Non-UTF-8 content is treated as binary and skipped
+    std::fs::read_to_string(path).ok()
+}
+
+pub(crate) fn collect_files(dir: &Path, files: &mut Vec<std::path::PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+    entries.sort();
+
+    for path in entries {
+        if files.len() >= MAX_FILES_PER_FOLDER {
+            return;
+        }
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if name.starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            if !SKIPPED_DIRS.contains(&name.as_str()) {
+                collect_files(&path, files);
+            }
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+}
+
+async fn snapshot_source(source: &PackSource) -> Result<Vec<Chunk>, String> {
+    match source.kind.as_str() {
+        "file" => {
+            let path = Path::new(&source.location);
+            let text = read_text_file(path).ok_or_else(|| format!("Cannot read text file {}", source.location))?;
+            Ok(chunk_text(&source.location, &text))
+        }
+        "folder" => {
+            let mut files = Vec::new();
+            collect_files(Path::new(&source.location), &mut files);
+            Ok(files
+                .iter()
+                .filter_map(|path| read_text_file(path).map(|text| chunk_text(&path.to_string_lossy(), &text)))
+                .flatten()
+                .collect())
+        }
+        "url" => {
+            // Same allowlist, size cap and boilerplate stripping as the fetch_url tool
+            let page = crate::web::fetch_page(&source.location).await?;
+            Ok(chunk_text(&source.location, &page.text))
+        }
+        other => Err(format!("Unknown context pack source kind: {}", other)),
+    }
+}
+
+// Re-reads every source and replaces the stored snapshot
+async fn index_pack(pack_id: &str, sources: &[PackSource]) -> Result<(), String> {
+    let mut chunks = Vec::new();
+    for source in sources {
+        match snapshot_source(source).await {
+            Ok(mut source_chunks) => chunks.append(&mut source_chunks),
+            Err(e) => println!("Warning: Skipping context pack source: {}", e),
+        }
+    }
+    store_chunks(pack_id, chunks).await
+}
+
+// Replaces the pack's stored snapshot with the given chunks
+pub(crate) async fn store_chunks(pack_id: &str, chunks: Vec<Chunk>) -> Result<(), String> {
+    let pack_id = pack_id.to_string();
+    db_writer::write(move |tx| {
+        tx.execute("DELETE FROM context_pack_chunks WHERE pack_id = ?1", params![pack_id])
+            .map_err(db_err)?;
+        for chunk in chunks {
+            tx.execute(
+                "INSERT INTO context_pack_chunks (pack_id, source, content, tokens) VALUES (?1, ?2, ?3, ?4)",
+                params![pack_id, chunk.source, chunk.content, chunk.tokens as i64],
+            )
+            .map_err(db_err)?;
+        }
+        tx.execute(
+            "UPDATE context_packs SET refreshed_at = ?1 WHERE id = ?2",
+            params![now_ms() as i64, pack_id],
+        )
+        .map_err(db_err)?;
+        Ok(())
+    })
+    .await
+}
+
+fn keywords(text: &str) -> HashSet<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| w.len() > 2)
+        .map(|w| w.to_string())
+        .collect()
+}
+
+// Prepends the most relevant chunks of the conversation's pack that fit its token budget
+pub fn augment(conversation_id: Option<&str>, message: &str) -> Result<String, String> {
+    let Some(conversation_id) = conversation_id else {
+        return Ok(message.to_string());
+    };
+
+    let selected = with_db(|conn| {
+        let pack_id: Option<String> = conn
+            .query_row(
+                "SELECT pack_id FROM conversation_context_packs WHERE conversation_id = ?1",
+                params![conversation_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_err)?;
+        let Some(pack_id) = pack_id else {
+            return Ok(None);
+        };
+        let pack = pack_by_id(conn, &pack_id)?;
+
+        let mut stmt = conn
+            .prepare("SELECT source, content, tokens FROM context_pack_chunks WHERE pack_id = ?1")
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map(params![pack.id], |row| {
+                Ok(Chunk {
+                    source: row.get(0)?,
+                    content: row.get(1)?,
+                    tokens: row.get::<_, i64>(2)? as usize,
+                })
+            })
+            .map_err(db_err)?;
+        let chunks = rows.collect::<Result<Vec<_>, _>>().map_err(db_err)?;
+        Ok(Some((pack, chunks)))
+    })?;
+
+    let Some((pack, chunks)) = selected else {
+        return Ok(message.to_string());
+    };
+
+    let query = keywords(message);
+    let mut scored: Vec<(usize, &Chunk)> = chunks
+        .iter()
+        .map(|chunk| (keywords(&chunk.content).intersection(&query).count(), chunk))
+        .collect();
+    // Stable sort keeps source order among equally relevant chunks
+    scored.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let mut used = 0;
+    let mut context = String::new();
+    for (_, chunk) in scored {
+        if used + chunk.tokens > pack.token_budget {
+            continue;
+        }
+        used += chunk.tokens;
+        context.push_str(&format!("--- {} ---\n{}\n\n", chunk.source, chunk.content));
+    }
+
+    if context.is_empty() {
+        return Ok(message.to_string());
+    }
+    Ok(format!(
+        "Use the following project context (\"{}\") when it is relevant.\n\n{}Question:\n{}",
+        pack.name, context, message
+    ))
+}
+
+// The pack's chunks in source order up to its budget. Unlike augment() the result doesn't depend on
+// the question, so it can lead a prompt that stays identical across conversations.
+pub fn fixed_context(conn: &Connection, pack_id: &str) -> Result<String, String> {
+    let pack = pack_by_id(conn, pack_id)?;
+    let mut stmt = conn
+        .prepare("SELECT source, content, tokens FROM context_pack_chunks WHERE pack_id = ?1 ORDER BY rowid")
+        .map_err(db_err)?;
+    let rows = stmt
+        .query_map(params![pack.id], |row| {
+            Ok(Chunk {
+                source: row.get(0)?,
+                content: row.get(1)?,
+                tokens: row.get::<_, i64>(2)? as usize,
+            })
+        })
+        .map_err(db_err)?;
+
+    let mut used = 0;
+    let mut context = String::new();
+    for chunk in rows {
+        let chunk = chunk.map_err(db_err)?;
+        if used + chunk.tokens > pack.token_budget {
+            break;
+        }
+        used += chunk.tokens;
+        context.push_str(&format!("--- {} ---\n{}\n\n", chunk.source, chunk.content));
+    }
+    Ok(context)
+}
+
+// Stores a pack and its sources without indexing them; returns the new pack id
+pub(crate) fn insert_pack(conn: &Connection, name: &str, sources: &[PackSource], token_budget: Option<usize>) -> Result<String, String> {
+    let id = new_id();
+    conn.execute(
+        "INSERT INTO context_packs (id, name, token_budget, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![id, name, token_budget.unwrap_or(DEFAULT_TOKEN_BUDGET) as i64, now_ms() as i64],
+    )
+    .map_err(db_err)?;
+    for source in sources {
+        conn.execute(
+            "INSERT INTO context_pack_sources (pack_id, kind, location) VALUES (?1, ?2, ?3)",
+            params![id, source.kind, source.location],
+        )
+        .map_err(db_err)?;
+    }
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn create_context_pack(name: String, sources: Vec<PackSource>, token_budget: Option<usize>) -> Result<ContextPack, String> {
+    kiosk::ensure_unlocked("Context packs")?;
+    if name.trim().is_empty() {
+        return Err("Context pack name must not be empty".to_string());
+    }
+    let pack_sources = sources.clone();
+    let id = db_writer::write(move |conn| insert_pack(conn, name.trim(), &pack_sources, token_budget)).await?;
+
+    index_pack(&id, &sources).await?;
+    with_db(|conn| pack_by_id(conn, &id))
+}
+
+#[tauri::command]
+pub async fn list_context_packs() -> Result<Vec<ContextPack>, String> {
+    with_db(|conn| {
+        // Packs behind folder chats belong to their conversation and aren't offered elsewhere
+        let mut stmt = conn
+            .prepare("SELECT id FROM context_packs WHERE id NOT IN (SELECT pack_id FROM folder_sessions) ORDER BY name")
+            .map_err(db_err)?;
+        let ids = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(db_err)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db_err)?;
+        ids.iter().map(|id| pack_by_id(conn, id)).collect()
+    })
+}
+
+// Re-snapshots the pack's files, folders and URLs
+#[tauri::command]
+pub async fn refresh_context_pack(pack_id: String) -> Result<ContextPack, String> {
+    kiosk::ensure_unlocked("Context packs")?;
+    let pack = with_db(|conn| pack_by_id(conn, &pack_id))?;
+    index_pack(&pack.id, &pack.sources).await?;
+    with_db(|conn| pack_by_id(conn, &pack.id))
+}
+
+#[tauri::command]
+pub async fn delete_context_pack(pack_id: String) -> Result<(), String> {
+    db_writer::write(move |conn| {
+        let pack = pack_by_id(conn, &pack_id)?;
+        conn.execute("DELETE FROM context_packs WHERE id = ?1", params![pack.id])
+            .map_err(db_err)?;
+        Ok(())
+    })
+    .await
+}
+
+// Switches the conversation to another pack in one step; None detaches it
+#[tauri::command]
+pub async fn attach_context_pack(conversation_id: String, pack_id: Option<String>) -> Result<Option<ContextPack>, String> {
+    db_writer::write(move |conn| match pack_id {
+        Some(pack_id) => {
+            let pack = pack_by_id(conn, &pack_id)?;
+            conn.execute(
+                "INSERT INTO conversation_context_packs (conversation_id, pack_id) VALUES (?1, ?2)
+                 ON CONFLICT(conversation_id) DO UPDATE SET pack_id = excluded.pack_id",
+                params![conversation_id, pack.id],
+            )
+            .map_err(db_err)?;
+            Ok(Some(pack))
+        }
+        None => {
+            conn.execute(
+                "DELETE FROM conversation_context_packs WHERE conversation_id = ?1",
+                params![conversation_id],
+            )
+            .map_err(db_err)?;
+            Ok(None)
+        }
+    })
+    .await
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use crate::config;
+use crate::estimate::estimate_tokens;
+use crate::history::{self, with_db, Message};
+
+// Conversation id -> position of the oldest message still in its window
+static ANCHORS: OnceLock<Mutex<HashMap<String, i64>>> = OnceLock::new();
+
+fn anchors() -> &'static Mutex<HashMap<String, i64>> {
+    ANCHORS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContextWindowConfig {
+    // Send earlier turns of the conversation, truncating the oldest once the window is full. This is
+    // history truncation, not a KV cache shift: the turns left after a truncation are prefilled again.
+    #[serde(alias = "rolling")]
+    pub include_history: bool,
+    // Window size in estimated tokens, including the new message and max_tokens
+    pub window_tokens: usize,
+    // Share of the window freed at each truncation; the prefix then stays stable for several turns
+    #[serde(alias = "shift_fraction")]
+    pub truncate_fraction: f64,
+}
+
+impl Default for ContextWindowConfig {
+    fn default() -> Self {
+        Self {
+            include_history: false,
+            window_tokens: 4096,
+            truncate_fraction: 0.25,
+        }
+    }
+}
+
+impl ContextWindowConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.window_tokens == 0 {
+            errors.push("context_window.window_tokens must be greater than 0".to_string());
+        }
+        if self.truncate_fraction <= 0.0 || self.truncate_fraction >= 1.0 {
+            errors.push(format!("context_window.truncate_fraction must be in (0, 1) (got {})", self.truncate_fraction));
+        }
+        errors
+    }
+}
+
+// Earlier turns to send before `message`. The window start only moves when the window overflows, and
+// then by a whole truncate_fraction, so consecutive requests between truncations share a prefix that
+// mistral.rs's prefix cache can reuse. The request after a truncation starts with different tokens
+// and prefills the whole remaining window.
+pub fn window(conversation_id: &str, model_id: &str, message: &str, max_tokens: Option<usize>) -> Vec<Message> {
+    let settings = config::get().context_window;
+    if !settings.include_history {
+        return Vec::new();
+    }
+    let mut messages = match with_db(|conn| history::messages_for(conn, conversation_id)) {
+        Ok(messages) => messages,
+        Err(e) => {
+            println!("Warning: Failed to load conversation {} for the context window: {}", conversation_id, e);
+            return Vec::new();
+        }
+    };
+    // Cancelled partial answers kept only for reading (generation.on_cancel = "keep_excluded")
+    messages.retain(|m| (m.role == "user" || m.role == "assistant") && !m.excluded_from_context);
+    // The frontend may already have stored the message being answered
+    if messages.last().is_some_and(|m| m.role == "user" && m.content.trim() == message.trim()) {
+        messages.pop();
+    }
+
+    let window_tokens = config::get()
+        .limits
+        .for_model(model_id)
+        .max_context_tokens
+        .map_or(settings.window_tokens, |limit| limit.min(settings.window_tokens));
+    let budget = window_tokens.saturating_sub(estimate_tokens(message) + max_tokens.unwrap_or(0));
+    let tokens: Vec<usize> = messages.iter().map(|m| estimate_tokens(&m.content)).collect();
+
+    let mut anchors = anchors().lock().unwrap();
+    let anchor = anchors.get(conversation_id).copied().unwrap_or(0);
+    let mut start = messages.iter().position(|m| m.position >= anchor).unwrap_or(messages.len());
+    let mut total: usize = tokens[start..].iter().sum();
+
+    if total > budget {
+        let target = (budget as f64 * (1.0 - settings.truncate_fraction)) as usize;
+        let dropped_from = start;
+        while start < messages.len() && (total > target || messages[start].role != "user") {
+            total -= tokens[start];
+            start += 1;
+        }
+        println!(
+            "History truncated for {}: dropped {} message(s), {} estimated tokens remain",
+            conversation_id,
+            start - dropped_from,
+            total
+        );
+        let next_anchor = match messages.get(start) {
+            Some(first) => first.position,
+            None => messages.last().map_or(anchor, |last| last.position + 1),
+        };
+        anchors.insert(conversation_id.to_string(), next_anchor);
+    }
+
+    messages.split_off(start)
+}
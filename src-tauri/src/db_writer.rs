@@ -0,0 +1,189 @@
+// Write-behind actor for the history database. Writes are queued to one thread that owns its own
+// connection and commits whatever arrives within BATCH_WINDOW in a single transaction, so a chat
+// finishing (messages, provenance, stream recordings, usage counters, audit rows) costs one fsync
+// instead of one per row, and never waits on the lock readers use. Each write runs in its own
+// savepoint: one that fails is rolled back without taking the rest of the batch with it.
+//
+// The database is in WAL mode with synchronous = FULL, so readers on the with_db connection see
+// every committed batch, and a power cut loses at most the batch being written, never consistency.
+
+use rusqlite::Connection;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::history::db_err;
+
+// How long a batch stays open for more writes after its first one
+const BATCH_WINDOW: Duration = Duration::from_millis(20);
+const MAX_BATCH: usize = 256;
+
+pub const PRAGMAS: &str = "
+PRAGMA journal_mode = WAL;
+PRAGMA synchronous = FULL;
+PRAGMA busy_timeout = 5000;
+PRAGMA foreign_keys = ON;
+";
+
+// Runs inside the batch transaction; on success returns what to do once the batch is committed
+type Job = Box<dyn FnOnce(&Connection) -> Result<Done, String> + Send>;
+// Called with the result of committing the batch
+type Done = Box<dyn FnOnce(Result<(), String>) + Send>;
+
+enum Command {
+    Write(Job),
+    Open(String, Sender<Result<(), String>>),
+    Close(Sender<()>),
+    Flush(Sender<()>),
+}
+
+static WRITER: OnceLock<Mutex<Sender<Command>>> = OnceLock::new();
+
+fn writer() -> &'static Mutex<Sender<Command>> {
+    WRITER.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("history-writer".to_string())
+            .spawn(move || run(receiver))
+            .expect("Failed to start the history writer thread");
+        Mutex::new(sender)
+    })
+}
+
+fn send(command: Command) -> Result<(), String> {
+    writer()
+        .lock()
+        .unwrap()
+        .send(command)
+        .map_err(|_| "The history writer has stopped".to_string())
+}
+
+fn run(receiver: Receiver<Command>) {
+    // Until a profile's database is opened, writes fail on the missing tables
+    let mut conn = Connection::open_in_memory().expect("Failed to open an in-memory database");
+    while let Ok(first) = receiver.recv() {
+        let deadline = Instant::now() + BATCH_WINDOW;
+        let mut batch = Vec::new();
+        let mut control = None;
+        let mut next = Some(first);
+        while let Some(command) = next.take() {
+            match command {
+                Command::Write(job) => batch.push(job),
+                other => {
+                    control = Some(other);
+                    break;
+                }
+            }
+            if batch.len() >= MAX_BATCH {
+                break;
+            }
+            next = receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())).ok();
+        }
+        commit(&mut conn, batch);
+
+        // Queued writes are committed before the connection changes
+        match control {
+            Some(Command::Open(path, reply)) => {
+                let opened = Connection::open(&path)
+                    .and_then(|opened| opened.execute_batch(PRAGMAS).map(|_| opened))
+                    .map_err(db_err);
+                let _ = reply.send(opened.map(|opened| conn = opened));
+            }
+            Some(Command::Close(reply)) => {
+                if let Ok(memory) = Connection::open_in_memory() {
+                    conn = memory;
+                }
+                let _ = reply.send(());
+            }
+            Some(Command::Flush(reply)) => {
+                let _ = reply.send(());
+            }
+            Some(Command::Write(_)) | None => {}
+        }
+    }
+}
+
+fn commit(conn: &mut Connection, batch: Vec<Job>) {
+    if batch.is_empty() {
+        return;
+    }
+    let mut done = Vec::with_capacity(batch.len());
+    let committed = (|| {
+        let tx = conn.transaction()?;
+        for job in batch {
+            tx.execute_batch("SAVEPOINT job")?;
+            match job(&tx) {
+                Ok(finish) => {
+                    tx.execute_batch("RELEASE job")?;
+                    done.push(finish);
+                }
+                Err(_) => tx.execute_batch("ROLLBACK TO job; RELEASE job")?,
+            }
+        }
+        tx.commit()
+    })()
+    .map_err(db_err);
+    for finish in done {
+        finish(committed.clone());
+    }
+}
+
+// Points the writer at a profile's database, committing anything queued for the previous one
+pub fn open(path: &Path) -> Result<(), String> {
+    let (reply, opened) = mpsc::channel();
+    send(Command::Open(path.to_string_lossy().to_string(), reply))?;
+    opened.recv().map_err(|_| "The history writer has stopped".to_string())?
+}
+
+// Commits queued writes and releases the database file
+pub fn close() {
+    let (reply, closed) = mpsc::channel();
+    if send(Command::Close(reply)).is_ok() {
+        let _ = closed.recv();
+    }
+}
+
+// Blocks until every write queued so far is committed, e.g. before the app exits
+pub fn flush() {
+    let (reply, flushed) = mpsc::channel();
+    if send(Command::Flush(reply)).is_ok() {
+        let _ = flushed.recv();
+    }
+}
+
+// Queues a write nobody waits for; failures are logged under `label`
+pub fn submit(label: &'static str, write: impl FnOnce(&Connection) -> Result<(), String> + Send + 'static) {
+    let job: Job = Box::new(move |conn| match write(conn) {
+        Ok(()) => Ok(Box::new(move |committed: Result<(), String>| {
+            if let Err(e) = committed {
+                println!("Warning: Failed to save {}: {}", label, e);
+            }
+        }) as Done),
+        Err(e) => {
+            println!("Warning: Failed to save {}: {}", label, e);
+            Err(e)
+        }
+    });
+    if let Err(e) = send(Command::Write(job)) {
+        println!("Warning: Failed to save {}: {}", label, e);
+    }
+}
+
+// Queues a write and waits until its batch is committed
+pub async fn write<T: Send + 'static>(
+    write: impl FnOnce(&Connection) -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    let (reply, result) = tokio::sync::oneshot::channel();
+    let job: Job = Box::new(move |conn| match write(conn) {
+        Ok(value) => Ok(Box::new(move |committed: Result<(), String>| {
+            let _ = reply.send(committed.map(|_| value));
+        }) as Done),
+        Err(e) => {
+            let _ = reply.send(Err(e.clone()));
+            Err(e)
+        }
+    });
+    send(Command::Write(job))?;
+    result.await.map_err(|_| "The write was dropped before it was committed".to_string())?
+}
@@ -0,0 +1,408 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::Emitter;
+
+use crate::dates::format_day;
+use crate::history::{self, db_err, new_id, with_db};
+use crate::priority::Priority;
+use crate::streaming::now_ms;
+use crate::{config, db_writer, generate_text, keep_alive, kiosk, sampling, web};
+
+// How often the scheduler checks whether today's digest is due
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+// Item summaries are cut to this many characters so a busy feed can't fill the context
+const MAX_SUMMARY_CHARS: usize = 600;
+
+pub const DIGEST_READY_EVENT: &str = "digest-ready";
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS digest_feeds (
+    id TEXT PRIMARY KEY,
+    url TEXT NOT NULL UNIQUE,
+    title TEXT,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS digest_seen_items (
+    feed_id TEXT NOT NULL REFERENCES digest_feeds(id) ON DELETE CASCADE,
+    guid TEXT NOT NULL,
+    seen_at INTEGER NOT NULL,
+    PRIMARY KEY (feed_id, guid)
+);
+
+CREATE TABLE IF NOT EXISTS digest_runs (
+    day TEXT PRIMARY KEY,
+    conversation_id TEXT,
+    item_count INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
+";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DigestConfig {
+    pub enabled: bool,
+    // The digest is built on the first check after this hour (UTC) each day
+    pub hour_utc: u32,
+    // Falls back to models.default_model
+    pub model_id: Option<String>,
+    pub max_items_per_feed: usize,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hour_utc: 7,
+            model_id: None,
+            max_items_per_feed: 10,
+        }
+    }
+}
+
+impl DigestConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.hour_utc > 23 {
+            errors.push("digest.hour_utc must be between 0 and 23".to_string());
+        }
+        if self.max_items_per_feed == 0 {
+            errors.push("digest.max_items_per_feed must be greater than 0".to_string());
+        }
+        errors
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Feed {
+    pub id: String,
+    pub url: String,
+    pub title: Option<String>,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DigestReady {
+    pub day: String,
+    pub conversation_id: String,
+    pub item_count: usize,
+}
+
+struct FeedItem {
+    guid: String,
+    title: String,
+    link: Option<String>,
+    summary: String,
+}
+
+pub fn init() -> Result<(), String> {
+    with_db(|conn| conn.execute_batch(SCHEMA).map_err(db_err))
+}
+
+fn today() -> String {
+    format_day((now_ms() / 86_400_000) as i64)
+}
+
+// Contents of every <tag>...</tag> in the document; self-closing tags are skipped
+fn elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let lower = xml.to_ascii_lowercase();
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut found = Vec::new();
+    let mut pos = 0;
+
+    while let Some(start) = lower[pos..].find(&open).map(|i| pos + i) {
+        let after = start + open.len();
+        pos = after;
+        if !matches!(lower[after..].chars().next(), Some('>') | Some(' ') | Some('\n') | Some('\t')) {
+            continue;
+        }
+        let Some(content_start) = lower[after..].find('>').map(|i| after + i + 1) else {
+            break;
+        };
+        if lower[..content_start].ends_with("/>") {
+            continue;
+        }
+        let Some(end) = lower[content_start..].find(&close).map(|i| content_start + i) else {
+            break;
+        };
+        found.push(&xml[content_start..end]);
+        pos = end + close.len();
+    }
+    found
+}
+
+fn child(xml: &str, tag: &str) -> Option<String> {
+    let content = elements(xml, tag).into_iter().next()?.trim();
+    let content = content
+        .strip_prefix("<![CDATA[")
+        .and_then(|c| c.strip_suffix("]]>"))
+        .unwrap_or(content);
+    Some(web::decode_entities(content.trim())).filter(|c| !c.is_empty())
+}
+
+// Atom links live in an href attribute rather than element text
+fn atom_link(entry: &str) -> Option<String> {
+    let start = entry.find("<link")?;
+    let tag = &entry[start..start + entry[start..].find('>')?];
+    let href = tag.find("href=\"")? + "href=\"".len();
+    let end = tag[href..].find('"')? + href;
+    Some(web::decode_entities(&tag[href..end]))
+}
+
+// Minimal RSS 2.0 / Atom reader: returns the feed title and its items, newest first as published
+fn parse_feed(xml: &str) -> (Option<String>, Vec<FeedItem>) {
+    let mut items = elements(xml, "item");
+    if items.is_empty() {
+        items = elements(xml, "entry");
+    }
+    let header_end = items.first().and_then(|first| xml.find(first)).unwrap_or(xml.len());
+    let title = child(&xml[..header_end], "title");
+
+    let items = items
+        .into_iter()
+        .filter_map(|item| {
+            let title = child(item, "title").unwrap_or_default();
+            let link = child(item, "link").or_else(|| atom_link(item));
+            let guid = child(item, "guid")
+                .or_else(|| child(item, "id"))
+                .or_else(|| link.clone())
+                .or_else(|| (!title.is_empty()).then(|| title.clone()))?;
+            let summary = child(item, "description")
+                .or_else(|| child(item, "summary"))
+                .or_else(|| child(item, "content"))
+                .map(|html| web::html_to_text(&html))
+                .unwrap_or_default();
+            Some(FeedItem {
+                guid,
+                title,
+                link,
+                summary: summary.chars().take(MAX_SUMMARY_CHARS).collect(),
+            })
+        })
+        .collect();
+
+    (title, items)
+}
+
+fn load_feeds(conn: &Connection) -> Result<Vec<Feed>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, url, title, created_at FROM digest_feeds ORDER BY created_at")
+        .map_err(db_err)?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(Feed {
+                id: row.get(0)?,
+                url: row.get(1)?,
+                title: row.get(2)?,
+                created_at: row.get(3)?,
+            })
+        })
+        .map_err(db_err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+}
+
+fn is_seen(conn: &Connection, feed_id: &str, guid: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT 1 FROM digest_seen_items WHERE feed_id = ?1 AND guid = ?2",
+        params![feed_id, guid],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|row| row.is_some())
+    .map_err(db_err)
+}
+
+// Items are (feed_id, feed title, item)
+fn build_prompt(items: &[(String, String, FeedItem)]) -> String {
+    let mut prompt = String::from(
+        "Summarize the following news items into a short daily digest. Group related items, \
+         give each one or two sentences, and keep the links so the reader can follow up.\n",
+    );
+    for (_, feed_title, item) in items {
+        prompt.push_str(&format!("\n### {}: {}\n", feed_title, item.title));
+        if let Some(link) = &item.link {
+            prompt.push_str(&format!("{}\n", link));
+        }
+        if !item.summary.is_empty() {
+            prompt.push_str(&format!("{}\n", item.summary));
+        }
+    }
+    prompt
+}
+
+// Fetches new items from every feed, summarizes them and stores the digest as a conversation
+pub async fn run_digest(app: &tauri::AppHandle) -> Result<Option<DigestReady>, String> {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return Err("A digest is already being built".to_string());
+    }
+    let result = build_digest(app).await;
+    RUNNING.store(false, Ordering::SeqCst);
+    result
+}
+
+async fn build_digest(app: &tauri::AppHandle) -> Result<Option<DigestReady>, String> {
+    let settings = config::get().digest;
+    let day = today();
+    let feeds = with_db(|conn| load_feeds(conn))?;
+
+    let mut new_items = Vec::new();
+    for feed in &feeds {
+        let raw = match web::fetch_raw(&feed.url).await {
+            Ok(raw) => raw,
+            Err(e) => {
+                println!("Warning: Failed to fetch feed {}: {}", feed.url, e);
+                continue;
+            }
+        };
+        let (_, items) = parse_feed(&raw.body);
+        let feed_title = feed.title.clone().unwrap_or_else(|| feed.url.clone());
+        for item in items.into_iter().take(settings.max_items_per_feed) {
+            if !with_db(|conn| is_seen(conn, &feed.id, &item.guid))? {
+                new_items.push((feed.id.clone(), feed_title.clone(), item));
+            }
+        }
+    }
+
+    if new_items.is_empty() {
+        let run_day = day.clone();
+        db_writer::write(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO digest_runs (day, conversation_id, item_count, created_at) VALUES (?1, NULL, 0, ?2)",
+                params![run_day, now_ms()],
+            )
+            .map_err(db_err)
+        })
+        .await?;
+        println!("Digest for {}: no new items", day);
+        return Ok(None);
+    }
+
+    let model_id = settings
+        .model_id
+        .or_else(keep_alive::default_model)
+        .ok_or("Set digest.model_id or models.default_model to build digests")?;
+    let _pin = keep_alive::pin(&model_id);
+    let (_, sampling) = sampling::resolve_profile(None, None)?;
+    let digest = generate_text(&model_id, &build_prompt(&new_items), &sampling, Priority::Background, app).await?;
+
+    let item_count = new_items.len();
+    let run_day = day.clone();
+    let conversation_id = db_writer::write(move |tx| {
+        let conversation = history::insert_conversation(tx, &format!("Digest {}", run_day))?;
+        history::insert_message(tx, &conversation.id, "assistant", &digest)?;
+        let now = now_ms();
+        for (feed_id, _, item) in &new_items {
+            tx.execute(
+                "INSERT OR IGNORE INTO digest_seen_items (feed_id, guid, seen_at) VALUES (?1, ?2, ?3)",
+                params![feed_id, item.guid, now],
+            )
+            .map_err(db_err)?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO digest_runs (day, conversation_id, item_count, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![run_day, conversation.id, item_count as i64, now],
+        )
+        .map_err(db_err)?;
+        Ok(conversation.id)
+    })
+    .await?;
+
+    let ready = DigestReady {
+        day,
+        conversation_id,
+        item_count,
+    };
+    println!("Digest for {} ready with {} items", ready.day, ready.item_count);
+    if let Err(e) = app.emit(DIGEST_READY_EVENT, &ready) {
+        println!("Warning: Failed to emit digest-ready event: {}", e);
+    }
+    Ok(Some(ready))
+}
+
+fn is_due() -> Result<bool, String> {
+    let settings = config::get().digest;
+    let hour = ((now_ms() / 3_600_000) % 24) as u32;
+    if !settings.enabled || hour < settings.hour_utc {
+        return Ok(false);
+    }
+    let day = today();
+    with_db(|conn| {
+        conn.query_row("SELECT 1 FROM digest_runs WHERE day = ?1", params![day], |_| Ok(()))
+            .optional()
+            .map(|row| row.is_none())
+            .map_err(db_err)
+    })
+}
+
+// Background task that builds one digest per day once digest.hour_utc has passed
+pub fn spawn_scheduler(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(SCHEDULER_INTERVAL).await;
+            match is_due() {
+                Ok(true) => {
+                    if let Err(e) = run_digest(&app).await {
+                        println!("Warning: Failed to build digest: {}", e);
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => println!("Warning: Failed to check digest schedule: {}", e),
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn add_digest_feed(url: String) -> Result<Feed, String> {
+    kiosk::ensure_unlocked("Adding feeds")?;
+    // Fetching once up front checks the allowlist and that the URL really is a feed
+    let raw = web::fetch_raw(&url).await?;
+    let (title, items) = parse_feed(&raw.body);
+    if items.is_empty() && title.is_none() {
+        return Err(format!("{} does not look like an RSS or Atom feed", url));
+    }
+
+    let feed = Feed {
+        id: new_id(),
+        url,
+        title,
+        created_at: now_ms(),
+    };
+    let row = feed.clone();
+    db_writer::write(move |conn| {
+        conn.execute(
+            "INSERT INTO digest_feeds (id, url, title, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![row.id, row.url, row.title, row.created_at],
+        )
+        .map_err(db_err)
+    })
+    .await?;
+    Ok(feed)
+}
+
+#[tauri::command]
+pub fn list_digest_feeds() -> Result<Vec<Feed>, String> {
+    with_db(|conn| load_feeds(conn))
+}
+
+#[tauri::command]
+pub async fn remove_digest_feed(feed_id: String) -> Result<(), String> {
+    db_writer::write(move |conn| {
+        conn.execute("DELETE FROM digest_feeds WHERE id = ?1", params![feed_id])
+            .map_err(db_err)
+    })
+    .await?;
+    Ok(())
+}
+
+// Builds a digest immediately, regardless of the schedule
+#[tauri::command]
+pub async fn run_digest_now(app: tauri::AppHandle) -> Result<Option<DigestReady>, String> {
+    run_digest(&app).await
+}
@@ -0,0 +1,345 @@
+// Retrieval over local documents. Imported text, Markdown and PDF files are chunked, embedded with
+// the local embedding model and stored with their vectors; a conversation with documents attached
+// gets the chunks closest to each message added to its prompt.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tauri::Emitter;
+
+use crate::context_packs::{self, Chunk};
+use crate::db_writer;
+use crate::history::{db_err, new_id, with_db};
+use crate::streaming::now_ms;
+use crate::{config, embeddings, kiosk};
+
+pub const DOCUMENT_IMPORT_PROGRESS_EVENT: &str = "document-import-progress";
+
+// Chunks embedded between progress events
+const EMBED_BATCH: usize = 16;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS documents (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    path TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    bytes INTEGER NOT NULL,
+    embedding_model TEXT NOT NULL,
+    imported_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS document_chunks (
+    document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    ordinal INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    tokens INTEGER NOT NULL,
+    embedding BLOB NOT NULL,
+    PRIMARY KEY (document_id, ordinal)
+);
+CREATE TABLE IF NOT EXISTS conversation_documents (
+    conversation_id TEXT NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    PRIMARY KEY (conversation_id, document_id)
+);
+";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DocumentsConfig {
+    // Chunks added to a message's prompt
+    pub top_k: usize,
+    // Chunks less similar to the message than this are left out, even within top_k
+    pub min_score: f32,
+    // Larger files are refused at import
+    pub max_file_bytes: u64,
+}
+
+impl Default for DocumentsConfig {
+    fn default() -> Self {
+        Self {
+            top_k: 4,
+            min_score: 0.3,
+            max_file_bytes: 32 * 1024 * 1024,
+        }
+    }
+}
+
+impl DocumentsConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.top_k == 0 {
+            errors.push("documents.top_k must be at least 1".to_string());
+        }
+        if !(-1.0..=1.0).contains(&self.min_score) {
+            errors.push("documents.min_score must be between -1 and 1".to_string());
+        }
+        errors
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Document {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    // "text", "markdown" or "pdf"
+    pub kind: String,
+    pub bytes: u64,
+    pub chunk_count: usize,
+    pub embedding_model: String,
+    pub imported_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentImportProgress {
+    pub path: String,
+    pub chunks_done: usize,
+    pub chunks_total: usize,
+}
+
+pub fn init() -> Result<(), String> {
+    with_db(|conn| conn.execute_batch(SCHEMA).map_err(db_err))
+}
+
+const COLUMNS: &str = "d.id, d.name, d.path, d.kind, d.bytes, d.embedding_model, d.imported_at,
+    (SELECT COUNT(*) FROM document_chunks c WHERE c.document_id = d.id)";
+
+fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Document> {
+    Ok(Document {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        path: row.get(2)?,
+        kind: row.get(3)?,
+        bytes: row.get::<_, i64>(4)? as u64,
+        embedding_model: row.get(5)?,
+        imported_at: row.get::<_, i64>(6)? as u64,
+        chunk_count: row.get::<_, i64>(7)? as usize,
+    })
+}
+
+fn document_by_id(conn: &Connection, document_id: &str) -> Result<Document, String> {
+    conn.query_row(&format!("SELECT {} FROM documents d WHERE d.id = ?1", COLUMNS), params![document_id], from_row)
+        .optional()
+        .map_err(db_err)?
+        .ok_or_else(|| format!("Document not found: {}", document_id))
+}
+
+fn kind_of(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_string_lossy().to_ascii_lowercase();
+    match extension.as_str() {
+        "txt" | "text" | "log" | "csv" => Some("text"),
+        "md" | "markdown" => Some("markdown"),
+        "pdf" => Some("pdf"),
+        _ => None,
+    }
+}
+
+fn read_document(path: &Path, kind: &str) -> Result<String, String> {
+    let text = match kind {
+        "pdf" => pdf_extract::extract_text(path).map_err(|e| format!("Failed to read the text of {}: {}", path.display(), e))?,
+        _ => std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+    };
+    if text.trim().is_empty() {
+        return Err(format!("{} has no text to import (a scanned PDF needs OCR first)", path.display()));
+    }
+    Ok(text)
+}
+
+fn emit_progress(app: &tauri::AppHandle, progress: &DocumentImportProgress) {
+    if let Err(e) = app.emit(DOCUMENT_IMPORT_PROGRESS_EVENT, progress) {
+        println!("Warning: Failed to emit document import progress: {}", e);
+    }
+}
+
+// Chunks of the conversation's documents embedded with `model`, with their vectors
+fn attached_chunks(conn: &Connection, conversation_id: &str, model: &str) -> Result<Vec<(Chunk, Vec<f32>)>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT d.name, c.content, c.tokens, c.embedding FROM conversation_documents cd
+             JOIN documents d ON d.id = cd.document_id
+             JOIN document_chunks c ON c.document_id = d.id
+             WHERE cd.conversation_id = ?1 AND d.embedding_model = ?2
+             ORDER BY d.name, c.ordinal",
+        )
+        .map_err(db_err)?;
+    let rows = stmt
+        .query_map(params![conversation_id, model], |row| {
+            let chunk = Chunk { source: row.get(0)?, content: row.get(1)?, tokens: row.get::<_, i64>(2)? as usize };
+            Ok((chunk, embeddings::from_bytes(&row.get::<_, Vec<u8>>(3)?)))
+        })
+        .map_err(db_err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+}
+
+fn has_attachments(conn: &Connection, conversation_id: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM conversation_documents WHERE conversation_id = ?1)",
+        params![conversation_id],
+        |row| row.get(0),
+    )
+    .map_err(db_err)
+}
+
+// Prepends the chunks of the conversation's documents closest to the message. Retrieval problems
+// leave the message as it is, so a chat never fails over its attachments.
+pub async fn augment(conversation_id: Option<&str>, message: &str) -> Result<String, String> {
+    let Some(conversation_id) = conversation_id else {
+        return Ok(message.to_string());
+    };
+    if !with_db(|conn| has_attachments(conn, conversation_id))? {
+        return Ok(message.to_string());
+    }
+
+    let (model, query) = match embeddings::embed(&[message.to_string()]).await {
+        Ok((model, mut vectors)) => (model, vectors.pop().unwrap_or_default()),
+        Err(e) => {
+            println!("Warning: Skipping document retrieval: {}", e);
+            return Ok(message.to_string());
+        }
+    };
+    let chunks = with_db(|conn| attached_chunks(conn, conversation_id, &model))?;
+    if chunks.is_empty() {
+        println!("Warning: The documents of conversation {} were embedded with another model; re-import them", conversation_id);
+        return Ok(message.to_string());
+    }
+
+    let settings = config::get().documents;
+    let mut scored: Vec<(f32, &Chunk)> = chunks
+        .iter()
+        .map(|(chunk, vector)| (embeddings::cosine(&query, vector), chunk))
+        .filter(|(score, _)| *score >= settings.min_score)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.truncate(settings.top_k);
+    if scored.is_empty() {
+        return Ok(message.to_string());
+    }
+
+    let mut context = String::new();
+    for (_, chunk) in scored {
+        context.push_str(&format!("--- {} ---\n{}\n\n", chunk.source, chunk.content));
+    }
+    Ok(format!(
+        "Excerpts from documents the user attached, use them when they are relevant:\n\n{}Question:\n{}",
+        context, message
+    ))
+}
+
+// Reads, chunks and embeds a text, Markdown or PDF file. Progress arrives as
+// document-import-progress events while the chunks are embedded.
+#[tauri::command]
+pub async fn import_document(path: String, app: tauri::AppHandle) -> Result<Document, String> {
+    kiosk::ensure_unlocked("Documents")?;
+    let file = Path::new(&path);
+    let kind = kind_of(file).ok_or_else(|| format!("{} is not a text, Markdown or PDF file", path))?;
+    let bytes = std::fs::metadata(file).map_err(|e| format!("Failed to read {}: {}", path, e))?.len();
+    let max_bytes = config::get().documents.max_file_bytes;
+    if bytes > max_bytes {
+        return Err(format!("{} is {} bytes, over the documents.max_file_bytes limit of {}", path, bytes, max_bytes));
+    }
+    let name = file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| path.clone());
+
+    // PDF extraction of a large file takes seconds, so it runs off the async workers
+    let source = file.to_path_buf();
+    let text = tokio::task::spawn_blocking(move || read_document(&source, kind))
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path, e))??;
+    let chunks = context_packs::chunk_text(&name, &text);
+    let mut progress = DocumentImportProgress { path: path.clone(), chunks_done: 0, chunks_total: chunks.len() };
+    emit_progress(&app, &progress);
+
+    let mut model = embeddings::model_name();
+    let mut vectors = Vec::with_capacity(chunks.len());
+    for batch in chunks.chunks(EMBED_BATCH) {
+        let texts: Vec<String> = batch.iter().map(|chunk| chunk.content.clone()).collect();
+        let (used, mut batch_vectors) = embeddings::embed(&texts).await?;
+        model = used;
+        vectors.append(&mut batch_vectors);
+        progress.chunks_done += batch.len();
+        emit_progress(&app, &progress);
+    }
+
+    // One writer job is one savepoint, so a failed import leaves no partial chunks behind
+    db_writer::write(move |conn| {
+        let id = new_id();
+        conn.execute(
+            "INSERT INTO documents (id, name, path, kind, bytes, embedding_model, imported_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![id, name, path, kind, bytes as i64, model, now_ms() as i64],
+        )
+        .map_err(db_err)?;
+        for (ordinal, (chunk, vector)) in chunks.iter().zip(&vectors).enumerate() {
+            conn.execute(
+                "INSERT INTO document_chunks (document_id, ordinal, content, tokens, embedding) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![id, ordinal as i64, chunk.content, chunk.tokens as i64, embeddings::to_bytes(vector)],
+            )
+            .map_err(db_err)?;
+        }
+        document_by_id(conn, &id)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn list_documents() -> Result<Vec<Document>, String> {
+    with_db(|conn| {
+        let mut stmt = conn
+            .prepare(&format!("SELECT {} FROM documents d ORDER BY d.name", COLUMNS))
+            .map_err(db_err)?;
+        let rows = stmt.query_map([], from_row).map_err(db_err)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+    })
+}
+
+// Deletes a document and its vectors, detaching it from every conversation
+#[tauri::command]
+pub async fn delete_document(document_id: String) -> Result<(), String> {
+    db_writer::write(move |conn| {
+        let document = document_by_id(conn, &document_id)?;
+        conn.execute("DELETE FROM documents WHERE id = ?1", params![document.id])
+            .map_err(db_err)?;
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn attach_document(conversation_id: String, document_id: String) -> Result<Document, String> {
+    db_writer::write(move |conn| {
+        let document = document_by_id(conn, &document_id)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO conversation_documents (conversation_id, document_id) VALUES (?1, ?2)",
+            params![conversation_id, document.id],
+        )
+        .map_err(db_err)?;
+        Ok(document)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn detach_document(conversation_id: String, document_id: String) -> Result<(), String> {
+    db_writer::write(move |conn| {
+        conn.execute(
+            "DELETE FROM conversation_documents WHERE conversation_id = ?1 AND document_id = ?2",
+            params![conversation_id, document_id],
+        )
+        .map_err(db_err)?;
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn list_conversation_documents(conversation_id: String) -> Result<Vec<Document>, String> {
+    with_db(|conn| {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM documents d JOIN conversation_documents cd ON cd.document_id = d.id
+                 WHERE cd.conversation_id = ?1 ORDER BY d.name",
+                COLUMNS
+            ))
+            .map_err(db_err)?;
+        let rows = stmt.query_map(params![conversation_id], from_row).map_err(db_err)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+    })
+}
@@ -0,0 +1,146 @@
+use base64::Engine;
+use serde::Serialize;
+use tauri::Manager;
+use tauri_plugin_opener::OpenerExt;
+
+use crate::history::new_id;
+use crate::priority::Priority;
+use crate::{generate_text, keep_alive, kiosk, sampling};
+
+// Many mail clients truncate or reject longer mailto: URLs, so bigger drafts go to an .eml file
+const MAX_MAILTO_LEN: usize = 2000;
+
+const DRAFTS_DIR: &str = "email_drafts";
+
+// A generated email; nothing leaves the machine until the user sends it from their mail client
+#[derive(Debug, Clone, Serialize)]
+pub struct EmailDraft {
+    pub subject: String,
+    pub body: String,
+    pub mailto_url: Option<String>,
+    pub eml_path: Option<String>,
+}
+
+// Splits "Subject: ..." from the rest of the model's reply
+fn parse_draft(response: &str) -> (String, String) {
+    let response = response.trim();
+    let mut lines = response.lines();
+    match lines.next().and_then(|first| {
+        let first = first.trim().trim_start_matches('*').trim();
+        first
+            .strip_prefix("Subject:")
+            .or_else(|| first.strip_prefix("subject:"))
+            .map(|s| s.trim().trim_end_matches('*').trim().to_string())
+    }) {
+        Some(subject) => (subject, lines.collect::<Vec<_>>().join("\n").trim().to_string()),
+        None => (String::new(), response.to_string()),
+    }
+}
+
+// RFC 6068 percent-encoding; line breaks must be sent as CRLF
+fn encode_mailto_component(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len() * 3);
+    for byte in text.replace("\r\n", "\n").replace('\n', "\r\n").bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn mailto_url(to: Option<&str>, subject: &str, body: &str) -> String {
+    format!(
+        "mailto:{}?subject={}&body={}",
+        to.map(encode_mailto_component).unwrap_or_default(),
+        encode_mailto_component(subject),
+        encode_mailto_component(body)
+    )
+}
+
+// Non-ASCII header values need RFC 2047 encoded-words
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", base64::engine::general_purpose::STANDARD.encode(value))
+    }
+}
+
+// X-Unsent makes Outlook and Apple Mail open the file as an editable draft
+fn write_eml(app: &tauri::AppHandle, to: Option<&str>, subject: &str, body: &str) -> Result<String, String> {
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| format!("Failed to resolve cache directory: {}", e))?
+        .join(DRAFTS_DIR);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let mut contents = String::new();
+    if let Some(to) = to {
+        contents.push_str(&format!("To: {}\r\n", to));
+    }
+    contents.push_str(&format!("Subject: {}\r\n", encode_header(subject)));
+    contents.push_str("X-Unsent: 1\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n");
+    contents.push_str(&body.replace("\r\n", "\n").replace('\n', "\r\n"));
+
+    let path = dir.join(format!("{}.eml", new_id()));
+    std::fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+// Drafts an email with the local model and hands it to the default mail client
+#[tauri::command]
+pub async fn draft_email(
+    instructions: String,
+    context: Option<String>,
+    to: Option<String>,
+    model_id: Option<String>,
+    save_eml: Option<bool>,
+    open: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<EmailDraft, String> {
+    kiosk::ensure_unlocked("Email drafting")?;
+    let model_id = model_id
+        .or_else(keep_alive::default_model)
+        .ok_or("No model selected and no models.default_model configured")?;
+
+    let mut prompt = format!(
+        "Write an email following these instructions: {}\n\
+         Reply with the subject on the first line as \"Subject: ...\", then a blank line, then the \
+         body as plain text. Do not add any commentary.",
+        instructions.trim()
+    );
+    if let Some(context) = context.as_deref().filter(|c| !c.trim().is_empty()) {
+        prompt.push_str(&format!("\n\nRelevant context:\n{}", context.trim()));
+    }
+
+    let (_, sampling) = sampling::resolve_profile(None, None)?;
+    let response = generate_text(&model_id, &prompt, &sampling, Priority::Interactive, &app).await?;
+    let (subject, body) = parse_draft(&response);
+
+    let url = mailto_url(to.as_deref(), &subject, &body);
+    let (mailto_url, eml_path) = if save_eml.unwrap_or(false) || url.len() > MAX_MAILTO_LEN {
+        (None, Some(write_eml(&app, to.as_deref(), &subject, &body)?))
+    } else {
+        (Some(url), None)
+    };
+
+    if open.unwrap_or(true) {
+        let opened = match (&mailto_url, &eml_path) {
+            (Some(url), _) => app.opener().open_url(url, None::<&str>),
+            (None, Some(path)) => app.opener().open_path(path, None::<&str>),
+            (None, None) => Ok(()),
+        };
+        if let Err(e) = opened {
+            println!("Warning: Failed to open the mail client: {}", e);
+        }
+    }
+
+    Ok(EmailDraft {
+        subject,
+        body,
+        mailto_url,
+        eml_path,
+    })
+}
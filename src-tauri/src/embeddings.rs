@@ -0,0 +1,157 @@
+// Text embeddings computed on this machine with mistral.rs, for retrieval over imported documents
+// and for the frontend through embed_text. Embedding models are small and kept apart from the chat
+// models: each loads on first use and stays loaded for the rest of the session.
+
+use mistralrs::EmbeddingModelBuilder;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::{catalog, config, hardware, kiosk};
+
+// Loaded embedding models by directory or Hugging Face id
+static MODELS: Mutex<Option<HashMap<String, Arc<mistralrs::Model>>>> = Mutex::const_new(None);
+
+// Texts one embed_text call may embed
+const MAX_TEXTS: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmbeddingsConfig {
+    // Local directory or Hugging Face id of the embedding model; a Hugging Face model is fetched
+    // into the cache once, unless offline mode is on
+    pub model: String,
+}
+
+impl Default for EmbeddingsConfig {
+    fn default() -> Self {
+        Self { model: "google/embeddinggemma-300m".to_string() }
+    }
+}
+
+impl EmbeddingsConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.model.trim().is_empty() {
+            errors.push("embeddings.model must not be empty".to_string());
+        }
+        errors
+    }
+}
+
+// The configured embedding model's name, stored with every embedding so vectors from different
+// models are never compared
+pub fn model_name() -> String {
+    config::get().embeddings.model
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TextEmbeddings {
+    pub model: String,
+    pub dimensions: usize,
+    // One vector per input text, in order
+    pub embeddings: Vec<Vec<f32>>,
+}
+
+async fn model(name: &str) -> Result<Arc<mistralrs::Model>, String> {
+    let mut loaded = MODELS.lock().await;
+    let loaded = loaded.get_or_insert_with(HashMap::new);
+    if let Some(model) = loaded.get(name) {
+        return Ok(model.clone());
+    }
+    if !Path::new(name).exists() && catalog::is_offline() {
+        return Err(format!("The embedding model {} isn't a local directory and offline mode is on", name));
+    }
+    println!("Loading embedding model {}", name);
+    let mut builder = EmbeddingModelBuilder::new(name);
+    if hardware::use_cpu() {
+        builder = builder.with_force_cpu();
+    }
+    let model = Arc::new(
+        builder
+            .build()
+            .await
+            .map_err(|e: anyhow::Error| format!("Failed to load the embedding model {}: {}", name, e))?,
+    );
+    loaded.insert(name.to_string(), model.clone());
+    Ok(model)
+}
+
+async fn embed_with(name: &str, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+    let model = model(name).await?;
+    let mut vectors = Vec::with_capacity(texts.len());
+    for text in texts {
+        let vector = model
+            .generate_embedding(text)
+            .await
+            .map_err(|e| format!("Failed to embed text: {}", e))?;
+        vectors.push(vector);
+    }
+    Ok(vectors)
+}
+
+// One vector per text, from the configured model; returns the model's name with them
+pub async fn embed(texts: &[String]) -> Result<(String, Vec<Vec<f32>>), String> {
+    let name = model_name();
+    let vectors = embed_with(&name, texts).await?;
+    Ok((name, vectors))
+}
+
+// Directory or Hugging Face id of an embedding model: "local-<dir>" is a directory in the models
+// folder, anything else is passed to mistral.rs as it is
+fn resolve(model_id: &str, app: &tauri::AppHandle) -> Result<String, String> {
+    if model_id.starts_with("local-") {
+        return crate::local_model_dir(model_id, app)
+            .map(|dir| dir.to_string_lossy().to_string())
+            .ok_or_else(|| format!("{} is not a model directory; embedding models load from safetensors directories, not single GGUF files", model_id));
+    }
+    Ok(model_id.to_string())
+}
+
+// Embeds texts with a local embedding model, embeddings.model unless model_id names another one.
+// Nothing is sent to a remote API; a Hugging Face model is only downloaded the first time.
+#[tauri::command]
+pub async fn embed_text(texts: Vec<String>, model_id: Option<String>, app: tauri::AppHandle) -> Result<TextEmbeddings, String> {
+    kiosk::ensure_unlocked("Embeddings")?;
+    if texts.is_empty() {
+        return Err("No texts to embed".to_string());
+    }
+    if texts.len() > MAX_TEXTS {
+        return Err(format!("At most {} texts can be embedded at once, got {}", MAX_TEXTS, texts.len()));
+    }
+    let name = match model_id.filter(|id| !id.trim().is_empty()) {
+        Some(model_id) => resolve(&model_id, &app)?,
+        None => model_name(),
+    };
+    let embeddings = embed_with(&name, &texts).await?;
+    Ok(TextEmbeddings {
+        model: name,
+        dimensions: embeddings.first().map_or(0, Vec::len),
+        embeddings,
+    })
+}
+
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 {
+        0.0
+    } else {
+        dot / denominator
+    }
+}
+
+// Vectors are stored as little-endian f32 bytes
+pub fn to_bytes(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+pub fn from_bytes(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
@@ -0,0 +1,93 @@
+use serde::Serialize;
+use std::path::Path;
+
+use crate::{config, local_model_dir, metrics};
+
+// Generation length assumed when neither the caller nor the profile sets max_tokens
+const DEFAULT_MAX_TOKENS: usize = 512;
+
+// Rough characters-per-token ratio of BPE tokenizers on English text
+const CHARS_PER_TOKEN: f64 = 4.0;
+
+// Pre-flight prediction so the UI can say "this will take ~45s" before sending
+#[derive(Debug, Clone, Serialize)]
+pub struct GenerationEstimate {
+    pub model_id: String,
+    pub prompt_tokens: usize,
+    pub max_tokens: usize,
+    pub context_window: Option<usize>,
+    // None when the context window of the model is unknown
+    pub fits_context: Option<bool>,
+    pub expected_ttft_ms: Option<u64>,
+    pub expected_duration_ms: Option<u64>,
+    // True when timings come from earlier generations with this model
+    pub based_on_history: bool,
+}
+
+// Approximates the token count without loading a tokenizer; errs on the high side
+pub fn estimate_tokens(text: &str) -> usize {
+    let chars = text.chars().count() as f64;
+    let words = text.split_whitespace().count() as f64;
+    (chars / CHARS_PER_TOKEN).max(words * 1.3).ceil() as usize
+}
+
+// Rejects a request whose messages plus reply can't fit the model's configured context cap. `messages`
+// is everything the request sends: system prompt, earlier turns and the message with its context.
+pub fn check_context_limit(model_id: &str, messages: &[&str], max_tokens: Option<usize>) -> Result<(), String> {
+    let Some(limit) = config::get().limits.for_model(model_id).max_context_tokens else {
+        return Ok(());
+    };
+    let prompt_tokens: usize = messages.iter().copied().map(estimate_tokens).sum();
+    let max_tokens = max_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+    if prompt_tokens + max_tokens > limit {
+        return Err(format!(
+            "The request is about {} tokens and the reply may use up to {}, which exceeds the {}-token context limit set for {}; shorten the prompt or conversation, or lower max_tokens",
+            prompt_tokens, max_tokens, limit, model_id
+        ));
+    }
+    Ok(())
+}
+
+// Reads max_position_embeddings from a Hugging Face style config.json
+fn context_window(model_dir: &Path) -> Option<usize> {
+    let contents = std::fs::read_to_string(model_dir.join("config.json")).ok()?;
+    let config: serde_json::Value = serde_json::from_str(&contents).ok()?;
+    config
+        .get("max_position_embeddings")
+        .or_else(|| config.get("text_config").and_then(|c| c.get("max_position_embeddings")))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as usize)
+}
+
+#[tauri::command]
+pub fn estimate_generation(
+    model_id: String,
+    prompt: String,
+    max_tokens: Option<usize>,
+    app: tauri::AppHandle,
+) -> Result<GenerationEstimate, String> {
+    let max_tokens = max_tokens
+        .or_else(|| crate::sampling::resolve_profile(None, None).ok().and_then(|(_, s)| s.max_tokens))
+        .unwrap_or(DEFAULT_MAX_TOKENS);
+    let prompt_tokens = estimate_tokens(&prompt);
+
+    let context_window = local_model_dir(&model_id, &app).and_then(|dir| context_window(&dir));
+    let fits_context = context_window.map(|window| prompt_tokens + max_tokens <= window);
+
+    let history = metrics::snapshot().models.get(&model_id).cloned();
+    let expected_ttft_ms = history.as_ref().and_then(|m| m.avg_ttft_ms).map(|ms| ms as u64);
+    let expected_duration_ms = metrics::tokens_per_sec(&model_id)
+        .filter(|tps| *tps > 0.0)
+        .map(|tps| expected_ttft_ms.unwrap_or(0) + (max_tokens as f64 / tps * 1000.0) as u64);
+
+    Ok(GenerationEstimate {
+        model_id,
+        prompt_tokens,
+        max_tokens,
+        context_window,
+        fits_context,
+        expected_ttft_ms,
+        expected_duration_ms,
+        based_on_history: history.is_some(),
+    })
+}
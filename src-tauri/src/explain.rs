@@ -0,0 +1,142 @@
+// "Explain this error": a failed command's structured error and the latest log lines of its request
+// are handed to a small local model, which answers in plain language with fixes to try. Without a
+// model to ask, or when the model fails too, the error's own hint is all there is.
+
+use serde::{Deserialize, Serialize};
+
+use crate::priority::Priority;
+use crate::sampling::SamplingConfig;
+use crate::{config, generate_text, keep_alive, trace};
+
+// Each log line is cut to this many characters in the prompt
+const MAX_LINE_CHARS: usize = 300;
+
+// Asking a model to explain running out of memory would load one more model
+const NEEDS_NO_MODEL: [&str; 1] = ["out_of_memory"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExplainConfig {
+    // Falls back to models.default_model
+    pub model_id: Option<String>,
+    // Latest log lines of the failed request included in the prompt
+    pub max_log_lines: usize,
+}
+
+impl Default for ExplainConfig {
+    fn default() -> Self {
+        Self { model_id: None, max_log_lines: 20 }
+    }
+}
+
+impl ExplainConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.model_id.as_deref().is_some_and(|id| id.trim().is_empty()) {
+            errors.push("explain.model_id must not be empty; remove it to use models.default_model".to_string());
+        }
+        errors
+    }
+}
+
+// The error as the frontend received it, e.g. a serialized ModelError
+#[derive(Debug, Clone, Deserialize)]
+pub struct ErrorContext {
+    pub message: String,
+    #[serde(default)]
+    pub hint: Option<String>,
+    #[serde(default)]
+    pub request_id: Option<String>,
+    // What the user was doing, e.g. "Loading Gemma 3n"
+    #[serde(default)]
+    pub operation: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Explanation {
+    pub explanation: String,
+    pub suggestions: Vec<String>,
+    // None when the explanation is the error's own hint
+    pub model_id: Option<String>,
+}
+
+fn build_prompt(error_code: &str, context: &ErrorContext, lines: &[trace::TraceLine]) -> String {
+    let mut prompt = String::from(
+        "You help users of a desktop app that runs language models locally with mistral.rs. Explain the \
+         error below in plain language for someone who isn't a developer, in two or three sentences, then \
+         list up to three fixes they can try. Answer in this format:\n\
+         Explanation: <explanation>\n\
+         Fixes:\n\
+         - <fix>\n\n",
+    );
+    if let Some(operation) = &context.operation {
+        prompt.push_str(&format!("What the user was doing: {}\n", operation));
+    }
+    prompt.push_str(&format!("Error code: {}\nError: {}\n", error_code, context.message));
+    if let Some(hint) = &context.hint {
+        prompt.push_str(&format!("Known advice: {}\n", hint));
+    }
+    if !lines.is_empty() {
+        prompt.push_str("\nLog:\n");
+        for line in lines {
+            let message: String = line.message.chars().take(MAX_LINE_CHARS).collect();
+            prompt.push_str(&format!("{}{}\n", if line.error { "ERROR " } else { "" }, message));
+        }
+    }
+    prompt
+}
+
+// Splits "Explanation: ... Fixes: - ..." apart; anything off-format is taken as the explanation
+fn parse(answer: &str) -> (String, Vec<String>) {
+    let (explanation, fixes) = match answer.split_once("Fixes:") {
+        Some((explanation, fixes)) => (explanation, fixes),
+        None => (answer, ""),
+    };
+    let explanation = explanation.trim().trim_start_matches("Explanation:").trim().to_string();
+    let suggestions = fixes
+        .lines()
+        .map(|line| line.trim().trim_start_matches(['-', '*']).trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+    (explanation, suggestions)
+}
+
+fn from_hint(context: &ErrorContext) -> Explanation {
+    Explanation {
+        explanation: context.message.clone(),
+        suggestions: context.hint.iter().cloned().collect(),
+        model_id: None,
+    }
+}
+
+// Plain-language explanation of a failed command, for the error dialog
+#[tauri::command]
+pub async fn explain_error(error_code: String, context: ErrorContext, app: tauri::AppHandle) -> Result<Explanation, String> {
+    let settings = config::get().explain;
+    let model_id = settings.model_id.or_else(keep_alive::default_model);
+    let Some(model_id) = model_id.filter(|_| !NEEDS_NO_MODEL.contains(&error_code.as_str())) else {
+        return Ok(from_hint(&context));
+    };
+
+    let mut lines = context.request_id.as_deref().map(trace::lines).unwrap_or_default();
+    let skip = lines.len().saturating_sub(settings.max_log_lines);
+    lines.drain(..skip);
+
+    let sampling = SamplingConfig {
+        temperature: Some(0.2),
+        max_tokens: Some(300),
+        ..SamplingConfig::default()
+    };
+    let prompt = build_prompt(&error_code, &context, &lines);
+    match generate_text(&model_id, &prompt, &sampling, Priority::Interactive, &app).await {
+        Ok(answer) => {
+            let (explanation, suggestions) = parse(&answer);
+            Ok(Explanation { explanation, suggestions, model_id: Some(model_id) })
+        }
+        Err(e) => {
+            println!("Warning: Failed to explain error {}: {}", error_code, e);
+            Ok(from_hint(&context))
+        }
+    }
+}
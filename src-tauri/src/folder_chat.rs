@@ -0,0 +1,160 @@
+// "Chat with this folder": a dropped folder is indexed into a context pack that belongs to a new
+// conversation. The pack is hidden from list_context_packs and is deleted along with the
+// conversation, including when the conversation is merged away.
+
+use rusqlite::params;
+use serde::Serialize;
+use std::path::Path;
+use tauri::Emitter;
+
+use crate::context_packs::{self, ContextPack, PackSource};
+use crate::history::{self, db_err, with_db, Conversation};
+use crate::{db_writer, kiosk};
+use crate::streaming::now_ms;
+
+pub const FOLDER_INDEX_PROGRESS_EVENT: &str = "folder-index-progress";
+
+// Text read from a folder stops here; the rest of the folder is left out of the index
+const MAX_FOLDER_BYTES: u64 = 16 * 1024 * 1024;
+
+// Progress is reported after every this many files
+const PROGRESS_EVERY_FILES: usize = 10;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS folder_sessions (
+    conversation_id TEXT PRIMARY KEY REFERENCES conversations(id) ON DELETE CASCADE,
+    pack_id TEXT NOT NULL REFERENCES context_packs(id) ON DELETE CASCADE,
+    folder TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+CREATE TRIGGER IF NOT EXISTS folder_sessions_teardown AFTER DELETE ON folder_sessions
+BEGIN
+    DELETE FROM context_packs WHERE id = OLD.pack_id;
+END;
+";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FolderIndexProgress {
+    pub conversation_id: String,
+    pub files_done: usize,
+    pub files_total: usize,
+    pub bytes_read: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FolderSession {
+    pub conversation: Conversation,
+    pub pack: ContextPack,
+    pub folder: String,
+    pub files_indexed: usize,
+    // Binary, unreadable or oversized files
+    pub files_skipped: usize,
+    // Whether MAX_FOLDER_BYTES (or the per-folder file cap) cut the index short
+    pub truncated: bool,
+}
+
+pub fn init() -> Result<(), String> {
+    with_db(|conn| conn.execute_batch(SCHEMA).map_err(db_err))
+}
+
+fn emit_progress(app: &tauri::AppHandle, progress: &FolderIndexProgress) {
+    if let Err(e) = app.emit(FOLDER_INDEX_PROGRESS_EVENT, progress) {
+        println!("Warning: Failed to emit folder index progress: {}", e);
+    }
+}
+
+// Opens a conversation over a dropped folder. Indexing progress arrives as folder-index-progress
+// events; if indexing fails the conversation is removed again.
+#[tauri::command]
+pub async fn start_folder_chat(
+    path: String,
+    title: Option<String>,
+    token_budget: Option<usize>,
+    app: tauri::AppHandle,
+) -> Result<FolderSession, String> {
+    kiosk::ensure_unlocked("Folder chats")?;
+    let folder = Path::new(&path);
+    if !folder.is_dir() {
+        return Err(format!("{} is not a folder", path));
+    }
+    let folder_name = folder
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.clone());
+
+    let folder_path = path.clone();
+    let (conversation, pack_id) = db_writer::write(move |tx| {
+        let conversation = history::insert_conversation(tx, title.as_deref().unwrap_or(&format!("Folder: {}", folder_name)))?;
+        let sources = [PackSource { kind: "folder".to_string(), location: folder_path.clone() }];
+        // Pack names are unique, so the conversation id keeps two chats over one folder apart
+        let pack_name = format!("Folder chat {} ({})", folder_name, conversation.id);
+        let pack_id = context_packs::insert_pack(tx, &pack_name, &sources, token_budget)?;
+        tx.execute(
+            "INSERT INTO folder_sessions (conversation_id, pack_id, folder, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![conversation.id, pack_id, folder_path, now_ms() as i64],
+        )
+        .map_err(db_err)?;
+        tx.execute(
+            "INSERT INTO conversation_context_packs (conversation_id, pack_id) VALUES (?1, ?2)",
+            params![conversation.id, pack_id],
+        )
+        .map_err(db_err)?;
+        Ok((conversation, pack_id))
+    })
+    .await?;
+
+    let mut files = Vec::new();
+    context_packs::collect_files(folder, &mut files);
+    let files_total = files.len();
+    let mut progress = FolderIndexProgress { conversation_id: conversation.id.clone(), files_done: 0, files_total, bytes_read: 0 };
+    emit_progress(&app, &progress);
+
+    let mut chunks = Vec::new();
+    let mut files_indexed = 0;
+    let mut truncated = false;
+    for file in &files {
+        let size = std::fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+        if progress.bytes_read + size > MAX_FOLDER_BYTES {
+            truncated = true;
+            break;
+        }
+        if let Some(text) = context_packs::read_text_file(file) {
+            chunks.append(&mut context_packs::chunk_text(&file.to_string_lossy(), &text));
+            progress.bytes_read += size;
+            files_indexed += 1;
+        }
+        progress.files_done += 1;
+        if progress.files_done % PROGRESS_EVERY_FILES == 0 {
+            emit_progress(&app, &progress);
+        }
+    }
+    let files_skipped = progress.files_done - files_indexed;
+    emit_progress(&app, &progress);
+
+    let stored = match context_packs::store_chunks(&pack_id, chunks).await {
+        Ok(()) => with_db(|conn| context_packs::pack_by_id(conn, &pack_id)),
+        Err(e) => Err(e),
+    };
+    let pack = match stored {
+        Ok(pack) => pack,
+        Err(e) => {
+            if let Err(cleanup) = history::delete_conversation(conversation.id.clone()).await {
+                println!("Warning: Failed to remove folder chat {}: {}", conversation.id, cleanup);
+            }
+            return Err(e);
+        }
+    };
+
+    println!(
+        "Indexed {} of {} files from {} for conversation {}",
+        files_indexed, files_total, path, conversation.id
+    );
+    Ok(FolderSession {
+        conversation,
+        pack,
+        folder: path,
+        files_indexed,
+        files_skipped,
+        truncated: truncated || files_total >= context_packs::MAX_FILES_PER_FOLDER,
+    })
+}
@@ -0,0 +1,182 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::history::new_id;
+use crate::priority::Priority;
+use crate::{generate_text, kiosk, sampling};
+
+// Filled documents waiting for the user to review the diff, keyed by preview id
+static PREVIEWS: OnceLock<Mutex<HashMap<String, FormPreview>>> = OnceLock::new();
+
+fn previews() -> &'static Mutex<HashMap<String, FormPreview>> {
+    PREVIEWS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FormPreview {
+    pub preview_id: String,
+    pub template_path: String,
+    // Placeholder name -> extracted value; None when the source didn't contain it
+    pub values: BTreeMap<String, Option<String>>,
+    pub missing: Vec<String>,
+    // Changed lines as "- template" / "+ filled" pairs, prefixed with their line number
+    pub diff: String,
+    pub output: String,
+}
+
+// Names of {{placeholder}} fields in order of first appearance
+fn placeholders(template: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + end].trim().to_string();
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+        rest = &rest[start + 2 + end + 2..];
+    }
+    names
+}
+
+fn fill_line(line: &str, values: &BTreeMap<String, Option<String>>) -> String {
+    let mut filled = line.to_string();
+    for (name, value) in values {
+        if let Some(value) = value {
+            // Tolerate both {{name}} and {{ name }}
+            filled = filled.replace(&format!("{{{{{}}}}}", name), value);
+            filled = filled.replace(&format!("{{{{ {} }}}}", name), value);
+        }
+    }
+    filled
+}
+
+// Placeholders never span lines, so filling keeps a one-to-one mapping from template lines
+fn render(template: &str, values: &BTreeMap<String, Option<String>>) -> (String, String) {
+    let mut output = Vec::new();
+    let mut diff = String::new();
+    for (index, line) in template.lines().enumerate() {
+        let filled = fill_line(line, values);
+        if filled != line {
+            diff.push_str(&format!("@@ line {}\n- {}\n", index + 1, line));
+            for filled_line in filled.lines() {
+                diff.push_str(&format!("+ {}\n", filled_line));
+            }
+        }
+        output.push(filled);
+    }
+    let mut output = output.join("\n");
+    if template.ends_with('\n') {
+        output.push('\n');
+    }
+    (output, diff)
+}
+
+// Pulls the first JSON object out of a reply that may wrap it in prose or a code fence
+fn parse_values(response: &str, fields: &[String]) -> Result<BTreeMap<String, Option<String>>, String> {
+    let start = response.find('{').ok_or("The model did not return a JSON object")?;
+    let end = response.rfind('}').ok_or("The model did not return a JSON object")?;
+    let json: serde_json::Value = serde_json::from_str(&response[start..=end])
+        .map_err(|e| format!("The model returned invalid JSON: {}", e))?;
+
+    Ok(fields
+        .iter()
+        .map(|field| {
+            let value = match json.get(field) {
+                Some(serde_json::Value::String(s)) if !s.trim().is_empty() => Some(s.trim().to_string()),
+                Some(serde_json::Value::Number(n)) => Some(n.to_string()),
+                Some(serde_json::Value::Bool(b)) => Some(b.to_string()),
+                _ => None,
+            };
+            (field.clone(), value)
+        })
+        .collect())
+}
+
+fn default_output_path(template_path: &Path) -> PathBuf {
+    let stem = template_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let name = match template_path.extension() {
+        Some(ext) => format!("{}-filled.{}", stem, ext.to_string_lossy()),
+        None => format!("{}-filled", stem),
+    };
+    template_path.with_file_name(name)
+}
+
+// Extracts placeholder values from the source material and returns a preview; nothing is written yet
+#[tauri::command]
+pub async fn fill_form_template(
+    template_path: String,
+    source: String,
+    model_id: String,
+    app: tauri::AppHandle,
+) -> Result<FormPreview, String> {
+    kiosk::ensure_unlocked("Form filling")?;
+    let template = std::fs::read_to_string(&template_path).map_err(|e| format!("Failed to read {}: {}", template_path, e))?;
+    let fields = placeholders(&template);
+    if fields.is_empty() {
+        return Err(format!("{} has no {{{{placeholder}}}} fields", template_path));
+    }
+
+    let prompt = format!(
+        "Extract values for the following fields from the source material. Reply with only a JSON \
+         object whose keys are exactly: {}. Use null for any field the source does not state; do \
+         not guess.\n\nSource material:\n{}",
+        fields.iter().map(|f| format!("\"{}\"", f)).collect::<Vec<_>>().join(", "),
+        source.trim()
+    );
+    let (_, sampling) = sampling::resolve_profile(Some("precise"), None)
+        .or_else(|_| sampling::resolve_profile(None, None))?;
+    let response = generate_text(&model_id, &prompt, &sampling, Priority::Interactive, &app).await?;
+    let values = parse_values(&response, &fields)?;
+
+    let (output, diff) = render(&template, &values);
+    let preview = FormPreview {
+        preview_id: new_id(),
+        template_path,
+        missing: values.iter().filter(|(_, v)| v.is_none()).map(|(k, _)| k.clone()).collect(),
+        values,
+        diff,
+        output,
+    };
+    previews().lock().unwrap().insert(preview.preview_id.clone(), preview.clone());
+    Ok(preview)
+}
+
+// Writes a reviewed preview, applying any values the user corrected; returns the written path
+#[tauri::command]
+pub fn save_filled_form(
+    preview_id: String,
+    output_path: Option<String>,
+    overrides: Option<BTreeMap<String, String>>,
+) -> Result<String, String> {
+    kiosk::ensure_unlocked("Form filling")?;
+    let mut preview = previews()
+        .lock()
+        .unwrap()
+        .get(&preview_id)
+        .cloned()
+        .ok_or_else(|| format!("No form preview with id {}", preview_id))?;
+
+    if let Some(overrides) = overrides {
+        let template = std::fs::read_to_string(&preview.template_path)
+            .map_err(|e| format!("Failed to read {}: {}", preview.template_path, e))?;
+        for (name, value) in overrides {
+            preview.values.insert(name, Some(value));
+        }
+        preview.output = render(&template, &preview.values).0;
+    }
+
+    let path = output_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| default_output_path(Path::new(&preview.template_path)));
+    if path == Path::new(&preview.template_path) {
+        return Err("Refusing to overwrite the template itself".to_string());
+    }
+    std::fs::write(&path, &preview.output).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    previews().lock().unwrap().remove(&preview_id);
+    Ok(path.to_string_lossy().to_string())
+}
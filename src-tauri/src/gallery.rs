@@ -0,0 +1,143 @@
+// Images already stored as message attachments, offered again for new vision requests. A request
+// names them by attachment id or by their number in the conversation ("#3" is its third image), so a
+// follow-up question, or one about several earlier images at once, needs no new upload.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::path::Path;
+
+use crate::db_writer;
+use crate::history::{db_err, with_db};
+use crate::image_input::{self, ImageInput};
+use crate::presentation;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GalleryImage {
+    pub id: String,
+    pub conversation_id: String,
+    pub message_id: String,
+    // 1-based position among the conversation's images, as "#n" references use it
+    pub number: usize,
+    pub path: String,
+    pub mime: Option<String>,
+    pub created_at: i64,
+}
+
+// Images of one conversation, numbered in the order they were sent
+fn conversation_images(conn: &Connection, conversation_id: &str) -> Result<Vec<GalleryImage>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, conversation_id, message_id, path, mime, created_at FROM attachments
+             WHERE conversation_id = ?1 AND kind = 'image' ORDER BY created_at, id",
+        )
+        .map_err(db_err)?;
+    let rows = stmt
+        .query_map(params![conversation_id], |row| {
+            Ok(GalleryImage {
+                id: row.get(0)?,
+                conversation_id: row.get(1)?,
+                message_id: row.get(2)?,
+                number: 0,
+                path: row.get(3)?,
+                mime: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        })
+        .map_err(db_err)?;
+    let mut images = rows.collect::<Result<Vec<_>, _>>().map_err(db_err)?;
+    for (i, image) in images.iter_mut().enumerate() {
+        image.number = i + 1;
+    }
+    Ok(images)
+}
+
+fn conversation_ids(conn: &Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT DISTINCT conversation_id FROM attachments WHERE kind = 'image'")
+        .map_err(db_err)?;
+    let rows = stmt.query_map([], |row| row.get(0)).map_err(db_err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+}
+
+fn image_by_reference(conn: &Connection, conversation_id: Option<&str>, reference: &str) -> Result<GalleryImage, String> {
+    let reference = reference.trim();
+    if let Some(number) = reference.strip_prefix('#') {
+        let conversation_id = conversation_id.ok_or_else(|| format!("Image {} needs a conversation_id", reference))?;
+        let number: usize = number.parse().map_err(|_| format!("{} is not an image number such as #3", reference))?;
+        return conversation_images(conn, conversation_id)?
+            .into_iter()
+            .find(|image| image.number == number)
+            .ok_or_else(|| format!("Conversation {} has no image {}", conversation_id, reference));
+    }
+    let owner: String = conn
+        .query_row(
+            "SELECT conversation_id FROM attachments WHERE id = ?1 AND kind = 'image'",
+            params![reference],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db_err)?
+        .ok_or_else(|| format!("Image not found: {}", reference))?;
+    conversation_images(conn, &owner)?
+        .into_iter()
+        .find(|image| image.id == reference)
+        .ok_or_else(|| format!("Image not found: {}", reference))
+}
+
+// Each referenced image's file, in the order given
+pub fn resolve(conversation_id: Option<&str>, references: &[String]) -> Result<Vec<ImageInput>, String> {
+    with_db(|conn| {
+        references
+            .iter()
+            .map(|reference| {
+                let image = image_by_reference(conn, conversation_id, reference)?;
+                Ok(image_input::from_attachment(Path::new(&image.path)))
+            })
+            .collect()
+    })
+}
+
+// Stored images of one conversation, oldest first, or of every conversation, newest first
+#[tauri::command]
+pub async fn list_images(conversation_id: Option<String>) -> Result<Vec<GalleryImage>, String> {
+    let mut images = with_db(|conn| match &conversation_id {
+        Some(conversation_id) => conversation_images(conn, conversation_id),
+        None => {
+            let mut images = Vec::new();
+            for conversation_id in conversation_ids(conn)? {
+                images.extend(conversation_images(conn, &conversation_id)?);
+            }
+            images.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(a.number.cmp(&b.number)));
+            Ok(images)
+        }
+    })?;
+    if presentation::is_active() {
+        for image in &mut images {
+            image.path = presentation::redact_path(&image.path);
+        }
+    }
+    Ok(images)
+}
+
+// Deletes a stored image, and its file unless a duplicated conversation still refers to it. The
+// message keeps its text; later images are renumbered.
+#[tauri::command]
+pub async fn delete_image(image: String, conversation_id: Option<String>) -> Result<(), String> {
+    let unreferenced = db_writer::write(move |conn| {
+        let image = image_by_reference(conn, conversation_id.as_deref(), &image)?;
+        conn.execute("DELETE FROM attachments WHERE id = ?1", params![image.id])
+            .map_err(db_err)?;
+        let references: i64 = conn
+            .query_row("SELECT COUNT(*) FROM attachments WHERE path = ?1", params![image.path], |row| row.get(0))
+            .map_err(db_err)?;
+        Ok((references == 0).then_some(image.path))
+    })
+    .await?;
+    // Only once the delete is committed
+    if let Some(path) = unreferenced {
+        if let Err(e) = std::fs::remove_file(&path) {
+            println!("Warning: Failed to remove {}: {}", path, e);
+        }
+    }
+    Ok(())
+}
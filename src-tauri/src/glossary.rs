@@ -0,0 +1,206 @@
+// Terminology each assistant sticks to: a term with its preferred translation or definition, and
+// wordings to avoid. The glossary joins the system prompt, and a finished answer that still uses an
+// avoided wording is flagged in a glossary-violations event rather than rewritten.
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tauri::Emitter;
+
+use crate::assistants;
+use crate::db_writer;
+use crate::history::{db_err, with_db};
+use crate::kiosk;
+
+pub const GLOSSARY_VIOLATIONS_EVENT: &str = "glossary-violations";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS assistant_glossary (
+    assistant_id TEXT NOT NULL REFERENCES assistants(id) ON DELETE CASCADE,
+    term TEXT NOT NULL,
+    preferred TEXT NOT NULL,
+    -- JSON array of wordings the answer must not use
+    avoid TEXT NOT NULL DEFAULT '[]',
+    PRIMARY KEY (assistant_id, term)
+);
+";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GlossaryEntry {
+    pub term: String,
+    // Translation or definition the answer should use for the term
+    pub preferred: String,
+    pub avoid: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GlossaryViolation {
+    pub term: String,
+    // The avoided wording as it appears in the answer
+    pub found: String,
+    pub preferred: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GlossaryViolations {
+    pub request_id: String,
+    pub violations: Vec<GlossaryViolation>,
+}
+
+pub fn init() -> Result<(), String> {
+    with_db(|conn| conn.execute_batch(SCHEMA).map_err(db_err))
+}
+
+fn entries(conn: &Connection, assistant_id: &str) -> Result<Vec<GlossaryEntry>, String> {
+    let mut stmt = conn
+        .prepare("SELECT term, preferred, avoid FROM assistant_glossary WHERE assistant_id = ?1 ORDER BY term")
+        .map_err(db_err)?;
+    let rows = stmt
+        .query_map(params![assistant_id], |row| {
+            let avoid: String = row.get(2)?;
+            Ok(GlossaryEntry {
+                term: row.get(0)?,
+                preferred: row.get(1)?,
+                avoid: serde_json::from_str(&avoid).unwrap_or_default(),
+            })
+        })
+        .map_err(db_err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+}
+
+// Glossary of the conversation's assistant; empty without one
+pub fn for_conversation(conversation_id: Option<&str>) -> Result<Vec<GlossaryEntry>, String> {
+    let Some(conversation_id) = conversation_id else {
+        return Ok(Vec::new());
+    };
+    with_db(|conn| match assistants::assistant_id_for(conn, conversation_id)? {
+        Some(assistant_id) => entries(conn, &assistant_id),
+        None => Ok(Vec::new()),
+    })
+}
+
+// The system prompt with the glossary appended
+pub fn apply(system_prompt: &str, glossary: &[GlossaryEntry]) -> String {
+    if glossary.is_empty() {
+        return system_prompt.to_string();
+    }
+    let mut prompt = format!("{}\n\nUse this terminology consistently:", system_prompt);
+    for entry in glossary {
+        prompt.push_str(&format!("\n- {}: {}", entry.term, entry.preferred));
+        if !entry.avoid.is_empty() {
+            prompt.push_str(&format!(" (never write {})", entry.avoid.join(", ")));
+        }
+    }
+    prompt
+}
+
+// The first whole-word, case-insensitive occurrence of `word` in `text`, as written there
+fn find_word(text: &str, word: &str) -> Option<String> {
+    let lower = text.to_lowercase();
+    let needle = word.to_lowercase();
+    // Lowercasing can change byte lengths outside ASCII, and then offsets don't map back
+    if lower.len() != text.len() {
+        return lower.contains(&needle).then(|| word.to_string());
+    }
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let mut from = 0;
+    while let Some(offset) = lower[from..].find(&needle) {
+        let start = from + offset;
+        let end = start + needle.len();
+        let before = lower[..start].chars().next_back().is_some_and(is_word_char);
+        let after = lower[end..].chars().next().is_some_and(is_word_char);
+        if !before && !after {
+            return Some(text[start..end].to_string());
+        }
+        from = start + lower[start..].chars().next().map_or(1, char::len_utf8);
+    }
+    None
+}
+
+fn find_violations(answer: &str, glossary: &[GlossaryEntry]) -> Vec<GlossaryViolation> {
+    glossary
+        .iter()
+        .flat_map(|entry| {
+            entry.avoid.iter().filter_map(|avoided| {
+                find_word(answer, avoided).map(|found| GlossaryViolation {
+                    term: entry.term.clone(),
+                    found,
+                    preferred: entry.preferred.clone(),
+                })
+            })
+        })
+        .collect()
+}
+
+// Flags avoided wordings in a finished answer
+pub fn check(answer: &str, glossary: &[GlossaryEntry], request_id: &str, app: &tauri::AppHandle) {
+    let violations = find_violations(answer, glossary);
+    if violations.is_empty() {
+        return;
+    }
+    println!("[{}] Answer uses {} avoided glossary term(s)", request_id, violations.len());
+    let payload = GlossaryViolations { request_id: request_id.to_string(), violations };
+    if let Err(e) = app.emit(GLOSSARY_VIOLATIONS_EVENT, &payload) {
+        println!("Warning: Failed to emit glossary violations: {}", e);
+    }
+}
+
+// Adds a term to an assistant's glossary, or replaces the term's entry
+#[tauri::command]
+pub async fn set_glossary_entry(assistant_id: String, term: String, preferred: String, avoid: Vec<String>) -> Result<GlossaryEntry, String> {
+    kiosk::ensure_unlocked("Assistants")?;
+    let term = term.trim().to_string();
+    let preferred = preferred.trim().to_string();
+    if term.is_empty() || preferred.is_empty() {
+        return Err("A glossary entry needs a term and its preferred translation or definition".to_string());
+    }
+    let avoid: Vec<String> = avoid
+        .iter()
+        .map(|wording| wording.trim().to_string())
+        .filter(|wording| !wording.is_empty())
+        .collect();
+    if let Some(clash) = avoid.iter().find(|wording| wording.eq_ignore_ascii_case(&preferred)) {
+        return Err(format!("\"{}\" can't be both the preferred wording and one to avoid", clash));
+    }
+    let avoid_json = serde_json::to_string(&avoid).map_err(|e| e.to_string())?;
+    let entry = GlossaryEntry { term, preferred, avoid };
+    let row = entry.clone();
+    db_writer::write(move |conn| {
+        let assistant = assistants::assistant_by_id(conn, &assistant_id)?;
+        conn.execute(
+            "INSERT INTO assistant_glossary (assistant_id, term, preferred, avoid) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(assistant_id, term) DO UPDATE SET preferred = excluded.preferred, avoid = excluded.avoid",
+            params![assistant.id, row.term, row.preferred, avoid_json],
+        )
+        .map_err(db_err)?;
+        Ok(())
+    })
+    .await?;
+    Ok(entry)
+}
+
+#[tauri::command]
+pub async fn remove_glossary_entry(assistant_id: String, term: String) -> Result<(), String> {
+    kiosk::ensure_unlocked("Assistants")?;
+    db_writer::write(move |conn| {
+        let assistant = assistants::assistant_by_id(conn, &assistant_id)?;
+        let removed = conn
+            .execute(
+                "DELETE FROM assistant_glossary WHERE assistant_id = ?1 AND term = ?2",
+                params![assistant.id, term.trim()],
+            )
+            .map_err(db_err)?;
+        if removed == 0 {
+            return Err(format!("{} has no glossary entry for {}", assistant.name, term));
+        }
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_glossary(assistant_id: String) -> Result<Vec<GlossaryEntry>, String> {
+    with_db(|conn| {
+        let assistant = assistants::assistant_by_id(conn, &assistant_id)?;
+        entries(conn, &assistant.id)
+    })
+}
@@ -0,0 +1,379 @@
+// Lark grammars that constrain generation, registered from files and attached to a request or an
+// assistant. mistral.rs only reports grammar problems when a request runs, so files are checked
+// here when they are registered, with errors pointing at lines of the file.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::assistants;
+use crate::db_writer;
+use crate::history::{db_err, new_id, with_db};
+use crate::kiosk;
+use crate::streaming::now_ms;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS grammars (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    path TEXT NOT NULL,
+    source TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS assistant_grammars (
+    assistant_id TEXT PRIMARY KEY REFERENCES assistants(id) ON DELETE CASCADE,
+    grammar_id TEXT NOT NULL REFERENCES grammars(id) ON DELETE CASCADE
+);
+";
+
+// Larger grammar files are almost certainly not grammars
+const MAX_GRAMMAR_BYTES: u64 = 256 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Grammar {
+    pub id: String,
+    pub name: String,
+    // File the grammar was registered from; the stored copy is used for generation
+    pub path: String,
+    pub source: String,
+    pub created_at: u64,
+}
+
+pub fn init() -> Result<(), String> {
+    with_db(|conn| conn.execute_batch(SCHEMA).map_err(db_err))
+}
+
+// Open brackets of the rule being read, with the line each was opened on
+struct Rule {
+    line: usize,
+    open: Vec<(char, usize)>,
+    has_body: bool,
+}
+
+fn end_rule(rule: Option<Rule>, errors: &mut Vec<(usize, String)>) {
+    let Some(rule) = rule else {
+        return;
+    };
+    for (bracket, line) in rule.open {
+        errors.push((line, format!("`{}` is never closed", bracket)));
+    }
+    if !rule.has_body {
+        errors.push((rule.line, "rule has no body".to_string()));
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// Index just past the closing `close` of a literal starting at `start`, honouring backslash escapes
+fn skip_delimited(chars: &[char], start: usize, close: char) -> Option<usize> {
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            c if c == close => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+// Reads the body of a rule on one line, tracking brackets and collecting the names it refers to
+fn scan_body(body: &str, line: usize, rule: &mut Rule, references: &mut Vec<(String, usize)>, errors: &mut Vec<(usize, String)>) {
+    let chars: Vec<char> = body.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            return;
+        }
+        rule.has_body = true;
+        match c {
+            '"' | '/' => match skip_delimited(&chars, i, c) {
+                Some(end) => {
+                    i = end;
+                    while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                        i += 1;
+                    }
+                }
+                None => {
+                    let what = if c == '"' { "string" } else { "regular expression" };
+                    errors.push((line, format!("unterminated {}", what)));
+                    return;
+                }
+            },
+            '(' | '[' => {
+                rule.open.push((c, line));
+                i += 1;
+            }
+            ')' | ']' => {
+                let expected = if c == ')' { '(' } else { '[' };
+                match rule.open.pop() {
+                    Some((open, _)) if open == expected => {}
+                    Some((open, open_line)) => {
+                        errors.push((line, format!("`{}` closes the `{}` opened on line {}", c, open, open_line)));
+                    }
+                    None => errors.push((line, format!("unmatched `{}`", c))),
+                }
+                i += 1;
+            }
+            // Inline JSON schemas (%json { ... }) and special tokens (<...>) are passed through as-is
+            '{' | '<' => {
+                let close = if c == '{' { '}' } else { '>' };
+                match chars[i..].iter().position(|&ch| ch == close) {
+                    Some(offset) => i += offset + 1,
+                    None => {
+                        errors.push((line, format!("`{}` is never closed", c)));
+                        return;
+                    }
+                }
+            }
+            // Directives inside bodies (%json) and the alias after `->` aren't references
+            '%' => {
+                i += 1;
+                while i < chars.len() && is_name_char(chars[i]) {
+                    i += 1;
+                }
+            }
+            '-' if chars.get(i + 1) == Some(&'>') => {
+                i += 2;
+                while i < chars.len() && (chars[i].is_whitespace() || is_name_char(chars[i])) {
+                    i += 1;
+                }
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && is_name_char(chars[i]) {
+                    i += 1;
+                }
+                references.push((chars[start..i].iter().collect(), line));
+            }
+            _ => i += 1,
+        }
+    }
+}
+
+// Names a %import line makes available: `%import common.NUMBER` or `%import common (WORD, NUMBER)`
+fn imported_names(rest: &str) -> Vec<String> {
+    let rest = rest.trim();
+    let names = match (rest.find('('), rest.rfind(')')) {
+        (Some(open), Some(close)) if open < close => rest[open + 1..close].split(',').map(str::trim).collect(),
+        _ => {
+            let (path, alias) = rest.split_once("->").unwrap_or((rest, ""));
+            match alias.trim() {
+                "" => vec![path.trim().rsplit('.').next().unwrap_or_default()],
+                alias => vec![alias],
+            }
+        }
+    };
+    names.into_iter().filter(|n| !n.is_empty()).map(str::to_string).collect()
+}
+
+// Checks a Lark grammar's structure: rule syntax, balanced brackets and literals, references to
+// rules or terminals that are never defined, and a `start` rule. Errors are "line N: message".
+pub fn validate(source: &str) -> Vec<String> {
+    let mut errors: Vec<(usize, String)> = Vec::new();
+    let mut defined: HashMap<String, usize> = HashMap::new();
+    let mut references: Vec<(String, usize)> = Vec::new();
+    let mut rule: Option<Rule> = None;
+
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with("//") {
+            continue;
+        }
+
+        if let Some(directive) = trimmed.strip_prefix('%') {
+            end_rule(rule.take(), &mut errors);
+            let keyword: String = directive.chars().take_while(|c| is_name_char(*c)).collect();
+            let rest = &directive[keyword.len()..];
+            match keyword.as_str() {
+                "import" => {
+                    for name in imported_names(rest) {
+                        defined.entry(name).or_insert(line);
+                    }
+                }
+                "ignore" => {
+                    let mut ignored = Rule { line, open: Vec::new(), has_body: false };
+                    scan_body(rest, line, &mut ignored, &mut references, &mut errors);
+                    end_rule(Some(ignored), &mut errors);
+                }
+                "llguidance" | "declare" => {}
+                _ => errors.push((line, format!("unknown directive `%{}`", keyword))),
+            }
+            continue;
+        }
+
+        let continues = raw.starts_with(char::is_whitespace) || trimmed.starts_with('|');
+        let body = if continues {
+            match rule.as_mut() {
+                Some(_) => trimmed,
+                None => {
+                    errors.push((line, "indented line outside a rule".to_string()));
+                    continue;
+                }
+            }
+        } else {
+            end_rule(rule.take(), &mut errors);
+            let Some((head, body)) = trimmed.split_once(':') else {
+                errors.push((line, "expected a rule definition like `name: ...`".to_string()));
+                continue;
+            };
+            // Rules may be inlined (`?name`) or keep all tokens (`!name`), and carry a priority (`NAME.2`)
+            let head = head.trim().trim_start_matches(['?', '!']);
+            let mut parts = head.split('.');
+            let name = parts.next().unwrap_or_default().to_string();
+            let priority_ok = parts.all(|p| p.parse::<i64>().is_ok());
+            if name.is_empty() || !name.chars().all(is_name_char) || name.starts_with(|c: char| c.is_ascii_digit()) || !priority_ok {
+                errors.push((line, format!("`{}` is not a valid rule or terminal name", head)));
+                continue;
+            }
+            if let Some(first) = defined.insert(name.clone(), line) {
+                errors.push((line, format!("`{}` is already defined on line {}", name, first)));
+            }
+            rule = Some(Rule { line, open: Vec::new(), has_body: false });
+            body
+        };
+        if let Some(current) = rule.as_mut() {
+            scan_body(body, line, current, &mut references, &mut errors);
+        }
+    }
+    end_rule(rule, &mut errors);
+
+    for (name, line) in references {
+        if !defined.contains_key(&name) {
+            errors.push((line, format!("`{}` is used but never defined", name)));
+        }
+    }
+    if !defined.contains_key("start") {
+        errors.push((1, "grammar has no `start` rule".to_string()));
+    }
+
+    errors.sort_by_key(|(line, _)| *line);
+    errors.into_iter().map(|(line, message)| format!("line {}: {}", line, message)).collect()
+}
+
+fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Grammar> {
+    Ok(Grammar {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        path: row.get(2)?,
+        source: row.get(3)?,
+        created_at: row.get::<_, i64>(4)? as u64,
+    })
+}
+
+fn grammar_by_id(conn: &Connection, grammar_id: &str) -> Result<Grammar, String> {
+    conn.query_row(
+        "SELECT id, name, path, source, created_at FROM grammars WHERE id = ?1 OR name = ?1",
+        params![grammar_id],
+        from_row,
+    )
+    .optional()
+    .map_err(db_err)?
+    .ok_or_else(|| format!("Grammar not found: {}", grammar_id))
+}
+
+// Grammar a request runs under: the one it names, else its conversation's assistant's
+pub fn source_for(grammar: Option<&str>, conversation_id: Option<&str>) -> Result<Option<String>, String> {
+    with_db(|conn| {
+        if let Some(grammar) = grammar {
+            return Ok(Some(grammar_by_id(conn, grammar)?.source));
+        }
+        let Some(assistant_id) = conversation_id.map(|id| assistants::assistant_id_for(conn, id)).transpose()?.flatten() else {
+            return Ok(None);
+        };
+        conn.query_row(
+            "SELECT g.source FROM assistant_grammars a JOIN grammars g ON g.id = a.grammar_id WHERE a.assistant_id = ?1",
+            params![assistant_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db_err)
+    })
+}
+
+// Reads and validates a Lark grammar file; re-registering a name replaces its grammar
+#[tauri::command]
+pub async fn register_grammar(name: String, path: String) -> Result<Grammar, String> {
+    kiosk::ensure_unlocked("Grammars")?;
+    if name.trim().is_empty() {
+        return Err("Grammar name must not be empty".to_string());
+    }
+    let file = Path::new(&path);
+    let size = std::fs::metadata(file).map_err(|e| format!("Failed to read {}: {}", path, e))?.len();
+    if size > MAX_GRAMMAR_BYTES {
+        return Err(format!("{} is {} bytes, over the {} byte limit for grammars", path, size, MAX_GRAMMAR_BYTES));
+    }
+    let source = std::fs::read_to_string(file).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let errors = validate(&source);
+    if !errors.is_empty() {
+        return Err(format!("{} is not a valid Lark grammar:\n{}", path, errors.join("\n")));
+    }
+
+    db_writer::write(move |conn| {
+        conn.execute(
+            "INSERT INTO grammars (id, name, path, source, created_at) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(name) DO UPDATE SET path = excluded.path, source = excluded.source",
+            params![new_id(), name.trim(), path, source, now_ms() as i64],
+        )
+        .map_err(db_err)?;
+        grammar_by_id(conn, name.trim())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn list_grammars() -> Result<Vec<Grammar>, String> {
+    with_db(|conn| {
+        let mut stmt = conn
+            .prepare("SELECT id, name, path, source, created_at FROM grammars ORDER BY name")
+            .map_err(db_err)?;
+        let rows = stmt.query_map([], from_row).map_err(db_err)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+    })
+}
+
+#[tauri::command]
+pub async fn delete_grammar(grammar_id: String) -> Result<(), String> {
+    db_writer::write(move |conn| {
+        let grammar = grammar_by_id(conn, &grammar_id)?;
+        conn.execute("DELETE FROM grammars WHERE id = ?1", params![grammar.id])
+            .map_err(db_err)?;
+        Ok(())
+    })
+    .await
+}
+
+// Constrains every answer of an assistant to a grammar; None removes it
+#[tauri::command]
+pub async fn set_assistant_grammar(assistant_id: String, grammar_id: Option<String>) -> Result<Option<Grammar>, String> {
+    db_writer::write(move |conn| {
+        let assistant = assistants::assistant_by_id(conn, &assistant_id)?;
+        match grammar_id {
+            Some(grammar_id) => {
+                let grammar = grammar_by_id(conn, &grammar_id)?;
+                conn.execute(
+                    "INSERT INTO assistant_grammars (assistant_id, grammar_id) VALUES (?1, ?2)
+                     ON CONFLICT(assistant_id) DO UPDATE SET grammar_id = excluded.grammar_id",
+                    params![assistant.id, grammar.id],
+                )
+                .map_err(db_err)?;
+                Ok(Some(grammar))
+            }
+            None => {
+                conn.execute("DELETE FROM assistant_grammars WHERE assistant_id = ?1", params![assistant.id])
+                    .map_err(db_err)?;
+                Ok(None)
+            }
+        }
+    })
+    .await
+}
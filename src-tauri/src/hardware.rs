@@ -0,0 +1,234 @@
+use mistralrs::{RequestBuilder, TextMessageRole, TextMessages, TextModelBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use sysinfo::System;
+use tauri::Manager;
+
+use crate::{config, safe_mode};
+
+const HARDWARE_PROFILE_FILE: &str = "hardware_profile.json";
+
+// Each backend gets roughly half of the ~30 second budget
+const BACKEND_BUDGET: Duration = Duration::from_secs(15);
+
+const BENCHMARK_PROMPT: &str = "Count from one to fifty in words, separated by commas.";
+
+static PROFILE: OnceLock<RwLock<Option<HardwareProfile>>> = OnceLock::new();
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BenchmarkConfig {
+    pub run_on_first_launch: bool,
+    // Small model fetched into the HF cache once and used only for benchmarking
+    pub model: String,
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        Self {
+            run_on_first_launch: true,
+            model: "HuggingFaceTB/SmolLM2-135M-Instruct".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendResult {
+    // "cpu", "metal" or "cuda"
+    pub backend: String,
+    pub load_ms: Option<u64>,
+    pub tokens_per_sec: Option<f64>,
+    pub error: Option<String>,
+}
+
+// Persisted outcome of the benchmark, used to pick defaults on later launches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HardwareProfile {
+    pub measured_at_ms: u64,
+    pub benchmark_model: String,
+    pub cpu_threads: usize,
+    pub total_memory_mb: u64,
+    pub results: Vec<BackendResult>,
+    pub recommended_backend: String,
+    pub recommended_threads: usize,
+    pub recommended_isq: String,
+}
+
+fn cell() -> &'static RwLock<Option<HardwareProfile>> {
+    PROFILE.get_or_init(|| RwLock::new(None))
+}
+
+fn profile_path(app: &tauri::AppHandle) -> Option<PathBuf> {
+    app.path().app_data_dir().ok().map(|dir| dir.join(HARDWARE_PROFILE_FILE))
+}
+
+pub(crate) fn accelerated_backend() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "metal"
+    } else {
+        "cuda"
+    }
+}
+
+// Loads the saved profile, applies its defaults and benchmarks in the background on first launch
+pub fn init(app: &tauri::AppHandle) {
+    let saved = profile_path(app)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str::<HardwareProfile>(&contents).ok());
+
+    match saved {
+        Some(profile) => {
+            apply_thread_default(&profile);
+            println!(
+                "Hardware profile: {} backend, {} threads, {} quantization",
+                profile.recommended_backend, profile.recommended_threads, profile.recommended_isq
+            );
+            *cell().write().unwrap() = Some(profile);
+        }
+        None if config::get().benchmark.run_on_first_launch && !safe_mode::is_active() => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = run_benchmark(&app).await {
+                    println!("Warning: First-run hardware benchmark failed: {}", e);
+                }
+            });
+        }
+        None => {}
+    }
+}
+
+// Candle's CPU kernels use rayon, which reads this before its pool starts
+fn apply_thread_default(profile: &HardwareProfile) {
+    if std::env::var("RAYON_NUM_THREADS").is_err() {
+        std::env::set_var("RAYON_NUM_THREADS", profile.recommended_threads.to_string());
+    }
+}
+
+// True when models should load on the CPU, either for safe mode or because it benchmarked faster
+pub fn use_cpu() -> bool {
+    safe_mode::force_cpu()
+        || cell()
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|profile| profile.recommended_backend == "cpu")
+}
+
+async fn measure(model_id: &str, force_cpu: bool) -> BackendResult {
+    let backend = if force_cpu { "cpu" } else { accelerated_backend() };
+    let failed = |error: String| BackendResult {
+        backend: backend.to_string(),
+        load_ms: None,
+        tokens_per_sec: None,
+        error: Some(error),
+    };
+
+    let started = Instant::now();
+    let mut builder = TextModelBuilder::new(model_id);
+    if force_cpu {
+        builder = builder.with_force_cpu();
+    }
+    let model = match builder.build().await {
+        Ok(model) => model,
+        Err(e) => return failed(format!("Failed to load: {}", e)),
+    };
+    let load_ms = started.elapsed().as_millis() as u64;
+
+    // Repeat short generations until the budget is used up to smooth out warm-up effects
+    let decode_started = Instant::now();
+    let mut tokens = 0usize;
+    while decode_started.elapsed() < BACKEND_BUDGET {
+        let request = RequestBuilder::from(TextMessages::new().add_message(TextMessageRole::User, BENCHMARK_PROMPT))
+            .set_sampler_max_len(64);
+        match model.send_chat_request(request).await {
+            Ok(response) => tokens += response.usage.completion_tokens,
+            Err(e) => return failed(format!("Generation failed: {}", e)),
+        }
+    }
+    let secs = decode_started.elapsed().as_secs_f64();
+
+    BackendResult {
+        backend: backend.to_string(),
+        load_ms: Some(load_ms),
+        tokens_per_sec: (secs > 0.0).then(|| tokens as f64 / secs),
+        error: None,
+    }
+}
+
+fn recommend_isq(total_memory_mb: u64) -> &'static str {
+    match total_memory_mb {
+        0..=8_192 => "Q4K",
+        8_193..=16_384 => "Q5K",
+        _ => "Q8_0",
+    }
+}
+
+pub async fn run_benchmark(app: &tauri::AppHandle) -> Result<HardwareProfile, String> {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return Err("A hardware benchmark is already running".to_string());
+    }
+
+    let model_id = config::get().benchmark.model;
+    println!("Running hardware benchmark with {}...", model_id);
+
+    let cpu = measure(&model_id, true).await;
+    let accelerated = measure(&model_id, false).await;
+    RUNNING.store(false, Ordering::SeqCst);
+
+    let mut system = System::new();
+    system.refresh_memory();
+    let total_memory_mb = system.total_memory() / (1024 * 1024);
+    let cpu_threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+
+    if cpu.tokens_per_sec.is_none() && accelerated.tokens_per_sec.is_none() {
+        return Err(cpu.error.or(accelerated.error).unwrap_or_else(|| "Benchmark produced no results".to_string()));
+    }
+    let recommended_backend = match (cpu.tokens_per_sec, accelerated.tokens_per_sec) {
+        (_, None) => "cpu",
+        (Some(cpu_tps), Some(gpu_tps)) if cpu_tps >= gpu_tps => "cpu",
+        _ => accelerated_backend(),
+    };
+
+    let profile = HardwareProfile {
+        measured_at_ms: crate::streaming::now_ms(),
+        benchmark_model: model_id,
+        cpu_threads,
+        total_memory_mb,
+        results: vec![cpu, accelerated],
+        recommended_backend: recommended_backend.to_string(),
+        // Leave one core for the UI and the OS
+        recommended_threads: cpu_threads.saturating_sub(1).max(1),
+        recommended_isq: recommend_isq(total_memory_mb).to_string(),
+    };
+
+    if let Some(path) = profile_path(app) {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let contents = serde_json::to_string_pretty(&profile).map_err(|e| e.to_string())?;
+        std::fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+
+    println!(
+        "Hardware benchmark done: recommending {} backend, {} quantization",
+        profile.recommended_backend, profile.recommended_isq
+    );
+    apply_thread_default(&profile);
+    *cell().write().unwrap() = Some(profile.clone());
+    Ok(profile)
+}
+
+#[tauri::command]
+pub fn get_hardware_profile() -> Option<HardwareProfile> {
+    cell().read().unwrap().clone()
+}
+
+// Re-runs the benchmark on demand, e.g. after a driver or hardware change
+#[tauri::command]
+pub async fn run_hardware_benchmark(app: tauri::AppHandle) -> Result<HardwareProfile, String> {
+    run_benchmark(&app).await
+}
@@ -0,0 +1,166 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tauri::Manager;
+
+use crate::config::{self, AppConfig};
+use crate::kiosk;
+use crate::models;
+
+// HF_HOME as inherited from the environment, before any config override
+static ORIGINAL_HF_HOME: OnceLock<Option<String>> = OnceLock::new();
+
+// HF_HOME as set at startup. The environment isn't safe to change once model and runtime threads
+// may read it, so a new cache location takes effect on the next launch.
+static APPLIED_HF_HOME: OnceLock<Option<String>> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HfCacheInfo {
+    pub path: String,
+    // "config", "env" or "default"
+    pub source: String,
+    pub exists: bool,
+    pub size_bytes: u64,
+    // The configured location differs from the one downloads use until the app is restarted
+    pub restart_required: bool,
+}
+
+fn configured_home(config: &AppConfig) -> Option<String> {
+    let original = ORIGINAL_HF_HOME.get_or_init(|| std::env::var("HF_HOME").ok());
+    config.hf_cache.home.clone().or_else(|| original.clone())
+}
+
+// Points mistralrs/hf-hub downloads at the configured cache directory. Only the first call, during
+// startup before any model is loaded, sets HF_HOME; later changes wait for a restart.
+pub fn apply(config: &AppConfig) {
+    let home = configured_home(config);
+    if let Some(applied) = APPLIED_HF_HOME.get() {
+        if *applied != home {
+            println!("The new model cache location takes effect after a restart");
+        }
+        return;
+    }
+    if let Some(home) = &home {
+        std::env::set_var("HF_HOME", home);
+    }
+    let _ = APPLIED_HF_HOME.set(home);
+}
+
+fn restart_required() -> bool {
+    APPLIED_HF_HOME.get().is_some_and(|applied| *applied != configured_home(&config::get()))
+}
+
+// Resolves the effective cache root and where the setting came from
+pub(crate) fn resolve(app: &tauri::AppHandle) -> Result<(PathBuf, &'static str), String> {
+    if let Some(home) = config::get().hf_cache.home {
+        return Ok((PathBuf::from(home), "config"));
+    }
+    if let Some(Some(home)) = ORIGINAL_HF_HOME.get() {
+        return Ok((PathBuf::from(home), "env"));
+    }
+    let home_dir = app
+        .path()
+        .home_dir()
+        .map_err(|e| format!("Failed to resolve home directory: {}", e))?;
+    Ok((home_dir.join(".cache").join("huggingface"), "default"))
+}
+
+// Sums file sizes without following symlinks, since the HF cache links snapshots to blobs
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .flatten()
+        .map(|entry| match std::fs::symlink_metadata(entry.path()) {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) if meta.is_file() => meta.len(),
+            _ => 0,
+        })
+        .sum()
+}
+
+// Copies a directory tree, recreating symlinks rather than duplicating their targets
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let source = entry.path();
+        let target = to.join(entry.file_name());
+        let meta = std::fs::symlink_metadata(&source)?;
+
+        if meta.file_type().is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(std::fs::read_link(&source)?, &target)?;
+            #[cfg(not(unix))]
+            std::fs::copy(&source, &target).map(|_| ())?;
+        } else if meta.is_dir() {
+            copy_dir(&source, &target)?;
+        } else {
+            std::fs::copy(&source, &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Reports where remote downloads are cached and how much disk they use
+#[tauri::command]
+pub async fn get_hf_cache_info(app: tauri::AppHandle) -> Result<HfCacheInfo, String> {
+    let (path, source) = resolve(&app)?;
+    let scan_path = path.clone();
+    let size_bytes = tokio::task::spawn_blocking(move || dir_size(&scan_path))
+        .await
+        .map_err(|e| format!("Failed to measure cache size: {}", e))?;
+
+    Ok(HfCacheInfo {
+        path: path.to_string_lossy().to_string(),
+        source: source.to_string(),
+        exists: path.exists(),
+        size_bytes,
+        restart_required: restart_required(),
+    })
+}
+
+// Moves the cache to another location (e.g. a larger drive) and persists the new HF_HOME, which
+// downloads use from the next launch
+#[tauri::command]
+pub async fn relocate_hf_cache(target_dir: String, app: tauri::AppHandle) -> Result<HfCacheInfo, String> {
+    kiosk::ensure_unlocked("Moving the model cache")?;
+    if !models::manager(&app).is_empty().await {
+        return Err("Models are currently loaded from the cache; wait for them to unload before relocating it".to_string());
+    }
+
+    let (current, _) = resolve(&app)?;
+    let target = PathBuf::from(&target_dir);
+
+    if target.starts_with(&current) {
+        return Err("The new cache location cannot be inside the current cache".to_string());
+    }
+    if target.exists() && std::fs::read_dir(&target).map(|mut d| d.next().is_some()).unwrap_or(false) {
+        return Err(format!("Target directory {} is not empty", target_dir));
+    }
+
+    if current.exists() {
+        println!("Relocating HF cache from {} to {}", current.display(), target.display());
+        let (from, to) = (current.clone(), target.clone());
+        tokio::task::spawn_blocking(move || -> Result<(), String> {
+            if let Some(parent) = to.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            }
+            // Rename is instant on the same drive; fall back to copy + delete across drives
+            if std::fs::rename(&from, &to).is_err() {
+                copy_dir(&from, &to).map_err(|e| format!("Failed to copy cache: {}", e))?;
+                std::fs::remove_dir_all(&from).map_err(|e| format!("Failed to remove old cache: {}", e))?;
+            }
+            Ok(())
+        })
+        .await
+        .map_err(|e| format!("Cache relocation task failed: {}", e))??;
+    }
+
+    config::update(&app, |config| config.hf_cache.home = Some(target_dir.clone()))?;
+    get_hf_cache_info(app).await
+}
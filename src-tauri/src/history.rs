@@ -0,0 +1,704 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use crate::config;
+use crate::db_writer;
+use crate::image_input;
+use crate::presentation;
+use crate::profiles;
+use crate::provenance::{self, Provenance};
+use crate::recap::{self, Recap};
+use crate::streaming::now_ms;
+
+pub const HISTORY_DB_FILE: &str = "history.db";
+
+// Uploaded images are stored here, in the profile's data directory, and referenced by attachments
+pub const ATTACHMENTS_DIR: &str = "attachments";
+
+static HISTORY_DB: OnceLock<Mutex<Connection>> = OnceLock::new();
+
+// Cancelled requests whose partial answer hasn't been appended yet -> when they were cancelled
+static CANCELLED: Mutex<Option<HashMap<String, u64>>> = Mutex::new(None);
+
+// Cancelled requests never appended are forgotten after this long
+const CANCELLED_TTL_MS: u64 = 60 * 60 * 1000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS conversations (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS messages (
+    id TEXT PRIMARY KEY,
+    conversation_id TEXT NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    role TEXT NOT NULL,
+    content TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_messages_conversation ON messages(conversation_id, position);
+CREATE TABLE IF NOT EXISTS attachments (
+    id TEXT PRIMARY KEY,
+    message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    conversation_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    path TEXT NOT NULL,
+    mime TEXT,
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_attachments_message ON attachments(message_id);
+CREATE TABLE IF NOT EXISTS excluded_messages (
+    message_id TEXT PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE
+);
+";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CancelBehavior {
+    // Store the partial answer like any other
+    #[default]
+    Keep,
+    // Don't store it at all
+    Discard,
+    // Store it for reading, but leave it out of the context of later turns
+    KeepExcluded,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    pub title: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub message_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    pub message_id: String,
+    pub kind: String,
+    pub path: String,
+    pub mime: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub id: String,
+    pub conversation_id: String,
+    pub position: i64,
+    pub role: String,
+    pub content: String,
+    pub created_at: u64,
+    pub attachments: Vec<Attachment>,
+    // Partial answer of a cancelled request that later turns don't see
+    #[serde(default)]
+    pub excluded_from_context: bool,
+    // Configuration that produced an assistant answer, when it was appended with its request id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationDetail {
+    pub conversation: Conversation,
+    pub messages: Vec<Message>,
+    // Only set by load_conversation, when a recap is cached for the conversation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recap: Option<Recap>,
+}
+
+pub fn db_err(e: rusqlite::Error) -> String {
+    format!("History database error: {}", e)
+}
+
+pub fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+// Opens (or creates) the history database in the active profile's data directory. This connection
+// serves reads; writes go through the db_writer actor's own connection.
+pub fn init(app: &tauri::AppHandle) -> Result<(), String> {
+    let data_dir = profiles::data_dir(app)?;
+    std::fs::create_dir_all(&data_dir).map_err(|e| format!("Failed to create {}: {}", data_dir.display(), e))?;
+
+    let path = data_dir.join(HISTORY_DB_FILE);
+    let conn = Connection::open(&path).map_err(db_err)?;
+    conn.execute_batch(db_writer::PRAGMAS).map_err(db_err)?;
+    conn.execute_batch(SCHEMA).map_err(db_err)?;
+    db_writer::open(&path)?;
+
+    // A profile switch swaps the connection in place
+    match HISTORY_DB.get() {
+        Some(db) => *db.lock().unwrap() = conn,
+        None => {
+            let _ = HISTORY_DB.set(Mutex::new(conn));
+        }
+    }
+    Ok(())
+}
+
+// Releases the database file (e.g. so a backup can replace it); init opens it again
+pub fn close() -> Result<(), String> {
+    db_writer::close();
+    if let Some(db) = HISTORY_DB.get() {
+        *db.lock().unwrap() = Connection::open_in_memory().map_err(db_err)?;
+    }
+    Ok(())
+}
+
+// Runs a closure against the shared connection
+pub fn with_db<T>(f: impl FnOnce(&mut Connection) -> Result<T, String>) -> Result<T, String> {
+    let db = HISTORY_DB.get().ok_or("History database is not initialized")?;
+    let mut conn = db.lock().unwrap();
+    f(&mut conn)
+}
+
+fn conversation_by_id(conn: &Connection, id: &str) -> Result<Option<Conversation>, String> {
+    conn.query_row(
+        "SELECT c.id, c.title, c.created_at, c.updated_at,
+                (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id)
+         FROM conversations c WHERE c.id = ?1",
+        params![id],
+        |row| {
+            Ok(Conversation {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                created_at: row.get(2)?,
+                updated_at: row.get(3)?,
+                message_count: row.get::<_, i64>(4)? as usize,
+            })
+        },
+    )
+    .optional()
+    .map_err(db_err)
+}
+
+fn attachments_for(conn: &Connection, message_id: &str) -> Result<Vec<Attachment>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, message_id, kind, path, mime FROM attachments WHERE message_id = ?1 ORDER BY created_at")
+        .map_err(db_err)?;
+    let rows = stmt
+        .query_map(params![message_id], |row| {
+            Ok(Attachment {
+                id: row.get(0)?,
+                message_id: row.get(1)?,
+                kind: row.get(2)?,
+                path: row.get(3)?,
+                mime: row.get(4)?,
+            })
+        })
+        .map_err(db_err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+}
+
+pub fn messages_for(conn: &Connection, conversation_id: &str) -> Result<Vec<Message>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, conversation_id, position, role, content, created_at,
+                    EXISTS(SELECT 1 FROM excluded_messages e WHERE e.message_id = messages.id)
+             FROM messages WHERE conversation_id = ?1 ORDER BY position",
+        )
+        .map_err(db_err)?;
+    let rows = stmt
+        .query_map(params![conversation_id], |row| {
+            Ok(Message {
+                id: row.get(0)?,
+                conversation_id: row.get(1)?,
+                position: row.get(2)?,
+                role: row.get(3)?,
+                content: row.get(4)?,
+                created_at: row.get(5)?,
+                attachments: Vec::new(),
+                excluded_from_context: row.get(6)?,
+                provenance: None,
+            })
+        })
+        .map_err(db_err)?;
+
+    let mut messages = rows.collect::<Result<Vec<_>, _>>().map_err(db_err)?;
+    for message in &mut messages {
+        message.attachments = attachments_for(conn, &message.id)?;
+        message.provenance = provenance::for_message(conn, &message.id)?;
+    }
+    Ok(messages)
+}
+
+pub fn insert_conversation(conn: &Connection, title: &str) -> Result<Conversation, String> {
+    let id = new_id();
+    let now = now_ms();
+    conn.execute(
+        "INSERT INTO conversations (id, title, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)",
+        params![id, title, now],
+    )
+    .map_err(db_err)?;
+
+    Ok(Conversation {
+        id,
+        title: title.to_string(),
+        created_at: now,
+        updated_at: now,
+        message_count: 0,
+    })
+}
+
+pub fn insert_message(conn: &Connection, conversation_id: &str, role: &str, content: &str) -> Result<Message, String> {
+    let position: i64 = conn
+        .query_row(
+            "SELECT COALESCE(MAX(position) + 1, 0) FROM messages WHERE conversation_id = ?1",
+            params![conversation_id],
+            |row| row.get(0),
+        )
+        .map_err(db_err)?;
+
+    let id = new_id();
+    let now = now_ms();
+    conn.execute(
+        "INSERT INTO messages (id, conversation_id, position, role, content, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![id, conversation_id, position, role, content, now],
+    )
+    .map_err(db_err)?;
+    touch_conversation(conn, conversation_id)?;
+
+    Ok(Message {
+        id,
+        conversation_id: conversation_id.to_string(),
+        position,
+        role: role.to_string(),
+        content: content.to_string(),
+        created_at: now,
+        attachments: Vec::new(),
+        excluded_from_context: false,
+        provenance: None,
+    })
+}
+
+fn touch_conversation(conn: &Connection, conversation_id: &str) -> Result<(), String> {
+    conn.execute(
+        "UPDATE conversations SET updated_at = ?1 WHERE id = ?2",
+        params![now_ms(), conversation_id],
+    )
+    .map_err(db_err)?;
+    Ok(())
+}
+
+pub fn message_content(conn: &Connection, message_id: &str) -> Result<String, String> {
+    conn.query_row("SELECT content FROM messages WHERE id = ?1", params![message_id], |row| row.get(0))
+        .optional()
+        .map_err(db_err)?
+        .ok_or_else(|| format!("Message not found: {}", message_id))
+}
+
+pub fn load(conn: &Connection, conversation_id: &str) -> Result<ConversationDetail, String> {
+    let conversation = conversation_by_id(conn, conversation_id)?
+        .ok_or_else(|| format!("Conversation not found: {}", conversation_id))?;
+    let messages = messages_for(conn, conversation_id)?;
+    Ok(ConversationDetail { conversation, messages, recap: None })
+}
+
+#[tauri::command]
+pub async fn create_conversation(title: Option<String>) -> Result<Conversation, String> {
+    db_writer::write(move |conn| insert_conversation(conn, title.as_deref().unwrap_or("New conversation"))).await
+}
+
+// Called when a chat request is cancelled, so appending its partial answer follows generation.on_cancel
+pub fn note_cancelled(request_id: &str) {
+    let now = now_ms();
+    let mut cancelled = CANCELLED.lock().unwrap();
+    let cancelled = cancelled.get_or_insert_with(HashMap::new);
+    cancelled.retain(|_, at| now.saturating_sub(*at) < CANCELLED_TTL_MS);
+    cancelled.insert(request_id.to_string(), now);
+}
+
+fn take_cancelled(request_id: &str) -> bool {
+    CANCELLED.lock().unwrap().as_mut().is_some_and(|cancelled| cancelled.remove(request_id).is_some())
+}
+
+pub fn set_excluded(conn: &Connection, message_id: &str, excluded: bool) -> Result<(), String> {
+    if excluded {
+        conn.execute("INSERT OR IGNORE INTO excluded_messages (message_id) VALUES (?1)", params![message_id])
+    } else {
+        conn.execute("DELETE FROM excluded_messages WHERE message_id = ?1", params![message_id])
+    }
+    .map_err(db_err)?;
+    Ok(())
+}
+
+// Writes an image to the attachments directory as a new attachment, not yet tied to a message
+fn store_image(app: &tauri::AppHandle, bytes: &[u8]) -> Result<Attachment, String> {
+    let format = image::guess_format(bytes).map_err(|e| format!("Unrecognized image format: {}", e))?;
+    let dir = profiles::data_dir(app)?.join(ATTACHMENTS_DIR);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let id = new_id();
+    let extension = format.extensions_str().first().copied().unwrap_or("img");
+    let path = dir.join(format!("{}.{}", id, extension));
+    std::fs::write(&path, bytes).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(Attachment {
+        id,
+        message_id: String::new(),
+        kind: "image".to_string(),
+        path: path.to_string_lossy().to_string(),
+        mime: Some(format.to_mime_type().to_string()),
+    })
+}
+
+// Copies the message's images into the attachments directory: base64 `image_data`, a file at
+// `image_path` (checked against image_input.allowed_dirs, like ai_chat checks it) and the clipboard
+// image read by the ai_chat request `clipboard_request_id`
+async fn store_images(
+    app: &tauri::AppHandle,
+    image_data: Option<&str>,
+    image_path: Option<&str>,
+    clipboard_request_id: Option<&str>,
+) -> Result<Vec<Attachment>, String> {
+    use base64::Engine;
+    let mut sources = Vec::new();
+    if let Some(image_base64) = image_data {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(image_base64)
+            .map_err(|e| format!("Failed to decode image: {}", e))?;
+        sources.push(bytes);
+    }
+    if let Some(path) = image_path {
+        let path = image_input::check_path(path)?;
+        let bytes = tokio::task::spawn_blocking(move || {
+            std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+        })
+        .await
+        .map_err(|e| format!("Failed to read the image: {}", e))??;
+        sources.push(bytes);
+    }
+    if let Some(request_id) = clipboard_request_id {
+        sources.push(image_input::take_clipboard(request_id)?);
+    }
+
+    let mut stored = Vec::new();
+    for bytes in sources {
+        match store_image(app, &bytes) {
+            Ok(attachment) => stored.push(attachment),
+            Err(e) => {
+                remove_files(&stored);
+                return Err(e);
+            }
+        }
+    }
+    Ok(stored)
+}
+
+fn remove_files(attachments: &[Attachment]) {
+    for attachment in attachments {
+        let _ = std::fs::remove_file(&attachment.path);
+    }
+}
+
+// Returns None when the message is the partial answer of a cancelled request and
+// generation.on_cancel discards those. Images given with the message are stored as its attachments.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn append_message(
+    conversation_id: String,
+    role: String,
+    content: String,
+    request_id: Option<String>,
+    image_data: Option<String>,
+    image_path: Option<String>,
+    clipboard_request_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<Option<Message>, String> {
+    let cancelled = request_id.as_deref().is_some_and(take_cancelled);
+    let on_cancel = config::get().generation.on_cancel;
+    if cancelled && on_cancel == CancelBehavior::Discard {
+        with_db(|conn| conversation_by_id(conn, &conversation_id))?
+            .ok_or_else(|| format!("Conversation not found: {}", conversation_id))?;
+        println!("Discarding the partial answer of cancelled request {}", request_id.as_deref().unwrap_or_default());
+        return Ok(None);
+    }
+
+    // Images are written before the rows that refer to them, and removed again if the rows aren't
+    let stored_images = store_images(&app, image_data.as_deref(), image_path.as_deref(), clipboard_request_id.as_deref()).await?;
+    let written = stored_images.clone();
+
+    let appended = db_writer::write(move |tx| {
+        conversation_by_id(tx, &conversation_id)?
+            .ok_or_else(|| format!("Conversation not found: {}", conversation_id))?;
+        let mut message = insert_message(tx, &conversation_id, &role, &content)?;
+        // The ai_chat request that produced this answer, so its configuration is kept with it and
+        // get_request_trace can find it
+        if let Some(request_id) = &request_id {
+            tx.execute("UPDATE messages SET request_id = ?1 WHERE id = ?2", params![request_id, message.id])
+                .map_err(db_err)?;
+            message.provenance = provenance::attach(tx, &message.id, request_id)?;
+        }
+        if cancelled && on_cancel == CancelBehavior::KeepExcluded {
+            set_excluded(tx, &message.id, true)?;
+            message.excluded_from_context = true;
+        }
+        for mut attachment in stored_images {
+            attachment.message_id = message.id.clone();
+            tx.execute(
+                "INSERT INTO attachments (id, message_id, conversation_id, kind, path, mime, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![attachment.id, message.id, conversation_id, attachment.kind, attachment.path, attachment.mime, now_ms()],
+            )
+            .map_err(db_err)?;
+            message.attachments.push(attachment);
+        }
+        Ok(Some(message))
+    })
+    .await;
+    if appended.is_err() {
+        remove_files(&written);
+    }
+    appended
+}
+
+// An attachment's file as base64, e.g. to show a stored image or send it to a vision model again
+#[tauri::command]
+pub async fn load_attachment(attachment_id: String) -> Result<String, String> {
+    use base64::Engine;
+    let path: String = with_db(|conn| {
+        conn.query_row("SELECT path FROM attachments WHERE id = ?1", params![attachment_id], |row| row.get(0))
+            .optional()
+            .map_err(db_err)?
+            .ok_or_else(|| format!("Attachment not found: {}", attachment_id))
+    })?;
+    let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+#[tauri::command]
+pub async fn list_conversations() -> Result<Vec<Conversation>, String> {
+    with_db(|conn| {
+        let mut stmt = conn
+            .prepare(
+                "SELECT c.id, c.title, c.created_at, c.updated_at,
+                        (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id)
+                 FROM conversations c ORDER BY c.updated_at DESC",
+            )
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(Conversation {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    created_at: row.get(2)?,
+                    updated_at: row.get(3)?,
+                    message_count: row.get::<_, i64>(4)? as usize,
+                })
+            })
+            .map_err(db_err)?;
+        let mut conversations = rows.collect::<Result<Vec<_>, _>>().map_err(db_err)?;
+        if presentation::is_active() {
+            conversations.iter_mut().for_each(presentation::redact_conversation);
+        }
+        Ok(conversations)
+    })
+}
+
+#[tauri::command]
+pub async fn load_conversation(conversation_id: String, app: tauri::AppHandle) -> Result<ConversationDetail, String> {
+    let mut detail = with_db(|conn| load(conn, &conversation_id))?;
+    detail.recap = recap::for_opened(&detail, &app);
+    if presentation::is_active() {
+        presentation::redact_detail(&mut detail);
+    }
+    Ok(detail)
+}
+
+// Moves every message of the given conversations (in the given order) into the first one
+#[tauri::command]
+pub async fn merge_conversations(ids: Vec<String>, title: Option<String>) -> Result<ConversationDetail, String> {
+    if ids.len() < 2 {
+        return Err("Select at least two conversations to merge".to_string());
+    }
+
+    db_writer::write(move |tx| {
+        let target = &ids[0];
+
+        for id in &ids {
+            conversation_by_id(tx, id)?.ok_or_else(|| format!("Conversation not found: {}", id))?;
+        }
+
+        for source in &ids[1..] {
+            if source == target {
+                continue;
+            }
+
+            let offset: i64 = tx
+                .query_row(
+                    "SELECT COALESCE(MAX(position) + 1, 0) FROM messages WHERE conversation_id = ?1",
+                    params![target],
+                    |row| row.get(0),
+                )
+                .map_err(db_err)?;
+
+            // Message ids are kept, so attachments only need their conversation reference updated
+            tx.execute(
+                "UPDATE messages SET conversation_id = ?1, position = position + ?2 WHERE conversation_id = ?3",
+                params![target, offset, source],
+            )
+            .map_err(db_err)?;
+            tx.execute(
+                "UPDATE attachments SET conversation_id = ?1 WHERE conversation_id = ?2",
+                params![target, source],
+            )
+            .map_err(db_err)?;
+            tx.execute("DELETE FROM conversations WHERE id = ?1", params![source])
+                .map_err(db_err)?;
+        }
+
+        if let Some(title) = &title {
+            tx.execute("UPDATE conversations SET title = ?1 WHERE id = ?2", params![title, target])
+                .map_err(db_err)?;
+        }
+        touch_conversation(tx, target)?;
+
+        load(tx, target)
+    })
+    .await
+}
+
+// Moves the given message and everything after it into a new conversation
+#[tauri::command]
+pub async fn split_conversation(conversation_id: String, at_message_id: String, title: Option<String>) -> Result<ConversationDetail, String> {
+    db_writer::write(move |tx| {
+        let original = conversation_by_id(tx, &conversation_id)?
+            .ok_or_else(|| format!("Conversation not found: {}", conversation_id))?;
+        let at_position: i64 = tx
+            .query_row(
+                "SELECT position FROM messages WHERE id = ?1 AND conversation_id = ?2",
+                params![at_message_id, conversation_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_err)?
+            .ok_or_else(|| format!("Message {} is not part of conversation {}", at_message_id, conversation_id))?;
+
+        let new_title = title.unwrap_or_else(|| format!("{} (split)", original.title));
+        let split = insert_conversation(tx, &new_title)?;
+
+        tx.execute(
+            "UPDATE attachments SET conversation_id = ?1
+             WHERE message_id IN (SELECT id FROM messages WHERE conversation_id = ?2 AND position >= ?3)",
+            params![split.id, conversation_id, at_position],
+        )
+        .map_err(db_err)?;
+        tx.execute(
+            "UPDATE messages SET conversation_id = ?1, position = position - ?2
+             WHERE conversation_id = ?3 AND position >= ?2",
+            params![split.id, at_position, conversation_id],
+        )
+        .map_err(db_err)?;
+        touch_conversation(tx, &conversation_id)?;
+
+        load(tx, &split.id)
+    })
+    .await
+}
+
+// Clones a conversation (optionally only up to a message, or only its system/context setup) into a new one
+#[tauri::command]
+pub async fn duplicate_conversation(
+    conversation_id: String,
+    up_to_message_id: Option<String>,
+    setup_only: Option<bool>,
+    title: Option<String>,
+) -> Result<ConversationDetail, String> {
+    let setup_only = setup_only.unwrap_or(false);
+
+    db_writer::write(move |tx| {
+        let source = load(tx, &conversation_id)?;
+
+        let last_position = match &up_to_message_id {
+            Some(message_id) => source
+                .messages
+                .iter()
+                .find(|m| &m.id == message_id)
+                .map(|m| m.position)
+                .ok_or_else(|| format!("Message {} is not part of conversation {}", message_id, conversation_id))?,
+            None => i64::MAX,
+        };
+
+        let new_title = title.unwrap_or_else(|| format!("{} (copy)", source.conversation.title));
+        let copy = insert_conversation(tx, &new_title)?;
+
+        for message in source
+            .messages
+            .iter()
+            .filter(|m| m.position <= last_position)
+            .filter(|m| !setup_only || m.role == "system")
+        {
+            let cloned = insert_message(tx, &copy.id, &message.role, &message.content)?;
+            if let Some(provenance) = &message.provenance {
+                provenance::store(tx, &cloned.id, provenance)?;
+            }
+            if message.excluded_from_context {
+                set_excluded(tx, &cloned.id, true)?;
+            }
+
+            // Attachment files are shared; only the references are duplicated
+            for attachment in &message.attachments {
+                tx.execute(
+                    "INSERT INTO attachments (id, message_id, conversation_id, kind, path, mime, created_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![new_id(), cloned.id, copy.id, attachment.kind, attachment.path, attachment.mime, now_ms()],
+                )
+                .map_err(db_err)?;
+            }
+        }
+
+        load(tx, &copy.id)
+    })
+    .await
+    .map(|mut detail| {
+        if presentation::is_active() {
+            presentation::redact_detail(&mut detail);
+        }
+        detail
+    })
+}
+
+// Deletes a conversation and its messages; anything bound to it, like a folder chat's index, goes too.
+// Attachment files are removed once no duplicated conversation refers to them anymore.
+#[tauri::command]
+pub async fn delete_conversation(conversation_id: String) -> Result<(), String> {
+    let unreferenced = db_writer::write(move |conn| {
+        let mut stmt = conn
+            .prepare("SELECT DISTINCT path FROM attachments WHERE conversation_id = ?1")
+            .map_err(db_err)?;
+        let paths = stmt
+            .query_map(params![conversation_id], |row| row.get::<_, String>(0))
+            .map_err(db_err)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db_err)?;
+        drop(stmt);
+
+        let deleted = conn
+            .execute("DELETE FROM conversations WHERE id = ?1", params![conversation_id])
+            .map_err(db_err)?;
+        if deleted == 0 {
+            return Err(format!("Conversation not found: {}", conversation_id));
+        }
+        let mut unreferenced = Vec::new();
+        for path in paths {
+            let references: i64 = conn
+                .query_row("SELECT COUNT(*) FROM attachments WHERE path = ?1", params![path], |row| row.get(0))
+                .map_err(db_err)?;
+            if references == 0 {
+                unreferenced.push(path);
+            }
+        }
+        Ok(unreferenced)
+    })
+    .await?;
+    // Only once the delete is committed
+    for path in unreferenced {
+        if let Err(e) = std::fs::remove_file(&path) {
+            println!("Warning: Failed to remove attachment {}: {}", path, e);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,185 @@
+// Where a vision request's images come from. Besides base64 from the webview, ai_chat takes a file
+// path inside image_input.allowed_dirs or the image on the system clipboard; both are read and decoded
+// here in Rust, so a large photo never crosses IPC as base64. Every image is turned upright by its
+// EXIF orientation and scaled down to image_input.max_dimension before the vision model sees it.
+// A clipboard image is also kept for a while under its request id, since the frontend never has its
+// bytes and append_message has to store it from here.
+
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config;
+use crate::streaming::now_ms;
+use crate::tools::expand_home;
+use crate::vision_cache;
+
+// Clipboard images by the request that read them, with when they were read
+static CLIPBOARD_IMAGES: Mutex<Option<HashMap<String, (DynamicImage, u64)>>> = Mutex::new(None);
+
+// How long a clipboard image waits for append_message to store it
+const CLIPBOARD_TTL_MS: u64 = 10 * 60 * 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageInputConfig {
+    // Directories image_path may point into; "~/" is the home directory
+    pub allowed_dirs: Vec<String>,
+    // Longest side, in pixels, images are scaled down to before vision inference; larger images only
+    // add preprocessing time and memory. 0 sends them at full size
+    pub max_dimension: u32,
+}
+
+impl Default for ImageInputConfig {
+    fn default() -> Self {
+        Self {
+            allowed_dirs: vec!["~/Pictures".to_string(), "~/Desktop".to_string(), "~/Downloads".to_string()],
+            max_dimension: 1536,
+        }
+    }
+}
+
+impl ImageInputConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.allowed_dirs.iter().any(|dir| dir.trim().is_empty()) {
+            errors.push("image_input.allowed_dirs must not contain empty entries".to_string());
+        }
+        if self.max_dimension > 0 && self.max_dimension < 224 {
+            errors.push("image_input.max_dimension must be 0 or at least 224, the smallest input vision encoders take".to_string());
+        }
+        errors
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ImageInput {
+    Base64(String),
+    // A file already checked against the allowlist, or one of the app's own attachments
+    File(PathBuf),
+    // Read from the clipboard, which holds pixels rather than an encoded file
+    Decoded(DynamicImage),
+}
+
+impl ImageInput {
+    // Size of the encoded image, as limits.max_image_bytes counts it; None for clipboard images
+    pub fn encoded_len(&self) -> Option<usize> {
+        match self {
+            // Base64 inflates by 4/3
+            ImageInput::Base64(data) => Some(data.len() / 4 * 3),
+            ImageInput::File(path) => std::fs::metadata(path).ok().map(|meta| meta.len() as usize),
+            ImageInput::Decoded(_) => None,
+        }
+    }
+
+    pub fn load(&self, model_id: &str) -> Result<DynamicImage, String> {
+        match self {
+            ImageInput::Base64(data) => vision_cache::image_for(model_id, data),
+            ImageInput::File(path) => vision_cache::file_image_for(model_id, path),
+            ImageInput::Decoded(image) => Ok(downscale(image.clone())),
+        }
+    }
+}
+
+// Decodes an encoded image and applies its EXIF orientation, so phone photos aren't seen sideways
+pub fn decode_bytes(bytes: &[u8]) -> Result<DynamicImage, String> {
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to read image: {}", e))?
+        .into_decoder()
+        .map_err(|e| format!("Failed to load image: {}", e))?;
+    let orientation = decoder.orientation().map_err(|e| format!("Failed to read image orientation: {}", e))?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|e| format!("Failed to load image: {}", e))?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+// Scales an image down, keeping its aspect ratio, until its longest side fits image_input.max_dimension
+pub fn downscale(image: DynamicImage) -> DynamicImage {
+    let max = config::get().image_input.max_dimension;
+    if max == 0 || (image.width() <= max && image.height() <= max) {
+        return image;
+    }
+    let resized = image.resize(max, max, FilterType::CatmullRom);
+    println!(
+        "Downscaled a {}x{} image to {}x{}",
+        image.width(),
+        image.height(),
+        resized.width(),
+        resized.height()
+    );
+    resized
+}
+
+// Resolves a user-supplied path, refusing anything outside image_input.allowed_dirs
+pub fn check_path(path: &str) -> Result<PathBuf, String> {
+    let allowed: Vec<PathBuf> = config::get()
+        .image_input
+        .allowed_dirs
+        .iter()
+        .filter_map(|dir| expand_home(dir).canonicalize().ok())
+        .collect();
+    let resolved = expand_home(path)
+        .canonicalize()
+        .map_err(|e| format!("Cannot access {}: {}", path, e))?;
+    if !allowed.iter().any(|dir| resolved.starts_with(dir)) {
+        return Err(format!("{} is not inside any of image_input.allowed_dirs", resolved.display()));
+    }
+    if !resolved.is_file() {
+        return Err(format!("{} is not a file", resolved.display()));
+    }
+    Ok(resolved)
+}
+
+fn read_clipboard() -> Result<DynamicImage, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| format!("Failed to open the clipboard: {}", e))?;
+    let data = clipboard
+        .get_image()
+        .map_err(|e| format!("The clipboard holds no image: {}", e))?;
+    image::RgbaImage::from_raw(data.width as u32, data.height as u32, data.bytes.into_owned())
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| "The clipboard image has an unexpected size".to_string())
+}
+
+// The image on the system clipboard, kept under `request_id` for take_clipboard. Clipboard access
+// blocks, so it runs off the async workers.
+pub async fn from_clipboard(request_id: &str) -> Result<ImageInput, String> {
+    let image = tokio::task::spawn_blocking(read_clipboard)
+        .await
+        .map_err(|e| format!("Failed to read the clipboard: {}", e))??;
+    let now = now_ms();
+    let mut kept = CLIPBOARD_IMAGES.lock().unwrap();
+    let kept = kept.get_or_insert_with(HashMap::new);
+    kept.retain(|_, (_, at)| now.saturating_sub(*at) < CLIPBOARD_TTL_MS);
+    kept.insert(request_id.to_string(), (image.clone(), now));
+    Ok(ImageInput::Decoded(image))
+}
+
+// The clipboard image a request read, as PNG, so append_message can store it with the message
+pub fn take_clipboard(request_id: &str) -> Result<Vec<u8>, String> {
+    let image = CLIPBOARD_IMAGES
+        .lock()
+        .unwrap()
+        .as_mut()
+        .and_then(|kept| kept.remove(request_id))
+        .map(|(image, _)| image)
+        .ok_or_else(|| format!("No clipboard image is kept for request {} (it may have expired)", request_id))?;
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode the clipboard image: {}", e))?;
+    Ok(bytes)
+}
+
+pub fn from_path(path: &str) -> Result<ImageInput, String> {
+    check_path(path).map(ImageInput::File)
+}
+
+// Stored attachments are the app's own files, so they skip the allowlist
+pub fn from_attachment(path: &Path) -> ImageInput {
+    ImageInput::File(path.to_path_buf())
+}
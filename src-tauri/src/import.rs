@@ -0,0 +1,344 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Read;
+use std::path::Path;
+
+use crate::db_writer;
+use crate::history::{db_err, new_id};
+use crate::kiosk;
+use crate::streaming::now_ms;
+
+// Speaker labels recognised in Markdown transcripts, lower-cased
+const USER_LABELS: [&str; 6] = ["user", "you", "human", "me", "prompt", "question"];
+const ASSISTANT_LABELS: [&str; 12] = [
+    "assistant", "ai", "chatgpt", "gpt", "gpt-4", "claude", "gemini", "model", "bot", "llama", "mistral", "answer",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    // The zip from ChatGPT's "Export data", or the conversations.json inside it
+    Chatgpt,
+    // Open WebUI's chat export, or a JSON file with an Ollama-style "messages" array
+    OpenWebui,
+    Markdown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportSummary {
+    pub format: ImportFormat,
+    pub conversation_ids: Vec<String>,
+    pub messages: usize,
+    // Conversations without any usable message
+    pub skipped: usize,
+}
+
+struct ImportedMessage {
+    role: String,
+    content: String,
+    created_at: Option<u64>,
+}
+
+struct ImportedConversation {
+    title: String,
+    created_at: Option<u64>,
+    messages: Vec<ImportedMessage>,
+}
+
+// Export timestamps are seconds (sometimes fractional); anything that big is already milliseconds
+fn timestamp_ms(value: Option<&Value>) -> Option<u64> {
+    let seconds = value?.as_f64().filter(|t| *t > 0.0)?;
+    Some(if seconds > 1e11 { seconds as u64 } else { (seconds * 1000.0) as u64 })
+}
+
+fn normalize_role(role: &str) -> Option<&'static str> {
+    match role {
+        "user" | "human" => Some("user"),
+        "assistant" | "model" | "bot" => Some("assistant"),
+        _ => None,
+    }
+}
+
+fn detect_format(path: &Path) -> Result<ImportFormat, String> {
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "zip" => Ok(ImportFormat::Chatgpt),
+        "md" | "markdown" | "txt" => Ok(ImportFormat::Markdown),
+        "json" => {
+            let json = read_json(path)?;
+            let first = json.as_array().and_then(|items| items.first()).unwrap_or(&json);
+            if first.get("mapping").is_some() {
+                Ok(ImportFormat::Chatgpt)
+            } else {
+                Ok(ImportFormat::OpenWebui)
+            }
+        }
+        _ => Err(format!("Can't tell the format of {}; pass it explicitly", path.display())),
+    }
+}
+
+fn read_json(path: &Path) -> Result<Value, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("{} is not valid JSON: {}", path.display(), e))
+}
+
+// conversations.json, either inside the export zip or extracted from it
+fn read_chatgpt(path: &Path) -> Result<Value, String> {
+    if !path.extension().is_some_and(|e| e.eq_ignore_ascii_case("zip")) {
+        return read_json(path);
+    }
+    let file = std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("{} is not a zip file: {}", path.display(), e))?;
+    let mut entry = archive
+        .by_name("conversations.json")
+        .map_err(|_| "The zip has no conversations.json; is it a ChatGPT data export?".to_string())?;
+    let mut text = String::new();
+    entry
+        .read_to_string(&mut text)
+        .map_err(|e| format!("Failed to read conversations.json: {}", e))?;
+    serde_json::from_str(&text).map_err(|e| format!("conversations.json is not valid JSON: {}", e))
+}
+
+// ChatGPT stores each conversation as a tree of edits; the visible thread runs from current_node up
+// through the parents
+fn parse_chatgpt(json: &Value) -> Vec<ImportedConversation> {
+    let conversations = json.as_array().cloned().unwrap_or_else(|| vec![json.clone()]);
+    conversations
+        .iter()
+        .map(|conversation| {
+            let mapping = conversation.get("mapping").and_then(Value::as_object);
+            let mut thread = Vec::new();
+            let mut node_id = conversation.get("current_node").and_then(Value::as_str);
+            while let Some(node) = node_id.and_then(|id| mapping?.get(id)) {
+                thread.push(node);
+                node_id = node.get("parent").and_then(Value::as_str);
+            }
+            thread.reverse();
+
+            let messages = thread
+                .iter()
+                .filter_map(|node| {
+                    let message = node.get("message")?;
+                    let role = normalize_role(message.pointer("/author/role")?.as_str()?)?;
+                    // Parts are text, or objects for images and other attachments that aren't imported
+                    let content = message
+                        .pointer("/content/parts")?
+                        .as_array()?
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    Some(ImportedMessage {
+                        role: role.to_string(),
+                        content,
+                        created_at: timestamp_ms(message.get("create_time")),
+                    })
+                })
+                .collect();
+
+            ImportedConversation {
+                title: conversation.get("title").and_then(Value::as_str).unwrap_or("ChatGPT conversation").to_string(),
+                created_at: timestamp_ms(conversation.get("create_time")),
+                messages,
+            }
+        })
+        .collect()
+}
+
+fn parse_webui_messages(messages: &[&Value]) -> Vec<ImportedMessage> {
+    messages
+        .iter()
+        .filter_map(|message| {
+            let role = normalize_role(message.get("role")?.as_str()?)?;
+            Some(ImportedMessage {
+                role: role.to_string(),
+                content: message.get("content")?.as_str()?.to_string(),
+                created_at: timestamp_ms(message.get("timestamp").or_else(|| message.get("created_at"))),
+            })
+        })
+        .collect()
+}
+
+// Open WebUI wraps each chat as {title, chat: {messages | history}}; a bare {messages} object is
+// accepted too, which covers transcripts saved from the Ollama chat API
+fn parse_open_webui(json: &Value) -> Vec<ImportedConversation> {
+    let chats = json.as_array().cloned().unwrap_or_else(|| vec![json.clone()]);
+    chats
+        .iter()
+        .map(|item| {
+            let chat = item.get("chat").unwrap_or(item);
+            let mut thread: Vec<&Value> = chat
+                .get("messages")
+                .and_then(Value::as_array)
+                .map(|messages| messages.iter().collect())
+                .unwrap_or_default();
+            // Newer exports may only keep the branching history; follow the current branch
+            if thread.is_empty() {
+                let history = chat.pointer("/history/messages").and_then(Value::as_object);
+                let mut message_id = chat.pointer("/history/currentId").and_then(Value::as_str);
+                while let Some(message) = message_id.and_then(|id| history?.get(id)) {
+                    thread.push(message);
+                    message_id = message.get("parentId").and_then(Value::as_str);
+                }
+                thread.reverse();
+            }
+
+            let title = item
+                .get("title")
+                .or_else(|| chat.get("title"))
+                .and_then(Value::as_str)
+                .unwrap_or("Open WebUI chat");
+            ImportedConversation {
+                title: title.to_string(),
+                created_at: timestamp_ms(item.get("created_at").or_else(|| chat.get("timestamp"))),
+                messages: parse_webui_messages(&thread),
+            }
+        })
+        .collect()
+}
+
+// The role a line introduces, if it is a speaker label such as "**User:**", "## Assistant",
+// "ChatGPT said:" or "> You: text", along with any text after the label
+fn speaker(line: &str) -> Option<(&'static str, &str)> {
+    let stripped = line.trim_start_matches(|c: char| c == '#' || c == '>' || c.is_whitespace());
+    let (label, rest) = match stripped.find(':') {
+        Some(colon) => (&stripped[..colon], &stripped[colon + 1..]),
+        // Headings can name the speaker without a colon
+        None if line.starts_with('#') => (stripped, ""),
+        None => return None,
+    };
+    let label = label.trim_matches(|c: char| c == '*' || c == '_' || c.is_whitespace()).to_lowercase();
+    let label = label.strip_suffix(" said").unwrap_or(&label);
+    let rest = rest.trim_start_matches(|c: char| c == '*' || c == '_').trim();
+    if USER_LABELS.contains(&label) {
+        Some(("user", rest))
+    } else if ASSISTANT_LABELS.contains(&label) {
+        Some(("assistant", rest))
+    } else {
+        None
+    }
+}
+
+fn parse_markdown(text: &str, fallback_title: &str) -> Result<ImportedConversation, String> {
+    let mut title = None;
+    let mut messages: Vec<ImportedMessage> = Vec::new();
+    let mut in_fence = false;
+
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if !in_fence {
+            if let Some((role, rest)) = speaker(line) {
+                messages.push(ImportedMessage {
+                    role: role.to_string(),
+                    content: rest.to_string(),
+                    created_at: None,
+                });
+                continue;
+            }
+            if messages.is_empty() && title.is_none() {
+                if let Some(heading) = line.strip_prefix("# ") {
+                    title = Some(heading.trim().to_string());
+                    continue;
+                }
+            }
+        }
+        if let Some(current) = messages.last_mut() {
+            current.content.push('\n');
+            current.content.push_str(line);
+        }
+    }
+
+    if messages.is_empty() {
+        return Err("No speaker labels such as \"User:\" or \"## Assistant\" were found in the transcript".to_string());
+    }
+    Ok(ImportedConversation {
+        title: title.unwrap_or_else(|| fallback_title.to_string()),
+        created_at: None,
+        messages,
+    })
+}
+
+// Writes the conversations with their original timestamps where the export has them. It is one
+// writer job, so a failed import leaves nothing behind.
+async fn store(conversations: Vec<ImportedConversation>, format: ImportFormat) -> Result<ImportSummary, String> {
+    db_writer::write(move |tx| {
+        let mut summary = ImportSummary {
+            format,
+            conversation_ids: Vec::new(),
+            messages: 0,
+            skipped: 0,
+        };
+        for conversation in conversations {
+            let messages: Vec<ImportedMessage> = conversation
+                .messages
+                .into_iter()
+                .filter(|m| !m.content.trim().is_empty())
+                .collect();
+            if messages.is_empty() {
+                summary.skipped += 1;
+                continue;
+            }
+
+            let created_at = conversation.created_at.or_else(|| messages[0].created_at).unwrap_or_else(now_ms);
+            let updated_at = messages.iter().filter_map(|m| m.created_at).max().unwrap_or(created_at).max(created_at);
+            let id = new_id();
+            tx.execute(
+                "INSERT INTO conversations (id, title, created_at, updated_at) VALUES (?1, ?2, ?3, ?4)",
+                params![id, conversation.title.trim(), created_at as i64, updated_at as i64],
+            )
+            .map_err(db_err)?;
+            for (position, message) in messages.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO messages (id, conversation_id, position, role, content, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        new_id(),
+                        id,
+                        position as i64,
+                        message.role,
+                        message.content.trim(),
+                        message.created_at.unwrap_or(created_at) as i64
+                    ],
+                )
+                .map_err(db_err)?;
+            }
+            summary.messages += messages.len();
+            summary.conversation_ids.push(id);
+        }
+        Ok(summary)
+    })
+    .await
+}
+
+// Imports conversations from another chat app into local history; the format is guessed from the
+// file when not given
+#[tauri::command]
+pub async fn import_chat_history(path: String, format: Option<ImportFormat>) -> Result<ImportSummary, String> {
+    kiosk::ensure_unlocked("Importing chat history")?;
+    let path = Path::new(&path);
+    let format = match format {
+        Some(format) => format,
+        None => detect_format(path)?,
+    };
+
+    let conversations = match format {
+        ImportFormat::Chatgpt => parse_chatgpt(&read_chatgpt(path)?),
+        ImportFormat::OpenWebui => parse_open_webui(&read_json(path)?),
+        ImportFormat::Markdown => {
+            let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let fallback_title = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            vec![parse_markdown(&text, &fallback_title)?]
+        }
+    };
+
+    let summary = store(conversations, format).await?;
+    println!(
+        "Imported {} conversations ({} messages) from {}",
+        summary.conversation_ids.len(),
+        summary.messages,
+        path.display()
+    );
+    Ok(summary)
+}
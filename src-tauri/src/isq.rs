@@ -0,0 +1,105 @@
+// In-situ quantization (ISQ) level each model loads with. Every loader has a default that suits it;
+// models.isq overrides it per model, e.g. Q4K on an 8 GB machine or Q8_0 with plenty of memory.
+
+use mistralrs::IsqType;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::{config, hardware};
+
+// Names accepted in models.isq, as mistral.rs spells them
+const ISQ_TYPES: [(&str, IsqType); 15] = [
+    ("Q4_0", IsqType::Q4_0),
+    ("Q4_1", IsqType::Q4_1),
+    ("Q5_0", IsqType::Q5_0),
+    ("Q5_1", IsqType::Q5_1),
+    ("Q8_0", IsqType::Q8_0),
+    ("Q8_1", IsqType::Q8_1),
+    ("Q2K", IsqType::Q2K),
+    ("Q3K", IsqType::Q3K),
+    ("Q4K", IsqType::Q4K),
+    ("Q5K", IsqType::Q5K),
+    ("Q6K", IsqType::Q6K),
+    ("Q8K", IsqType::Q8K),
+    ("HQQ4", IsqType::HQQ4),
+    ("HQQ8", IsqType::HQQ8),
+    ("F8E4M3", IsqType::F8E4M3),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelIsq {
+    pub model_id: String,
+    // models.isq entry for the model; None loads it with its loader's default
+    pub configured: Option<String>,
+    // From the hardware benchmark's memory size, when it has run
+    pub recommended: Option<String>,
+    pub available: Vec<String>,
+}
+
+// Case-insensitive, so "q4k" works as well as "Q4K"
+pub fn parse(name: &str) -> Option<(&'static str, IsqType)> {
+    ISQ_TYPES.iter().find(|(n, _)| n.eq_ignore_ascii_case(name.trim())).copied()
+}
+
+pub fn validate(per_model: &HashMap<String, String>) -> Vec<String> {
+    per_model
+        .iter()
+        .filter(|(_, name)| parse(name).is_none())
+        .map(|(model_id, name)| {
+            format!("models.isq.{} = \"{}\" is not an ISQ type (expected one of {})", model_id, name, names().join(", "))
+        })
+        .collect()
+}
+
+fn names() -> Vec<String> {
+    ISQ_TYPES.iter().map(|(name, _)| name.to_string()).collect()
+}
+
+pub fn name(isq: IsqType) -> String {
+    ISQ_TYPES
+        .iter()
+        .find(|(_, known)| *known == isq)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| format!("{:?}", isq))
+}
+
+// The ISQ type a model loads with and its name for provenance
+pub fn for_model(model_id: &str, default: IsqType) -> (IsqType, String) {
+    let configured = config::get().models.isq.get(model_id).and_then(|name| parse(name));
+    let isq = configured.map_or(default, |(_, isq)| isq);
+    (isq, name(isq))
+}
+
+// Sets the ISQ type a model loads with; None returns it to the loader's default. Takes effect the
+// next time the model loads, so unload_model it first to apply it now.
+#[tauri::command]
+pub fn set_model_isq(model_id: String, isq: Option<String>, app: tauri::AppHandle) -> Result<ModelIsq, String> {
+    let isq = match isq.filter(|name| !name.trim().is_empty()) {
+        Some(name) => Some(
+            parse(&name)
+                .ok_or_else(|| format!("{} is not an ISQ type (expected one of {})", name, names().join(", ")))?
+                .0,
+        ),
+        None => None,
+    };
+    config::update(&app, |config| match isq {
+        Some(name) => {
+            config.models.isq.insert(model_id.clone(), name.to_string());
+        }
+        None => {
+            config.models.isq.remove(&model_id);
+        }
+    })?;
+    Ok(get_model_isq(model_id))
+}
+
+#[tauri::command]
+pub fn get_model_isq(model_id: String) -> ModelIsq {
+    let configured = config::get().models.isq.get(&model_id).cloned();
+    ModelIsq {
+        model_id,
+        configured,
+        recommended: hardware::get_hardware_profile().map(|profile| profile.recommended_isq),
+        available: names(),
+    }
+}
@@ -0,0 +1,279 @@
+// JSON mode for chat requests. With a response_format the answer is constrained to a JSON object
+// or array (matching a schema, if one is given) and parsed as it streams: every value that
+// completes, from a single field to a whole table row, is checked against its part of the schema
+// and sent as a "field" event, so the frontend can fill in structured results before the object
+// is finished.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    // Any JSON object
+    JsonObject,
+    JsonSchema { schema: Value },
+}
+
+impl ResponseFormat {
+    pub fn schema(&self) -> Value {
+        match self {
+            ResponseFormat::JsonObject => json!({ "type": "object" }),
+            ResponseFormat::JsonSchema { schema } => schema.clone(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            ResponseFormat::JsonSchema { schema } if !schema.is_object() => {
+                Err("response_format.schema must be a JSON object".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// A value of the answer that finished streaming
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonField {
+    // JSON Pointer to the value, e.g. "/rows/2/name"; "" for the whole answer
+    pub path: String,
+    pub value: Value,
+    pub valid: bool,
+    // Why the value doesn't match its part of the schema
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug)]
+enum Container {
+    // Current member key, and whether the next string is a key
+    Object { key: Option<String>, expecting_key: bool },
+    Array { index: usize },
+}
+
+#[derive(Debug)]
+struct Frame {
+    container: Container,
+    start: usize,
+}
+
+// Incremental parser fed with streamed text. It only tracks structure; completed values are cut
+// out of the buffered text and parsed with serde_json.
+pub struct JsonStream {
+    schema: Value,
+    text: String,
+    // Bytes of `text` already scanned
+    scanned: usize,
+    stack: Vec<Frame>,
+    // Start of the string being read, and whether it is an object key
+    string: Option<(usize, bool)>,
+    escaped: bool,
+    // Start of the number or literal being read
+    scalar: Option<usize>,
+    completed: bool,
+}
+
+impl JsonStream {
+    pub fn new(schema: Value) -> Self {
+        Self {
+            schema,
+            text: String::new(),
+            scanned: 0,
+            stack: Vec::new(),
+            string: None,
+            escaped: false,
+            scalar: None,
+            completed: false,
+        }
+    }
+
+    // Whether the whole answer has been read
+    pub fn is_complete(&self) -> bool {
+        self.completed
+    }
+
+    // Adds streamed text and returns the values it completed, innermost first
+    pub fn push(&mut self, chunk: &str) -> Vec<JsonField> {
+        self.text.push_str(chunk);
+        let mut fields = Vec::new();
+        while self.scanned < self.text.len() && !self.completed {
+            let i = self.scanned;
+            let c = self.text.as_bytes()[i];
+            self.scanned += 1;
+
+            if let Some((start, is_key)) = self.string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if c == b'\\' {
+                    self.escaped = true;
+                } else if c == b'"' {
+                    self.string = None;
+                    if is_key {
+                        let key = serde_json::from_str::<String>(&self.text[start..=i]).unwrap_or_default();
+                        if let Some(Frame { container: Container::Object { key: current, expecting_key }, .. }) = self.stack.last_mut() {
+                            *current = Some(key);
+                            *expecting_key = false;
+                        }
+                    } else {
+                        self.complete(start, i + 1, &mut fields);
+                    }
+                }
+                continue;
+            }
+
+            if let Some(start) = self.scalar {
+                if matches!(c, b',' | b'}' | b']') || c.is_ascii_whitespace() {
+                    self.scalar = None;
+                    self.complete(start, i, &mut fields);
+                    if self.completed {
+                        break;
+                    }
+                } else {
+                    continue;
+                }
+            }
+
+            // Text before the answer starts, e.g. from a model ignoring the constraint, is skipped
+            if self.stack.is_empty() && !matches!(c, b'{' | b'[') {
+                continue;
+            }
+            match c {
+                b'"' => {
+                    let is_key = matches!(
+                        self.stack.last(),
+                        Some(Frame { container: Container::Object { expecting_key: true, .. }, .. })
+                    );
+                    self.string = Some((i, is_key));
+                }
+                b'{' => self.stack.push(Frame { container: Container::Object { key: None, expecting_key: true }, start: i }),
+                b'[' => self.stack.push(Frame { container: Container::Array { index: 0 }, start: i }),
+                b'}' | b']' => {
+                    if let Some(frame) = self.stack.pop() {
+                        self.complete(frame.start, i + 1, &mut fields);
+                    }
+                }
+                b',' => match self.stack.last_mut().map(|frame| &mut frame.container) {
+                    Some(Container::Object { expecting_key, .. }) => *expecting_key = true,
+                    Some(Container::Array { index }) => *index += 1,
+                    None => {}
+                },
+                b':' => {}
+                c if c.is_ascii_whitespace() => {}
+                _ => self.scalar = Some(i),
+            }
+        }
+        fields
+    }
+
+    fn path(&self) -> Vec<Segment> {
+        self.stack
+            .iter()
+            .filter_map(|frame| match &frame.container {
+                Container::Object { key, .. } => key.clone().map(Segment::Key),
+                Container::Array { index } => Some(Segment::Index(*index)),
+            })
+            .collect()
+    }
+
+    fn complete(&mut self, start: usize, end: usize, fields: &mut Vec<JsonField>) {
+        let path = self.path();
+        if self.stack.is_empty() {
+            self.completed = true;
+        }
+        let value = match serde_json::from_str::<Value>(&self.text[start..end]) {
+            Ok(value) => value,
+            Err(e) => {
+                fields.push(JsonField {
+                    path: pointer(&path),
+                    value: Value::String(self.text[start..end].to_string()),
+                    valid: false,
+                    error: Some(format!("Not valid JSON: {}", e)),
+                });
+                return;
+            }
+        };
+        let error = schema_at(&self.schema, &path).and_then(|schema| check(&value, schema));
+        fields.push(JsonField { path: pointer(&path), value, valid: error.is_none(), error });
+    }
+}
+
+fn pointer(path: &[Segment]) -> String {
+    path.iter()
+        .map(|segment| match segment {
+            Segment::Key(key) => format!("/{}", key.replace('~', "~0").replace('/', "~1")),
+            Segment::Index(index) => format!("/{}", index),
+        })
+        .collect()
+}
+
+// The part of the schema that describes the value at `path`; None where the schema doesn't say
+fn schema_at<'a>(schema: &'a Value, path: &[Segment]) -> Option<&'a Value> {
+    path.iter().try_fold(schema, |schema, segment| match segment {
+        Segment::Key(key) => schema["properties"]
+            .get(key)
+            .or_else(|| schema.get("additionalProperties").filter(|extra| extra.is_object())),
+        Segment::Index(index) => schema["prefixItems"]
+            .get(*index)
+            .or_else(|| schema.get("items").filter(|items| items.is_object())),
+    })
+}
+
+fn type_matches(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+// Checks the keywords that can be judged from the value alone: type, enum, const, required and
+// additionalProperties. Children were already checked when they completed.
+fn check(value: &Value, schema: &Value) -> Option<String> {
+    match &schema["type"] {
+        Value::String(name) if !type_matches(value, name) => return Some(format!("expected {}", name)),
+        Value::Array(names) if !names.iter().filter_map(Value::as_str).any(|name| type_matches(value, name)) => {
+            return Some(format!("expected one of {}", Value::Array(names.clone())));
+        }
+        _ => {}
+    }
+    if let Some(options) = schema["enum"].as_array() {
+        if !options.contains(value) {
+            return Some(format!("must be one of {}", schema["enum"]));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if value != expected {
+            return Some(format!("must be {}", expected));
+        }
+    }
+    if let Some(object) = value.as_object() {
+        let missing: Vec<&str> = schema["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .filter(|key| !object.contains_key(*key))
+            .collect();
+        if !missing.is_empty() {
+            return Some(format!("missing {}", missing.join(", ")));
+        }
+        if schema["additionalProperties"] == Value::Bool(false) {
+            if let Some(extra) = object.keys().find(|key| schema["properties"].get(key.as_str()).is_none()) {
+                return Some(format!("unexpected property {}", extra));
+            }
+        }
+    }
+    None
+}
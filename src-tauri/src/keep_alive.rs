@@ -0,0 +1,224 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tauri::Emitter;
+
+use crate::config::{self, AppConfig, KeepAliveConfig};
+use crate::models::{self, LoadedModel};
+use crate::vision_cache;
+
+// How often the reaper checks for idle models
+const REAPER_INTERVAL: Duration = Duration::from_secs(30);
+
+pub const MODEL_UNLOADED_EVENT: &str = "model-unloaded";
+
+// Pin counts per model; pinned models are skipped by the idle reaper and the memory monitor
+static PINS: OnceLock<Mutex<HashMap<String, usize>>> = OnceLock::new();
+
+fn pins() -> &'static Mutex<HashMap<String, usize>> {
+    PINS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn is_pinned(model_id: &str) -> bool {
+    pins().lock().unwrap().contains_key(model_id)
+}
+
+fn add_pin(model_id: &str) -> usize {
+    let mut pins = pins().lock().unwrap();
+    let count = pins.entry(model_id.to_string()).or_insert(0);
+    *count += 1;
+    *count
+}
+
+fn remove_pin(model_id: &str) -> Option<usize> {
+    let mut pins = pins().lock().unwrap();
+    let count = pins.get_mut(model_id)?;
+    *count -= 1;
+    let remaining = *count;
+    if remaining == 0 {
+        pins.remove(model_id);
+    }
+    Some(remaining)
+}
+
+// Keeps a model loaded for as long as an in-process job holds the guard
+pub struct PinGuard(String);
+
+impl Drop for PinGuard {
+    fn drop(&mut self) {
+        remove_pin(&self.0);
+    }
+}
+
+pub fn pin(model_id: &str) -> PinGuard {
+    add_pin(model_id);
+    PinGuard(model_id.to_string())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelUnloadedEvent {
+    pub model_id: String,
+    // "idle", "evicted" (to make room for another model) or "manual"
+    pub reason: String,
+    pub idle_secs: u64,
+}
+
+fn emit_unloaded(app: &tauri::AppHandle, model_id: String, reason: &str, idle_secs: u64) {
+    let event = ModelUnloadedEvent {
+        model_id,
+        reason: reason.to_string(),
+        idle_secs,
+    };
+    if let Err(e) = app.emit(MODEL_UNLOADED_EVENT, &event) {
+        println!("Warning: Failed to emit model-unloaded event: {}", e);
+    }
+}
+
+// A strong count above one means a request is still holding the model
+fn is_busy(loaded: &LoadedModel) -> bool {
+    Arc::strong_count(&loaded.model) > 1
+}
+
+// Pinned models, and the default model when keep_default_loaded is set, are never unloaded
+fn is_kept(config: &AppConfig, model_id: &str) -> bool {
+    is_pinned(model_id) || (config.keep_alive.keep_default_loaded && config.models.default_model.as_deref() == Some(model_id))
+}
+
+// Effective idle timeout for a model, or None if it should never be unloaded
+fn timeout_for(config: &AppConfig, model_id: &str) -> Option<Duration> {
+    if is_kept(config, model_id) {
+        return None;
+    }
+
+    let minutes = config
+        .keep_alive
+        .per_model_minutes
+        .get(model_id)
+        .copied()
+        .unwrap_or(config.keep_alive.idle_minutes);
+
+    if minutes == 0 {
+        None
+    } else {
+        Some(Duration::from_secs(minutes * 60))
+    }
+}
+
+// Background task that unloads models which haven't served a request recently
+pub fn spawn_reaper(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(REAPER_INTERVAL).await;
+            reap_idle_models(&app).await;
+        }
+    });
+}
+
+async fn reap_idle_models(app: &tauri::AppHandle) {
+    let config = config::get();
+    let manager = models::manager(app);
+    let mut instances = manager.lock().await;
+
+    let idle: Vec<(String, u64)> = instances
+        .iter()
+        .filter_map(|(model_id, loaded)| {
+            let timeout = timeout_for(&config, model_id)?;
+            let idle_for = loaded.last_used.elapsed();
+            (idle_for >= timeout).then(|| (model_id.clone(), idle_for.as_secs()))
+        })
+        .collect();
+
+    for (model_id, idle_secs) in idle {
+        instances.remove(&model_id);
+        vision_cache::forget_model(&model_id);
+        println!("Unloaded idle model {} after {}s without requests", model_id, idle_secs);
+        emit_unloaded(app, model_id, "idle", idle_secs);
+    }
+}
+
+// Unloads least recently used models until one more of `incoming_bytes` fits the configured
+// limits. Busy, pinned and kept-default models are never evicted; if those alone exceed the limits
+// the new model loads anyway.
+pub(crate) fn make_room(instances: &mut HashMap<String, LoadedModel>, incoming_bytes: Option<u64>, app: &tauri::AppHandle) {
+    let config = config::get();
+    let limits = &config.keep_alive;
+    let over_limits = |instances: &HashMap<String, LoadedModel>| {
+        let loaded_bytes: u64 = instances.values().filter_map(|loaded| loaded.size_bytes).sum();
+        (limits.max_loaded_models > 0 && instances.len() >= limits.max_loaded_models)
+            || (limits.max_loaded_bytes > 0 && loaded_bytes + incoming_bytes.unwrap_or(0) > limits.max_loaded_bytes)
+    };
+
+    while over_limits(instances) {
+        let lru = instances
+            .iter()
+            .filter(|(model_id, loaded)| !is_busy(loaded) && !is_kept(&config, model_id))
+            .min_by_key(|(_, loaded)| loaded.last_used)
+            .map(|(model_id, loaded)| (model_id.clone(), loaded.last_used.elapsed().as_secs()));
+        let Some((model_id, idle_secs)) = lru else {
+            println!("Warning: Every loaded model is busy or kept loaded; loading past the configured limits");
+            return;
+        };
+        instances.remove(&model_id);
+        vision_cache::forget_model(&model_id);
+        println!("Unloaded {} (least recently used) to make room for another model", model_id);
+        emit_unloaded(app, model_id, "evicted", idle_secs);
+    }
+}
+
+// Model configured as default in config.toml
+pub fn default_model() -> Option<String> {
+    config::get().models.default_model
+}
+
+// Unloads a model now, releasing its memory; refused while a request is using it
+#[tauri::command]
+pub async fn unload_model(model_id: String, app: tauri::AppHandle) -> Result<(), String> {
+    let manager = models::manager(&app);
+    let mut instances = manager.lock().await;
+    let loaded = instances.get(&model_id).ok_or_else(|| format!("Model {} is not loaded", model_id))?;
+    if is_busy(loaded) {
+        return Err(format!("Model {} is still generating; cancel its requests first", model_id));
+    }
+    let idle_secs = loaded.last_used.elapsed().as_secs();
+    instances.remove(&model_id);
+    vision_cache::forget_model(&model_id);
+    println!("Unloaded model {} on request", model_id);
+    emit_unloaded(&app, model_id, "manual", idle_secs);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_keep_alive_settings() -> KeepAliveConfig {
+    config::get().keep_alive
+}
+
+// Overrides the idle timeout for one model; None restores the global default
+#[tauri::command]
+pub fn set_model_idle_timeout(model_id: String, idle_minutes: Option<u64>, app: tauri::AppHandle) -> Result<KeepAliveConfig, String> {
+    let config = config::update(&app, |config| match idle_minutes {
+        Some(minutes) => {
+            config.keep_alive.per_model_minutes.insert(model_id, minutes);
+        }
+        None => {
+            config.keep_alive.per_model_minutes.remove(&model_id);
+        }
+    })?;
+    Ok(config.keep_alive)
+}
+
+// Marks a model as non-evictable until a matching unpin_model; pins nest, returns the pin count
+#[tauri::command]
+pub fn pin_model(model_id: String) -> usize {
+    add_pin(&model_id)
+}
+
+#[tauri::command]
+pub fn unpin_model(model_id: String) -> Result<usize, String> {
+    remove_pin(&model_id).ok_or_else(|| format!("Model {} is not pinned", model_id))
+}
+
+#[tauri::command]
+pub fn list_pinned_models() -> HashMap<String, usize> {
+    pins().lock().unwrap().clone()
+}
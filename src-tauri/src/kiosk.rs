@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::config;
+
+// Timestamps of recently accepted chat requests, for the kiosk rate limit
+static RECENT_REQUESTS: OnceLock<Mutex<VecDeque<Instant>>> = OnceLock::new();
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+// Locked-down public demo setup; only editable in config.toml, never through commands
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KioskConfig {
+    pub enabled: bool,
+    // Every chat uses this model regardless of what the client asks for
+    pub model_id: Option<String>,
+    // Replaces the built-in assistant persona
+    pub system_prompt: Option<String>,
+    pub max_requests_per_minute: u32,
+    // Case-insensitive words that cause a message to be rejected
+    pub blocked_words: Vec<String>,
+    // Reject messages that look like they contain email addresses or phone numbers
+    pub reject_pii: bool,
+}
+
+impl Default for KioskConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model_id: None,
+            system_prompt: None,
+            max_requests_per_minute: 6,
+            blocked_words: Vec::new(),
+            reject_pii: true,
+        }
+    }
+}
+
+impl KioskConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.enabled && self.model_id.is_none() {
+            errors.push("kiosk.model_id is required when kiosk mode is enabled".to_string());
+        }
+        if self.enabled && self.max_requests_per_minute == 0 {
+            errors.push("kiosk.max_requests_per_minute must be greater than 0".to_string());
+        }
+        errors
+    }
+}
+
+pub fn is_active() -> bool {
+    config::get().kiosk.enabled
+}
+
+// Guard for settings, download and tool commands that a public terminal must not expose
+pub fn ensure_unlocked(action: &str) -> Result<(), String> {
+    if is_active() {
+        return Err(format!("{} is disabled in kiosk mode", action));
+    }
+    Ok(())
+}
+
+// The pinned model when kiosk mode is on, otherwise the one the client picked
+pub fn model_for(requested: String) -> String {
+    let kiosk = config::get().kiosk;
+    match kiosk.model_id {
+        Some(pinned) if kiosk.enabled => pinned,
+        _ => requested,
+    }
+}
+
+pub fn system_prompt() -> Option<String> {
+    let kiosk = config::get().kiosk;
+    kiosk.system_prompt.filter(|_| kiosk.enabled)
+}
+
+pub(crate) fn looks_like_email(word: &str) -> bool {
+    let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+    match word.split_once('@') {
+        Some((user, domain)) => !user.is_empty() && domain.contains('.') && !domain.starts_with('.'),
+        None => false,
+    }
+}
+
+// Seven or more digits in a run of digits, spaces, dashes, dots or parentheses
+fn contains_phone_number(text: &str) -> bool {
+    let mut digits = 0;
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            digits += 1;
+            if digits >= 7 {
+                return true;
+            }
+        } else if !matches!(c, ' ' | '-' | '.' | '(' | ')' | '+') {
+            digits = 0;
+        }
+    }
+    false
+}
+
+// Rate-limits and screens an incoming message before any model work happens
+pub fn check_request(message: &str) -> Result<(), String> {
+    let kiosk = config::get().kiosk;
+    if !kiosk.enabled {
+        return Ok(());
+    }
+
+    let lowered = message.to_lowercase();
+    let words: Vec<&str> = lowered
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|w| !w.is_empty())
+        .collect();
+    if kiosk.blocked_words.iter().any(|blocked| words.contains(&blocked.to_lowercase().as_str())) {
+        return Err("This message contains language that isn't allowed on this terminal".to_string());
+    }
+    if kiosk.reject_pii && (message.split_whitespace().any(looks_like_email) || contains_phone_number(message)) {
+        return Err("Please don't enter personal information such as email addresses or phone numbers".to_string());
+    }
+
+    let mut recent = RECENT_REQUESTS.get_or_init(|| Mutex::new(VecDeque::new())).lock().unwrap();
+    let now = Instant::now();
+    while recent.front().is_some_and(|t| now.duration_since(*t) > RATE_WINDOW) {
+        recent.pop_front();
+    }
+    if recent.len() >= kiosk.max_requests_per_minute as usize {
+        let wait = recent
+            .front()
+            .map(|t| RATE_WINDOW.saturating_sub(now.duration_since(*t)).as_secs() + 1)
+            .unwrap_or(1);
+        return Err(format!("Too many requests, please wait {} seconds", wait));
+    }
+    recent.push_back(now);
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+
+use crate::priority::Priority;
+use crate::streaming::StreamTracker;
+use crate::{config, generate_text, sampling};
+
+// (code, English name, common function words) for languages told apart by vocabulary
+const LATIN_LANGUAGES: [(&str, &str, &[&str]); 7] = [
+    ("en", "English", &["the", "and", "is", "are", "of", "to", "in", "that", "it", "with", "for", "this", "you", "not"]),
+    ("fr", "French", &["le", "la", "les", "et", "est", "des", "une", "un", "que", "pour", "dans", "vous", "pas", "avec"]),
+    ("es", "Spanish", &["el", "la", "los", "las", "y", "es", "que", "de", "en", "por", "para", "una", "con", "está"]),
+    ("de", "German", &["der", "die", "das", "und", "ist", "nicht", "ein", "eine", "mit", "sie", "ich", "zu", "auf", "für"]),
+    ("it", "Italian", &["il", "lo", "gli", "e", "è", "che", "di", "un", "una", "per", "non", "sono", "con", "della"]),
+    ("pt", "Portuguese", &["o", "os", "as", "e", "é", "que", "de", "um", "uma", "para", "não", "com", "você", "está"]),
+    ("nl", "Dutch", &["de", "het", "een", "en", "is", "van", "niet", "dat", "met", "voor", "zijn", "ik", "je", "op"]),
+];
+
+// (code, English name, Unicode ranges) for languages identified by their script
+const SCRIPT_LANGUAGES: [(&str, &str, &[(u32, u32)]); 9] = [
+    ("ja", "Japanese", &[(0x3040, 0x30FF)]),
+    ("ko", "Korean", &[(0xAC00, 0xD7AF), (0x1100, 0x11FF)]),
+    ("zh", "Chinese", &[(0x4E00, 0x9FFF), (0x3400, 0x4DBF)]),
+    ("ru", "Russian", &[(0x0400, 0x04FF)]),
+    ("ar", "Arabic", &[(0x0600, 0x06FF)]),
+    ("el", "Greek", &[(0x0370, 0x03FF)]),
+    ("he", "Hebrew", &[(0x0590, 0x05FF)]),
+    ("hi", "Hindi", &[(0x0900, 0x097F)]),
+    ("th", "Thai", &[(0x0E00, 0x0E7F)]),
+];
+
+// Fewer function-word hits than this is too little evidence to call a language
+const MIN_WORD_HITS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LanguageConfig {
+    // ISO 639-1 code every response must be written in, e.g. "fr"; None follows the user
+    pub enforce: Option<String>,
+    // How many times a response in the wrong language is re-asked before giving up
+    pub max_retries: u32,
+}
+
+impl Default for LanguageConfig {
+    fn default() -> Self {
+        Self {
+            enforce: None,
+            max_retries: 1,
+        }
+    }
+}
+
+impl LanguageConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if let Some(code) = &self.enforce {
+            if code.len() < 2 || !code.chars().all(|c| c.is_ascii_alphabetic() || c == '-') {
+                errors.push(format!("language.enforce: \"{}\" is not a language code such as \"fr\" or \"pt-BR\"", code));
+            }
+        }
+        errors
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DetectedLanguage {
+    pub code: String,
+    pub name: String,
+    // Share of the evidence that pointed to this language, 0.0 - 1.0
+    pub confidence: f64,
+}
+
+fn primary_subtag(code: &str) -> String {
+    code.split(['-', '_']).next().unwrap_or(code).to_ascii_lowercase()
+}
+
+fn language_name(code: &str) -> Option<&'static str> {
+    let code = primary_subtag(code);
+    LATIN_LANGUAGES
+        .iter()
+        .map(|(c, name, _)| (*c, *name))
+        .chain(SCRIPT_LANGUAGES.iter().map(|(c, name, _)| (*c, *name)))
+        .find(|(c, _)| *c == code)
+        .map(|(_, name)| name)
+}
+
+// Code blocks are the same in every language and would only dilute the signal
+fn prose(text: &str) -> String {
+    let mut in_code = false;
+    text.lines()
+        .filter(|line| {
+            if line.trim_start().starts_with("```") {
+                in_code = !in_code;
+                return false;
+            }
+            !in_code
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Local, dictionary-free detection: script ranges first, then function-word counts for Latin text
+pub fn detect(text: &str) -> Option<DetectedLanguage> {
+    let text = prose(text);
+    let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.is_empty() {
+        return None;
+    }
+
+    let in_ranges = |c: char, ranges: &[(u32, u32)]| ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&(c as u32)));
+    // Kana outranks Han so Japanese text with kanji isn't reported as Chinese
+    for (code, name, ranges) in SCRIPT_LANGUAGES {
+        let count = letters.iter().filter(|c| in_ranges(**c, ranges)).count();
+        let share = count as f64 / letters.len() as f64;
+        if share > 0.1 && (code != "zh" || share > 0.3) {
+            return Some(DetectedLanguage {
+                code: code.to_string(),
+                name: name.to_string(),
+                confidence: share.min(1.0),
+            });
+        }
+    }
+
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect();
+    let scores: Vec<(usize, &str, &str)> = LATIN_LANGUAGES
+        .iter()
+        .map(|(code, name, stopwords)| (words.iter().filter(|w| stopwords.contains(&w.as_str())).count(), *code, *name))
+        .collect();
+    let total: usize = scores.iter().map(|(hits, _, _)| hits).sum();
+    let (hits, code, name) = scores.into_iter().max_by_key(|(hits, _, _)| *hits)?;
+    if hits < MIN_WORD_HITS {
+        return None;
+    }
+
+    Some(DetectedLanguage {
+        code: code.to_string(),
+        name: name.to_string(),
+        confidence: hits as f64 / total as f64,
+    })
+}
+
+// Extra system instruction when a response language is enforced
+pub fn instruction() -> Option<String> {
+    let code = config::get().language.enforce?;
+    let name = language_name(&code).map(str::to_string).unwrap_or(code);
+    Some(format!(
+        "Always write your reply in {}, even if the user writes in another language or asks otherwise.",
+        name
+    ))
+}
+
+// Enforced language name when `text` was detected as something else; None means it's fine
+pub fn mismatch(text: &str) -> Option<String> {
+    let code = config::get().language.enforce?;
+    // Without a detector for the target language a mismatch can't be told apart from success
+    let name = language_name(&code)?;
+    let detected = detect(text)?;
+    (detected.code != primary_subtag(&code)).then(|| name.to_string())
+}
+
+// Re-asks the model until the finished response is in the enforced language or retries run out
+pub async fn enforce(tracker: &mut StreamTracker, model_id: &str, sampling: &sampling::SamplingConfig, app: &tauri::AppHandle) {
+    let max_retries = config::get().language.max_retries;
+    for _ in 0..max_retries {
+        let Some(name) = mismatch(tracker.content()) else {
+            return;
+        };
+        println!("Response language mismatch; asking for a {} version", name);
+        let prompt = format!(
+            "Rewrite the following answer in {}. Keep the meaning, formatting and code unchanged and \
+             reply with the rewritten answer only.\n\n{}",
+            name,
+            tracker.content()
+        );
+        match generate_text(model_id, &prompt, sampling, Priority::Interactive, app).await {
+            Ok(rewritten) => tracker.replace_content(rewritten.trim().to_string()),
+            Err(e) => {
+                println!("Warning: Failed to re-ask for the enforced language: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub fn detect_language(text: String) -> Option<DetectedLanguage> {
+    detect(&text)
+}
+
+#[tauri::command]
+pub fn set_response_language(code: Option<String>, app: tauri::AppHandle) -> Result<LanguageConfig, String> {
+    let config = config::update(&app, |config| {
+        config.language.enforce = code.filter(|c| !c.trim().is_empty());
+    })?;
+    Ok(config.language)
+}
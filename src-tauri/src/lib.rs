@@ -1,37 +1,230 @@
 use mistralrs::{
     TextMessageRole, TextMessages, VisionMessages, GgufModelBuilder, VisionModelBuilder, TextModelBuilder, UqffVisionModelBuilder, UqffTextModelBuilder, IsqType,
+    RequestBuilder, Response, PagedAttentionMetaBuilder, MemoryGpuConfig, TokenSource, Constraint,
 };
 use std::sync::Arc;
 use tauri::{path::BaseDirectory, Manager};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::path::Path;
 use std::fs;
-use std::sync::OnceLock;
+use std::time::Instant;
 use anyhow::Result as AnyhowResult;
+use tracing::Instrument;
 
-// Global model instances to avoid reloading models on each request
-static MODEL_INSTANCES: OnceLock<Arc<tokio::sync::Mutex<HashMap<String, Arc<mistralrs::Model>>>>> = OnceLock::new();
+mod accessibility;
+mod analytics;
+mod assistants;
+mod audio;
+mod audit;
+mod backup;
+pub mod bench;
+mod capabilities;
+mod catalog;
+mod chaos;
+mod checksums;
+mod code_blocks;
+mod compat;
+mod config;
+mod context_packs;
+mod context_window;
+mod dates;
+mod db_writer;
+mod digest;
+mod documents;
+mod email;
+mod embeddings;
+mod estimate;
+mod explain;
+mod folder_chat;
+mod forms;
+mod gallery;
+mod glossary;
+mod grammar;
+mod hf_cache;
+mod hardware;
+mod history;
+mod image_input;
+mod import;
+mod isq;
+mod json_stream;
+mod keep_alive;
+mod kiosk;
+mod language;
+mod memory;
+mod metrics;
+mod migrations;
+mod middleware;
+mod mock;
+mod model_card;
+mod models;
+mod pinned_files;
+mod presentation;
+mod priority;
+mod profiles;
+mod prompt_format;
+mod provenance;
+mod queue;
+mod recap;
+mod record_export;
+mod replay;
+mod response_style;
+mod safe_mode;
+mod sampling;
+mod sanitize;
+mod server;
+mod shutdown;
+mod slash_commands;
+mod streaming;
+mod system_prompt;
+mod telemetry;
+mod thinking;
+mod tools;
+mod trace;
+mod transcript;
+mod vision_cache;
+mod watchdog;
+mod web;
+mod windows;
 
-// Comprehensive error handling for mistral.rs model operations
+use streaming::StreamTracker;
+use image_input::ImageInput;
+
+use models::LoadedModel;
+
+// Error of the chat and model commands. It reaches the frontend as { code, message, hint, request_id },
+// so the UI can tell "model not found" from "out of memory" and say what to do about it.
 #[derive(Debug, thiserror::Error)]
 pub enum ModelError {
-    #[error("Model loading failed: {0}")]
-    LoadingError(#[from] anyhow::Error),
     #[error("Model not found: {0}")]
     NotFound(String),
+    // The bundled mistral.rs can't run the model; see compat.rs
+    #[error("{0}")]
+    Incompatible(String),
+    #[error("Model loading failed: {0}")]
+    LoadingError(String),
+    #[error("Out of memory: {0}")]
+    OutOfMemory(String),
     #[error("Invalid configuration: {0}")]
     Configuration(String),
+    #[error("{0}")]
+    InvalidRequest(String),
     #[error("Vision model requires image input")]
     MissingImage,
     #[error("Image processing failed: {0}")]
-    ImageError(#[from] image::ImageError),
-    #[error("Base64 decode error: {0}")]
-    Base64Error(#[from] base64::DecodeError),
-    #[error("IO error: {0}")]
-    IoError(#[from] std::io::Error),
-    #[error("JSON parsing error: {0}")]
-    JsonError(#[from] serde_json::Error),
+    ImageError(String),
+    #[error("Audio processing failed: {0}")]
+    AudioError(String),
+    // A message, image or prompt over one of the [limits]
+    #[error("{0}")]
+    LimitExceeded(String),
+    #[error("Generation failed: {0}")]
+    Generation(String),
+    // Errors of the helpers in other modules, which are plain strings
+    #[error("{0}")]
+    Other(String),
+    #[error("{source} (request {request_id})")]
+    Request { request_id: String, source: Box<ModelError> },
+}
+
+// Allocation failures as worded by candle, CUDA and Metal
+fn is_out_of_memory(detail: &str) -> bool {
+    let detail = detail.to_lowercase();
+    ["out of memory", "out_of_memory", "outofmemory", "failed to allocate", "cannot allocate memory"]
+        .iter()
+        .any(|pattern| detail.contains(pattern))
+}
+
+impl ModelError {
+    // A failed model build, told apart by whether it ran out of memory
+    fn load_failed(detail: String) -> Self {
+        if is_out_of_memory(&detail) {
+            ModelError::OutOfMemory(detail)
+        } else {
+            ModelError::LoadingError(detail)
+        }
+    }
+
+    fn generation_failed(detail: String) -> Self {
+        if is_out_of_memory(&detail) {
+            ModelError::OutOfMemory(detail)
+        } else {
+            ModelError::Generation(detail)
+        }
+    }
+
+    // Tags the error with the request it ended, as get_request_trace takes
+    fn for_request(self, request_id: &str) -> Self {
+        ModelError::Request { request_id: request_id.to_string(), source: Box::new(self) }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ModelError::NotFound(_) => "model_not_found",
+            ModelError::Incompatible(_) => "model_incompatible",
+            ModelError::LoadingError(_) => "model_load_failed",
+            ModelError::OutOfMemory(_) => "out_of_memory",
+            ModelError::Configuration(_) => "invalid_configuration",
+            ModelError::InvalidRequest(_) => "invalid_request",
+            ModelError::MissingImage => "missing_image",
+            ModelError::ImageError(_) => "invalid_image",
+            ModelError::AudioError(_) => "invalid_audio",
+            ModelError::LimitExceeded(_) => "limit_exceeded",
+            ModelError::Generation(_) => "generation_failed",
+            ModelError::Other(_) => "error",
+            ModelError::Request { source, .. } => source.code(),
+        }
+    }
+
+    // What the user can do about it
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            ModelError::NotFound(_) => Some("Pick a model from discover_models, or download it with `cargo run --example download_models`."),
+            ModelError::Incompatible(_) => Some("This build of mistral.rs can't run the model; get_compatibility_table lists what it supports."),
+            ModelError::LoadingError(_) => Some("The model files may be incomplete; verify_model_checksums checks them, and the log has the loader's details."),
+            ModelError::OutOfMemory(_) => Some("Unload other models, pick a smaller quantization, or lower max_gpu_memory_fraction under [limits.per_model]."),
+            ModelError::Configuration(_) => Some("Fix the setting in the config file; the app reloads it when it is saved."),
+            ModelError::InvalidRequest(_) => Some("Check the request's arguments."),
+            ModelError::MissingImage => Some("Attach an image, or pick a text model."),
+            ModelError::ImageError(_) => Some("Attach a PNG, JPEG or WebP image."),
+            ModelError::AudioError(_) => Some("Attach a WAV or MP3 recording, and pick a model that takes audio."),
+            ModelError::LimitExceeded(_) => Some("Shorten the message, image or recording, or raise the limit under [limits]."),
+            ModelError::Generation(_) => Some("Try again; if it keeps failing, unload and reload the model."),
+            ModelError::Other(_) => None,
+            ModelError::Request { source, .. } => source.hint(),
+        }
+    }
+
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            ModelError::Request { request_id, .. } => Some(request_id),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for ModelError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut error = serializer.serialize_struct("ModelError", 4)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.serialize_field("hint", &self.hint())?;
+        error.serialize_field("request_id", &self.request_id())?;
+        error.end()
+    }
+}
+
+impl From<String> for ModelError {
+    fn from(error: String) -> Self {
+        ModelError::Other(error)
+    }
+}
+
+// Callers elsewhere in the crate keep working with plain strings
+impl From<ModelError> for String {
+    fn from(error: ModelError) -> Self {
+        error.to_string()
+    }
 }
 
 type ModelResult<T> = Result<T, ModelError>;
@@ -44,10 +237,31 @@ pub struct ModelInfo {
     pub description: String,
     pub model_type: String, // "local-gguf", "local-matformer", "remote-gguf", "remote-vision"
     pub size_estimate: Option<String>,
+    // Bytes on disk, or the download size for models that aren't downloaded yet
+    pub size_bytes: Option<u64>,
     pub is_available: bool,
     pub repo: Option<String>,
     pub files: Vec<String>,
     pub is_vision: bool, // Whether this model supports vision/image inputs
+    pub is_audio: bool, // Whether this model also takes audio, sent as audio_data
+    // Detected from config.json or the GGUF header; see capabilities.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub architecture: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_length: Option<u64>,
+    pub license: Option<String>,
+    pub gated: bool, // Whether downloading requires accepting terms on Hugging Face
+    // Why is_available is false, e.g. not downloaded, offline mode or a missing token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unavailable_reason: Option<String>,
+    // The bundled mistral.rs can't run this model at all; see compat.rs
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub incompatible: bool,
+    // UQFF quantizations in the model's folder, and the one it loads; see set_model_quantization
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quantizations: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantization: Option<String>,
 }
 
 #[tauri::command]
@@ -57,43 +271,17 @@ fn greet(name: &str) -> String {
 
 // Core demo function: discovers available local AI models in multiple formats
 #[tauri::command]
-async fn discover_models(app: tauri::AppHandle) -> Result<Vec<ModelInfo>, String> {
+async fn discover_models(app: tauri::AppHandle) -> ModelResult<Vec<ModelInfo>> {
     println!("Discovering available models...");
     let mut models = Vec::new();
     
-    // Search multiple possible locations for models directory
-    let possible_paths = [
-        "models",                    // When running from src-tauri directory (most common)
-        "../models",                // When running from target directory  
-        "src-tauri/models",         // When running from project root
-    ];
-    
-    let mut models_base_path = None;
-    for path in &possible_paths {
-        if Path::new(path).exists() {
-            models_base_path = Some(*path);
-            println!("Found models directory at: {}", path);
-            break;
-        }
-    }
-    
-    // Try to resolve using Tauri's path API as well
-    if models_base_path.is_none() {
-        if let Ok(app_dir) = app.path().app_data_dir() {
-            let models_dir = app_dir.join("models");
-            if models_dir.exists() {
-                if let Some(path_str) = models_dir.to_str() {
-                    let path_string = path_str.to_string();
-                    println!("Found models directory at app data: {}", path_string);
-                    // Note: We can't use this path easily since it would need to be static
-                    // For now, we'll stick with the relative paths approach
-                }
-            }
-        }
+    let models_base_path = find_models_dir(&app);
+    if let Some(path) = &models_base_path {
+        println!("Found models directory at: {}", path);
     }
     
     if let Some(base_path) = models_base_path {
-        match discover_local_models(base_path) {
+        match discover_local_models(&base_path) {
             Ok(local_models) => {
                 for (model_dir, model_file, model_type) in local_models {
                     let model_id = if model_dir.is_empty() {
@@ -101,6 +289,28 @@ async fn discover_models(app: tauri::AppHandle) -> Result<Vec<ModelInfo>, String
                     } else {
                         format!("local-{}", model_dir)
                     };
+                    let entry = catalog::lookup(&model_dir);
+                    let size_bytes = local_model_size(&base_path, &model_dir, &model_file, &model_type);
+                    let incompatible = compat::incompatibility(&base_path, &model_dir, &model_file, &model_type);
+                    if let Some(reason) = &incompatible {
+                        println!("Warning: {} is incompatible: {}", model_id, reason);
+                    }
+                    let corrupt = if config::get().models.verify_checksums && !model_dir.is_empty() {
+                        checksums::failures(&Path::new(&base_path).join(&model_dir)).1.into_iter().next()
+                    } else {
+                        None
+                    };
+                    if let Some(reason) = &corrupt {
+                        println!("Warning: {} failed verification: {}", model_id, reason);
+                    }
+                    let detected = capabilities::of_local(&base_path, &model_dir, &model_file, &model_type);
+                    // MatFormer folders hold .uqff files too, but load with in-situ quantization
+                    let uqff_files = if matches!(model_type.as_str(), "smollm3" | "llama-uqff" | "llama-uqff-vision") {
+                        get_uqff_files(&Path::new(&base_path).join(&model_dir).to_string_lossy()).unwrap_or_default()
+                    } else {
+                        Vec::new()
+                    };
+                    let quantization = uqff_choice(&model_id, &uqff_files);
                     
                     // Generate user-friendly names and descriptions for different model types
                     let (name, description, is_vision) = if model_type == "matformer-vision" {
@@ -152,11 +362,21 @@ async fn discover_models(app: tauri::AppHandle) -> Result<Vec<ModelInfo>, String
                         name,
                         description,
                         model_type: format!("local-{}", model_type),
-                        size_estimate: None,
-                        is_available: true,
-                        repo: None,
+                        size_estimate: size_bytes.map(catalog::format_size),
+                        size_bytes,
+                        is_available: incompatible.is_none() && corrupt.is_none(),
+                        repo: entry.map(|e| e.repo.to_string()),
                         files: vec![model_file.clone()],
                         is_vision,
+                        is_audio: detected.audio,
+                        architecture: detected.architecture,
+                        context_length: detected.context_length,
+                        license: entry.map(|e| e.license.to_string()),
+                        gated: entry.is_some_and(|e| e.gated),
+                        incompatible: incompatible.is_some(),
+                        unavailable_reason: incompatible.or(corrupt),
+                        quantization,
+                        quantizations: uqff_quantizations(&uqff_files),
                     });
                 }
             }
@@ -165,14 +385,182 @@ async fn discover_models(app: tauri::AppHandle) -> Result<Vec<ModelInfo>, String
             }
         }
     } else {
-        println!("No models directory found. Checked paths: {:?}", possible_paths);
+        println!("No models directory found. Checked paths: {:?}", config::get().models.dirs);
         println!("Current working directory: {:?}", std::env::current_dir());
     }
     
+    // Catalog models that aren't downloaded yet are listed with their download size
+    for entry in catalog::CATALOG {
+        let id = format!("local-{}", entry.directory);
+        if models.iter().any(|m| m.id == id) {
+            continue;
+        }
+        models.push(ModelInfo {
+            id,
+            name: entry.directory.to_string(),
+            description: format!("Download with: cargo run --example download_models download {}", entry.directory),
+            model_type: "not-downloaded".to_string(),
+            size_estimate: Some(catalog::format_size(entry.download_bytes)),
+            size_bytes: Some(entry.download_bytes),
+            is_available: false,
+            repo: Some(entry.repo.to_string()),
+            files: Vec::new(),
+            is_vision: false,
+            is_audio: false,
+            architecture: None,
+            context_length: None,
+            license: Some(entry.license.to_string()),
+            gated: entry.gated,
+            unavailable_reason: Some("Not downloaded yet".to_string()),
+            incompatible: false,
+            quantizations: Vec::new(),
+            quantization: None,
+        });
+    }
+    
+    if config::get().models.show_remote {
+        for remote in catalog::REMOTE_MODELS {
+            let unavailable_reason = catalog::remote_unavailable_reason(remote, &app);
+            models.push(ModelInfo {
+                id: remote.id.to_string(),
+                name: remote.name.to_string(),
+                description: remote.description.to_string(),
+                model_type: remote.model_type.to_string(),
+                size_estimate: None,
+                size_bytes: None,
+                is_available: unavailable_reason.is_none(),
+                repo: Some(remote.repo.to_string()),
+                files: Vec::new(),
+                is_vision: false,
+                is_audio: false,
+                architecture: None,
+                context_length: None,
+                license: Some(remote.license.to_string()),
+                gated: false,
+                unavailable_reason,
+                incompatible: false,
+                quantizations: Vec::new(),
+                quantization: None,
+            });
+        }
+    }
+    
+    if mock::is_enabled() {
+        models.push(mock::model_info());
+    }
+    
+    // Kiosk terminals only ever offer the pinned model
+    if let Some(pinned) = config::get().kiosk.model_id.filter(|_| kiosk::is_active()) {
+        models.retain(|m| m.id == pinned);
+    }
+    
     println!("Found {} models", models.len());
     Ok(models)
 }
 
+// GGUF models are a single file; UQFF models are everything in their directory
+fn local_model_size(base_path: &str, model_dir: &str, model_file: &str, model_type: &str) -> Option<u64> {
+    let dir = Path::new(base_path).join(model_dir);
+    if model_type.starts_with("gguf") {
+        return fs::metadata(dir.join(model_file)).ok().map(|meta| meta.len());
+    }
+    let total = fs::read_dir(&dir)
+        .ok()?
+        .flatten()
+        // Skips bookkeeping such as the downloader's manifest
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|meta| meta.is_file())
+        .map(|meta| meta.len())
+        .sum();
+    Some(total)
+}
+
+// On-disk size of a local model by id; None for remote models
+fn model_size(model_id: &str, app: &tauri::AppHandle) -> Option<u64> {
+    model_id.strip_prefix("local-")?;
+    let base_path = find_models_dir(app)?;
+    discover_local_models(&base_path).ok()?.into_iter().find_map(|(model_dir, model_file, model_type)| {
+        let id = if model_dir.is_empty() {
+            format!("local-{}", model_file.replace(".gguf", "").replace(".uqff", ""))
+        } else {
+            format!("local-{}", model_dir)
+        };
+        (id == model_id).then(|| local_model_size(&base_path, &model_dir, &model_file, &model_type)).flatten()
+    })
+}
+
+// Resolves the first existing models directory from the configured search paths,
+// falling back to the app data directory
+fn find_models_dir(app: &tauri::AppHandle) -> Option<String> {
+    let mut candidates = config::get().models.dirs;
+    
+    if let Ok(app_dir) = app.path().app_data_dir() {
+        candidates.push(app_dir.join("models").to_string_lossy().to_string());
+    }
+    
+    candidates.into_iter().find(|path| Path::new(path).exists())
+}
+
+// Whether a local model takes images, from the modality detected when it was discovered
+pub(crate) fn is_vision_model(model_id: &str, app: &tauri::AppHandle) -> bool {
+    let Some(base_path) = find_models_dir(app) else {
+        return false;
+    };
+    discover_local_models(&base_path).ok().into_iter().flatten().any(|(model_dir, model_file, model_type)| {
+        let id = if model_dir.is_empty() {
+            format!("local-{}", model_file.replace(".gguf", "").replace(".uqff", ""))
+        } else {
+            format!("local-{}", model_dir)
+        };
+        id == model_id && model_type.ends_with("vision")
+    })
+}
+
+// Whether a local model takes audio, from the audio_config in its config.json
+pub(crate) fn supports_audio(model_id: &str, app: &tauri::AppHandle) -> bool {
+    let Some(base_path) = find_models_dir(app) else {
+        return false;
+    };
+    discover_local_models(&base_path).ok().into_iter().flatten().any(|(model_dir, model_file, model_type)| {
+        let id = if model_dir.is_empty() {
+            format!("local-{}", model_file.replace(".gguf", "").replace(".uqff", ""))
+        } else {
+            format!("local-{}", model_dir)
+        };
+        id == model_id && capabilities::of_local(&base_path, &model_dir, &model_file, &model_type).audio
+    })
+}
+
+// SmolLM3's hybrid reasoning, switched on and off by its chat template's enable_thinking flag
+pub(crate) fn supports_thinking(model_id: &str, app: &tauri::AppHandle) -> bool {
+    if model_id == "smollm3-remote" {
+        return true;
+    }
+    let Some(base_path) = find_models_dir(app) else {
+        return false;
+    };
+    discover_local_models(&base_path).ok().into_iter().flatten().any(|(model_dir, model_file, model_type)| {
+        let id = if model_dir.is_empty() {
+            format!("local-{}", model_file.replace(".gguf", "").replace(".uqff", ""))
+        } else {
+            format!("local-{}", model_dir)
+        };
+        id == model_id
+            && (model_type == "smollm3"
+                || capabilities::of_local(&base_path, &model_dir, &model_file, &model_type)
+                    .architecture
+                    .is_some_and(|architecture| architecture.to_lowercase().starts_with("smollm3")))
+    })
+}
+
+// Directory of a local model like "local-smollm3-3b"; None for remote or single-file models
+pub(crate) fn local_model_dir(model_id: &str, app: &tauri::AppHandle) -> Option<std::path::PathBuf> {
+    let name = model_id.strip_prefix("local-")?;
+    let dir = Path::new(&find_models_dir(app)?).join(name);
+    dir.is_dir().then_some(dir)
+}
+
 // Helper function to find UQFF files in model directories
 fn get_uqff_files(model_path: &str) -> Result<Vec<std::path::PathBuf>, String> {
     let mut uqff_files = Vec::new();
@@ -226,7 +614,6 @@ fn discover_local_models(base_path: &str) -> Result<Vec<(String, String, String)
             // Check for MatFormer models (require config.json and .uqff files)
             let config_path = path.join("config.json");
             let mut has_uqff = false;
-            let mut uqff_files = Vec::new();
             
             if let Ok(subdir_entries) = fs::read_dir(&path) {
                 for subentry in subdir_entries {
@@ -238,18 +625,11 @@ fn discover_local_models(base_path: &str) -> Result<Vec<(String, String, String)
                                 if extension == "gguf" {
                                     let file_name = subpath.file_name().unwrap().to_string_lossy().to_string();
                                     
-                                    // Detect vision models by directory or filename patterns
-                                    let is_vision_gguf = subdir_name.to_lowercase().contains("vision") || 
-                                                        subdir_name.to_lowercase().contains("llama") ||
-                                                        file_name.to_lowercase().contains("vision") ||
-                                                        file_name.to_lowercase().contains("llama");
-                                    
+                                    let is_vision_gguf = capabilities::from_gguf(&subpath).vision;
                                     let model_type = if is_vision_gguf { "gguf-vision" } else { "gguf" };
                                     models.push((subdir_name.clone(), file_name, model_type.to_string()));
                                 } else if extension == "uqff" {
                                     has_uqff = true;
-                                    let file_name = subpath.file_name().unwrap().to_string_lossy().to_string();
-                                    uqff_files.push(file_name);
                                 }
                             }
                         }
@@ -259,23 +639,21 @@ fn discover_local_models(base_path: &str) -> Result<Vec<(String, String, String)
             
             // Process UQFF-based models (SmolLM3, Llama, MatFormer)
             if has_uqff {
-                if subdir_name.to_lowercase().contains("smollm") {
+                // The builder follows the architecture in config.json; the folder name is only a
+                // fallback for models downloaded without one
+                let detected = capabilities::from_config(&path);
+                let family = detected.architecture.as_deref().unwrap_or(&subdir_name).to_lowercase();
+                if family.contains("smollm") {
                     // SmolLM3 models use TextModelBuilder and don't need config.json
                     models.push((subdir_name.clone(), "smollm3".to_string(), "smollm3".to_string()));
-                } else if subdir_name.to_lowercase().contains("llama") {
-                    // Llama UQFF models (including vision models) - don't require config.json
-                    let is_vision_model = subdir_name.to_lowercase().contains("vision") ||
-                                        uqff_files.iter().any(|f| f.to_lowercase().contains("vision"));
-                    
-                    let model_type = if is_vision_model { "llama-uqff-vision" } else { "llama-uqff" };
+                } else if family.contains("llama") {
+                    // Llama UQFF models (including vision models); MllamaForConditionalGeneration
+                    // is Llama 3.2 Vision
+                    let model_type = if detected.vision { "llama-uqff-vision" } else { "llama-uqff" };
                     models.push((subdir_name.clone(), "llama-uqff".to_string(), model_type.to_string()));
                 } else if config_path.exists() {
                     // MatFormer models that need config.json
-                    let is_vision_model = subdir_name.to_lowercase().contains("vision") || 
-                                        subdir_name.to_lowercase().contains("gemma-3n") ||
-                                        subdir_name.to_lowercase().contains("llama");
-                    
-                    let model_type = if is_vision_model { "matformer-vision" } else { "matformer" };
+                    let model_type = if detected.vision { "matformer-vision" } else { "matformer" };
                     
                     models.push((subdir_name.clone(), "matformer".to_string(), model_type.to_string()));
                 }
@@ -286,10 +664,7 @@ fn discover_local_models(base_path: &str) -> Result<Vec<(String, String, String)
                 if extension == "gguf" {
                     let file_name = path.file_name().unwrap().to_string_lossy().to_string();
                     
-                    // Detect vision models by filename patterns
-                    let is_vision_gguf = file_name.to_lowercase().contains("vision") ||
-                                        file_name.to_lowercase().contains("llama");
-                    
+                    let is_vision_gguf = capabilities::from_gguf(&path).vision;
                     let model_type = if is_vision_gguf { "gguf-vision" } else { "gguf" };
                     models.push(("".to_string(), file_name, model_type.to_string()));
                 }
@@ -300,114 +675,782 @@ fn discover_local_models(base_path: &str) -> Result<Vec<(String, String, String)
     Ok(models)
 }
 
-// Main chat interface - handles both text and vision models
+// Arguments of a chat request, shared by the Tauri IPC and the remote WebSocket channel
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ChatArgs {
+    pub message: String,
+    pub model_id: String,
+    pub image_data: Option<String>,
+    // An image file inside image_input.allowed_dirs, read in Rust instead of sent as base64
+    #[serde(default)]
+    pub image_path: Option<String>,
+    // Sends the image on the system clipboard
+    #[serde(default)]
+    pub image_from_clipboard: bool,
+    // Stored images sent again, by attachment id or "#n" number in the conversation; see gallery.rs
+    #[serde(default)]
+    pub images: Vec<String>,
+    // Base64 WAV or MP3 bytes, for models that take audio (is_audio in discover_models)
+    #[serde(default)]
+    pub audio_data: Option<String>,
+    pub request_id: Option<String>,
+    pub conversation_id: Option<String>,
+    pub profile: Option<String>,
+    // Background callers (batch runs, indexing) yield to chat the user is waiting on
+    #[serde(default)]
+    pub priority: Option<priority::Priority>,
+    // Registered grammar (id or name) that constrains the answer, instead of the assistant's
+    #[serde(default)]
+    pub grammar: Option<String>,
+    // Sampling parameters for this request only, on top of the generation profile
+    #[serde(default)]
+    pub sampling: Option<sampling::SamplingConfig>,
+    // Constrains the answer to JSON and streams its completed values as "field" events
+    #[serde(default)]
+    pub response_format: Option<json_stream::ResponseFormat>,
+    // Lets models with a thinking mode (SmolLM3) reason before answering; off by default
+    #[serde(default)]
+    pub enable_thinking: Option<bool>,
+    // Output style for this message, instead of the assistant's
+    #[serde(default)]
+    pub response_style: Option<response_style::ResponseStyle>,
+}
+
+// Main chat interface - handles both text and vision models, streaming tokens as chat-stream events
 #[tauri::command]
-async fn ai_chat(message: String, model_id: String, image_data: Option<String>, app: tauri::AppHandle) -> Result<String, String> {
-    println!("AI Chat called with message: {} using model: {}", message, model_id);
-    
-    dotenvy::dotenv().ok();
-    
-    // Initialize the model instances map if not already done
-    let model_instances = MODEL_INSTANCES
-        .get_or_init(|| Arc::new(tokio::sync::Mutex::new(HashMap::new())))
-        .clone();
+#[allow(clippy::too_many_arguments)]
+async fn ai_chat(
+    message: String,
+    model_id: String,
+    image_data: Option<String>,
+    image_path: Option<String>,
+    image_from_clipboard: Option<bool>,
+    images: Option<Vec<String>>,
+    audio_data: Option<String>,
+    request_id: Option<String>,
+    conversation_id: Option<String>,
+    profile: Option<String>,
+    priority: Option<priority::Priority>,
+    grammar: Option<String>,
+    sampling: Option<sampling::SamplingConfig>,
+    response_format: Option<json_stream::ResponseFormat>,
+    enable_thinking: Option<bool>,
+    response_style: Option<response_style::ResponseStyle>,
+    app: tauri::AppHandle,
+    window: tauri::Window,
+) -> ModelResult<String> {
+    let args = ChatArgs {
+        message,
+        model_id,
+        image_data,
+        image_path,
+        image_from_clipboard: image_from_clipboard.unwrap_or(false),
+        images: images.unwrap_or_default(),
+        audio_data,
+        request_id,
+        conversation_id,
+        profile,
+        priority,
+        grammar,
+        sampling,
+        response_format,
+        enable_thinking,
+        response_style,
+    };
+    chat(args, app, Some(window.label().to_string())).await
+}
+
+// Runs a chat request, sending its stream events to `target` (or every listener when None)
+pub(crate) async fn chat(mut args: ChatArgs, app: tauri::AppHandle, target: Option<String>) -> ModelResult<String> {
+    let request_id = args.request_id.get_or_insert_with(streaming::new_request_id).clone();
+    trace::begin(&request_id, "chat");
+    run_chat(args, app, target).await.map_err(|e| {
+        trace::fail(&request_id, &e.to_string());
+        e.for_request(&request_id)
+    })
+}
+
+async fn run_chat(args: ChatArgs, app: tauri::AppHandle, target: Option<String>) -> ModelResult<String> {
+    let ChatArgs {
+        message,
+        model_id,
+        image_data,
+        image_path,
+        image_from_clipboard,
+        images,
+        audio_data,
+        request_id,
+        conversation_id,
+        profile,
+        priority,
+        grammar,
+        sampling: overrides,
+        response_format,
+        enable_thinking,
+        response_style,
+    } = args;
+    let request_id = request_id.unwrap_or_else(streaming::new_request_id);
+    let model_id = kiosk::model_for(model_id);
+    kiosk::check_request(&message)?;
+    trace::log(&request_id, format!("AI Chat called with message: {} using model: {}", message, model_id));
     
-    let mut instances = model_instances.lock().await;
+    // Resolve "/command args text" into its stored prompt template
+    let expanded = slash_commands::expand(&message)?;
+    if let Some(command) = &expanded.command {
+        trace::log(&request_id, format!("Expanded slash command: /{}", command));
+        analytics::record(analytics::UsageEvent::SlashCommandUsed { command });
+    }
+    let message = context_packs::augment(conversation_id.as_deref(), &expanded.prompt)?;
+    let message = pinned_files::augment(conversation_id.as_deref(), &message, &request_id, &app)?;
+    let message = documents::augment(conversation_id.as_deref(), &message).await?;
+    let message = tools::augment(&message);
     
-    // Use cached model if available, otherwise load new model
-    let model = if let Some(existing_model) = instances.get(&model_id) {
-        println!("Using cached model: {}", model_id);
-        existing_model.clone()
+    let (profile_name, mut sampling) = sampling::resolve_profile(profile.as_deref(), conversation_id.as_deref())?;
+    if let Some(overrides) = &overrides {
+        // Checked together with the profile, since some fields only make sense in combination
+        sampling = sampling.overridden_by(overrides);
+        let errors = sampling.validate("sampling");
+        if !errors.is_empty() {
+            return Err(ModelError::InvalidRequest(errors.join("; ")));
+        }
+    }
+    trace::log(&request_id, format!("Using generation profile: {}", profile_name));
+    if response_format.is_some() && grammar.is_some() {
+        return Err(ModelError::InvalidRequest("A request can use a grammar or a response_format, not both".to_string()));
+    }
+    let constraint = match &response_format {
+        Some(format) => {
+            format.validate().map_err(ModelError::InvalidRequest)?;
+            Some(Constraint::JsonSchema(format.schema()))
+        }
+        // The request's grammar, else the one of the conversation's assistant
+        None => grammar::source_for(grammar.as_deref(), conversation_id.as_deref())?.map(Constraint::Lark),
+    };
+    // Styles reshape the answer, which a grammar or schema has already fixed
+    if response_style.is_some() && constraint.is_some() {
+        return Err(ModelError::InvalidRequest("A response_style can't be combined with a grammar or a response_format".to_string()));
+    }
+    let style = if constraint.is_some() {
+        None
     } else {
-        println!("Loading new model: {}", model_id);
-        
-        let new_model = load_model_by_id(&model_id, &app).await?;
-        let model_arc = Arc::new(new_model);
-        
-        // Cache the model for future requests
-        instances.insert(model_id.clone(), model_arc.clone());
-        model_arc
+        response_style::resolve(response_style, conversation_id.as_deref())?
     };
+    // Models with a thinking mode get the flag either way, since their templates default to thinking
+    let thinking = supports_thinking(&model_id, &app).then(|| enable_thinking.unwrap_or(false));
+    if enable_thinking == Some(true) {
+        if thinking.is_none() {
+            return Err(ModelError::InvalidRequest(format!("{} has no thinking mode", model_id)));
+        }
+        if constraint.is_some() {
+            return Err(ModelError::InvalidRequest("Thinking mode can't be combined with a grammar or a response_format".to_string()));
+        }
+    }
+    
+    // Images sent again from earlier turns come first, then the newly attached ones
+    let mut images = gallery::resolve(conversation_id.as_deref(), &images)?;
+    images.extend(image_data.map(ImageInput::Base64));
+    if let Some(path) = &image_path {
+        images.push(image_input::from_path(path).map_err(ModelError::ImageError)?);
+    }
+    if image_from_clipboard {
+        images.push(image_input::from_clipboard(&request_id).await.map_err(ModelError::ImageError)?);
+    }
+    
+    let guard = shutdown::begin_request(&request_id)?;
+    let priority = priority::begin(priority.unwrap_or_default(), &guard).await;
+    let mut tracker = StreamTracker::start(&app, target.as_deref(), &request_id, &model_id);
+    if let Some(transcript) = conversation_id.as_deref().and_then(|id| transcript::open(id, &request_id, &model_id, &expanded.prompt)) {
+        tracker.tee_to(transcript);
+    }
+    if let Some(format) = &response_format {
+        tracker.parse_json(format.schema());
+    }
+    if thinking.is_some() {
+        tracker.split_thinking();
+    }
     
-    drop(instances);
+    let span = tracing::info_span!("chat", request_id = %request_id, model_id = %model_id, profile = %profile_name);
+    let earlier_turns = conversation_id
+        .as_deref()
+        .map(|id| context_window::window(id, &model_id, &message, sampling.max_tokens))
+        .unwrap_or_default();
+    let assistant_prompt = conversation_id.as_deref().and_then(assistants::system_prompt_for);
+    let system = system_prompt::resolve(conversation_id.as_deref(), &model_id, assistant_prompt.as_deref());
+    let glossary = glossary::for_conversation(conversation_id.as_deref())?;
+    let system_prompt = glossary::apply(&response_style::apply(&system.prompt, style), &glossary);
+    let mut restarts = 0;
+    let mut prepared = None;
+    let outcome = loop {
+        let outcome = stream_chat(
+            &message,
+            &earlier_turns,
+            &system_prompt,
+            &model_id,
+            images.clone(),
+            audio_data.clone(),
+            &sampling,
+            constraint.clone(),
+            thinking,
+            &mut prepared,
+            &mut tracker,
+            &guard,
+            &priority,
+            &app,
+        )
+            .instrument(span.clone())
+            .await;
+        // A stalled attempt is generated again from scratch while restarts remain
+        if matches!(outcome, Ok(StreamOutcome::Stalled)) && watchdog::should_restart(restarts) {
+            restarts += 1;
+            trace::log(&request_id, format!("Restarting stalled generation (restart {})", restarts));
+            tracker.restart();
+            if let Some(format) = &response_format {
+                tracker.parse_json(format.schema());
+            }
+            if thinking.is_some() {
+                tracker.split_thinking();
+            }
+            continue;
+        }
+        break outcome;
+    };
+    let result = match outcome {
+        Ok(StreamOutcome::Completed) => {
+            if !tracker.thought().is_empty() {
+                trace::log(&request_id, format!("AI Thinking: {}", tracker.thought()));
+            }
+            response_style::enforce(&mut tracker, style, &model_id, &sampling, &app).await;
+            language::enforce(&mut tracker, &model_id, &sampling, &app).await;
+            glossary::check(tracker.content(), &glossary, &request_id, &app);
+            let content = tracker.finish();
+            trace::log(&request_id, format!("AI Response: {}", content));
+            Ok(content)
+        }
+        Ok(outcome @ (StreamOutcome::Cancelled | StreamOutcome::Stalled)) => {
+            trace::log(&request_id, if matches!(outcome, StreamOutcome::Stalled) { "Cancelled after stalling" } else { "Cancelled" });
+            history::note_cancelled(&request_id);
+            let partial = tracker.cancel();
+            // Callers that store the returned text have nothing to store
+            if config::get().generation.on_cancel == history::CancelBehavior::Discard {
+                Ok(String::new())
+            } else {
+                Ok(partial)
+            }
+        }
+        Err(e) => {
+            tracker.fail(&e.to_string());
+            Err(e)
+        }
+    };
+    if result.is_ok() {
+        provenance::remember(&request_id, &model_id, &profile_name, &sampling);
+    }
+    if let Some(conversation_id) = &conversation_id {
+        replay::record(conversation_id, &request_id);
+    }
+    result
+}
+
+// Leads a request's turns with the system prompt: as a system message when the model's chat
+// template has a system role, otherwise at the start of the first user turn
+fn with_system_prompt(system_prompt: &str, model_id: &str, mut turns: Vec<(TextMessageRole, String)>) -> Vec<(TextMessageRole, String)> {
+    if prompt_format::supports_system_role(model_id) {
+        turns.insert(0, (TextMessageRole::System, system_prompt.to_string()));
+    } else if let Some((_, first)) = turns.first_mut() {
+        *first = format!("{}\n\n{}", system_prompt, first);
+    }
+    turns
+}
+
+// How often "prefill" progress events are sent while a long prompt is processed
+const PREFILL_PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+// How often "waiting" events are sent while a request waits on a model load or earlier requests
+const WAIT_STATUS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
 
+// How a streamed generation ended
+pub(crate) enum StreamOutcome {
+    Completed,
+    Cancelled,
+    // The watchdog stopped it after no token arrived for too long
+    Stalled,
+}
+
+// Renders a chat request: the chat template's turns, the decoded image, and the logit bias and
+// banned phrases tokenized with the model's tokenizer. Built once per ai_chat and reused by its
+// restarts, which then send the same prompt and hit mistral.rs's prefix cache.
+#[allow(clippy::too_many_arguments)]
+async fn prepare_chat(
+    message: &str,
+    earlier_turns: &[history::Message],
+    system_prompt: &str,
+    model_id: &str,
+    images: Vec<ImageInput>,
+    audio_data: Option<String>,
+    sampling: &sampling::SamplingConfig,
+    constraint: Option<Constraint>,
+    thinking: Option<bool>,
+    model: &mistralrs::Model,
+    app: &tauri::AppHandle,
+) -> ModelResult<RequestBuilder> {
+    if audio_data.is_some() && !supports_audio(model_id, app) {
+        return Err(ModelError::AudioError(format!("{} doesn't take audio input", model_id)));
+    }
     // Handle vision vs text models differently
-    let response = if model_id.contains("vision") || model_id.contains("gemma-3n") || model_id.contains("llama") {
-        // Vision model processing
-        if let Some(image_base64) = image_data {
-            use base64::Engine;
-            let image_bytes = base64::engine::general_purpose::STANDARD.decode(&image_base64)
-                .map_err(|e| format!("Failed to decode image: {}", e))?;
-            
-            let image = image::load_from_memory(&image_bytes)
-                .map_err(|e| format!("Failed to load image: {}", e))?;
+    let request = if is_vision_model(model_id, app) {
+        // Vision model processing; models that take audio accept a recording instead of an image
+        if !images.is_empty() || audio_data.is_some() {
+            let images = images
+                .iter()
+                .map(|image| image.load(model_id).map_err(ModelError::ImageError))
+                .collect::<ModelResult<Vec<_>>>()?;
+            let audios = match audio_data {
+                Some(audio_base64) => vec![audio::decode(&audio_base64).map_err(ModelError::AudioError)?],
+                None => Vec::new(),
+            };
             
-            // Create vision messages with image and text
-            let messages = VisionMessages::new().add_image_message(
+            // Create vision messages with the images or recording and text
+            let mut turns = with_system_prompt(system_prompt, model_id, vec![(TextMessageRole::User, message.to_string())]);
+            let (_, message) = turns.pop().expect("the user turn is always present");
+            let messages = turns
+                .into_iter()
+                .fold(VisionMessages::new(), |messages, (role, content)| messages.add_message(role, &content));
+            let messages = messages.add_multimodal_message(
                 TextMessageRole::User,
                 &message,
-                vec![image],
-                &model,
-            ).map_err(|e| format!("Failed to create vision message: {}", e))?;
+                images,
+                audios,
+                model,
+            ).map_err(|e| ModelError::ImageError(format!("Failed to create vision message: {}", e)))?;
             
-            model
-                .send_chat_request(messages)
-                .await
-                .map_err(|e| format!("Failed to send vision chat request: {}", e))?
+            RequestBuilder::from(messages)
         } else {
-            return Err("Vision model requires an image input".to_string());
+            return Err(ModelError::MissingImage);
         }
     } else {
         // Text-only model processing
-        let messages = TextMessages::new()
-            .add_message(
-                TextMessageRole::User,
-                &format!("You are a helpful AI assistant. Keep your responses concise and friendly.\n\n{}", message)
-            );
+        // Earlier turns come from the truncated history window
+        let mut turns: Vec<(TextMessageRole, String)> = earlier_turns
+            .iter()
+            .map(|turn| {
+                let role = if turn.role == "assistant" { TextMessageRole::Assistant } else { TextMessageRole::User };
+                (role, turn.content.clone())
+            })
+            .collect();
+        turns.push((TextMessageRole::User, message.to_string()));
+        let messages = with_system_prompt(system_prompt, model_id, turns)
+            .into_iter()
+            .fold(TextMessages::new(), |messages, (role, content)| messages.add_message(role, &content));
+
+        RequestBuilder::from(messages)
+    };
+
+    let mut request = sampling.apply_token_rules(sampling.apply(request), model).await;
+    if let Some(constraint) = constraint {
+        request = request.set_constraint(constraint);
+    }
+    if let Some(enabled) = thinking {
+        request = request.enable_thinking(enabled);
+    }
+    Ok(request)
+}
 
-        model
-            .send_chat_request(messages)
+// Loads (or reuses) the model and feeds its token stream into the tracker
+#[allow(clippy::too_many_arguments)]
+async fn stream_chat(
+    message: &str,
+    earlier_turns: &[history::Message],
+    system_prompt: &str,
+    model_id: &str,
+    images: Vec<ImageInput>,
+    audio_data: Option<String>,
+    sampling: &sampling::SamplingConfig,
+    constraint: Option<Constraint>,
+    thinking: Option<bool>,
+    // The request rendered by an earlier attempt, reused as it is; set by the first attempt
+    prepared: &mut Option<RequestBuilder>,
+    tracker: &mut StreamTracker,
+    guard: &shutdown::InFlightGuard,
+    priority: &priority::PriorityGuard,
+    app: &tauri::AppHandle,
+) -> ModelResult<StreamOutcome> {
+    dotenvy::dotenv().ok();
+    
+    let limits = config::get().limits;
+    if message.chars().count() > limits.max_message_chars {
+        return Err(ModelError::LimitExceeded(format!(
+            "Message exceeds the configured limit of {} characters",
+            limits.max_message_chars
+        )));
+    }
+    if images.len() > limits.max_images_per_request {
+        return Err(ModelError::LimitExceeded(format!(
+            "A request can send at most {} images",
+            limits.max_images_per_request
+        )));
+    }
+    for image in &images {
+        if image.encoded_len().is_some_and(|bytes| bytes > limits.max_image_bytes) {
+            return Err(ModelError::LimitExceeded(format!(
+                "Image exceeds the configured limit of {} bytes",
+                limits.max_image_bytes
+            )));
+        }
+    }
+    if let Some(audio_base64) = &audio_data {
+        if audio_base64.len() / 4 * 3 > limits.max_audio_bytes {
+            return Err(ModelError::LimitExceeded(format!(
+                "Audio exceeds the configured limit of {} bytes",
+                limits.max_audio_bytes
+            )));
+        }
+    }
+    
+    // Counted over what is actually sent; vision requests don't carry earlier turns
+    let mut sent = vec![system_prompt];
+    if !is_vision_model(model_id, app) {
+        sent.extend(earlier_turns.iter().map(|turn| turn.content.as_str()));
+    }
+    sent.push(message);
+    estimate::check_context_limit(model_id, &sent, sampling.max_tokens).map_err(ModelError::LimitExceeded)?;
+    
+    if mock::is_mock(model_id) {
+        return Ok(mock::stream(message, tracker, guard).await?);
+    }
+    
+    // Until the first token, the UI is told what the request is waiting on and for roughly how long
+    let _ticket = queue::join(guard.request_id(), model_id);
+    let mut wait_ticker = tokio::time::interval(WAIT_STATUS_INTERVAL);
+    let load = get_or_load_model(model_id, guard.request_id(), app);
+    tokio::pin!(load);
+    let model = loop {
+        tokio::select! {
+            model = &mut load => break model?,
+            _ = wait_ticker.tick() => {
+                if let Some(status) = queue::status(guard.request_id(), model_id) {
+                    tracker.report_wait(status);
+                }
+            }
+        }
+    };
+
+    let request = match prepared.take() {
+        Some(request) => {
+            trace::log(guard.request_id(), "Reusing the rendered prompt");
+            request
+        }
+        None => prepare_chat(message, earlier_turns, system_prompt, model_id, images, audio_data, sampling, constraint, thinking, &model, app).await?,
+    };
+    *prepared = Some(request.clone());
+    
+    // Cancelled while the model was loading
+    if guard.is_cancelled() {
+        return Ok(StreamOutcome::Cancelled);
+    }
+    
+    // Prompt processing (tokenization and prefill) ends with the first chunk; decode covers the rest
+    let mut prefill = Some(tracing::info_span!("prefill", prompt_chars = message.len()));
+    let mut decode = None;
+    tracker.start_prefill(estimate::estimate_tokens(message));
+    let mut stream = model
+        .stream_chat_request(request)
+        .await
+        .map_err(|e| ModelError::generation_failed(format!("Failed to send chat request: {}", e)))?;
+    
+    // Forward each generated chunk to the frontend as it arrives
+    let mut streamed = 0;
+    let mut progress_ticker = tokio::time::interval_at(tokio::time::Instant::now() + PREFILL_PROGRESS_INTERVAL, PREFILL_PROGRESS_INTERVAL);
+    let mut watchdog_ticker = tokio::time::interval_at(tokio::time::Instant::now() + watchdog::CHECK_INTERVAL, watchdog::CHECK_INTERVAL);
+    let mut watchdog = watchdog::Watchdog::start();
+    loop {
+        // Long prompts, and requests queued behind others, report progress until the first token
+        // instead of stalling silently
+        let response = if streamed == 0 {
+            tokio::select! {
+                response = stream.next() => response,
+                _ = progress_ticker.tick() => {
+                    // No chunk arrives until prefill ends, so cancellation is checked here too
+                    if guard.is_cancelled() {
+                        models::manager(app).touch(model_id).await;
+                        return Ok(StreamOutcome::Cancelled);
+                    }
+                    if tracker.is_prefilling() {
+                        tracker.report_prefill();
+                    }
+                    if let Some(status) = queue::status(guard.request_id(), model_id) {
+                        tracker.report_wait(status);
+                    }
+                    if let Some((action, idle)) = watchdog.check(false) {
+                        tracker.report_stall(action, idle);
+                        if action != watchdog::StallAction::Notify {
+                            models::manager(app).touch(model_id).await;
+                            return Ok(StreamOutcome::Stalled);
+                        }
+                    }
+                    continue;
+                }
+            }
+        } else {
+            tokio::select! {
+                response = stream.next() => response,
+                _ = watchdog_ticker.tick() => {
+                    if guard.is_cancelled() {
+                        models::manager(app).touch(model_id).await;
+                        return Ok(StreamOutcome::Cancelled);
+                    }
+                    if let Some((action, idle)) = watchdog.check(true) {
+                        tracker.report_stall(action, idle);
+                        if action != watchdog::StallAction::Notify {
+                            // Dropping the stream tells the engine to stop generating
+                            models::manager(app).touch(model_id).await;
+                            return Ok(StreamOutcome::Stalled);
+                        }
+                    }
+                    continue;
+                }
+            }
+        };
+        let Some(response) = response else {
+            break;
+        };
+        watchdog.progress();
+        if prefill.take().is_some() {
+            decode = Some(tracing::info_span!("decode", tokens = tracing::field::Empty));
+        }
+        if guard.is_cancelled() {
+            // Dropping the stream tells the engine to stop generating
+            models::manager(app).touch(model_id).await;
+            return Ok(StreamOutcome::Cancelled);
+        }
+        
+        match response {
+            Response::Chunk(chunk) => {
+                if let Some(choice) = chunk.choices.first() {
+                    if let Some(content) = &choice.delta.content {
+                        tracker.push_chunk(content);
+                        streamed += 1;
+                        chaos::on_chunk(streamed)?;
+                    }
+                    if choice.finish_reason.is_some() {
+                        break;
+                    }
+                }
+                priority.yield_now(guard).await;
+            }
+            Response::ModelError(e, _) => return Err(ModelError::generation_failed(format!("Model error: {}", e))),
+            Response::InternalError(e) | Response::ValidationError(e) => {
+                return Err(ModelError::generation_failed(e.to_string()));
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(decode) = decode {
+        decode.record("tokens", streamed);
+    }
+
+    // Long generations count as activity for the keep-alive timer
+    models::manager(app).touch(model_id).await;
+
+    Ok(StreamOutcome::Completed)
+}
+
+// Returns the cached model or loads it into the cache on first use. A load is logged, and reported
+// in model-load events, under the id of the request that needed it.
+pub(crate) async fn get_or_load_model(model_id: &str, request_id: &str, app: &tauri::AppHandle) -> ModelResult<Arc<mistralrs::Model>> {
+    let manager = models::manager(app);
+    let mut instances = manager.lock().await;
+    
+    // Use cached model if available, otherwise load new model
+    if let Some(existing_model) = instances.get_mut(model_id) {
+        trace::log(request_id, format!("Using cached model: {}", model_id));
+        existing_model.last_used = Instant::now();
+        return Ok(existing_model.model.clone());
+    }
+    
+    trace::log(request_id, format!("Loading new model: {}", model_id));
+    models::emit_load(app, request_id, model_id, models::LoadStatus::Started, None);
+    
+    let size_bytes = model_size(model_id, app);
+    keep_alive::make_room(&mut instances, size_bytes, app);
+    let _loading = queue::loading(model_id);
+    let load_started = Instant::now();
+    let loaded = async {
+        chaos::before_load(model_id).await?;
+        load_model_by_id(model_id, app)
+            .instrument(tracing::info_span!("model.load", model_id = %model_id, request_id = %request_id))
             .await
-            .map_err(|e| format!("Failed to send text chat request: {}", e))?
+    }
+    .await;
+    let new_model = match loaded {
+        Ok(model) => model,
+        Err(e) => {
+            // Builder failures arrive as plain strings
+            let e = match e {
+                ModelError::Other(detail) => ModelError::load_failed(detail),
+                e => e,
+            };
+            trace::log(request_id, format!("Failed to load {}: {}", model_id, e));
+            models::emit_load(app, request_id, model_id, models::LoadStatus::Failed, Some(&e.to_string()));
+            return Err(e);
+        }
     };
+    metrics::record_load(model_id, load_started.elapsed().as_millis() as u64);
+    trace::log(request_id, format!("Loaded model: {}", model_id));
+    models::emit_load(app, request_id, model_id, models::LoadStatus::Loaded, None);
+    let model_arc = Arc::new(new_model);
+    
+    // Cache the model for future requests
+    instances.insert(model_id.to_string(), LoadedModel {
+        model: model_arc.clone(),
+        last_used: Instant::now(),
+        size_bytes,
+    });
+    assistants::warm_for_model(model_id, app);
+    
+    Ok(model_arc)
+}
 
-    // Extract response content
-    let content = response.choices[0]
-        .message
-        .content
-        .as_ref()
-        .ok_or("No content in response")?
-        .clone();
+// Runs a one-shot generation for backend tools and returns the full text
+pub(crate) async fn generate_text(
+    model_id: &str,
+    prompt: &str,
+    sampling: &sampling::SamplingConfig,
+    priority: priority::Priority,
+    app: &tauri::AppHandle,
+) -> Result<String, String> {
+    let request_id = streaming::new_request_id();
+    trace::begin(&request_id, "chat");
+    let guard = shutdown::begin_request(&request_id)?;
+    estimate::check_context_limit(model_id, &[prompt], sampling.max_tokens)?;
+    let priority = priority::begin(priority, &guard).await;
+    if guard.is_cancelled() {
+        return Err("Generation was cancelled".to_string());
+    }
+    if mock::is_mock(model_id) {
+        return mock::generate(prompt).await;
+    }
+    // Counted in the queue so chats behind it get an honest ETA
+    let _ticket = queue::join(&request_id, model_id);
+    let model = get_or_load_model(model_id, &request_id, app).await?;
+    
+    let request = sampling.apply(RequestBuilder::from(
+        TextMessages::new().add_message(TextMessageRole::User, prompt),
+    ));
+    let request = sampling.apply_token_rules(request, &model).await;
+    // Streamed even though the caller wants the whole text, so background work can pause between chunks
+    let mut stream = model
+        .stream_chat_request(request)
+        .await
+        .map_err(|e| format!("Generation failed: {}", e))?;
+    let mut text = String::new();
+    while let Some(response) = stream.next().await {
+        match response {
+            Response::Chunk(chunk) => {
+                if let Some(choice) = chunk.choices.first() {
+                    if let Some(content) = &choice.delta.content {
+                        text.push_str(content);
+                    }
+                    if choice.finish_reason.is_some() {
+                        break;
+                    }
+                }
+                priority.yield_now(&guard).await;
+                if guard.is_cancelled() {
+                    return Err("Generation was cancelled".to_string());
+                }
+            }
+            Response::ModelError(e, _) => return Err(format!("Generation failed: {}", e)),
+            Response::InternalError(e) | Response::ValidationError(e) => {
+                return Err(format!("Generation failed: {}", e));
+            }
+            _ => {}
+        }
+    }
+    models::manager(app).touch(model_id).await;
+    
+    if text.trim().is_empty() {
+        return Err("The model returned an empty response".to_string());
+    }
+    Ok(text)
+}
 
-    println!("AI Response: {}", content);
-    Ok(content)
+// Prefills `prefix` as a system prompt, laid out the way stream_chat lays it out, so mistral.rs's
+// prefix cache holds its KV state for later requests that begin the same way
+pub(crate) async fn warm_prefix(model_id: &str, prefix: &str, app: &tauri::AppHandle) -> Result<(), String> {
+    if mock::is_mock(model_id) {
+        return Ok(());
+    }
+    let request_id = streaming::new_request_id();
+    trace::begin(&request_id, "chat");
+    let guard = shutdown::begin_request(&request_id)?;
+    let _priority = priority::begin(priority::Priority::Background, &guard).await;
+    if guard.is_cancelled() {
+        return Ok(());
+    }
+    let model = get_or_load_model(model_id, &request_id, app).await?;
+    let messages = with_system_prompt(prefix, model_id, vec![(TextMessageRole::User, String::new())])
+        .into_iter()
+        .fold(TextMessages::new(), |messages, (role, content)| messages.add_message(role, &content));
+    let request = RequestBuilder::from(messages).set_sampler_max_len(1);
+    model
+        .send_chat_request(request)
+        .await
+        .map_err(|e| format!("Prefill failed: {}", e))?;
+    Ok(())
 }
 
 // Routes model loading to appropriate builder based on model ID
-async fn load_model_by_id(model_id: &str, app: &tauri::AppHandle) -> Result<mistralrs::Model, String> {
+async fn load_model_by_id(model_id: &str, app: &tauri::AppHandle) -> ModelResult<mistralrs::Model> {
+    if let Some(remote) = catalog::REMOTE_MODELS.iter().find(|m| m.id == model_id) {
+        if let Some(reason) = catalog::remote_unavailable_reason(remote, app) {
+            return Err(ModelError::Configuration(format!("{} can't be loaded: {}", model_id, reason)));
+        }
+    }
+    
     if model_id == "mistral-7b-remote" {
-        return load_remote_mistral_model(app).await;
+        return Ok(load_remote_mistral_model(app).await?);
     }
     
     if model_id == "smollm3-remote" {
-        return load_remote_smollm3_model().await;
+        return Ok(load_remote_smollm3_model().await?);
     }
     
     if model_id.starts_with("local-") {
         return load_local_model(model_id, app).await;
     }
     
-    Err(format!("Unknown model ID: {}", model_id))
+    Err(ModelError::NotFound(model_id.to_string()))
+}
+
+// Applies the [limits.per_model] GPU memory and batch caps to any mistral.rs model builder
+macro_rules! apply_model_limits {
+    ($builder:expr, $model_id:expr) => {{
+        let limits = config::get().limits.for_model($model_id);
+        let mut builder = $builder;
+        if let Some(max_batch_size) = limits.max_batch_size {
+            builder = builder.with_max_num_seqs(max_batch_size);
+        }
+        // The KV cache is only sized up front on GPU backends
+        if let Some(fraction) = limits.max_gpu_memory_fraction.filter(|_| !hardware::use_cpu()) {
+            println!("Limiting {} to {:.0}% of GPU memory", $model_id, fraction * 100.0);
+            builder = builder
+                .with_paged_attn(|| {
+                    PagedAttentionMetaBuilder::default()
+                        .with_gpu_memory(MemoryGpuConfig::Utilization(fraction))
+                        .build()
+                })
+                .map_err(|e| format!("Failed to apply the GPU memory limit for {}: {}", $model_id, e))?;
+        }
+        builder
+    }};
 }
 
-// Example remote model loading (requires HF_TOKEN)
+// Example remote model loading (requires a Hugging Face token)
 async fn load_remote_mistral_model(app: &tauri::AppHandle) -> Result<mistralrs::Model, String> {
     println!("Loading remote Mistral 7B model...");
     
-    if std::env::var("HF_TOKEN").is_err() {
-        return Err("HF_TOKEN not found. Set HF_TOKEN in .env file for remote model access".to_string());
-    }
-    
     // Try to find local chat template
     let mut mistral_json_path = None;
     
@@ -432,25 +1475,39 @@ async fn load_remote_mistral_model(app: &tauri::AppHandle) -> Result<mistralrs::
     }
     
     // Build the remote model with optional local chat template
-    let model = if let Some(template_path) = mistral_json_path {
+    let mut builder = GgufModelBuilder::new(
+        "TheBloke/Mistral-7B-Instruct-v0.1-GGUF",
+        vec!["mistral-7b-instruct-v0.1.Q4_K_M.gguf".to_string()],
+    );
+    
+    provenance::note_build(
+        "mistral-7b-remote",
+        provenance::gguf_quantization("mistral-7b-instruct-v0.1.Q4_K_M.gguf"),
+        mistral_json_path.as_deref(),
+    );
+    prompt_format::note_template("mistral-7b-remote", mistral_json_path.as_deref());
+    if let Some(template_path) = mistral_json_path {
         println!("Using local chat template: {:?}", template_path);
-        GgufModelBuilder::new(
-            "TheBloke/Mistral-7B-Instruct-v0.1-GGUF",
-            vec!["mistral-7b-instruct-v0.1.Q4_K_M.gguf".to_string()],
-        )
-        .with_chat_template(template_path.to_str().unwrap())
-        .build()
-        .await
+        builder = builder.with_chat_template(template_path.to_str().unwrap());
     } else {
         println!("Using remote tokenizer");
-        GgufModelBuilder::new(
-            "TheBloke/Mistral-7B-Instruct-v0.1-GGUF",
-            vec!["mistral-7b-instruct-v0.1.Q4_K_M.gguf".to_string()],
-        )
-        .with_tok_model_id("mistralai/Mistral-7B-Instruct-v0.1".to_string())
+        builder = builder.with_tok_model_id("mistralai/Mistral-7B-Instruct-v0.1".to_string());
+    }
+    
+    // The token can come from .env as well as the huggingface-cli login file
+    if let Some(token) = catalog::hf_token(app) {
+        builder = builder.with_token_source(TokenSource::Literal(token));
+    }
+    
+    if hardware::use_cpu() {
+        builder = builder.with_force_cpu();
+    }
+    
+    builder = apply_model_limits!(builder, "mistral-7b-remote");
+    
+    let model = builder
         .build()
         .await
-    }
     .map_err(|e: anyhow::Error| format!("Failed to build remote model: {}", e))?;
     
     println!("Remote model loaded successfully!");
@@ -461,9 +1518,22 @@ async fn load_remote_smollm3_model() -> Result<mistralrs::Model, String> {
     println!("Loading remote SmolLM3 3B model...");
     
     // Build the remote SmolLM3 model using TextModelBuilder
-    let model = TextModelBuilder::new("HuggingFaceTB/SmolLM3-3B")
-        .with_isq(IsqType::Q8_0)
-        .with_logging()
+    let (isq, isq_name) = isq::for_model("smollm3-remote", IsqType::Q8_0);
+    let mut builder = TextModelBuilder::new("HuggingFaceTB/SmolLM3-3B")
+        .with_isq(isq);
+    
+    if hardware::use_cpu() {
+        builder = builder.with_force_cpu();
+    }
+    
+    builder = apply_model_limits!(builder, "smollm3-remote");
+    if !telemetry::is_enabled() {
+        builder = builder.with_logging();
+    }
+    
+    provenance::note_build("smollm3-remote", Some(format!("ISQ {}", isq_name)), None);
+    
+    let model = builder
         .build()
         .await
         .map_err(|e: anyhow::Error| format!("Failed to build remote SmolLM3 model: {}", e))?;
@@ -472,28 +1542,124 @@ async fn load_remote_smollm3_model() -> Result<mistralrs::Model, String> {
     Ok(model)
 }
 
+// Quantizations picked when a model folder holds several, best balance first
+const UQFF_PREFERENCE: [&str; 4] = ["Q4K", "Q8_0", "AFQ4", "F8E4M3"];
+
+fn uqff_file_quantization(file: &std::path::Path) -> Option<String> {
+    provenance::uqff_quantization(&file.to_string_lossy())
+}
+
+// Quantizations a UQFF model folder holds, e.g. ["Q4K", "Q8_0"]
+fn uqff_quantizations(files: &[std::path::PathBuf]) -> Vec<String> {
+    let mut available: Vec<String> = Vec::new();
+    for quantization in files.iter().filter_map(|file| uqff_file_quantization(file)) {
+        if !available.contains(&quantization) {
+            available.push(quantization);
+        }
+    }
+    available
+}
+
+// The quantization a model loads: the one chosen with set_model_quantization when its files are
+// there, otherwise the first of UQFF_PREFERENCE present
+fn uqff_choice(model_id: &str, files: &[std::path::PathBuf]) -> Option<String> {
+    let available = uqff_quantizations(files);
+    if let Some(chosen) = config::get().models.uqff_quantization.get(model_id).map(|q| q.to_uppercase()) {
+        if available.contains(&chosen) {
+            return Some(chosen);
+        }
+        println!("Warning: {} has no {} UQFF files (found {:?}); picking another quantization", model_id, chosen, available);
+    }
+    UQFF_PREFERENCE
+        .iter()
+        .map(|q| q.to_string())
+        .find(|q| available.contains(q))
+        .or_else(|| available.first().cloned())
+}
+
+// The shards of one quantization. The downloader fetches every quantization a repo offers, and
+// mistral.rs would load all of them as one model.
+fn select_uqff_files(model_id: &str, files: Vec<std::path::PathBuf>) -> Vec<std::path::PathBuf> {
+    let Some(chosen) = uqff_choice(model_id, &files) else {
+        return files;
+    };
+    files.into_iter().filter(|file| uqff_file_quantization(file).as_ref() == Some(&chosen)).collect()
+}
+
+// Picks the UQFF quantization a model loads from now on; None returns it to the automatic choice.
+// A loaded model keeps its weights until it is unloaded.
+#[tauri::command]
+fn set_model_quantization(model_id: String, quantization: Option<String>, app: tauri::AppHandle) -> ModelResult<()> {
+    let quantization = quantization.map(|q| q.trim().to_uppercase()).filter(|q| !q.is_empty());
+    if let Some(quantization) = &quantization {
+        let dir = local_model_dir(&model_id, &app).ok_or_else(|| ModelError::NotFound(model_id.clone()))?;
+        let available = uqff_quantizations(&get_uqff_files(&dir.to_string_lossy()).map_err(ModelError::InvalidRequest)?);
+        if !available.contains(quantization) {
+            return Err(ModelError::InvalidRequest(format!(
+                "{} has no {} UQFF files (available: {})",
+                model_id,
+                quantization,
+                available.join(", ")
+            )));
+        }
+    }
+    config::update(&app, |config| match quantization {
+        Some(quantization) => {
+            config.models.uqff_quantization.insert(model_id, quantization);
+        }
+        None => {
+            config.models.uqff_quantization.remove(&model_id);
+        }
+    })
+    .map_err(ModelError::InvalidRequest)?;
+    Ok(())
+}
+
+// SmolLM3 from the UQFF files in its folder, without touching the network
+async fn load_local_smollm3(model_id: &str, model_path: &str) -> Result<mistralrs::Model, String> {
+    for required in ["config.json", "tokenizer.json"] {
+        if !Path::new(model_path).join(required).exists() {
+            return Err(format!("{} is missing from {}", required, model_path));
+        }
+    }
+    let uqff_files = select_uqff_files(model_id, get_uqff_files(model_path)?);
+    println!("Loading SmolLM3 UQFF model from: {} with files: {:?}", model_path, uqff_files);
+    
+    let quantization = uqff_files
+        .first()
+        .and_then(|file| provenance::uqff_quantization(&file.to_string_lossy()))
+        .map(|q| format!("UQFF {}", q));
+    let mut builder = UqffTextModelBuilder::new(model_path, uqff_files).into_inner();
+    
+    if hardware::use_cpu() {
+        builder = builder.with_force_cpu();
+    }
+    
+    builder = apply_model_limits!(builder, model_id);
+    if !telemetry::is_enabled() {
+        builder = builder.with_logging();
+    }
+    
+    provenance::note_build(model_id, quantization, None);
+    
+    let model = builder
+        .build()
+        .await
+        .map_err(|e: anyhow::Error| format!("Failed to build SmolLM3 from {}: {}", model_path, e))?;
+    
+    println!("SmolLM3 model loaded successfully!");
+    Ok(model)
+}
+
 // Loads local models using appropriate mistral.rs builders for each format
-async fn load_local_model(model_id: &str, _app: &tauri::AppHandle) -> Result<mistralrs::Model, String> {
+async fn load_local_model(model_id: &str, app: &tauri::AppHandle) -> ModelResult<mistralrs::Model> {
     println!("Loading local model: {}", model_id);
     
     // Find the models directory using the same logic as discover_models
-    let possible_paths = [
-        "models",
-        "../models",
-        "src-tauri/models",
-    ];
-    
-    let mut models_base_path = None;
-    for path in &possible_paths {
-        if Path::new(path).exists() {
-            models_base_path = Some(*path);
-            break;
-        }
-    }
+    let base_path = find_models_dir(app)
+        .ok_or_else(|| ModelError::NotFound(format!("{} (no models directory found)", model_id)))?;
     
-    let base_path = models_base_path.ok_or("No models directory found")?;
-    
-    let discovered_models = discover_local_models(base_path)
+    let discovered_models = discover_local_models(&base_path)
         .map_err(|e| format!("Failed to discover local models: {}", e))?;
     
     // Find the matching model and load with appropriate builder
@@ -505,15 +1671,31 @@ async fn load_local_model(model_id: &str, _app: &tauri::AppHandle) -> Result<mis
         };
         
         if expected_id == model_id {
+            if let Some(reason) = compat::incompatibility(&base_path, &model_dir, &model_file, &model_type) {
+                return Err(ModelError::Incompatible(format!("{} can't be loaded: {}", model_id, reason)));
+            }
             if model_type == "matformer-vision" {
                 // MatFormer vision model using VisionModelBuilder
                 let model_path = format!("{}/{}", base_path, model_dir);
                 
                 println!("Loading MatFormer vision model from: {}", model_path);
                 
-                let model = VisionModelBuilder::new(&model_path)
-                    .with_isq(IsqType::Q4K)
-                    .with_logging()
+                let (isq, isq_name) = isq::for_model(model_id, IsqType::Q4K);
+                let mut builder = VisionModelBuilder::new(&model_path)
+                    .with_isq(isq);
+                
+                if hardware::use_cpu() {
+                    builder = builder.with_force_cpu();
+                }
+                
+                builder = apply_model_limits!(builder, model_id);
+                if !telemetry::is_enabled() {
+                    builder = builder.with_logging();
+                }
+                
+                provenance::note_build(model_id, Some(format!("ISQ {}", isq_name)), None);
+                
+                let model = builder
                     .build()
                     .await
                     .map_err(|e: anyhow::Error| format!("Failed to build MatFormer vision model: {}", e))?;
@@ -532,32 +1714,26 @@ async fn load_local_model(model_id: &str, _app: &tauri::AppHandle) -> Result<mis
                 
                 println!("Loading GGUF vision model from: {}{}", model_path, model_file);
                 
-                // Look for chat template files
-                let mut chat_template_path = None;
-                let template_locations = [
-                    "mistral.json",
-                    "src-tauri/mistral.json",
-                    &format!("{}mistral.json", model_path),
-                    &format!("{}tokenizer_config.json", model_path),
-                ];
-                
-                for location in &template_locations {
-                    let path = Path::new(location);
-                    if path.exists() {
-                        chat_template_path = Some(*location);
-                        break;
-                    }
-                }
+                let chat_template_path = prompt_format::chat_template_for(app, Path::new(&model_path), &model_file);
                 
                 let mut builder = GgufModelBuilder::new(
                     &model_path,
                     vec![model_file.to_string()],
                 );
                 
-                if let Some(template_path) = chat_template_path {
-                    builder = builder.with_chat_template(template_path);
+                if let Some(template_path) = &chat_template_path {
+                    builder = builder.with_chat_template(template_path.to_string_lossy());
+                }
+                
+                if hardware::use_cpu() {
+                    builder = builder.with_force_cpu();
                 }
                 
+                builder = apply_model_limits!(builder, model_id);
+                
+                provenance::note_build(model_id, provenance::gguf_quantization(&model_file), chat_template_path.as_deref());
+                prompt_format::note_template(model_id, chat_template_path.as_deref());
+                
                 let model = builder
                     .build()
                     .await
@@ -568,12 +1744,36 @@ async fn load_local_model(model_id: &str, _app: &tauri::AppHandle) -> Result<mis
             }
             
             if model_type == "smollm3" {
-                // SmolLM3 model loaded remotely for better compatibility
-                println!("Loading SmolLM3 model remotely (local UQFF files detected but using remote for compatibility)");
+                let model_path = format!("{}/{}", base_path, model_dir);
+                match load_local_smollm3(model_id, &model_path).await {
+                    Ok(model) => return Ok(model),
+                    Err(e) if config::get().models.allow_remote_fallback && !catalog::is_offline() => {
+                        println!("Warning: {}; loading SmolLM3 from Hugging Face instead", e);
+                    }
+                    Err(e) => {
+                        return Err(ModelError::LoadingError(format!(
+                            "{} (set models.allow_remote_fallback to load SmolLM3 from Hugging Face instead)",
+                            e
+                        )));
+                    }
+                }
+                
+                let (isq, isq_name) = isq::for_model(model_id, IsqType::Q8_0);
+                let mut builder = TextModelBuilder::new("HuggingFaceTB/SmolLM3-3B")
+                    .with_isq(isq);
+                
+                if hardware::use_cpu() {
+                    builder = builder.with_force_cpu();
+                }
                 
-                let model = TextModelBuilder::new("HuggingFaceTB/SmolLM3-3B")
-                    .with_isq(IsqType::Q8_0)
-                    .with_logging()
+                builder = apply_model_limits!(builder, model_id);
+                if !telemetry::is_enabled() {
+                    builder = builder.with_logging();
+                }
+                
+                provenance::note_build(model_id, Some(format!("ISQ {}", isq_name)), None);
+                
+                let model = builder
                     .build()
                     .await
                     .map_err(|e: anyhow::Error| format!("Failed to build SmolLM3 model: {}", e))?;
@@ -588,13 +1788,27 @@ async fn load_local_model(model_id: &str, _app: &tauri::AppHandle) -> Result<mis
                 
                 let uqff_files = get_uqff_files(&model_path)
                     .map_err(|e| format!("Failed to get UQFF files: {}", e))?;
+                let uqff_files = select_uqff_files(model_id, uqff_files);
                 
                 println!("Loading Llama UQFF vision model from: {} with files: {:?}", model_path, uqff_files);
                 
-                let model = UqffVisionModelBuilder::new(&model_path, uqff_files)
+                let (isq, isq_name) = isq::for_model(model_id, IsqType::Q5_0);
+                let mut builder = UqffVisionModelBuilder::new(&model_path, uqff_files)
                     .into_inner()
-                    .with_isq(IsqType::Q5_0)
-                    .with_logging()
+                    .with_isq(isq);
+                
+                if hardware::use_cpu() {
+                    builder = builder.with_force_cpu();
+                }
+                
+                builder = apply_model_limits!(builder, model_id);
+                if !telemetry::is_enabled() {
+                    builder = builder.with_logging();
+                }
+                
+                provenance::note_build(model_id, Some(format!("ISQ {}", isq_name)), None);
+                
+                let model = builder
                     .build()
                     .await
                     .map_err(|e: anyhow::Error| format!("Failed to build Llama UQFF vision model: {}", e))?;
@@ -609,13 +1823,27 @@ async fn load_local_model(model_id: &str, _app: &tauri::AppHandle) -> Result<mis
                 
                 let uqff_files = get_uqff_files(&model_path)
                     .map_err(|e| format!("Failed to get UQFF files: {}", e))?;
+                let uqff_files = select_uqff_files(model_id, uqff_files);
                 
                 println!("Loading Llama UQFF text model from: {} with files: {:?}", model_path, uqff_files);
                 
-                let model = UqffTextModelBuilder::new(&model_path, uqff_files)
+                let (isq, isq_name) = isq::for_model(model_id, IsqType::Q5_0);
+                let mut builder = UqffTextModelBuilder::new(&model_path, uqff_files)
                     .into_inner()
-                    .with_isq(IsqType::Q5_0)
-                    .with_logging()
+                    .with_isq(isq);
+                
+                if hardware::use_cpu() {
+                    builder = builder.with_force_cpu();
+                }
+                
+                builder = apply_model_limits!(builder, model_id);
+                if !telemetry::is_enabled() {
+                    builder = builder.with_logging();
+                }
+                
+                provenance::note_build(model_id, Some(format!("ISQ {}", isq_name)), None);
+                
+                let model = builder
                     .build()
                     .await
                     .map_err(|e: anyhow::Error| format!("Failed to build Llama UQFF text model: {}", e))?;
@@ -625,7 +1853,7 @@ async fn load_local_model(model_id: &str, _app: &tauri::AppHandle) -> Result<mis
             }
             
             if model_type == "matformer" {
-                return Err("MatFormer text models are not yet fully supported in this version".to_string());
+                return Err(ModelError::Incompatible("MatFormer text models are not yet fully supported in this version".to_string()));
             }
             
             // Standard GGUF model using GgufModelBuilder
@@ -637,32 +1865,26 @@ async fn load_local_model(model_id: &str, _app: &tauri::AppHandle) -> Result<mis
             
             println!("Loading GGUF model from: {}{}", model_path, model_file);
             
-            // Look for chat template files
-            let mut chat_template_path = None;
-            let template_locations = [
-                "mistral.json",
-                "src-tauri/mistral.json",
-                &format!("{}mistral.json", model_path),
-                &format!("{}tokenizer_config.json", model_path),
-            ];
-            
-            for location in &template_locations {
-                let path = Path::new(location);
-                if path.exists() {
-                    chat_template_path = Some(*location);
-                    break;
-                }
-            }
+            let chat_template_path = prompt_format::chat_template_for(app, Path::new(&model_path), &model_file);
             
             let mut builder = GgufModelBuilder::new(
                 &model_path,
                 vec![model_file.to_string()],
             );
             
-            if let Some(template_path) = chat_template_path {
-                builder = builder.with_chat_template(template_path);
+            if let Some(template_path) = &chat_template_path {
+                builder = builder.with_chat_template(template_path.to_string_lossy());
             }
             
+            if hardware::use_cpu() {
+                builder = builder.with_force_cpu();
+            }
+            
+            builder = apply_model_limits!(builder, model_id);
+            
+            provenance::note_build(model_id, provenance::gguf_quantization(&model_file), chat_template_path.as_deref());
+            prompt_format::note_template(model_id, chat_template_path.as_deref());
+            
             let model = builder
                 .build()
                 .await
@@ -673,14 +1895,215 @@ async fn load_local_model(model_id: &str, _app: &tauri::AppHandle) -> Result<mis
         }
     }
     
-    Err(format!("Local model not found: {}", model_id))
+    Err(ModelError::NotFound(model_id.to_string()))
+}
+
+// Opens the active profile's history database, creates each feature's tables in it and migrates it
+pub(crate) fn open_stores(app: &tauri::AppHandle) -> Result<(), String> {
+    history::init(app)
+        .and_then(|_| analytics::init())
+        .and_then(|_| context_packs::init())
+        .and_then(|_| folder_chat::init())
+        .and_then(|_| pinned_files::init())
+        .and_then(|_| documents::init())
+        .and_then(|_| assistants::init())
+        .and_then(|_| grammar::init())
+        .and_then(|_| response_style::init())
+        .and_then(|_| glossary::init())
+        .and_then(|_| digest::init())
+        .and_then(|_| replay::init())
+        .and_then(|_| audit::init())
+        .and_then(|_| provenance::init())
+        .and_then(|_| recap::init())
+        .and_then(|_| migrations::run(app))
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![greet, ai_chat, discover_models])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .manage(models::ModelManager::default())
+        .setup(|app| {
+            profiles::init(app.handle());
+            config::init(app.handle());
+            telemetry::init();
+            safe_mode::init(app.handle());
+            hardware::init(app.handle());
+            metrics::load(app.handle());
+            if let Err(e) = open_stores(app.handle()) {
+                println!("Warning: Chat history is unavailable: {}", e);
+            }
+            keep_alive::spawn_reaper(app.handle().clone());
+            memory::spawn_monitor(app.handle().clone());
+            digest::spawn_scheduler(app.handle().clone());
+            backup::spawn_scheduler(app.handle().clone());
+            server::spawn_if_enabled(app.handle().clone());
+            
+            // Warm up the default model unless a previous crash put us in safe mode
+            if !safe_mode::is_active() {
+                if let Some(model_id) = keep_alive::default_model() {
+                    let handle = app.handle().clone();
+                    tauri::async_runtime::spawn(async move {
+                        let request_id = streaming::new_request_id();
+                        trace::begin(&request_id, "load");
+                        trace::log(&request_id, format!("Preloading default model: {}", model_id));
+                        if let Err(e) = get_or_load_model(&model_id, &request_id, &handle).await {
+                            println!("Warning: Failed to preload default model: {}", trace::fail(&request_id, &e.to_string()));
+                        }
+                    });
+                }
+            }
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Destroyed = event {
+                windows::unbind(window.label());
+            }
+        })
+        .invoke_handler(middleware::wrap(tauri::generate_handler![
+            greet,
+            ai_chat,
+            discover_models,
+            set_model_quantization,
+            metrics::get_metrics,
+            streaming::resume_stream,
+            sampling::list_generation_profiles,
+            sampling::save_generation_profile,
+            sampling::set_conversation_profile,
+            sampling::set_conversation_token_rules,
+            sampling::get_conversation_token_rules,
+            system_prompt::set_model_system_prompt,
+            system_prompt::set_conversation_system_prompt,
+            system_prompt::get_system_prompt,
+            slash_commands::list_slash_commands,
+            slash_commands::expand_slash_command,
+            slash_commands::save_slash_command,
+            keep_alive::get_keep_alive_settings,
+            keep_alive::set_model_idle_timeout,
+            keep_alive::pin_model,
+            keep_alive::unpin_model,
+            keep_alive::list_pinned_models,
+            keep_alive::unload_model,
+            isq::set_model_isq,
+            isq::get_model_isq,
+            safe_mode::get_safe_mode_reason,
+            config::get_config,
+            config::get_config_path,
+            hf_cache::get_hf_cache_info,
+            hf_cache::relocate_hf_cache,
+            windows::new_chat_window,
+            windows::bind_window_conversation,
+            windows::get_window_conversation,
+            history::create_conversation,
+            history::append_message,
+            history::load_attachment,
+            gallery::list_images,
+            gallery::delete_image,
+            history::list_conversations,
+            history::load_conversation,
+            history::merge_conversations,
+            history::split_conversation,
+            history::duplicate_conversation,
+            history::delete_conversation,
+            import::import_chat_history,
+            server::start_lan_share,
+            server::stop_lan_share,
+            server::list_lan_shares,
+            server::get_remote_control_info,
+            server::set_remote_control,
+            server::rotate_remote_token,
+            catalog::list_model_catalog,
+            catalog::check_model_access,
+            catalog::preview_catalog_model,
+            model_card::get_model_card,
+            analytics::export_usage_stats,
+            analytics::clear_usage_stats,
+            analytics::set_usage_stats_enabled,
+            accessibility::get_accessibility_options,
+            accessibility::set_accessibility_options,
+            hardware::get_hardware_profile,
+            hardware::run_hardware_benchmark,
+            compat::get_compatibility_table,
+            estimate::estimate_generation,
+            code_blocks::list_code_blocks,
+            code_blocks::generate_tests,
+            context_packs::create_context_pack,
+            context_packs::list_context_packs,
+            context_packs::refresh_context_pack,
+            context_packs::delete_context_pack,
+            context_packs::attach_context_pack,
+            folder_chat::start_folder_chat,
+            pinned_files::pin_context_file,
+            pinned_files::unpin_context_file,
+            pinned_files::list_pinned_files,
+            pinned_files::resolve_file_change,
+            documents::import_document,
+            documents::list_documents,
+            documents::delete_document,
+            documents::attach_document,
+            documents::detach_document,
+            documents::list_conversation_documents,
+            embeddings::embed_text,
+            assistants::create_assistant,
+            assistants::list_assistants,
+            assistants::delete_assistant,
+            assistants::set_conversation_assistant,
+            backup::backup_now,
+            backup::list_backups,
+            backup::restore_backup,
+            grammar::register_grammar,
+            grammar::list_grammars,
+            grammar::delete_grammar,
+            grammar::set_assistant_grammar,
+            response_style::set_assistant_response_style,
+            glossary::set_glossary_entry,
+            glossary::remove_glossary_entry,
+            glossary::get_glossary,
+            assistants::reset_builtin_templates,
+            web::fetch_url,
+            digest::add_digest_feed,
+            digest::list_digest_feeds,
+            digest::remove_digest_feed,
+            digest::run_digest_now,
+            email::draft_email,
+            tools::list_calendar_events,
+            tools::query_file_metadata,
+            tools::complete_file_path,
+            tools::set_tool_permission,
+            forms::fill_form_template,
+            forms::save_filled_form,
+            language::detect_language,
+            language::set_response_language,
+            profiles::list_profiles,
+            profiles::create_profile,
+            profiles::switch_profile,
+            profiles::set_profile_passphrase,
+            profiles::delete_profile,
+            transcript::set_conversation_transcript,
+            replay::replay_conversation,
+            replay::stop_replay,
+            chaos::inject_faults,
+            chaos::clear_faults,
+            chaos::get_faults,
+            middleware::unlock_sensitive_commands,
+            middleware::lock_sensitive_commands,
+            presentation::set_presentation_mode,
+            presentation::get_presentation_mode,
+            audit::list_audit_log,
+            record_export::export_system_of_record,
+            record_export::verify_system_of_record,
+            shutdown::cancel_generation,
+            trace::get_request_trace,
+            explain::explain_error,
+            checksums::verify_model_checksums,
+            vision_cache::get_vision_cache_stats,
+            vision_cache::clear_vision_cache,
+        ]))
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::ExitRequested { api, .. } = &event {
+                shutdown::handle_exit_requested(app, api);
+            }
+        });
 }
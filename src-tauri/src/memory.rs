@@ -0,0 +1,104 @@
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use sysinfo::System;
+use tauri::Emitter;
+
+use crate::{config, keep_alive, models, shutdown, vision_cache};
+
+pub const MEMORY_PRESSURE_EVENT: &str = "memory-pressure";
+
+// Give the OS time to reclaim memory before acting again
+const ACTION_COOLDOWN: Duration = Duration::from_secs(30);
+
+const MB: u64 = 1024 * 1024;
+
+// Explains what the backend did to relieve memory pressure
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryPressureEvent {
+    pub available_mb: u64,
+    pub total_mb: u64,
+    // "unloaded_model" or "cancelled_generations"
+    pub action: String,
+    pub model_id: Option<String>,
+    pub message: String,
+}
+
+// Polls system memory and reacts before the OS out-of-memory killer does
+pub fn spawn_monitor(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut system = System::new();
+        let mut last_action: Option<Instant> = None;
+
+        loop {
+            let config = config::get().memory;
+            tokio::time::sleep(Duration::from_secs(config.check_interval_secs)).await;
+
+            if config.critical_available_mb == 0 {
+                continue;
+            }
+            if last_action.is_some_and(|at| at.elapsed() < ACTION_COOLDOWN) {
+                continue;
+            }
+
+            system.refresh_memory();
+            let available_mb = system.available_memory() / MB;
+            let total_mb = system.total_memory() / MB;
+
+            if available_mb < config.critical_available_mb {
+                if let Some(event) = relieve_pressure(available_mb, total_mb, &app).await {
+                    println!("Low memory: {}", event.message);
+                    last_action = Some(Instant::now());
+                    if let Err(e) = app.emit(MEMORY_PRESSURE_EVENT, &event) {
+                        println!("Warning: Failed to emit memory-pressure event: {}", e);
+                    }
+                }
+            }
+        }
+    });
+}
+
+// Unloads the least-recently-used idle, unpinned model, or stops running generations if every model is busy
+async fn relieve_pressure(available_mb: u64, total_mb: u64, app: &tauri::AppHandle) -> Option<MemoryPressureEvent> {
+    let manager = models::manager(app);
+    let mut instances = manager.lock().await;
+
+    // A strong count above one means a request is still holding the model
+    let lru_idle = instances
+        .iter()
+        .filter(|(model_id, loaded)| Arc::strong_count(&loaded.model) == 1 && !keep_alive::is_pinned(model_id))
+        .min_by_key(|(_, loaded)| loaded.last_used)
+        .map(|(model_id, _)| model_id.clone());
+
+    if let Some(model_id) = lru_idle {
+        instances.remove(&model_id);
+        vision_cache::forget_model(&model_id);
+        return Some(MemoryPressureEvent {
+            available_mb,
+            total_mb,
+            action: "unloaded_model".to_string(),
+            message: format!(
+                "Only {} MB of memory left; unloaded {} (least recently used) to keep the app running",
+                available_mb, model_id
+            ),
+            model_id: Some(model_id),
+        });
+    }
+    drop(instances);
+
+    let cancelled = shutdown::cancel_all_in_flight();
+    if cancelled == 0 {
+        return None;
+    }
+
+    Some(MemoryPressureEvent {
+        available_mb,
+        total_mb,
+        action: "cancelled_generations".to_string(),
+        model_id: None,
+        message: format!(
+            "Only {} MB of memory left; stopped {} running generation(s) to free their caches",
+            available_mb, cancelled
+        ),
+    })
+}
@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use tauri::Manager;
+
+const METRICS_FILE: &str = "metrics.json";
+
+// Keep a bounded window of recent generations for the frontend
+const MAX_RECENT_GENERATIONS: usize = 100;
+
+// In-memory metrics store fed by the streaming chat pipeline
+static METRICS: OnceLock<Mutex<MetricsStore>> = OnceLock::new();
+
+// Timing summary of a single completed generation
+#[derive(Debug, Clone, Serialize)]
+pub struct GenerationRecord {
+    pub request_id: String,
+    pub model_id: String,
+    pub prompt_tokens: Option<usize>,
+    pub ttft_ms: Option<u64>,
+    pub completion_tokens: usize,
+    pub duration_ms: u64,
+    pub tokens_per_sec: Option<f64>,
+    pub finished_at_ms: u64,
+}
+
+// Aggregated statistics per model
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelMetrics {
+    pub requests: u64,
+    pub completion_tokens: u64,
+    pub decode_ms: u64,
+    pub avg_ttft_ms: Option<f64>,
+    pub avg_tokens_per_sec: Option<f64>,
+    pub last_tokens_per_sec: Option<f64>,
+    // Estimated prompt tokens and time to first token, summed over generations with long prompts
+    pub prefill_tokens: u64,
+    pub prefill_ms: u64,
+    // Completed model loads and their total duration
+    pub loads: u64,
+    pub load_ms: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MetricsSnapshot {
+    pub models: HashMap<String, ModelMetrics>,
+    pub recent: Vec<GenerationRecord>,
+}
+
+// Aggregates persisted across restarts so estimates survive a relaunch
+#[derive(Default, Serialize, Deserialize)]
+struct PersistedMetrics {
+    models: HashMap<String, ModelMetrics>,
+    ttft_samples: HashMap<String, u64>,
+}
+
+#[derive(Default)]
+struct MetricsStore {
+    models: HashMap<String, ModelMetrics>,
+    recent: VecDeque<GenerationRecord>,
+    ttft_samples: HashMap<String, u64>,
+}
+
+fn store() -> &'static Mutex<MetricsStore> {
+    METRICS.get_or_init(|| Mutex::new(MetricsStore::default()))
+}
+
+// Records a finished generation and updates the per-model averages
+pub fn record_generation(record: GenerationRecord) {
+    let mut store = store().lock().unwrap();
+
+    if let Some(ttft) = record.ttft_ms {
+        let samples = store.ttft_samples.entry(record.model_id.clone()).or_insert(0);
+        *samples += 1;
+        let count = *samples as f64;
+        let entry = store.models.entry(record.model_id.clone()).or_default();
+        entry.avg_ttft_ms = Some(match entry.avg_ttft_ms {
+            Some(avg) => avg + (ttft as f64 - avg) / count,
+            None => ttft as f64,
+        });
+    }
+
+    let entry = store.models.entry(record.model_id.clone()).or_default();
+    if let (Some(prompt_tokens), Some(ttft)) = (record.prompt_tokens, record.ttft_ms) {
+        entry.prefill_tokens += prompt_tokens as u64;
+        entry.prefill_ms += ttft;
+    }
+    entry.requests += 1;
+    entry.completion_tokens += record.completion_tokens as u64;
+    entry.decode_ms += record.duration_ms.saturating_sub(record.ttft_ms.unwrap_or(0));
+    entry.last_tokens_per_sec = record.tokens_per_sec;
+    if entry.decode_ms > 0 {
+        entry.avg_tokens_per_sec = Some(entry.completion_tokens as f64 / (entry.decode_ms as f64 / 1000.0));
+    }
+
+    if store.recent.len() >= MAX_RECENT_GENERATIONS {
+        store.recent.pop_front();
+    }
+    store.recent.push_back(record);
+}
+
+// Records how long a model took to load, for load ETAs
+pub fn record_load(model_id: &str, duration_ms: u64) {
+    let mut store = store().lock().unwrap();
+    let entry = store.models.entry(model_id.to_string()).or_default();
+    entry.loads += 1;
+    entry.load_ms += duration_ms;
+}
+
+pub fn avg_load_ms(model_id: &str) -> Option<u64> {
+    let store = store().lock().unwrap();
+    let metrics = store.models.get(model_id)?;
+    (metrics.loads > 0).then(|| metrics.load_ms / metrics.loads)
+}
+
+// Average time from accepting a request to its last token
+pub fn avg_generation_ms(model_id: &str) -> Option<u64> {
+    let store = store().lock().unwrap();
+    let metrics = store.models.get(model_id)?;
+    (metrics.requests > 0).then(|| {
+        let ttft = metrics.avg_ttft_ms.unwrap_or(0.0) as u64;
+        metrics.decode_ms / metrics.requests + ttft
+    })
+}
+
+// Returns the recorded tok/s average for a model, if any generations were seen
+pub fn tokens_per_sec(model_id: &str) -> Option<f64> {
+    store().lock().unwrap().models.get(model_id).and_then(|m| m.avg_tokens_per_sec)
+}
+
+// Prompt processing speed seen so far, used to turn elapsed prefill time into a percentage
+pub fn prefill_tokens_per_sec(model_id: &str) -> Option<f64> {
+    let store = store().lock().unwrap();
+    let metrics = store.models.get(model_id)?;
+    (metrics.prefill_ms > 0).then(|| metrics.prefill_tokens as f64 / (metrics.prefill_ms as f64 / 1000.0))
+}
+
+pub fn snapshot() -> MetricsSnapshot {
+    let store = store().lock().unwrap();
+    MetricsSnapshot {
+        models: store.models.clone(),
+        recent: store.recent.iter().cloned().collect(),
+    }
+}
+
+fn metrics_path(app: &tauri::AppHandle) -> Option<std::path::PathBuf> {
+    app.path().app_data_dir().ok().map(|dir| dir.join(METRICS_FILE))
+}
+
+// Restores per-model aggregates saved by a previous session
+pub fn load(app: &tauri::AppHandle) {
+    let Some(path) = metrics_path(app) else {
+        return;
+    };
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return;
+    };
+
+    match serde_json::from_str::<PersistedMetrics>(&contents) {
+        Ok(persisted) => {
+            let mut store = store().lock().unwrap();
+            store.models = persisted.models;
+            store.ttft_samples = persisted.ttft_samples;
+        }
+        Err(e) => println!("Warning: Ignoring unreadable metrics file: {}", e),
+    }
+}
+
+// Writes per-model aggregates to disk (called on shutdown)
+pub fn flush(app: &tauri::AppHandle) {
+    let Some(path) = metrics_path(app) else {
+        return;
+    };
+
+    let persisted = {
+        let store = store().lock().unwrap();
+        PersistedMetrics {
+            models: store.models.clone(),
+            ttft_samples: store.ttft_samples.clone(),
+        }
+    };
+
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    match serde_json::to_string_pretty(&persisted) {
+        Ok(contents) => {
+            if let Err(e) = std::fs::write(&path, contents) {
+                println!("Warning: Failed to write metrics: {}", e);
+            }
+        }
+        Err(e) => println!("Warning: Failed to serialize metrics: {}", e),
+    }
+}
+
+// Exposes collected TTFT and throughput statistics to the frontend
+#[tauri::command]
+pub fn get_metrics() -> MetricsSnapshot {
+    snapshot()
+}
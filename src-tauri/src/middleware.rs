@@ -0,0 +1,135 @@
+// Checks every command goes through before it runs: payload size, presentation mode, unlock state for
+// sensitive commands, and an audit log entry.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::ipc::{Invoke, InvokeBody};
+
+use crate::{audit, config, kiosk, presentation, profiles};
+
+// Profile that unlocked the sensitive commands, and until when
+static UNLOCKED: Mutex<Option<(String, Instant)>> = Mutex::new(None);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MiddlewareConfig {
+    // Larger command payloads are rejected before the command runs; base64 images count too
+    pub max_payload_bytes: usize,
+    // Refused in kiosk mode and, when the active profile has a passphrase, until it is unlocked
+    pub sensitive_commands: Vec<String>,
+    // How long unlock_sensitive_commands lasts
+    pub unlock_minutes: u64,
+}
+
+impl Default for MiddlewareConfig {
+    fn default() -> Self {
+        Self {
+            max_payload_bytes: 32 * 1024 * 1024,
+            sensitive_commands: [
+                "export_usage_stats",
+                "export_system_of_record",
+                "clear_usage_stats",
+                "relocate_hf_cache",
+                "delete_context_pack",
+                "delete_conversation",
+                "delete_assistant",
+                "delete_profile",
+                "set_profile_passphrase",
+                "restore_backup",
+            ]
+            .iter()
+            .map(|c| c.to_string())
+            .collect(),
+            unlock_minutes: 5,
+        }
+    }
+}
+
+impl MiddlewareConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.max_payload_bytes == 0 {
+            errors.push("middleware.max_payload_bytes must be greater than 0".to_string());
+        }
+        if self.unlock_minutes == 0 {
+            errors.push("middleware.unlock_minutes must be greater than 0".to_string());
+        }
+        errors
+    }
+}
+
+fn is_unlocked() -> bool {
+    let active = profiles::active_id();
+    matches!(&*UNLOCKED.lock().unwrap(), Some((profile, until)) if *profile == active && Instant::now() < *until)
+}
+
+// Rejects a command before it runs; shared by IPC and the remote WebSocket channel
+pub fn check(command: &str, payload_bytes: usize) -> Result<(), String> {
+    let settings = config::get().middleware;
+    if payload_bytes > settings.max_payload_bytes {
+        return Err(format!(
+            "{} payload is {} bytes, over the configured limit of {}",
+            command, payload_bytes, settings.max_payload_bytes
+        ));
+    }
+    presentation::check(command)?;
+    if settings.sensitive_commands.iter().any(|c| c == command) {
+        kiosk::ensure_unlocked(command)?;
+        if profiles::active_is_protected() && !is_unlocked() {
+            return Err(format!("{} requires unlocking with the profile passphrase first", command));
+        }
+    }
+    Ok(())
+}
+
+fn payload_size(body: &InvokeBody) -> usize {
+    match body {
+        InvokeBody::Json(value) => serde_json::to_string(value).map_or(0, |json| json.len()),
+        InvokeBody::Raw(bytes) => bytes.len(),
+    }
+}
+
+// Wraps the generated command handler. Async commands only report back through the resolver, which
+// can't be intercepted, so their entries time the dispatch and have the outcome "dispatched".
+fn handle<R: tauri::Runtime>(invoke: Invoke<R>, commands: &dyn Fn(Invoke<R>) -> bool) -> bool {
+    let command = invoke.message.command().to_string();
+    let source = invoke.message.webview().label().to_string();
+    let payload_bytes = payload_size(invoke.message.payload());
+
+    if let Err(e) = check(&command, payload_bytes) {
+        audit::record(&command, &source, payload_bytes, 0, "rejected", Some(&e));
+        invoke.resolver.reject(e);
+        return true;
+    }
+
+    let started = Instant::now();
+    let handled = commands(invoke);
+    let outcome = if handled { "dispatched" } else { "unknown" };
+    audit::record(&command, &source, payload_bytes, started.elapsed().as_millis() as u64, outcome, None);
+    handled
+}
+
+// Puts the middleware in front of the handler generated by tauri::generate_handler!
+pub fn wrap<R, F>(commands: F) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static
+where
+    R: tauri::Runtime,
+    F: Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+{
+    move |invoke| handle(invoke, &commands)
+}
+
+// Unlocks the sensitive commands for unlock_minutes; needed only when the active profile has a passphrase
+#[tauri::command]
+pub fn unlock_sensitive_commands(passphrase: Option<String>) -> Result<u64, String> {
+    kiosk::ensure_unlocked("Unlocking")?;
+    profiles::check_active_passphrase(passphrase.as_deref())?;
+    let minutes = config::get().middleware.unlock_minutes;
+    *UNLOCKED.lock().unwrap() = Some((profiles::active_id(), Instant::now() + Duration::from_secs(minutes * 60)));
+    Ok(minutes)
+}
+
+#[tauri::command]
+pub fn lock_sensitive_commands() {
+    *UNLOCKED.lock().unwrap() = None;
+}
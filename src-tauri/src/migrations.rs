@@ -0,0 +1,122 @@
+// Versioned schema changes for the history database. Each feature's SCHEMA only creates its tables
+// when they are missing; anything that changes existing tables goes here as a numbered migration.
+// The applied version is kept in SQLite's user_version, and the database is backed up before
+// any migration runs.
+
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+
+use crate::history::{db_err, with_db, HISTORY_DB_FILE};
+use crate::profiles;
+use crate::streaming::now_ms;
+
+pub const BACKUP_DIR: &str = "backups";
+
+// Older backups beyond this many are removed after a successful migration
+const KEEP_BACKUPS: usize = 5;
+
+struct Migration {
+    version: u32,
+    name: &'static str,
+    sql: &'static str,
+}
+
+// Append only: released migrations must never change, or databases that already ran them diverge
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        // The tables created by each feature's SCHEMA
+        sql: "",
+    },
+    Migration {
+        version: 2,
+        name: "index conversations by last update",
+        sql: "CREATE INDEX IF NOT EXISTS idx_conversations_updated ON conversations(updated_at);",
+    },
+    Migration {
+        version: 3,
+        name: "record the request id behind each message",
+        sql: "ALTER TABLE messages ADD COLUMN request_id TEXT;
+              CREATE INDEX IF NOT EXISTS idx_messages_request ON messages(request_id);",
+    },
+];
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+fn user_version(conn: &Connection) -> Result<u32, String> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0)).map_err(db_err)
+}
+
+// A database without conversations has nothing worth backing up
+fn is_empty(conn: &Connection) -> Result<bool, String> {
+    let count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM conversations", [], |row| row.get(0))
+        .map_err(db_err)?;
+    Ok(count == 0)
+}
+
+// Consistent copy of the open database, taken with VACUUM INTO so no other file handle is needed
+fn back_up(conn: &Connection, dir: &Path, from_version: u32) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}.v{}-{}.bak", HISTORY_DB_FILE, from_version, now_ms()));
+    conn.execute("VACUUM INTO ?1", params![path.to_string_lossy()])
+        .map_err(|e| format!("Failed to back up the history database to {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+fn prune_backups(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut backups: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "bak"))
+        .collect();
+    // Oldest first
+    backups.sort_by_key(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok());
+    let excess = backups.len().saturating_sub(KEEP_BACKUPS);
+    for path in backups.into_iter().take(excess) {
+        if let Err(e) = std::fs::remove_file(&path) {
+            println!("Warning: Failed to remove old backup {}: {}", path.display(), e);
+        }
+    }
+}
+
+// Brings the database up to the latest version. Runs after every feature has created its tables,
+// so migrations can change any of them. Each migration commits together with its version bump.
+pub fn run(app: &tauri::AppHandle) -> Result<(), String> {
+    let backup_dir = profiles::data_dir(app)?.join(BACKUP_DIR);
+    with_db(|conn| {
+        let current = user_version(conn)?;
+        let latest = latest_version();
+        if current > latest {
+            return Err(format!(
+                "The history database is at version {}, but this build only knows up to version {}; \
+                 it was written by a newer version of the app",
+                current, latest
+            ));
+        }
+        if current == latest {
+            return Ok(());
+        }
+
+        if !is_empty(conn)? {
+            let backup = back_up(conn, &backup_dir, current)?;
+            println!("Backed up the history database to {} before migrating", backup.display());
+        }
+        for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+            let tx = conn.transaction().map_err(db_err)?;
+            tx.execute_batch(migration.sql)
+                .and_then(|_| tx.pragma_update(None, "user_version", migration.version))
+                .map_err(|e| format!("Migration {} ({}) failed: {}", migration.version, migration.name, e))?;
+            tx.commit().map_err(db_err)?;
+            println!("Applied history database migration {}: {}", migration.version, migration.name);
+        }
+        prune_backups(&backup_dir);
+        Ok(())
+    })
+}
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::shutdown::InFlightGuard;
+use crate::streaming::StreamTracker;
+use crate::{config, ModelInfo, StreamOutcome};
+
+// Offered by discover_models in debug builds only
+pub const MOCK_MODEL_ID: &str = "mock";
+
+const LOREM: [&str; 24] = [
+    "lorem", "ipsum", "dolor", "sit", "amet", "consectetur", "adipiscing", "elit", "sed", "do", "eiusmod",
+    "tempor", "incididunt", "ut", "labore", "et", "dolore", "magna", "aliqua", "enim", "ad", "minim",
+    "veniam", "quis",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MockConfig {
+    // Simulated prompt processing time before the first token
+    pub first_token_delay_ms: u64,
+    pub token_delay_ms: u64,
+    // Words in each canned reply
+    pub response_words: usize,
+    // Chance (0.0 - 1.0) that a request fails before streaming anything
+    pub failure_rate: f64,
+    // Fail mid-stream after this many tokens, to exercise partial-output handling
+    pub fail_after_tokens: Option<usize>,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            first_token_delay_ms: 300,
+            token_delay_ms: 40,
+            response_words: 80,
+            failure_rate: 0.0,
+            fail_after_tokens: None,
+        }
+    }
+}
+
+impl MockConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !(0.0..=1.0).contains(&self.failure_rate) {
+            errors.push(format!("mock.failure_rate must be between 0 and 1 (got {})", self.failure_rate));
+        }
+        if self.response_words == 0 {
+            errors.push("mock.response_words must be at least 1".to_string());
+        }
+        errors
+    }
+}
+
+pub fn is_enabled() -> bool {
+    cfg!(debug_assertions)
+}
+
+pub fn is_mock(model_id: &str) -> bool {
+    is_enabled() && model_id == MOCK_MODEL_ID
+}
+
+pub fn model_info() -> ModelInfo {
+    ModelInfo {
+        id: MOCK_MODEL_ID.to_string(),
+        name: "Mock model (dev)".to_string(),
+        description: "Streams canned lorem ipsum replies without loading a model".to_string(),
+        model_type: "mock".to_string(),
+        size_estimate: None,
+        size_bytes: None,
+        is_available: true,
+        repo: None,
+        files: Vec::new(),
+        is_vision: false,
+        is_audio: false,
+        architecture: None,
+        context_length: None,
+        license: None,
+        gated: false,
+        unavailable_reason: None,
+        incompatible: false,
+        quantizations: Vec::new(),
+        quantization: None,
+    }
+}
+
+// uuid v4 bytes are random enough to decide whether to inject a failure
+fn roll() -> f64 {
+    let bytes = uuid::Uuid::new_v4().into_bytes();
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64 / u32::MAX as f64
+}
+
+// The reply as a list of tokens: an echo of the prompt followed by lorem ipsum
+fn tokens(prompt: &str, words: usize) -> Vec<String> {
+    let excerpt: String = prompt.split_whitespace().take(8).collect::<Vec<_>>().join(" ");
+    let mut tokens = vec![format!("(mock reply to \"{}\")", excerpt)];
+    tokens.extend((0..words).map(|i| format!(" {}", LOREM[i % LOREM.len()])));
+    tokens.push(".".to_string());
+    tokens
+}
+
+fn check_failure(settings: &MockConfig) -> Result<(), String> {
+    if settings.failure_rate > 0.0 && roll() < settings.failure_rate {
+        return Err("Mock model failure (injected by mock.failure_rate)".to_string());
+    }
+    Ok(())
+}
+
+// Streams a canned reply into the tracker with the configured latency and failures
+pub async fn stream(prompt: &str, tracker: &mut StreamTracker, guard: &InFlightGuard) -> Result<StreamOutcome, String> {
+    let settings = config::get().mock;
+    tokio::time::sleep(Duration::from_millis(settings.first_token_delay_ms)).await;
+    check_failure(&settings)?;
+
+    for (index, token) in tokens(prompt, settings.response_words).iter().enumerate() {
+        if guard.is_cancelled() {
+            return Ok(StreamOutcome::Cancelled);
+        }
+        if settings.fail_after_tokens == Some(index) {
+            return Err(format!("Mock model disconnected after {} tokens (mock.fail_after_tokens)", index));
+        }
+        tracker.push_chunk(token);
+        tokio::time::sleep(Duration::from_millis(settings.token_delay_ms)).await;
+    }
+    Ok(StreamOutcome::Completed)
+}
+
+// Non-streamed counterpart used by generate_text
+pub async fn generate(prompt: &str) -> Result<String, String> {
+    let settings = config::get().mock;
+    tokio::time::sleep(Duration::from_millis(settings.first_token_delay_ms)).await;
+    check_failure(&settings)?;
+    Ok(tokens(prompt, settings.response_words).concat())
+}
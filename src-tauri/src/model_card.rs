@@ -0,0 +1,247 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::Manager;
+
+use crate::{catalog, kiosk, local_model_dir};
+
+const CARDS_DIR: &str = "model_cards";
+
+// Extracted sections are cut to this many characters to keep the details panel readable
+const MAX_SECTION_CHARS: usize = 4000;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+
+// Lower-cased heading fragments that identify each section
+const INTENDED_USE_HEADINGS: [&str; 4] = ["intended use", "use case", "direct use", "uses"];
+const LIMITATION_HEADINGS: [&str; 3] = ["limitation", "risk", "bias"];
+const PROMPT_FORMAT_HEADINGS: [&str; 4] = ["prompt format", "prompt template", "chat template", "instruction format"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelCard {
+    pub model_id: String,
+    pub repo: Option<String>,
+    // "local" (README next to the weights), "cache" or "huggingface"
+    pub source: String,
+    pub license: Option<String>,
+    pub tags: Vec<String>,
+    // First paragraph of the card
+    pub summary: Option<String>,
+    pub intended_use: Option<String>,
+    pub limitations: Option<String>,
+    pub prompt_format: Option<String>,
+    // Every heading, so the panel can offer the full card
+    pub headings: Vec<String>,
+    pub markdown: String,
+}
+
+// Hugging Face repo a model's card comes from
+fn repo_for(model_id: &str) -> Option<String> {
+    if let Some(remote) = catalog::REMOTE_MODELS.iter().find(|m| m.id == model_id) {
+        return Some(remote.repo.to_string());
+    }
+    if let Some(directory) = model_id.strip_prefix("local-") {
+        return catalog::lookup(directory).map(|entry| entry.repo.to_string());
+    }
+    // Plain "owner/name" repo ids are accepted as well
+    model_id.contains('/').then(|| model_id.to_string())
+}
+
+fn cache_path(app: &tauri::AppHandle, repo: &str) -> Option<PathBuf> {
+    let dir = app.path().app_cache_dir().ok()?.join(CARDS_DIR);
+    Some(dir.join(format!("{}.md", repo.replace('/', "__"))))
+}
+
+async fn fetch_readme(repo: &str, app: &tauri::AppHandle) -> Result<String, String> {
+    kiosk::ensure_unlocked("Model cards")?;
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut request = client.get(format!("https://huggingface.co/{}/raw/main/README.md", repo));
+    if let Some(token) = catalog::hf_token(app) {
+        request = request.bearer_auth(token);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach Hugging Face: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Hugging Face returned HTTP {} for the model card of {}", response.status().as_u16(), repo));
+    }
+    response.text().await.map_err(|e| format!("Failed to read the model card of {}: {}", repo, e))
+}
+
+// Splits off the YAML front matter Hugging Face cards start with
+fn split_front_matter(markdown: &str) -> (&str, &str) {
+    let Some(rest) = markdown.strip_prefix("---") else {
+        return ("", markdown);
+    };
+    match rest.find("\n---") {
+        Some(end) => {
+            let body = &rest[end + 4..];
+            (&rest[..end], body.strip_prefix('\n').unwrap_or(body))
+        }
+        None => ("", markdown),
+    }
+}
+
+// Reads `license:` and the `tags:` list; the rest of the metadata isn't shown
+fn parse_front_matter(front: &str) -> (Option<String>, Vec<String>) {
+    let mut license = None;
+    let mut tags = Vec::new();
+    let mut in_tags = false;
+    for line in front.lines() {
+        if let Some(item) = line.trim_start().strip_prefix("- ").filter(|_| in_tags) {
+            tags.push(item.trim().trim_matches('"').to_string());
+            continue;
+        }
+        in_tags = false;
+        if let Some(value) = line.strip_prefix("license:") {
+            license = Some(value.trim().trim_matches('"').to_string()).filter(|v| !v.is_empty());
+        } else if let Some(value) = line.strip_prefix("tags:") {
+            let value = value.trim();
+            if value.is_empty() {
+                in_tags = true;
+            } else {
+                // Inline form: tags: [a, b]
+                tags.extend(
+                    value
+                        .trim_matches(|c| c == '[' || c == ']')
+                        .split(',')
+                        .map(|t| t.trim().trim_matches('"').to_string())
+                        .filter(|t| !t.is_empty()),
+                );
+            }
+        }
+    }
+    (license, tags)
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let title = line[level..].strip_prefix(' ')?;
+    Some((level, title.trim()))
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_SECTION_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+// Body of the first section whose heading contains one of `names`, up to the next heading of the
+// same or a higher level
+fn section(body: &str, names: &[&str]) -> Option<String> {
+    let lines: Vec<&str> = body.lines().collect();
+    let mut in_fence = false;
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        let Some((level, title)) = heading(line).filter(|_| !in_fence) else {
+            continue;
+        };
+        let title = title.to_lowercase();
+        if !names.iter().any(|name| title.contains(name)) {
+            continue;
+        }
+
+        let mut content = Vec::new();
+        let mut fenced = false;
+        for line in &lines[i + 1..] {
+            if line.trim_start().starts_with("```") {
+                fenced = !fenced;
+            }
+            if !fenced && heading(line).is_some_and(|(next, _)| next <= level) {
+                break;
+            }
+            content.push(*line);
+        }
+        let content = content.join("\n").trim().to_string();
+        if !content.is_empty() {
+            return Some(truncate(&content));
+        }
+    }
+    None
+}
+
+// First prose paragraph, skipping headings, images, HTML and tables
+fn summary(body: &str) -> Option<String> {
+    body.split("\n\n")
+        .map(str::trim)
+        .find(|p| {
+            !p.is_empty()
+                && !p.starts_with('#')
+                && !p.starts_with('!')
+                && !p.starts_with('<')
+                && !p.starts_with('|')
+                && !p.starts_with("```")
+        })
+        .map(truncate)
+}
+
+fn parse(model_id: &str, repo: Option<String>, source: &str, markdown: String) -> ModelCard {
+    let (front, body) = split_front_matter(&markdown);
+    let (license, tags) = parse_front_matter(front);
+    let mut in_fence = false;
+    let headings = body
+        .lines()
+        .filter(|line| {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+            }
+            !in_fence
+        })
+        .filter_map(|line| heading(line).map(|(_, title)| title.to_string()))
+        .collect();
+
+    ModelCard {
+        model_id: model_id.to_string(),
+        repo,
+        source: source.to_string(),
+        license,
+        tags,
+        summary: summary(body),
+        intended_use: section(body, &INTENDED_USE_HEADINGS),
+        limitations: section(body, &LIMITATION_HEADINGS),
+        prompt_format: section(body, &PROMPT_FORMAT_HEADINGS),
+        headings,
+        markdown,
+    }
+}
+
+// Model card of a local, catalog or remote model: the README next to the weights if there is one,
+// otherwise the repo's card from Hugging Face, cached on disk until `refresh` is set
+#[tauri::command]
+pub async fn get_model_card(model_id: String, refresh: Option<bool>, app: tauri::AppHandle) -> Result<ModelCard, String> {
+    if let Some(readme) = local_model_dir(&model_id, &app).map(|dir| dir.join("README.md")) {
+        if let Ok(markdown) = std::fs::read_to_string(&readme) {
+            return Ok(parse(&model_id, repo_for(&model_id), "local", markdown));
+        }
+    }
+
+    let repo = repo_for(&model_id).ok_or_else(|| format!("No Hugging Face repository is known for {}", model_id))?;
+    let cache = cache_path(&app, &repo);
+    if !refresh.unwrap_or(false) {
+        if let Some(markdown) = cache.as_ref().and_then(|path| std::fs::read_to_string(path).ok()) {
+            return Ok(parse(&model_id, Some(repo), "cache", markdown));
+        }
+    }
+
+    let markdown = fetch_readme(&repo, &app).await?;
+    if let Some(path) = &cache {
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, &markdown));
+        if let Err(e) = written {
+            println!("Warning: Failed to cache the model card of {}: {}", repo, e);
+        }
+    }
+    Ok(parse(&model_id, Some(repo), "huggingface", markdown))
+}
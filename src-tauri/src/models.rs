@@ -0,0 +1,86 @@
+// Loaded models, held in Tauri's managed state so every window and background task shares one
+// cache, and shutdown can drop the models before the process exits. The manager is a plain value,
+// so code that needs an isolated cache can construct its own.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use serde::Serialize;
+use std::time::Instant;
+use tauri::{Emitter, Manager};
+use tokio::sync::{Mutex, MutexGuard};
+
+pub const MODEL_LOAD_EVENT: &str = "model-load";
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadStatus {
+    Started,
+    Loaded,
+    Failed,
+}
+
+// Payload of model-load events, tagged with the request that triggered the load
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelLoadEvent {
+    pub request_id: String,
+    pub model_id: String,
+    pub status: LoadStatus,
+    pub error: Option<String>,
+}
+
+// A cached model together with the last time a request used it
+pub(crate) struct LoadedModel {
+    pub model: Arc<mistralrs::Model>,
+    pub last_used: Instant,
+    // Size of the weights on disk; None for remote models
+    pub size_bytes: Option<u64>,
+}
+
+#[derive(Default)]
+pub(crate) struct ModelManager {
+    instances: Mutex<HashMap<String, LoadedModel>>,
+}
+
+impl ModelManager {
+    // Loading, evicting and unloading happen under this lock, so two requests never load the
+    // same model twice
+    pub async fn lock(&self) -> MutexGuard<'_, HashMap<String, LoadedModel>> {
+        self.instances.lock().await
+    }
+
+    pub async fn is_loaded(&self, model_id: &str) -> bool {
+        self.instances.lock().await.contains_key(model_id)
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.instances.lock().await.is_empty()
+    }
+
+    // Marks a cached model as recently used so the idle reaper leaves it alone
+    pub async fn touch(&self, model_id: &str) {
+        if let Some(loaded) = self.instances.lock().await.get_mut(model_id) {
+            loaded.last_used = Instant::now();
+        }
+    }
+
+    // Drops every model; requests still holding one keep it alive until they finish
+    pub async fn clear(&self) {
+        self.instances.lock().await.clear();
+    }
+}
+
+pub(crate) fn manager(app: &tauri::AppHandle) -> tauri::State<'_, ModelManager> {
+    app.state::<ModelManager>()
+}
+
+pub(crate) fn emit_load(app: &tauri::AppHandle, request_id: &str, model_id: &str, status: LoadStatus, error: Option<&str>) {
+    let event = ModelLoadEvent {
+        request_id: request_id.to_string(),
+        model_id: model_id.to_string(),
+        status,
+        error: error.map(str::to_string),
+    };
+    if let Err(e) = app.emit(MODEL_LOAD_EVENT, event) {
+        println!("Warning: Failed to emit the model-load event: {}", e);
+    }
+}
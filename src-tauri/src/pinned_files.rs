@@ -0,0 +1,232 @@
+// Files pinned to a conversation as context. Each pin keeps the text it was pinned (or last
+// refreshed) with and its SHA-256, and that text leads every prompt of the conversation. Before
+// each turn the files are hashed again; when one changed on disk, a context-file-changed event
+// asks the frontend whether to refresh it. Until it answers, the pinned text is used as is, so a
+// long coding session never silently mixes an old and a new version of a file.
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tauri::Emitter;
+
+use crate::context_packs::read_text_file;
+use crate::db_writer;
+use crate::estimate::estimate_tokens;
+use crate::history::{db_err, with_db};
+use crate::kiosk;
+use crate::streaming::now_ms;
+
+pub const CONTEXT_FILE_CHANGED_EVENT: &str = "context-file-changed";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS pinned_files (
+    conversation_id TEXT NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    content TEXT NOT NULL,
+    sha256 TEXT NOT NULL,
+    -- A version on disk the user chose not to pick up, so they aren't asked about it again
+    dismissed_sha256 TEXT,
+    pinned_at INTEGER NOT NULL,
+    refreshed_at INTEGER NOT NULL,
+    PRIMARY KEY (conversation_id, path)
+);
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileState {
+    // The file on disk matches the pinned text
+    Current,
+    Changed,
+    // Deleted, moved, or no longer readable as text
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PinnedFile {
+    pub path: String,
+    pub sha256: String,
+    pub tokens: usize,
+    pub pinned_at: u64,
+    pub refreshed_at: u64,
+    pub state: FileState,
+}
+
+// Payload of context-file-changed events
+#[derive(Debug, Clone, Serialize)]
+pub struct FileChanged {
+    pub conversation_id: String,
+    pub path: String,
+    // The chat turn that noticed the change
+    pub request_id: String,
+    pub pinned_sha256: String,
+    // None when the file is missing
+    pub current_sha256: Option<String>,
+}
+
+struct Pin {
+    path: String,
+    content: String,
+    sha256: String,
+    dismissed_sha256: Option<String>,
+    pinned_at: u64,
+    refreshed_at: u64,
+}
+
+pub fn init() -> Result<(), String> {
+    with_db(|conn| conn.execute_batch(SCHEMA).map_err(db_err))
+}
+
+fn sha256(text: &str) -> String {
+    hex::encode(Sha256::digest(text.as_bytes()))
+}
+
+// The file's text as it is on disk now, or None when it can't be read
+fn read(path: &str) -> Option<String> {
+    read_text_file(Path::new(path))
+}
+
+fn pins(conn: &Connection, conversation_id: &str) -> Result<Vec<Pin>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT path, content, sha256, dismissed_sha256, pinned_at, refreshed_at
+             FROM pinned_files WHERE conversation_id = ?1 ORDER BY pinned_at, path",
+        )
+        .map_err(db_err)?;
+    let rows = stmt
+        .query_map(params![conversation_id], |row| {
+            Ok(Pin {
+                path: row.get(0)?,
+                content: row.get(1)?,
+                sha256: row.get(2)?,
+                dismissed_sha256: row.get(3)?,
+                pinned_at: row.get::<_, i64>(4)? as u64,
+                refreshed_at: row.get::<_, i64>(5)? as u64,
+            })
+        })
+        .map_err(db_err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+}
+
+fn describe(pin: &Pin, current: Option<&str>) -> PinnedFile {
+    let state = match current {
+        None => FileState::Missing,
+        Some(current) if current == pin.sha256 => FileState::Current,
+        Some(_) => FileState::Changed,
+    };
+    PinnedFile {
+        path: pin.path.clone(),
+        sha256: pin.sha256.clone(),
+        tokens: estimate_tokens(&pin.content),
+        pinned_at: pin.pinned_at,
+        refreshed_at: pin.refreshed_at,
+        state,
+    }
+}
+
+// Prepends the conversation's pinned files to a message, and asks about files that changed since
+// they were pinned
+pub fn augment(conversation_id: Option<&str>, message: &str, request_id: &str, app: &tauri::AppHandle) -> Result<String, String> {
+    let Some(conversation_id) = conversation_id else {
+        return Ok(message.to_string());
+    };
+    let pins = with_db(|conn| pins(conn, conversation_id))?;
+    if pins.is_empty() {
+        return Ok(message.to_string());
+    }
+
+    let mut context = String::new();
+    for pin in &pins {
+        let current = read(&pin.path).map(|text| sha256(&text));
+        let unasked = current.as_deref() != Some(pin.sha256.as_str())
+            && (current.is_none() || current != pin.dismissed_sha256);
+        if unasked {
+            let changed = FileChanged {
+                conversation_id: conversation_id.to_string(),
+                path: pin.path.clone(),
+                request_id: request_id.to_string(),
+                pinned_sha256: pin.sha256.clone(),
+                current_sha256: current,
+            };
+            if let Err(e) = app.emit(CONTEXT_FILE_CHANGED_EVENT, changed) {
+                println!("Warning: Failed to emit the context-file-changed event: {}", e);
+            }
+        }
+        context.push_str(&format!("--- {} ---\n{}\n\n", pin.path, pin.content));
+    }
+    Ok(format!("Pinned files:\n\n{}{}", context, message))
+}
+
+// Pins a text file to a conversation, or re-pins it with its current content
+#[tauri::command]
+pub async fn pin_context_file(conversation_id: String, path: String) -> Result<PinnedFile, String> {
+    kiosk::ensure_unlocked("Pinning files")?;
+    let content = read(&path).ok_or_else(|| format!("Cannot read text file {}", path))?;
+    let sha256 = sha256(&content);
+    let now = now_ms();
+    let pin = Pin { path, content, sha256, dismissed_sha256: None, pinned_at: now, refreshed_at: now };
+    let pinned = describe(&pin, Some(&pin.sha256));
+    db_writer::write(move |conn| {
+        conn.execute(
+            "INSERT INTO pinned_files (conversation_id, path, content, sha256, pinned_at, refreshed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+             ON CONFLICT(conversation_id, path) DO UPDATE SET
+                content = excluded.content, sha256 = excluded.sha256, dismissed_sha256 = NULL,
+                refreshed_at = excluded.refreshed_at",
+            params![conversation_id, pin.path, pin.content, pin.sha256, now as i64],
+        )
+        .map_err(db_err)?;
+        Ok(())
+    })
+    .await?;
+    Ok(pinned)
+}
+
+#[tauri::command]
+pub async fn unpin_context_file(conversation_id: String, path: String) -> Result<(), String> {
+    db_writer::write(move |conn| {
+        let removed = conn
+            .execute("DELETE FROM pinned_files WHERE conversation_id = ?1 AND path = ?2", params![conversation_id, path])
+            .map_err(db_err)?;
+        if removed == 0 {
+            return Err(format!("{} is not pinned to conversation {}", path, conversation_id));
+        }
+        Ok(())
+    })
+    .await
+}
+
+// The conversation's pinned files and whether each still matches the file on disk
+#[tauri::command]
+pub async fn list_pinned_files(conversation_id: String) -> Result<Vec<PinnedFile>, String> {
+    let pins = with_db(|conn| pins(conn, &conversation_id))?;
+    Ok(pins
+        .iter()
+        .map(|pin| describe(pin, read(&pin.path).map(|text| sha256(&text)).as_deref()))
+        .collect())
+}
+
+// Answers a context-file-changed event: `refresh` takes the file's current content into the
+// conversation, otherwise the pinned text stays and this version of the file isn't asked about again
+#[tauri::command]
+pub async fn resolve_file_change(conversation_id: String, path: String, refresh: bool) -> Result<PinnedFile, String> {
+    if refresh {
+        return pin_context_file(conversation_id, path).await;
+    }
+    let current = read(&path).map(|text| sha256(&text));
+    let dismissed = current.clone();
+    let pin = db_writer::write(move |conn| {
+        conn.execute(
+            "UPDATE pinned_files SET dismissed_sha256 = ?1 WHERE conversation_id = ?2 AND path = ?3",
+            params![dismissed, conversation_id, path],
+        )
+        .map_err(db_err)?;
+        pins(conn, &conversation_id)?
+            .into_iter()
+            .find(|pin| pin.path == path)
+            .ok_or_else(|| format!("{} is not pinned to conversation {}", path, conversation_id))
+    })
+    .await?;
+    Ok(describe(&pin, current.as_deref()))
+}
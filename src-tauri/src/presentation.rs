@@ -0,0 +1,223 @@
+// Presentation mode, for screen sharing or handing the app to someone for a while. Destructive and
+// settings commands are refused, model downloads are off like in offline mode, and history comes
+// back with email addresses, phone numbers and attachment locations hidden. It lives in memory
+// only, so a restart ends it; turning it off takes the active profile's passphrase, if it has one.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::history::{Conversation, ConversationDetail};
+use crate::{config, kiosk, profiles};
+
+const HIDDEN: &str = "[hidden]";
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PresentationConfig {
+    // Commands refused while presentation mode is on; a trailing "*" matches every command with that prefix
+    pub blocked_commands: Vec<String>,
+}
+
+impl Default for PresentationConfig {
+    fn default() -> Self {
+        Self {
+            blocked_commands: [
+                "delete_*",
+                "clear_*",
+                "remove_*",
+                "add_*",
+                "attach_*",
+                "detach_*",
+                "set_*",
+                "save_*",
+                "reset_*",
+                "restore_*",
+                "relocate_*",
+                "rotate_*",
+                "import_*",
+                "export_*",
+                "register_*",
+                "pin_*",
+                "unpin_*",
+                "merge_conversations",
+                "split_conversation",
+                "resolve_file_change",
+                "create_profile",
+                "switch_profile",
+                "unlock_sensitive_commands",
+                "preview_catalog_model",
+                "inject_faults",
+                // Buffered and recorded streams and traces hold the original text, which can't be masked
+                // chunk by chunk
+                "resume_stream",
+                "replay_conversation",
+                "get_request_trace",
+                "backup_now",
+                "start_lan_share",
+                "get_config",
+                "get_config_path",
+                "get_remote_control_info",
+                "list_audit_log",
+            ]
+            .iter()
+            .map(|c| c.to_string())
+            .collect(),
+        }
+    }
+}
+
+impl PresentationConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.blocked_commands.iter().any(|c| c.trim_end_matches('*').trim().is_empty()) {
+            errors.push("presentation.blocked_commands must not contain empty entries or a bare \"*\"".to_string());
+        }
+        errors
+    }
+}
+
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+fn is_blocked(command: &str, blocked: &[String]) -> bool {
+    blocked.iter().any(|entry| match entry.strip_suffix('*') {
+        Some(prefix) => command.starts_with(prefix),
+        None => entry == command,
+    })
+}
+
+// Rejects a blocked command while presentation mode is on; called by the middleware
+pub fn check(command: &str) -> Result<(), String> {
+    // Never blocked, or there would be no way back
+    if !is_active() || command == "set_presentation_mode" {
+        return Ok(());
+    }
+    if is_blocked(command, &config::get().presentation.blocked_commands) {
+        return Err(format!("{} is disabled in presentation mode", command));
+    }
+    Ok(())
+}
+
+// Whether a run of digits and separators is shaped like a phone number rather than a date, a version
+// or a plain number: 7 to 15 digits, and either a leading "+" or "(" or several digit groups that end
+// in one of at least three digits, as in "555-123-4567"
+fn looks_like_phone_number(run: &str) -> bool {
+    let groups: Vec<usize> = run.split(|c: char| !c.is_ascii_digit()).map(str::len).filter(|&n| n > 0).collect();
+    let digits: usize = groups.iter().sum();
+    // Day, month and year, as in "15.01.2024"
+    if !(7..=15).contains(&digits) || matches!(groups.as_slice(), [1..=2, 1..=2, 4] | [4, 1..=2, 1..=2]) {
+        return false;
+    }
+    run.trim_start().starts_with(['+', '(']) || (groups.len() >= 2 && groups.last().is_some_and(|&n| n >= 3))
+}
+
+fn mask_phone_numbers_in_line(line: &str, out: &mut String) {
+    fn flush(run: &mut String, out: &mut String) {
+        if looks_like_phone_number(run) {
+            // Separators around the number stay, so the surrounding text keeps its spacing
+            let start = run.find(|c: char| c.is_ascii_digit() || c == '+' || c == '(').unwrap_or(0);
+            let end = run.rfind(|c: char| c.is_ascii_digit()).map_or(run.len(), |i| i + 1);
+            out.push_str(&run[..start]);
+            out.push_str(HIDDEN);
+            out.push_str(&run[end..]);
+        } else {
+            out.push_str(run);
+        }
+        run.clear();
+    }
+
+    let mut run = String::new();
+    for c in line.chars() {
+        if c.is_ascii_digit() || matches!(c, ' ' | '-' | '.' | '(' | ')' | '+') {
+            run.push(c);
+        } else {
+            flush(&mut run, out);
+            out.push(c);
+        }
+    }
+    flush(&mut run, out);
+}
+
+// Numbers inside fenced code blocks are left alone; they are literals, not someone's phone
+fn mask_phone_numbers(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_fence = false;
+    for line in text.split_inclusive('\n') {
+        let fence = line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~");
+        if fence {
+            in_fence = !in_fence;
+        }
+        if fence || in_fence {
+            out.push_str(line);
+        } else {
+            mask_phone_numbers_in_line(line, &mut out);
+        }
+    }
+    out
+}
+
+fn mask_emails(text: &str) -> String {
+    text.split_inclusive(char::is_whitespace)
+        .map(|piece| {
+            let word = piece.trim_end();
+            if !kiosk::looks_like_email(word) {
+                return piece.to_string();
+            }
+            // Keep punctuation around the address, like the brackets of "<jane@example.com>,"
+            let start = word.find(char::is_alphanumeric).unwrap_or(0);
+            let end = word
+                .char_indices()
+                .rfind(|(_, c)| c.is_alphanumeric())
+                .map_or(word.len(), |(i, c)| i + c.len_utf8());
+            format!("{}{}{}{}", &word[..start], HIDDEN, &word[end..], &piece[word.len()..])
+        })
+        .collect()
+}
+
+pub fn redact(text: &str) -> String {
+    mask_phone_numbers(&mask_emails(text))
+}
+
+// Only the file name of an attachment; its directory usually gives away the user name
+pub fn redact_path(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map_or_else(|| HIDDEN.to_string(), |name| name.to_string_lossy().into_owned())
+}
+
+pub fn redact_conversation(conversation: &mut Conversation) {
+    conversation.title = redact(&conversation.title);
+}
+
+pub fn redact_detail(detail: &mut ConversationDetail) {
+    redact_conversation(&mut detail.conversation);
+    for message in &mut detail.messages {
+        message.content = redact(&message.content);
+        for attachment in &mut message.attachments {
+            attachment.path = redact_path(&attachment.path);
+        }
+    }
+    if let Some(recap) = &mut detail.recap {
+        recap.summary = redact(&recap.summary);
+    }
+}
+
+// Turning presentation mode on needs nothing; turning it off needs the active profile's passphrase
+#[tauri::command]
+pub fn set_presentation_mode(enabled: bool, passphrase: Option<String>) -> Result<bool, String> {
+    if !enabled && is_active() {
+        profiles::check_active_passphrase(passphrase.as_deref())?;
+    }
+    ACTIVE.store(enabled, Ordering::Relaxed);
+    println!("Presentation mode {}", if enabled { "on" } else { "off" });
+    Ok(enabled)
+}
+
+#[tauri::command]
+pub fn get_presentation_mode() -> bool {
+    is_active()
+}
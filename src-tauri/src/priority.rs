@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::config;
+use crate::shutdown::InFlightGuard;
+
+// How often a waiting background job checks whether interactive chat has finished
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Interactive generations currently running
+static INTERACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    // Chat the user is watching
+    #[default]
+    Interactive,
+    // Batch runs, indexing and scheduled digests
+    Background,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PriorityConfig {
+    // Whether background generations pause while an interactive chat is streaming
+    pub yield_to_interactive: bool,
+    // Longest a background job waits in one go, so a busy chat can't starve it; 0 waits indefinitely
+    pub max_wait_secs: u64,
+}
+
+impl Default for PriorityConfig {
+    fn default() -> Self {
+        Self {
+            yield_to_interactive: true,
+            max_wait_secs: 120,
+        }
+    }
+}
+
+// Held for the duration of a generation; interactive ones make background work wait
+pub struct PriorityGuard {
+    priority: Priority,
+}
+
+impl Drop for PriorityGuard {
+    fn drop(&mut self) {
+        if self.priority == Priority::Interactive {
+            INTERACTIVE.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl PriorityGuard {
+    // Called between chunks; a background stream stops consuming while chat is streaming, until
+    // `request` is cancelled
+    pub async fn yield_now(&self, request: &InFlightGuard) {
+        if self.priority == Priority::Background {
+            wait_for_interactive(request).await;
+        }
+    }
+}
+
+pub fn interactive_count() -> usize {
+    INTERACTIVE.load(Ordering::SeqCst)
+}
+
+async fn wait_for_interactive(request: &InFlightGuard) {
+    let settings = config::get().priority;
+    if !settings.yield_to_interactive {
+        return;
+    }
+    let started = Instant::now();
+    // A cancel, or shutdown cancelling everything, ends the wait; the caller then sees it cancelled
+    while interactive_count() > 0 && !request.is_cancelled() {
+        if settings.max_wait_secs > 0 && started.elapsed() >= Duration::from_secs(settings.max_wait_secs) {
+            println!("Background generation resuming after waiting {}s for chat", settings.max_wait_secs);
+            return;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+// Registers a generation; background ones first wait until no interactive chat is running or
+// `request` is cancelled
+pub async fn begin(priority: Priority, request: &InFlightGuard) -> PriorityGuard {
+    match priority {
+        Priority::Interactive => {
+            INTERACTIVE.fetch_add(1, Ordering::SeqCst);
+        }
+        Priority::Background => wait_for_interactive(request).await,
+    }
+    PriorityGuard { priority }
+}
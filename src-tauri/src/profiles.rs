@@ -0,0 +1,293 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
+use tauri::{Emitter, Manager};
+
+use crate::streaming::now_ms;
+use crate::{config, kiosk};
+
+const PROFILES_FILE: &str = "profiles.json";
+
+// Non-default profiles live in <app dir>/profiles/<id>; models and the HF cache stay shared
+const PROFILES_DIR: &str = "profiles";
+
+// Uses the app's original data and config directories, so existing installs keep their history
+pub const DEFAULT_PROFILE: &str = "default";
+
+pub const PROFILE_CHANGED_EVENT: &str = "profile_changed";
+
+static STORE: OnceLock<RwLock<ProfileStore>> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredProfile {
+    id: String,
+    name: String,
+    created_at: u64,
+    // Argon2 PHC string; this gates switching in the app, it does not encrypt the profile's files
+    passphrase_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProfileStore {
+    active: String,
+    profiles: Vec<StoredProfile>,
+}
+
+impl Default for ProfileStore {
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_PROFILE.to_string(),
+            profiles: vec![StoredProfile {
+                id: DEFAULT_PROFILE.to_string(),
+                name: "Default".to_string(),
+                created_at: now_ms(),
+                passphrase_hash: None,
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+    pub created_at: u64,
+    pub protected: bool,
+    pub active: bool,
+}
+
+fn cell() -> &'static RwLock<ProfileStore> {
+    STORE.get_or_init(|| RwLock::new(ProfileStore::default()))
+}
+
+fn store_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(PROFILES_FILE))
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))
+}
+
+fn save(app: &tauri::AppHandle, store: &ProfileStore) -> Result<(), String> {
+    let path = store_path(app)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let contents = serde_json::to_string_pretty(store).map_err(|e| e.to_string())?;
+    std::fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn to_profile(profile: &StoredProfile, active: &str) -> Profile {
+    Profile {
+        id: profile.id.clone(),
+        name: profile.name.clone(),
+        created_at: profile.created_at,
+        protected: profile.passphrase_hash.is_some(),
+        active: profile.id == active,
+    }
+}
+
+// Loads profiles.json; a protected profile is never reopened without its passphrase
+pub fn init(app: &tauri::AppHandle) {
+    let mut store = store_path(app)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str::<ProfileStore>(&contents).ok())
+        .unwrap_or_default();
+
+    let locked = store
+        .profiles
+        .iter()
+        .find(|p| p.id == store.active)
+        .map(|p| p.passphrase_hash.is_some())
+        .unwrap_or(true);
+    if locked {
+        store.active = DEFAULT_PROFILE.to_string();
+    }
+    println!("Using profile: {}", store.active);
+    *cell().write().unwrap() = store;
+}
+
+pub fn active_id() -> String {
+    cell().read().unwrap().active.clone()
+}
+
+fn profile_dir(base: PathBuf, id: &str) -> PathBuf {
+    if id == DEFAULT_PROFILE {
+        base
+    } else {
+        base.join(PROFILES_DIR).join(id)
+    }
+}
+
+// Where the active profile keeps its history database
+pub fn data_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let base = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+    Ok(profile_dir(base, &active_id()))
+}
+
+// Where the active profile keeps its config.toml
+pub fn config_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let base = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("No app config directory available: {}", e))?;
+    Ok(profile_dir(base, &active_id()))
+}
+
+fn hash_passphrase(passphrase: &str) -> Result<String, String> {
+    // uuid v4 bytes come from the OS random source and make a fine 16-byte salt
+    let salt = SaltString::encode_b64(uuid::Uuid::new_v4().as_bytes()).map_err(|e| e.to_string())?;
+    Argon2::default()
+        .hash_password(passphrase.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("Failed to hash passphrase: {}", e))
+}
+
+fn check_passphrase(profile: &StoredProfile, passphrase: Option<&str>) -> Result<(), String> {
+    let Some(stored) = &profile.passphrase_hash else {
+        return Ok(());
+    };
+    let passphrase = passphrase.ok_or_else(|| format!("Profile \"{}\" requires a passphrase", profile.name))?;
+    let parsed = PasswordHash::new(stored).map_err(|e| format!("Stored passphrase hash is invalid: {}", e))?;
+    Argon2::default()
+        .verify_password(passphrase.as_bytes(), &parsed)
+        .map_err(|_| "Incorrect passphrase".to_string())
+}
+
+fn find(store: &ProfileStore, profile_id: &str) -> Result<StoredProfile, String> {
+    store
+        .profiles
+        .iter()
+        .find(|p| p.id == profile_id)
+        .cloned()
+        .ok_or_else(|| format!("Unknown profile: {}", profile_id))
+}
+
+pub fn active_is_protected() -> bool {
+    let store = cell().read().unwrap();
+    store.profiles.iter().any(|p| p.id == store.active && p.passphrase_hash.is_some())
+}
+
+pub fn check_active_passphrase(passphrase: Option<&str>) -> Result<(), String> {
+    let store = cell().read().unwrap().clone();
+    check_passphrase(&find(&store, &store.active)?, passphrase)
+}
+
+#[tauri::command]
+pub fn list_profiles() -> Vec<Profile> {
+    let store = cell().read().unwrap();
+    store.profiles.iter().map(|p| to_profile(p, &store.active)).collect()
+}
+
+#[tauri::command]
+pub fn create_profile(name: String, passphrase: Option<String>, app: tauri::AppHandle) -> Result<Profile, String> {
+    kiosk::ensure_unlocked("Profiles")?;
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Profile name must not be empty".to_string());
+    }
+
+    let mut store = cell().read().unwrap().clone();
+    if store.profiles.iter().any(|p| p.name.eq_ignore_ascii_case(&name)) {
+        return Err(format!("A profile named \"{}\" already exists", name));
+    }
+    let profile = StoredProfile {
+        id: uuid::Uuid::new_v4().simple().to_string(),
+        name,
+        created_at: now_ms(),
+        passphrase_hash: passphrase.filter(|p| !p.is_empty()).map(|p| hash_passphrase(&p)).transpose()?,
+    };
+    store.profiles.push(profile.clone());
+    save(&app, &store)?;
+    let created = to_profile(&profile, &store.active);
+    *cell().write().unwrap() = store;
+    Ok(created)
+}
+
+// Reopens settings and history from the chosen profile's directories
+#[tauri::command]
+pub fn switch_profile(profile_id: String, passphrase: Option<String>, app: tauri::AppHandle) -> Result<Profile, String> {
+    kiosk::ensure_unlocked("Switching profiles")?;
+    let mut store = cell().read().unwrap().clone();
+    let profile = find(&store, &profile_id)?;
+    check_passphrase(&profile, passphrase.as_deref())?;
+
+    let previous = std::mem::replace(&mut store.active, profile.id.clone());
+    *cell().write().unwrap() = store.clone();
+    config::init(&app);
+    if let Err(e) = crate::open_stores(&app) {
+        // Go back rather than leave the app pointing at a half-opened profile
+        cell().write().unwrap().active = previous;
+        config::init(&app);
+        crate::open_stores(&app)?;
+        return Err(format!("Failed to open profile \"{}\": {}", profile.name, e));
+    }
+    save(&app, &store)?;
+
+    let switched = to_profile(&profile, &store.active);
+    println!("Switched to profile: {}", profile.name);
+    if let Err(e) = app.emit(config::CONFIG_CHANGED_EVENT, &config::get()) {
+        println!("Warning: Failed to emit config_changed event: {}", e);
+    }
+    if let Err(e) = app.emit(PROFILE_CHANGED_EVENT, &switched) {
+        println!("Warning: Failed to emit profile_changed event: {}", e);
+    }
+    Ok(switched)
+}
+
+// Sets, changes or (with new_passphrase None) removes a profile's passphrase
+#[tauri::command]
+pub fn set_profile_passphrase(
+    profile_id: String,
+    current_passphrase: Option<String>,
+    new_passphrase: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<Profile, String> {
+    kiosk::ensure_unlocked("Profiles")?;
+    let mut store = cell().read().unwrap().clone();
+    let mut profile = find(&store, &profile_id)?;
+    check_passphrase(&profile, current_passphrase.as_deref())?;
+
+    profile.passphrase_hash = new_passphrase.filter(|p| !p.is_empty()).map(|p| hash_passphrase(&p)).transpose()?;
+    if let Some(stored) = store.profiles.iter_mut().find(|p| p.id == profile_id) {
+        *stored = profile.clone();
+    }
+    save(&app, &store)?;
+    let updated = to_profile(&profile, &store.active);
+    *cell().write().unwrap() = store;
+    Ok(updated)
+}
+
+// Removes a profile and its settings and history; downloaded models are not touched
+#[tauri::command]
+pub fn delete_profile(profile_id: String, passphrase: Option<String>, app: tauri::AppHandle) -> Result<(), String> {
+    kiosk::ensure_unlocked("Profiles")?;
+    if profile_id == DEFAULT_PROFILE {
+        return Err("The default profile cannot be deleted".to_string());
+    }
+    let mut store = cell().read().unwrap().clone();
+    if store.active == profile_id {
+        return Err("Switch to another profile before deleting this one".to_string());
+    }
+    let profile = find(&store, &profile_id)?;
+    check_passphrase(&profile, passphrase.as_deref())?;
+
+    store.profiles.retain(|p| p.id != profile_id);
+    save(&app, &store)?;
+    *cell().write().unwrap() = store;
+
+    for base in [app.path().app_data_dir(), app.path().app_config_dir()].into_iter().flatten() {
+        let dir = profile_dir(base, &profile_id);
+        if dir.exists() {
+            if let Err(e) = std::fs::remove_dir_all(&dir) {
+                println!("Warning: Failed to remove {}: {}", dir.display(), e);
+            }
+        }
+    }
+    Ok(())
+}
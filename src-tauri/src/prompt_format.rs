@@ -0,0 +1,170 @@
+// Picks a chat template for GGUF models that don't bring their own. A template next to the model
+// or embedded in the GGUF file always wins; otherwise the architecture (and for "llama" files, the
+// tokenizer) selects one of the bundled templates, which also set the BOS/EOS tokens the format
+// expects. mistral.json stays the fallback for architectures without a known format.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{path::BaseDirectory, Manager};
+
+use crate::compat;
+
+const TEMPLATES_DIR: &str = "chat_templates";
+
+const MISTRAL_TEMPLATE: &str = include_str!("../mistral.json");
+const LLAMA3_TEMPLATE: &str = include_str!("../templates/chat/llama3.json");
+const GEMMA_TEMPLATE: &str = include_str!("../templates/chat/gemma.json");
+const CHATML_TEMPLATE: &str = include_str!("../templates/chat/chatml.json");
+
+// Models built with a template that rejects system messages, like the bundled mistral.json
+static NO_SYSTEM_ROLE: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+// Files beside the model that mistral.rs reads as a chat template
+const MODEL_TEMPLATE_FILES: [&str; 2] = ["tokenizer_config.json", "mistral.json"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptFormat {
+    Mistral,
+    Llama3,
+    Gemma,
+    // <|im_start|>/<|im_end|> turns, as used by SmolLM
+    ChatMl,
+}
+
+impl PromptFormat {
+    fn name(self) -> &'static str {
+        match self {
+            PromptFormat::Mistral => "mistral",
+            PromptFormat::Llama3 => "llama3",
+            PromptFormat::Gemma => "gemma",
+            PromptFormat::ChatMl => "chatml",
+        }
+    }
+
+    fn template(self) -> &'static str {
+        match self {
+            PromptFormat::Mistral => MISTRAL_TEMPLATE,
+            PromptFormat::Llama3 => LLAMA3_TEMPLATE,
+            PromptFormat::Gemma => GEMMA_TEMPLATE,
+            PromptFormat::ChatMl => CHATML_TEMPLATE,
+        }
+    }
+}
+
+// The prompt format a GGUF file's architecture calls for, or None when it isn't a known one
+pub fn detect(gguf: &Path) -> Option<PromptFormat> {
+    let architecture = compat::gguf_architecture(gguf).ok()?;
+    match architecture.as_str() {
+        "mistral" => Some(PromptFormat::Mistral),
+        "gemma" | "gemma2" | "gemma3" => Some(PromptFormat::Gemma),
+        "smollm3" => Some(PromptFormat::ChatMl),
+        // Llama 2, Llama 3, Mistral and SmolLM2 conversions all say "llama"; the pre-tokenizer
+        // tells them apart
+        "llama" => match compat::gguf_metadata_string(gguf, "tokenizer.ggml.pre").ok().flatten().as_deref() {
+            Some("llama-bpe") => Some(PromptFormat::Llama3),
+            Some("smollm") => Some(PromptFormat::ChatMl),
+            _ => Some(PromptFormat::Mistral),
+        },
+        _ => None,
+    }
+}
+
+// Writes a bundled template where mistral.rs can read it
+fn bundled_template_path(app: &tauri::AppHandle, format: PromptFormat) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| format!("Failed to resolve the cache directory: {}", e))?
+        .join(TEMPLATES_DIR);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}.json", format.name()));
+    std::fs::write(&path, format.template()).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+// The app-wide mistral.json, from the bundled resources or the working directory in development
+fn fallback_template(app: &tauri::AppHandle) -> Option<PathBuf> {
+    if let Ok(resource_path) = app.path().resolve("mistral.json", BaseDirectory::Resource) {
+        if resource_path.exists() {
+            return Some(resource_path);
+        }
+    }
+    ["mistral.json", "src-tauri/mistral.json"]
+        .into_iter()
+        .map(PathBuf::from)
+        .find(|path| path.exists())
+}
+
+// A template file saved beside the model, e.g. by the downloader
+pub fn template_file(model_path: &Path) -> Option<PathBuf> {
+    MODEL_TEMPLATE_FILES.iter().map(|name| model_path.join(name)).find(|path| path.exists())
+}
+
+// Chat template to build a local GGUF model with, or None to use the one embedded in the file
+pub fn chat_template_for(app: &tauri::AppHandle, model_path: &Path, model_file: &str) -> Option<PathBuf> {
+    if let Some(path) = template_file(model_path) {
+        return Some(path);
+    }
+
+    let gguf = model_path.join(model_file);
+    if let Ok(Some(_)) = compat::gguf_metadata_string(&gguf, "tokenizer.chat_template") {
+        println!("Using the chat template embedded in {}", model_file);
+        return None;
+    }
+
+    if let Some(format) = detect(&gguf) {
+        match bundled_template_path(app, format) {
+            Ok(path) => {
+                println!("Using the bundled {} chat template for {}", format.name(), model_file);
+                return Some(path);
+            }
+            Err(e) => println!("Warning: {}", e),
+        }
+    }
+
+    println!("No chat template found for {}, falling back to mistral.json", model_file);
+    fallback_template(app)
+}
+
+// The Jinja source in a template file: the "chat_template" of a tokenizer_config.json-style file
+// (or its "default" entry when there are several), otherwise the file itself
+fn template_source(path: &Path) -> Option<String> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(&contents) else {
+        return Some(contents);
+    };
+    match &json["chat_template"] {
+        serde_json::Value::String(template) => Some(template.clone()),
+        serde_json::Value::Array(templates) => templates
+            .iter()
+            .find(|t| t["name"] == "default")
+            .and_then(|t| t["template"].as_str())
+            .map(str::to_string),
+        _ => None,
+    }
+}
+
+// Templates that only know user and assistant turns raise on anything else, and never mention
+// the system role; templates that render any role, like ChatML, don't raise at all
+fn renders_system_role(template: &str) -> bool {
+    template.contains("system") || !template.contains("raise_exception")
+}
+
+// Called by the loaders with the template a model is built with; None means the model's own,
+// which is assumed to handle system messages
+pub fn note_template(model_id: &str, chat_template: Option<&Path>) {
+    let rejects = chat_template.and_then(template_source).is_some_and(|template| !renders_system_role(&template));
+    let mut no_system_role = NO_SYSTEM_ROLE.lock().unwrap();
+    let no_system_role = no_system_role.get_or_insert_with(HashSet::new);
+    if rejects {
+        no_system_role.insert(model_id.to_string());
+    } else {
+        no_system_role.remove(model_id);
+    }
+}
+
+// Whether a built model takes the system prompt as a system message
+pub fn supports_system_role(model_id: &str) -> bool {
+    !NO_SYSTEM_ROLE.lock().unwrap().as_ref().is_some_and(|models| models.contains(model_id))
+}
@@ -0,0 +1,196 @@
+// Which configuration produced each answer: model, quantization, sampling, chat template and app version.
+// Chat requests remember theirs until the frontend stores the answer with append_message.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::history::{db_err, with_db};
+use crate::sampling::SamplingConfig;
+use crate::streaming::now_ms;
+
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Requests whose answer was never appended are dropped after this long
+const PENDING_TTL_MS: u64 = 60 * 60 * 1000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS message_provenance (
+    message_id TEXT PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    model_id TEXT NOT NULL,
+    quantization TEXT,
+    profile TEXT NOT NULL,
+    sampling TEXT NOT NULL,
+    template_hash TEXT,
+    app_version TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+";
+
+// How each loaded model was built, recorded by the loaders
+static BUILDS: Mutex<Option<HashMap<String, ModelBuild>>> = Mutex::new(None);
+
+// Provenance of finished requests waiting for their message, keyed by request id
+static PENDING: Mutex<Option<HashMap<String, Provenance>>> = Mutex::new(None);
+
+#[derive(Debug, Clone)]
+struct ModelBuild {
+    quantization: Option<String>,
+    template_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    pub model_id: String,
+    // GGUF file quantization or in-situ quantization type; None when unknown
+    pub quantization: Option<String>,
+    // Generation profile the sampling parameters came from
+    pub profile: String,
+    pub sampling: SamplingConfig,
+    // Hash of the chat template file; None when the tokenizer's built-in template was used
+    pub template_hash: Option<String>,
+    pub app_version: String,
+    pub created_at: u64,
+}
+
+pub fn init() -> Result<(), String> {
+    with_db(|conn| conn.execute_batch(SCHEMA).map_err(db_err))
+}
+
+// FNV-1a, so hashes stay comparable across builds and platforms
+pub(crate) fn fnv1a(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+// Quantization named in a GGUF file name, e.g. "Q4_K_M" in "mistral-7b-instruct-v0.1.Q4_K_M.gguf"
+pub fn gguf_quantization(file_name: &str) -> Option<String> {
+    file_name
+        .trim_end_matches(".gguf")
+        .split(['.', '-'])
+        .rev()
+        .map(|part| part.to_uppercase())
+        .find(|part| {
+            let digits = part.strip_prefix("IQ").or_else(|| part.strip_prefix('Q'));
+            digits.is_some_and(|d| d.starts_with(|c: char| c.is_ascii_digit()))
+                || matches!(part.as_str(), "F16" | "BF16" | "F32")
+        })
+}
+
+// Quantization in a UQFF shard name, e.g. "Q4K" in "smollm33b-q4k-0.uqff"
+pub fn uqff_quantization(file_name: &str) -> Option<String> {
+    let stem = file_name.trim_end_matches(".uqff");
+    // Sharded files end in "-<shard number>"
+    let stem = match stem.rsplit_once('-') {
+        Some((rest, shard)) if shard.chars().all(|c| c.is_ascii_digit()) => rest,
+        _ => stem,
+    };
+    let (_, quantization) = stem.rsplit_once('-')?;
+    Some(quantization.to_uppercase())
+}
+
+// Called by the loaders right before a model is built
+pub fn note_build(model_id: &str, quantization: Option<String>, chat_template: Option<&Path>) {
+    let template_hash = chat_template.and_then(|path| match std::fs::read(path) {
+        Ok(bytes) => Some(fnv1a(&bytes)),
+        Err(e) => {
+            println!("Warning: Failed to hash chat template {}: {}", path.display(), e);
+            None
+        }
+    });
+    BUILDS
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(model_id.to_string(), ModelBuild { quantization, template_hash });
+}
+
+// Remembers what produced a finished request until append_message stores its answer
+pub fn remember(request_id: &str, model_id: &str, profile: &str, sampling: &SamplingConfig) {
+    let build = BUILDS.lock().unwrap().as_ref().and_then(|builds| builds.get(model_id).cloned());
+    let now = now_ms();
+    let provenance = Provenance {
+        model_id: model_id.to_string(),
+        quantization: build.as_ref().and_then(|b| b.quantization.clone()),
+        profile: profile.to_string(),
+        sampling: sampling.clone(),
+        template_hash: build.and_then(|b| b.template_hash),
+        app_version: APP_VERSION.to_string(),
+        created_at: now,
+    };
+    let mut pending = PENDING.lock().unwrap();
+    let pending = pending.get_or_insert_with(HashMap::new);
+    pending.retain(|_, p| now.saturating_sub(p.created_at) < PENDING_TTL_MS);
+    pending.insert(request_id.to_string(), provenance);
+}
+
+// Stores the provenance of a request with the message holding its answer
+pub fn attach(conn: &Connection, message_id: &str, request_id: &str) -> Result<Option<Provenance>, String> {
+    let Some(provenance) = PENDING.lock().unwrap().as_mut().and_then(|pending| pending.remove(request_id)) else {
+        println!("Warning: No provenance recorded for request {}", request_id);
+        return Ok(None);
+    };
+    store(conn, message_id, &provenance)?;
+    Ok(Some(provenance))
+}
+
+pub fn store(conn: &Connection, message_id: &str, provenance: &Provenance) -> Result<(), String> {
+    let sampling = serde_json::to_string(&provenance.sampling).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO message_provenance
+         (message_id, model_id, quantization, profile, sampling, template_hash, app_version, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            message_id,
+            provenance.model_id,
+            provenance.quantization,
+            provenance.profile,
+            sampling,
+            provenance.template_hash,
+            provenance.app_version,
+            provenance.created_at as i64
+        ],
+    )
+    .map_err(db_err)?;
+    Ok(())
+}
+
+pub fn for_message(conn: &Connection, message_id: &str) -> Result<Option<Provenance>, String> {
+    let row = conn
+        .query_row(
+            "SELECT model_id, quantization, profile, sampling, template_hash, app_version, created_at
+             FROM message_provenance WHERE message_id = ?1",
+            params![message_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, i64>(6)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(db_err)?;
+    let Some((model_id, quantization, profile, sampling, template_hash, app_version, created_at)) = row else {
+        return Ok(None);
+    };
+    Ok(Some(Provenance {
+        model_id,
+        quantization,
+        profile,
+        sampling: serde_json::from_str(&sampling).unwrap_or_default(),
+        template_hash,
+        app_version,
+        created_at: created_at as u64,
+    }))
+}
@@ -0,0 +1,137 @@
+// What a request is waiting on before its first token: a model load, other requests to the same
+// model, or both. Estimates come from recorded load times and generation durations, so the UI can
+// say "model loading, ~90s remaining; 2 requests ahead of you" instead of waiting silently.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::metrics;
+
+// Requests from when they are accepted until they finish, in arrival order
+static QUEUE: Mutex<Vec<Queued>> = Mutex::new(Vec::new());
+
+// Models being loaded -> when the load started
+static LOADING: Mutex<Option<HashMap<String, Instant>>> = Mutex::new(None);
+
+struct Queued {
+    request_id: String,
+    model_id: String,
+    joined_at: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WaitPhase {
+    Loading,
+    Queued,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaitStatus {
+    pub phase: WaitPhase,
+    // Earlier requests to the same model that haven't finished
+    pub ahead: usize,
+    // None until the model has been loaded or has answered at least once
+    pub eta_ms: Option<u64>,
+    pub message: String,
+}
+
+// Place of a request in its model's queue; dropping it leaves the queue
+pub struct QueueTicket {
+    request_id: String,
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        QUEUE.lock().unwrap().retain(|queued| queued.request_id != self.request_id);
+    }
+}
+
+pub fn join(request_id: &str, model_id: &str) -> QueueTicket {
+    QUEUE.lock().unwrap().push(Queued {
+        request_id: request_id.to_string(),
+        model_id: model_id.to_string(),
+        joined_at: Instant::now(),
+    });
+    QueueTicket { request_id: request_id.to_string() }
+}
+
+// Marks a model load as running until the guard is dropped
+pub struct LoadingGuard {
+    model_id: String,
+}
+
+impl Drop for LoadingGuard {
+    fn drop(&mut self) {
+        if let Some(loading) = LOADING.lock().unwrap().as_mut() {
+            loading.remove(&self.model_id);
+        }
+    }
+}
+
+pub fn loading(model_id: &str) -> LoadingGuard {
+    LOADING
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(model_id.to_string(), Instant::now());
+    LoadingGuard { model_id: model_id.to_string() }
+}
+
+fn format_secs(ms: u64) -> String {
+    match ms / 1000 {
+        0 => "<1s".to_string(),
+        secs if secs < 120 => format!("~{}s", secs),
+        secs => format!("~{}min", (secs + 30) / 60),
+    }
+}
+
+// Why a request isn't streaming yet, or None when nothing is in its way. Requests ahead are counted
+// as if they ran one after another; mistral.rs batches them, so the ETA errs on the long side.
+pub fn status(request_id: &str, model_id: &str) -> Option<WaitStatus> {
+    let load_started = LOADING.lock().unwrap().as_ref().and_then(|loading| loading.get(model_id).copied());
+    let ahead: Vec<Instant> = {
+        let queue = QUEUE.lock().unwrap();
+        let position = queue.iter().position(|queued| queued.request_id == request_id)?;
+        queue[..position]
+            .iter()
+            .filter(|queued| queued.model_id == model_id)
+            .map(|queued| queued.joined_at)
+            .collect()
+    };
+    if load_started.is_none() && ahead.is_empty() {
+        return None;
+    }
+
+    let load_remaining = match load_started {
+        Some(started) => metrics::avg_load_ms(model_id).map(|avg| avg.saturating_sub(started.elapsed().as_millis() as u64)),
+        None => Some(0),
+    };
+    let queue_remaining = match metrics::avg_generation_ms(model_id) {
+        Some(avg) => Some(ahead.iter().map(|joined| avg.saturating_sub(joined.elapsed().as_millis() as u64)).sum()),
+        None if ahead.is_empty() => Some(0),
+        None => None,
+    };
+    let eta_ms = load_remaining.zip(queue_remaining).map(|(load, queue)| load + queue);
+
+    let mut parts = Vec::new();
+    if load_started.is_some() {
+        parts.push(match load_remaining {
+            Some(ms) => format!("model loading, {} remaining", format_secs(ms)),
+            None => "model loading".to_string(),
+        });
+    }
+    match ahead.len() {
+        0 => {}
+        1 => parts.push("1 request ahead of you".to_string()),
+        n => parts.push(format!("{} requests ahead of you", n)),
+    }
+    Some(WaitStatus {
+        phase: if load_started.is_some() { WaitPhase::Loading } else { WaitPhase::Queued },
+        ahead: ahead.len(),
+        eta_ms,
+        message: parts.join("; "),
+    })
+}
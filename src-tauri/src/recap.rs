@@ -0,0 +1,188 @@
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use tauri::Emitter;
+
+use crate::db_writer;
+use crate::history::{db_err, with_db, ConversationDetail};
+use crate::priority::Priority;
+use crate::sampling::SamplingConfig;
+use crate::streaming::now_ms;
+use crate::{config, generate_text, keep_alive};
+
+pub const RECAP_READY_EVENT: &str = "recap-ready";
+
+// Each message is cut to this many characters in the recap prompt
+const MAX_MESSAGE_CHARS: usize = 600;
+
+const HOUR_MS: u64 = 60 * 60 * 1000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS conversation_recaps (
+    conversation_id TEXT PRIMARY KEY REFERENCES conversations(id) ON DELETE CASCADE,
+    summary TEXT NOT NULL,
+    message_count INTEGER NOT NULL,
+    model_id TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+";
+
+// Conversations whose recap is being generated, so reopening twice doesn't start a second run
+static GENERATING: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecapConfig {
+    pub enabled: bool,
+    // A conversation untouched for this long gets a recap when it is opened
+    pub gap_hours: u64,
+    // Falls back to models.default_model
+    pub model_id: Option<String>,
+    // Latest messages the recap is written from
+    pub max_messages: usize,
+}
+
+impl Default for RecapConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            gap_hours: 12,
+            model_id: None,
+            max_messages: 20,
+        }
+    }
+}
+
+impl RecapConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.max_messages == 0 {
+            errors.push("recap.max_messages must be at least 1".to_string());
+        }
+        errors
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Recap {
+    pub conversation_id: String,
+    pub summary: String,
+    // Messages the recap covers; a recap of fewer messages than the conversation has is stale
+    pub message_count: usize,
+    pub created_at: u64,
+}
+
+pub fn init() -> Result<(), String> {
+    with_db(|conn| conn.execute_batch(SCHEMA).map_err(db_err))
+}
+
+fn cached(conversation_id: &str) -> Result<Option<Recap>, String> {
+    with_db(|conn| {
+        conn.query_row(
+            "SELECT summary, message_count, created_at FROM conversation_recaps WHERE conversation_id = ?1",
+            params![conversation_id],
+            |row| {
+                Ok(Recap {
+                    conversation_id: conversation_id.to_string(),
+                    summary: row.get(0)?,
+                    message_count: row.get::<_, i64>(1)? as usize,
+                    created_at: row.get::<_, i64>(2)? as u64,
+                })
+            },
+        )
+        .optional()
+        .map_err(db_err)
+    })
+}
+
+fn build_prompt(detail: &ConversationDetail, max_messages: usize) -> String {
+    let start = detail.messages.len().saturating_sub(max_messages);
+    let mut transcript = String::new();
+    for message in &detail.messages[start..] {
+        let content: String = message.content.chars().take(MAX_MESSAGE_CHARS).collect();
+        transcript.push_str(&format!("{}: {}\n\n", message.role, content.trim()));
+    }
+    format!(
+        "The user is coming back to this conversation after a break. In one or two sentences, remind them \
+         what they were working on and where it left off. Address them directly and start with \
+         \"Previously you were\". Reply with the recap only.\n\nConversation \"{}\":\n\n{}",
+        detail.conversation.title, transcript
+    )
+}
+
+async fn generate(detail: ConversationDetail, model_id: String, app: tauri::AppHandle) -> Result<Recap, String> {
+    let settings = config::get().recap;
+    let sampling = SamplingConfig {
+        temperature: Some(0.3),
+        max_tokens: Some(120),
+        ..SamplingConfig::default()
+    };
+    let prompt = build_prompt(&detail, settings.max_messages);
+    let summary = generate_text(&model_id, &prompt, &sampling, Priority::Background, &app).await?;
+
+    let recap = Recap {
+        conversation_id: detail.conversation.id.clone(),
+        summary: summary.trim().to_string(),
+        message_count: detail.messages.len(),
+        created_at: now_ms(),
+    };
+    let stored = recap.clone();
+    db_writer::write(move |conn| {
+        conn.execute(
+            "INSERT OR REPLACE INTO conversation_recaps (conversation_id, summary, message_count, model_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![stored.conversation_id, stored.summary, stored.message_count as i64, model_id, stored.created_at as i64],
+        )
+        .map(|_| ())
+        .map_err(db_err)
+    })
+    .await?;
+    Ok(recap)
+}
+
+// Recap to show with a conversation being opened. A fresh cached one is returned directly; after a
+// long gap a new one is generated in the background and delivered as a recap-ready event.
+pub fn for_opened(detail: &ConversationDetail, app: &tauri::AppHandle) -> Option<Recap> {
+    let settings = config::get().recap;
+    if !settings.enabled || detail.messages.is_empty() {
+        return None;
+    }
+    let recap = match cached(&detail.conversation.id) {
+        Ok(recap) => recap,
+        Err(e) => {
+            println!("Warning: Failed to load the recap of {}: {}", detail.conversation.id, e);
+            return None;
+        }
+    };
+    if recap.as_ref().is_some_and(|r| r.message_count == detail.messages.len()) {
+        return recap;
+    }
+    if now_ms().saturating_sub(detail.conversation.updated_at) < settings.gap_hours * HOUR_MS {
+        return None;
+    }
+    let Some(model_id) = settings.model_id.or_else(keep_alive::default_model) else {
+        return None;
+    };
+
+    let conversation_id = detail.conversation.id.clone();
+    if !GENERATING.lock().unwrap().get_or_insert_with(HashSet::new).insert(conversation_id.clone()) {
+        return None;
+    }
+    let detail = detail.clone();
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        match generate(detail, model_id, app.clone()).await {
+            Ok(recap) => {
+                if let Err(e) = app.emit(RECAP_READY_EVENT, &recap) {
+                    println!("Warning: Failed to emit recap-ready event: {}", e);
+                }
+            }
+            Err(e) => println!("Warning: Failed to generate a recap of {}: {}", conversation_id, e),
+        }
+        if let Some(generating) = GENERATING.lock().unwrap().as_mut() {
+            generating.remove(&conversation_id);
+        }
+    });
+    None
+}
@@ -0,0 +1,191 @@
+// Compliance export of what the local model was asked and answered. Each line of the JSONL file
+// carries the SHA-256 of the previous line's hash plus its own content, so removing, reordering or
+// editing a line breaks the chain, and the closing line signs the last hash with a key that never
+// leaves the profile's data directory.
+
+use hmac::{Hmac, Mac};
+use rusqlite::params;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use crate::history::{db_err, with_db};
+use crate::provenance::{self, APP_VERSION};
+use crate::streaming::now_ms;
+use crate::{kiosk, profiles};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SIGNING_KEY_FILE: &str = "record_signing.key";
+
+// prev_hash of the first line
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordExport {
+    pub path: String,
+    pub profile: String,
+    pub messages: usize,
+    // Hash of the last message line, which the closing line signs
+    pub final_hash: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordVerification {
+    pub valid: bool,
+    pub messages: usize,
+    // First problem found, with its line number
+    pub error: Option<String>,
+}
+
+// The profile's signing key, created on first use
+fn signing_key(app: &tauri::AppHandle) -> Result<Vec<u8>, String> {
+    let path: PathBuf = profiles::data_dir(app)?.join(SIGNING_KEY_FILE);
+    if let Ok(contents) = std::fs::read_to_string(&path) {
+        return hex::decode(contents.trim()).map_err(|e| format!("{} is corrupt: {}", path.display(), e));
+    }
+    // Two v4 UUIDs give 244 random bits from the OS generator
+    let key: Vec<u8> = [uuid::Uuid::new_v4(), uuid::Uuid::new_v4()]
+        .iter()
+        .flat_map(|id| id.as_bytes().to_vec())
+        .collect();
+    std::fs::write(&path, hex::encode(&key)).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(key)
+}
+
+// Hash of a line without its "hash" field, chained to the previous line's
+fn chain_hash(prev_hash: &str, line: &Value) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(prev_hash.as_bytes());
+    hasher.update(line.to_string().as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+fn sign(key: &[u8], hash: &str) -> Result<HmacSha256, String> {
+    let mut mac = HmacSha256::new_from_slice(key).map_err(|e| e.to_string())?;
+    mac.update(hash.as_bytes());
+    Ok(mac)
+}
+
+// Adds prev_hash and hash to a line and returns its hash. "hash" goes last so removing it again
+// during verification leaves the other fields in place.
+fn seal_line(mut line: Value, prev_hash: &str) -> (Value, String) {
+    line["prev_hash"] = json!(prev_hash);
+    let hash = chain_hash(prev_hash, &line);
+    line["hash"] = json!(hash);
+    (line, hash)
+}
+
+// Writes every message of the active profile created in [from_ms, to_ms] to `path` as hash-chained
+// JSONL: a header line, one line per message in creation order, and a signed closing line
+#[tauri::command]
+pub fn export_system_of_record(from_ms: u64, to_ms: u64, path: String, app: tauri::AppHandle) -> Result<RecordExport, String> {
+    kiosk::ensure_unlocked("Exporting the system of record")?;
+    if from_ms > to_ms {
+        return Err("The export range ends before it starts".to_string());
+    }
+    let profile = profiles::active_id();
+    let key = signing_key(&app)?;
+
+    let lines = with_db(|conn| {
+        let mut stmt = conn
+            .prepare(
+                "SELECT m.id, m.conversation_id, c.title, m.position, m.role, m.content, m.created_at
+                 FROM messages m JOIN conversations c ON c.id = m.conversation_id
+                 WHERE m.created_at BETWEEN ?1 AND ?2
+                 ORDER BY m.created_at, m.conversation_id, m.position",
+            )
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map(params![from_ms as i64, to_ms as i64], |row| {
+                Ok(json!({
+                    "type": "message",
+                    "message_id": row.get::<_, String>(0)?,
+                    "conversation_id": row.get::<_, String>(1)?,
+                    "conversation_title": row.get::<_, String>(2)?,
+                    "position": row.get::<_, i64>(3)?,
+                    "role": row.get::<_, String>(4)?,
+                    "content": row.get::<_, String>(5)?,
+                    "created_at": row.get::<_, i64>(6)?,
+                }))
+            })
+            .map_err(db_err)?;
+        let mut lines = rows.collect::<Result<Vec<_>, _>>().map_err(db_err)?;
+        for line in &mut lines {
+            let message_id = line["message_id"].as_str().unwrap_or_default().to_string();
+            line["provenance"] = json!(provenance::for_message(conn, &message_id)?);
+        }
+        Ok(lines)
+    })?;
+    let messages = lines.len();
+
+    let file = std::fs::File::create(&path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut out = std::io::BufWriter::new(file);
+    let mut write = |line: &Value| writeln!(out, "{}", line).map_err(|e| format!("Failed to write {}: {}", path, e));
+
+    let header = json!({
+        "type": "header",
+        "profile": profile,
+        "from_ms": from_ms,
+        "to_ms": to_ms,
+        "exported_at": now_ms(),
+        "app_version": APP_VERSION,
+    });
+    let (header, mut prev_hash) = seal_line(header, GENESIS_HASH);
+    write(&header)?;
+    for line in lines {
+        let (line, hash) = seal_line(line, &prev_hash);
+        write(&line)?;
+        prev_hash = hash;
+    }
+    let signature = hex::encode(sign(&key, &prev_hash)?.finalize().into_bytes());
+    write(&json!({ "type": "seal", "messages": messages, "final_hash": prev_hash, "signature": signature }))?;
+    out.flush().map_err(|e| format!("Failed to write {}: {}", path, e))?;
+
+    println!("Exported {} messages of profile {} to {}", messages, profile, path);
+    Ok(RecordExport { path, profile, messages, final_hash: prev_hash })
+}
+
+// Re-checks an export's hash chain and, with the active profile's key, its signature
+#[tauri::command]
+pub fn verify_system_of_record(path: String, app: tauri::AppHandle) -> Result<RecordVerification, String> {
+    let file = std::fs::File::open(&path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let key = signing_key(&app)?;
+    let invalid = |messages, error: String| Ok(RecordVerification { valid: false, messages, error: Some(error) });
+
+    let mut prev_hash = GENESIS_HASH.to_string();
+    let mut messages = 0;
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let number = index + 1;
+        let line = line.map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let mut value: Value = match serde_json::from_str(&line) {
+            Ok(value) => value,
+            Err(e) => return invalid(messages, format!("Line {} is not valid JSON: {}", number, e)),
+        };
+
+        if value["type"] == "seal" {
+            if value["final_hash"] != prev_hash.as_str() || value["messages"] != messages {
+                return invalid(messages, format!("Line {}: the closing line doesn't match the records before it", number));
+            }
+            let signature = hex::decode(value["signature"].as_str().unwrap_or_default()).unwrap_or_default();
+            if sign(&key, &prev_hash)?.verify_slice(&signature).is_err() {
+                return invalid(messages, "The signature doesn't match this profile's key".to_string());
+            }
+            return Ok(RecordVerification { valid: true, messages, error: None });
+        }
+
+        let Some(hash) = value.as_object_mut().and_then(|fields| fields.remove("hash")) else {
+            return invalid(messages, format!("Line {} has no hash", number));
+        };
+        if value["prev_hash"] != prev_hash.as_str() || hash != chain_hash(&prev_hash, &value).as_str() {
+            return invalid(messages, format!("Line {} was changed, removed or reordered", number));
+        }
+        if value["type"] == "message" {
+            messages += 1;
+        }
+        prev_hash = hash.as_str().unwrap_or_default().to_string();
+    }
+    invalid(messages, "The export has no closing line; it may have been truncated".to_string())
+}
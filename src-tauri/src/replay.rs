@@ -0,0 +1,163 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tauri::Emitter;
+
+use crate::config;
+use crate::db_writer;
+use crate::history::{db_err, with_db};
+use crate::streaming::{self, now_ms, StreamEvent, CHAT_STREAM_EVENT};
+
+// Pauses between recorded requests are shortened to this, since they can be hours apart
+const MAX_GAP_BETWEEN_REQUESTS: Duration = Duration::from_secs(2);
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS stream_recordings (
+    conversation_id TEXT NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    request_id TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    event TEXT NOT NULL,
+    recorded_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_stream_recordings ON stream_recordings(conversation_id, recorded_at, seq);
+";
+
+// Bumped by every new replay and by stop_replay, so an older replay notices and stops
+static REPLAY_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReplayConfig {
+    // Store the stream events of conversations in history so they can be replayed later
+    pub record: bool,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self { record: true }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayStarted {
+    pub conversation_id: String,
+    pub requests: usize,
+    pub events: usize,
+    // Playback time at the chosen speed
+    pub duration_ms: u64,
+}
+
+pub fn init() -> Result<(), String> {
+    with_db(|conn| conn.execute_batch(SCHEMA).map_err(db_err))
+}
+
+// Saves the events a finished request emitted; only conversations stored in history are recorded
+pub fn record(conversation_id: &str, request_id: &str) {
+    if !config::get().replay.record {
+        return;
+    }
+    let events = streaming::buffered_events(request_id);
+    if events.is_empty() {
+        return;
+    }
+    let (conversation_id, request_id) = (conversation_id.to_string(), request_id.to_string());
+    let recorded_at = now_ms();
+    db_writer::submit("a stream recording for replay", move |conn| {
+        for (seq, event) in events.iter().enumerate() {
+            let json = serde_json::to_string(event).map_err(|e| e.to_string())?;
+            conn.execute(
+                "INSERT INTO stream_recordings (conversation_id, request_id, seq, event, recorded_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![conversation_id, request_id, seq as i64, json, recorded_at],
+            )
+            .map_err(db_err)?;
+        }
+        Ok(())
+    });
+}
+
+fn load_recording(conversation_id: &str) -> Result<Vec<StreamEvent>, String> {
+    with_db(|conn| {
+        let mut stmt = conn
+            .prepare("SELECT event FROM stream_recordings WHERE conversation_id = ?1 ORDER BY recorded_at, request_id, seq")
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map(params![conversation_id], |row| row.get::<_, String>(0))
+            .map_err(db_err)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db_err)?;
+        rows.iter()
+            .map(|json| serde_json::from_str(json).map_err(|e| format!("Corrupt stream recording: {}", e)))
+            .collect()
+    })
+}
+
+// Delay before each event: recorded spacing within a request, a capped pause between requests
+fn schedule(events: &[StreamEvent], speed: f64) -> Vec<Duration> {
+    let mut delays = Vec::with_capacity(events.len());
+    let mut previous: Option<&StreamEvent> = None;
+    for event in events {
+        let gap = match previous {
+            Some(prev) if prev.request_id == event.request_id => {
+                Duration::from_millis(event.elapsed_ms.saturating_sub(prev.elapsed_ms))
+            }
+            Some(prev) => Duration::from_millis(event.timestamp_ms.saturating_sub(prev.timestamp_ms))
+                .min(MAX_GAP_BETWEEN_REQUESTS),
+            None => Duration::ZERO,
+        };
+        delays.push(gap.div_f64(speed));
+        previous = Some(event);
+    }
+    delays
+}
+
+// Re-emits a conversation's recorded chat-stream events at the original timing divided by `speed`
+#[tauri::command]
+pub fn replay_conversation(
+    conversation_id: String,
+    speed: Option<f64>,
+    app: tauri::AppHandle,
+    window: tauri::Window,
+) -> Result<ReplayStarted, String> {
+    let speed = speed.unwrap_or(1.0);
+    if !speed.is_finite() || speed <= 0.0 {
+        return Err(format!("Replay speed must be greater than 0 (got {})", speed));
+    }
+    let events = load_recording(&conversation_id)?;
+    if events.is_empty() {
+        return Err(format!("No recorded streams for conversation {}", conversation_id));
+    }
+
+    let delays = schedule(&events, speed);
+    let mut requests: Vec<&str> = events.iter().map(|e| e.request_id.as_str()).collect();
+    requests.dedup();
+    let started = ReplayStarted {
+        conversation_id,
+        requests: requests.len(),
+        events: events.len(),
+        duration_ms: delays.iter().sum::<Duration>().as_millis() as u64,
+    };
+
+    let generation = REPLAY_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let target = window.label().to_string();
+    tauri::async_runtime::spawn(async move {
+        for (mut event, delay) in events.into_iter().zip(delays) {
+            tokio::time::sleep(delay).await;
+            if REPLAY_GENERATION.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            // A distinct id keeps replayed events apart from live streams and their resume buffers
+            event.request_id = format!("replay-{}", event.request_id);
+            event.timestamp_ms = now_ms();
+            if let Err(e) = app.emit_to(target.as_str(), CHAT_STREAM_EVENT, &event) {
+                println!("Warning: Failed to emit replayed stream event: {}", e);
+            }
+        }
+    });
+    Ok(started)
+}
+
+#[tauri::command]
+pub fn stop_replay() {
+    REPLAY_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
@@ -0,0 +1,215 @@
+// Output styles the backend holds answers to, picked per message or per assistant. The style's
+// instruction joins the system prompt, and the finished answer is checked against it: a concise
+// answer that runs long is compressed in a second pass, and a bullet answer written as prose is
+// split into bullets.
+
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::assistants;
+use crate::db_writer;
+use crate::history::{db_err, with_db};
+use crate::priority::Priority;
+use crate::sampling::SamplingConfig;
+use crate::streaming::StreamTracker;
+use crate::{config, generate_text};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS assistant_response_styles (
+    assistant_id TEXT PRIMARY KEY REFERENCES assistants(id) ON DELETE CASCADE,
+    style TEXT NOT NULL
+);
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseStyle {
+    Concise,
+    Detailed,
+    Bullet,
+}
+
+impl ResponseStyle {
+    fn as_str(self) -> &'static str {
+        match self {
+            ResponseStyle::Concise => "concise",
+            ResponseStyle::Detailed => "detailed",
+            ResponseStyle::Bullet => "bullet",
+        }
+    }
+
+    fn parse(style: &str) -> Option<Self> {
+        [ResponseStyle::Concise, ResponseStyle::Detailed, ResponseStyle::Bullet]
+            .into_iter()
+            .find(|s| s.as_str() == style)
+    }
+
+    fn instruction(self, max_words: usize) -> String {
+        match self {
+            ResponseStyle::Concise => format!(
+                "Answer concisely in at most {} words. Skip introductions, restating the question and closing remarks.",
+                max_words
+            ),
+            ResponseStyle::Detailed => {
+                "Answer in detail: explain the reasoning, cover edge cases and give examples where they help.".to_string()
+            }
+            ResponseStyle::Bullet => {
+                "Answer as a list of short bullet points starting with \"- \", one idea per bullet, without paragraphs.".to_string()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResponseStyleConfig {
+    // Length a concise answer is asked to stay within
+    pub concise_max_words: usize,
+    // Concise answers longer than this many words are compressed in a second pass; 0 disables
+    pub compress_above_words: usize,
+}
+
+impl Default for ResponseStyleConfig {
+    fn default() -> Self {
+        Self {
+            concise_max_words: 80,
+            compress_above_words: 120,
+        }
+    }
+}
+
+impl ResponseStyleConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.concise_max_words == 0 {
+            errors.push("response_style.concise_max_words must be at least 1".to_string());
+        }
+        if self.compress_above_words > 0 && self.compress_above_words < self.concise_max_words {
+            errors.push("response_style.compress_above_words must be 0 or at least concise_max_words".to_string());
+        }
+        errors
+    }
+}
+
+pub fn init() -> Result<(), String> {
+    with_db(|conn| conn.execute_batch(SCHEMA).map_err(db_err))
+}
+
+// Style a request runs under: the one it asks for, else its conversation's assistant's
+pub fn resolve(requested: Option<ResponseStyle>, conversation_id: Option<&str>) -> Result<Option<ResponseStyle>, String> {
+    if requested.is_some() {
+        return Ok(requested);
+    }
+    with_db(|conn| {
+        let Some(assistant_id) = conversation_id.map(|id| assistants::assistant_id_for(conn, id)).transpose()?.flatten() else {
+            return Ok(None);
+        };
+        let style: Option<String> = conn
+            .query_row(
+                "SELECT style FROM assistant_response_styles WHERE assistant_id = ?1",
+                params![assistant_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_err)?;
+        Ok(style.as_deref().and_then(ResponseStyle::parse))
+    })
+}
+
+// The system prompt with the style's instruction appended
+pub fn apply(system_prompt: &str, style: Option<ResponseStyle>) -> String {
+    match style {
+        Some(style) => format!("{} {}", system_prompt, style.instruction(config::get().response_style.concise_max_words)),
+        None => system_prompt.to_string(),
+    }
+}
+
+fn is_bullet(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("- ")
+        || line.starts_with("* ")
+        || line.split_once(". ").is_some_and(|(number, _)| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+// Prose turned into one bullet per sentence; answers with a list or a code block are left alone
+fn to_bullets(text: &str) -> Option<String> {
+    if text.contains("```") || text.lines().any(is_bullet) {
+        return None;
+    }
+    let mut bullets = Vec::new();
+    let mut sentence = String::new();
+    for (i, c) in text.char_indices() {
+        sentence.push(c);
+        let ends = matches!(c, '.' | '!' | '?') && text[i + c.len_utf8()..].starts_with(char::is_whitespace);
+        if ends || c == '\n' {
+            if !sentence.trim().is_empty() {
+                bullets.push(format!("- {}", sentence.trim()));
+            }
+            sentence.clear();
+        }
+    }
+    if !sentence.trim().is_empty() {
+        bullets.push(format!("- {}", sentence.trim()));
+    }
+    (bullets.len() > 1).then(|| bullets.join("\n"))
+}
+
+// Holds the finished answer to its style before the stream is finished
+pub async fn enforce(
+    tracker: &mut StreamTracker,
+    style: Option<ResponseStyle>,
+    model_id: &str,
+    sampling: &SamplingConfig,
+    app: &tauri::AppHandle,
+) {
+    match style {
+        Some(ResponseStyle::Concise) => {
+            let settings = config::get().response_style;
+            let words = tracker.content().split_whitespace().count();
+            if settings.compress_above_words == 0 || words <= settings.compress_above_words {
+                return;
+            }
+            println!("Concise answer ran to {} words; compressing it", words);
+            let prompt = format!(
+                "Shorten the following answer to at most {} words. Keep the facts, code and the language it is \
+                 written in, drop everything else and reply with the shortened answer only.\n\n{}",
+                settings.concise_max_words,
+                tracker.content()
+            );
+            match generate_text(model_id, &prompt, sampling, Priority::Interactive, app).await {
+                Ok(shortened) => tracker.replace_content(shortened.trim().to_string()),
+                Err(e) => println!("Warning: Failed to compress a concise answer: {}", e),
+            }
+        }
+        Some(ResponseStyle::Bullet) => {
+            if let Some(bullets) = to_bullets(tracker.content()) {
+                tracker.replace_content(bullets);
+            }
+        }
+        Some(ResponseStyle::Detailed) | None => {}
+    }
+}
+
+// Gives every answer of an assistant a style; None removes it
+#[tauri::command]
+pub async fn set_assistant_response_style(assistant_id: String, style: Option<ResponseStyle>) -> Result<Option<ResponseStyle>, String> {
+    db_writer::write(move |conn| {
+        let assistant = assistants::assistant_by_id(conn, &assistant_id)?;
+        match style {
+            Some(style) => {
+                conn.execute(
+                    "INSERT INTO assistant_response_styles (assistant_id, style) VALUES (?1, ?2)
+                     ON CONFLICT(assistant_id) DO UPDATE SET style = excluded.style",
+                    params![assistant.id, style.as_str()],
+                )
+                .map_err(db_err)?;
+            }
+            None => {
+                conn.execute("DELETE FROM assistant_response_styles WHERE assistant_id = ?1", params![assistant.id])
+                    .map_err(db_err)?;
+            }
+        }
+        Ok(style)
+    })
+    .await
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tauri::Manager;
+
+use crate::config;
+
+// Once the app has stayed up this long, the startup counts as healthy
+const HEALTHY_AFTER: Duration = Duration::from_secs(30);
+
+const STARTUP_STATE_FILE: &str = "startup_state.json";
+
+// Reason safe mode was entered, or None for a normal startup
+static SAFE_MODE_REASON: OnceLock<Option<String>> = OnceLock::new();
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StartupState {
+    consecutive_failures: u32,
+}
+
+fn state_path(app: &tauri::AppHandle) -> Option<PathBuf> {
+    app.path().app_data_dir().ok().map(|dir| dir.join(STARTUP_STATE_FILE))
+}
+
+fn read_state(path: &Path) -> StartupState {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn write_state(path: &Path, state: &StartupState) {
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    match serde_json::to_string(state) {
+        Ok(contents) => {
+            if let Err(e) = std::fs::write(path, contents) {
+                println!("Warning: Failed to write startup state: {}", e);
+            }
+        }
+        Err(e) => println!("Warning: Failed to serialize startup state: {}", e),
+    }
+}
+
+// Counts this launch as a failure until it proves healthy or exits cleanly, and decides whether to
+// start in safe mode
+pub fn init(app: &tauri::AppHandle) {
+    let config = config::get().safe_mode;
+    let path = state_path(app);
+
+    let previous_failures = path.as_ref().map(|p| read_state(p).consecutive_failures).unwrap_or(0);
+
+    let reason = if config.force {
+        Some("Safe mode was requested via safe_mode.force in config.toml".to_string())
+    } else if previous_failures >= config.crash_threshold {
+        Some(format!(
+            "The app failed to start {} times in a row; model preloading and GPU acceleration are disabled",
+            previous_failures
+        ))
+    } else {
+        None
+    };
+
+    if let Some(reason) = &reason {
+        println!("Starting in safe mode: {}", reason);
+    }
+    let _ = SAFE_MODE_REASON.set(reason);
+
+    if let Some(path) = path {
+        write_state(&path, &StartupState { consecutive_failures: previous_failures + 1 });
+
+        // Reset the counter once the app has been running long enough
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(HEALTHY_AFTER).await;
+            write_state(&path, &StartupState::default());
+            println!("Startup marked healthy");
+        });
+    }
+}
+
+// A graceful exit proves the launch wasn't a crash, however short the session was
+pub fn mark_clean_exit(app: &tauri::AppHandle) {
+    if let Some(path) = state_path(app) {
+        write_state(&path, &StartupState::default());
+    }
+}
+
+pub fn is_active() -> bool {
+    SAFE_MODE_REASON.get().map(|r| r.is_some()).unwrap_or(false)
+}
+
+// Safe mode loads every model on the CPU to sidestep broken GPU drivers
+pub fn force_cpu() -> bool {
+    is_active()
+}
+
+#[tauri::command]
+pub fn get_safe_mode_reason() -> Option<String> {
+    SAFE_MODE_REASON.get().cloned().flatten()
+}
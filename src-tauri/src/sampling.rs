@@ -0,0 +1,417 @@
+use either::Either;
+use mistralrs::{CustomLogitsProcessor, DrySamplingParams, RequestBuilder, StopTokens, Tensor};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::config;
+
+// Sampling parameters of a generation profile; unset fields keep the model's defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SamplingConfig {
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub top_k: Option<usize>,
+    pub min_p: Option<f64>,
+    pub frequency_penalty: Option<f32>,
+    pub presence_penalty: Option<f32>,
+    // Multiplicative penalty for tokens already in the context; 1.0 leaves them alone
+    pub repetition_penalty: Option<f32>,
+    pub max_tokens: Option<usize>,
+    // Generation stops at the first of these strings
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    // DRY repetition penalty; enabled by setting the multiplier (0.8 is a common start)
+    pub dry_multiplier: Option<f32>,
+    pub dry_base: Option<f32>,
+    pub dry_allowed_length: Option<usize>,
+    // Sample the first warmup_tokens tokens at this temperature, then switch to `temperature`
+    pub warmup_temperature: Option<f64>,
+    pub warmup_tokens: Option<usize>,
+    // Words and phrases the model may not emit, matched as the token sequences they encode to
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub banned_phrases: Vec<String>,
+    // Token id -> bias added to its logit; -100 effectively bans a token
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub logit_bias: BTreeMap<String, f32>,
+}
+
+// Banned phrases and logit bias kept for a single conversation, on top of its profile's
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenRules {
+    pub banned_phrases: Vec<String>,
+    pub logit_bias: BTreeMap<String, f32>,
+}
+
+impl TokenRules {
+    pub fn is_empty(&self) -> bool {
+        self.banned_phrases.is_empty() && self.logit_bias.is_empty()
+    }
+
+    pub fn validate(&self, name: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        for token in self.logit_bias.keys() {
+            if token.parse::<u32>().is_err() {
+                errors.push(format!("{}: logit_bias key \"{}\" is not a token id", name, token));
+            }
+        }
+        if self.banned_phrases.iter().any(|p| p.trim().is_empty()) {
+            errors.push(format!("{}: banned_phrases must not contain empty entries", name));
+        }
+        errors
+    }
+}
+
+// Masks the last token of each banned sequence whenever the tokens generated so far end with the rest of it
+struct PhraseBan {
+    sequences: Vec<Vec<u32>>,
+}
+
+impl CustomLogitsProcessor for PhraseBan {
+    fn apply(&self, logits: &Tensor, context: &[u32]) -> mistralrs::Result<Tensor> {
+        let banned: Vec<usize> = self
+            .sequences
+            .iter()
+            .filter_map(|seq| {
+                let (last, head) = seq.split_last()?;
+                context.ends_with(head).then_some(*last as usize)
+            })
+            .collect();
+        if banned.is_empty() {
+            return Ok(logits.clone());
+        }
+        let mut values = logits.to_vec1::<f32>()?;
+        for id in banned {
+            if let Some(value) = values.get_mut(id) {
+                *value = f32::NEG_INFINITY;
+            }
+        }
+        Tensor::from_vec(values, logits.dims1()?, logits.device())
+    }
+}
+
+// Rescales the logits of the first `tokens` generated tokens so the sampler's temperature acts as
+// `temperature` instead
+struct TemperatureWarmup {
+    // Logits multiplier: the request temperature over the warmup temperature
+    scale: f64,
+    tokens: usize,
+    // Context length of the first call, i.e. the prompt; usize::MAX until then
+    prompt_len: AtomicUsize,
+}
+
+impl CustomLogitsProcessor for TemperatureWarmup {
+    fn apply(&self, logits: &Tensor, context: &[u32]) -> mistralrs::Result<Tensor> {
+        let _ = self.prompt_len.compare_exchange(usize::MAX, context.len(), Ordering::SeqCst, Ordering::SeqCst);
+        let generated = context.len().saturating_sub(self.prompt_len.load(Ordering::SeqCst));
+        if generated < self.tokens {
+            logits.affine(self.scale, 0.0)
+        } else {
+            Ok(logits.clone())
+        }
+    }
+}
+
+impl SamplingConfig {
+    pub fn validate(&self, name: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if let Some(t) = self.temperature {
+            if !(0.0..=2.0).contains(&t) {
+                errors.push(format!("{}: temperature must be between 0 and 2 (got {})", name, t));
+            }
+        }
+        if let Some(p) = self.top_p {
+            if p <= 0.0 || p > 1.0 {
+                errors.push(format!("{}: top_p must be in (0, 1] (got {})", name, p));
+            }
+        }
+        if let Some(p) = self.min_p {
+            if !(0.0..=1.0).contains(&p) {
+                errors.push(format!("{}: min_p must be between 0 and 1 (got {})", name, p));
+            }
+        }
+        if self.top_k == Some(0) {
+            errors.push(format!("{}: top_k must be at least 1", name));
+        }
+        if self.max_tokens == Some(0) {
+            errors.push(format!("{}: max_tokens must be at least 1", name));
+        }
+        if let Some(p) = self.repetition_penalty {
+            if p <= 0.0 {
+                errors.push(format!("{}: repetition_penalty must be greater than 0 (got {})", name, p));
+            }
+        }
+        if self.stop.iter().any(|s| s.is_empty()) {
+            errors.push(format!("{}: stop must not contain empty entries", name));
+        }
+        if let Some(m) = self.dry_multiplier {
+            if m < 0.0 {
+                errors.push(format!("{}: dry_multiplier must not be negative (got {})", name, m));
+            }
+        }
+        if let Some(b) = self.dry_base {
+            if b <= 1.0 {
+                errors.push(format!("{}: dry_base must be greater than 1 (got {})", name, b));
+            }
+        }
+        if (self.dry_base.is_some() || self.dry_allowed_length.is_some()) && self.dry_multiplier.is_none() {
+            errors.push(format!("{}: dry_base and dry_allowed_length need dry_multiplier", name));
+        }
+        errors.extend(
+            TokenRules {
+                banned_phrases: self.banned_phrases.clone(),
+                logit_bias: self.logit_bias.clone(),
+            }
+            .validate(name),
+        );
+        if let Some(t) = self.warmup_temperature {
+            if t <= 0.0 || t > 2.0 {
+                errors.push(format!("{}: warmup_temperature must be in (0, 2] (got {})", name, t));
+            }
+            if !matches!(self.temperature, Some(t) if t > 0.0) {
+                errors.push(format!("{}: warmup_temperature needs a temperature above 0", name));
+            }
+        }
+        if self.warmup_temperature.is_some() != self.warmup_tokens.is_some_and(|n| n > 0) {
+            errors.push(format!("{}: warmup_temperature and warmup_tokens (at least 1) must be set together", name));
+        }
+
+        errors
+    }
+
+    // Plumbs the configured parameters into a mistralrs request
+    pub fn apply(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(temperature) = self.temperature {
+            request = request.set_sampler_temperature(temperature);
+        }
+        if let Some(top_p) = self.top_p {
+            request = request.set_sampler_topp(top_p);
+        }
+        if let Some(top_k) = self.top_k {
+            request = request.set_sampler_topk(top_k);
+        }
+        if let Some(min_p) = self.min_p {
+            request = request.set_sampler_minp(min_p);
+        }
+        if let Some(penalty) = self.frequency_penalty {
+            request = request.set_sampler_frequency_penalty(penalty);
+        }
+        if let Some(penalty) = self.presence_penalty {
+            request = request.set_sampler_presence_penalty(penalty);
+        }
+        if let Some(penalty) = self.repetition_penalty {
+            request = request.set_sampler_repetition_penalty(penalty);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            request = request.set_sampler_max_len(max_tokens);
+        }
+        if !self.stop.is_empty() {
+            request = request.set_sampler_stop_toks(StopTokens::Seqs(self.stop.clone()));
+        }
+        if let Some(multiplier) = self.dry_multiplier {
+            match DrySamplingParams::new_with_defaults(multiplier, None, self.dry_base, self.dry_allowed_length) {
+                Ok(dry) => request = request.set_sampler_dry(dry),
+                Err(e) => println!("Warning: Ignoring invalid DRY sampling parameters: {}", e),
+            }
+        }
+        if let (Some(warmup), Some(tokens), Some(temperature)) = (self.warmup_temperature, self.warmup_tokens, self.temperature) {
+            if warmup > 0.0 && temperature > 0.0 {
+                request = request.add_logits_processor(Arc::new(TemperatureWarmup {
+                    scale: temperature / warmup,
+                    tokens,
+                    prompt_len: AtomicUsize::new(usize::MAX),
+                }));
+            }
+        }
+        request
+    }
+
+    // Parameters set for a single request take precedence over the profile's; banned phrases,
+    // logit bias and stop sequences are added to it
+    pub fn overridden_by(self, overrides: &SamplingConfig) -> Self {
+        let mut merged = SamplingConfig {
+            temperature: overrides.temperature.or(self.temperature),
+            top_p: overrides.top_p.or(self.top_p),
+            top_k: overrides.top_k.or(self.top_k),
+            min_p: overrides.min_p.or(self.min_p),
+            frequency_penalty: overrides.frequency_penalty.or(self.frequency_penalty),
+            presence_penalty: overrides.presence_penalty.or(self.presence_penalty),
+            repetition_penalty: overrides.repetition_penalty.or(self.repetition_penalty),
+            max_tokens: overrides.max_tokens.or(self.max_tokens),
+            stop: self.stop,
+            dry_multiplier: overrides.dry_multiplier.or(self.dry_multiplier),
+            dry_base: overrides.dry_base.or(self.dry_base),
+            dry_allowed_length: overrides.dry_allowed_length.or(self.dry_allowed_length),
+            warmup_temperature: overrides.warmup_temperature.or(self.warmup_temperature),
+            warmup_tokens: overrides.warmup_tokens.or(self.warmup_tokens),
+            banned_phrases: self.banned_phrases,
+            logit_bias: self.logit_bias,
+        };
+        merged.stop.extend(overrides.stop.iter().cloned());
+        merged.with_rules(&TokenRules {
+            banned_phrases: overrides.banned_phrases.clone(),
+            logit_bias: overrides.logit_bias.clone(),
+        })
+    }
+
+    // Adds a conversation's banned phrases and logit bias to its profile's
+    pub fn with_rules(mut self, rules: &TokenRules) -> Self {
+        self.banned_phrases.extend(rules.banned_phrases.iter().cloned());
+        self.logit_bias.extend(rules.logit_bias.iter().map(|(token, bias)| (token.clone(), *bias)));
+        self
+    }
+
+    // Applies the logit bias and banned phrases, which need the model's tokenizer
+    pub async fn apply_token_rules(&self, mut request: RequestBuilder, model: &mistralrs::Model) -> RequestBuilder {
+        if !self.logit_bias.is_empty() {
+            let bias: HashMap<u32, f32> = self
+                .logit_bias
+                .iter()
+                .filter_map(|(token, bias)| Some((token.parse().ok()?, *bias)))
+                .collect();
+            request = request.set_sampler_logits_bias(bias);
+        }
+        if self.banned_phrases.is_empty() {
+            return request;
+        }
+
+        // Mid-sentence and capitalized forms encode differently, so each is banned separately
+        let mut sequences = HashSet::new();
+        for phrase in &self.banned_phrases {
+            let phrase = phrase.trim();
+            let mut chars = phrase.chars();
+            let capitalized = chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default();
+            for variant in [phrase.to_string(), format!(" {}", phrase), capitalized.clone(), format!(" {}", capitalized)] {
+                match model.tokenize(Either::Right(variant), None, false, false, None).await {
+                    Ok(tokens) if !tokens.is_empty() => {
+                        sequences.insert(tokens);
+                    }
+                    Ok(_) => {}
+                    Err(e) => println!("Warning: Failed to tokenize banned phrase \"{}\": {}", phrase, e),
+                }
+            }
+        }
+        request.add_logits_processor(Arc::new(PhraseBan {
+            sequences: sequences.into_iter().collect(),
+        }))
+    }
+}
+
+// Built-in "precise", "balanced" and "creative" profiles seeded into config.toml
+pub fn default_profiles() -> BTreeMap<String, SamplingConfig> {
+    let mut profiles = BTreeMap::new();
+
+    profiles.insert(
+        "precise".to_string(),
+        SamplingConfig {
+            temperature: Some(0.2),
+            top_p: Some(0.9),
+            top_k: Some(20),
+            ..Default::default()
+        },
+    );
+    profiles.insert(
+        "balanced".to_string(),
+        SamplingConfig {
+            temperature: Some(0.7),
+            top_p: Some(0.95),
+            top_k: Some(40),
+            ..Default::default()
+        },
+    );
+    profiles.insert(
+        "creative".to_string(),
+        SamplingConfig {
+            temperature: Some(1.1),
+            top_p: Some(0.98),
+            min_p: Some(0.05),
+            presence_penalty: Some(0.3),
+            ..Default::default()
+        },
+    );
+
+    profiles
+}
+
+// Resolves the effective profile: per-message choice, then the conversation's, then the default
+pub fn resolve_profile(message_profile: Option<&str>, conversation_id: Option<&str>) -> Result<(String, SamplingConfig), String> {
+    let generation = config::get().generation;
+
+    let name = message_profile
+        .map(|p| p.to_string())
+        .or_else(|| conversation_id.and_then(|id| generation.conversation_profiles.get(id).cloned()))
+        .unwrap_or(generation.default_profile);
+
+    let mut sampling = generation
+        .profiles
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| format!("Unknown generation profile: {}", name))?;
+    if let Some(rules) = conversation_id.and_then(|id| generation.conversation_token_rules.get(id)) {
+        sampling = sampling.with_rules(rules);
+    }
+
+    Ok((name, sampling))
+}
+
+#[tauri::command]
+pub fn list_generation_profiles() -> BTreeMap<String, SamplingConfig> {
+    config::get().generation.profiles
+}
+
+// Creates or replaces a named profile in config.toml
+#[tauri::command]
+pub fn save_generation_profile(name: String, sampling: SamplingConfig, app: tauri::AppHandle) -> Result<BTreeMap<String, SamplingConfig>, String> {
+    let config = config::update(&app, |config| {
+        config.generation.profiles.insert(name, sampling);
+    })?;
+    Ok(config.generation.profiles)
+}
+
+// Selects the profile a conversation uses; None falls back to the default profile
+#[tauri::command]
+pub fn set_conversation_profile(conversation_id: String, profile: Option<String>, app: tauri::AppHandle) -> Result<(), String> {
+    config::update(&app, |config| match profile {
+        Some(profile) => {
+            config.generation.conversation_profiles.insert(conversation_id, profile);
+        }
+        None => {
+            config.generation.conversation_profiles.remove(&conversation_id);
+        }
+    })?;
+    Ok(())
+}
+
+// Replaces a conversation's banned phrases and logit bias; empty lists remove them
+#[tauri::command]
+pub fn set_conversation_token_rules(
+    conversation_id: String,
+    banned_phrases: Option<Vec<String>>,
+    logit_bias: Option<BTreeMap<String, f32>>,
+    app: tauri::AppHandle,
+) -> Result<TokenRules, String> {
+    let rules = TokenRules {
+        banned_phrases: banned_phrases.unwrap_or_default(),
+        logit_bias: logit_bias.unwrap_or_default(),
+    };
+    config::update(&app, |config| {
+        if rules.is_empty() {
+            config.generation.conversation_token_rules.remove(&conversation_id);
+        } else {
+            config.generation.conversation_token_rules.insert(conversation_id, rules.clone());
+        }
+    })?;
+    Ok(rules)
+}
+
+#[tauri::command]
+pub fn get_conversation_token_rules(conversation_id: String) -> TokenRules {
+    config::get().generation.conversation_token_rules.get(&conversation_id).cloned().unwrap_or_default()
+}
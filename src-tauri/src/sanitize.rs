@@ -0,0 +1,273 @@
+// Makes streamed markdown safe to render as it arrives: partial constructs are held back until
+// complete, dangerous HTML is removed outside code, and an unclosed code fence is closed at the end.
+
+// Removed together with everything up to their closing tag
+const DROPPED_WITH_CONTENT: [&str; 6] = ["script", "style", "iframe", "object", "noscript", "template"];
+
+// Removed, but their content (if any) is kept as text
+const DROPPED_TAGS: [&str; 11] = [
+    "embed", "link", "meta", "base", "form", "input", "button", "textarea", "select", "frame", "frameset",
+];
+
+const DANGEROUS_SCHEMES: [&str; 3] = ["javascript:", "vbscript:", "data:text/html"];
+
+// A '<' with no '>' after this many bytes is treated as plain text rather than a tag
+const MAX_TAG_LEN: usize = 1024;
+
+// Long enough to hold "](" plus a link scheme
+const MAX_LINK_PREFIX: usize = 20;
+
+enum TagAction {
+    Keep(String),
+    Drop,
+    DropWithContent(String),
+}
+
+#[derive(Debug)]
+pub struct MarkdownSanitizer {
+    // Received text not yet released because it may be the start of a tag, link or fence
+    pending: String,
+    in_fence: bool,
+    in_inline_code: bool,
+    at_line_start: bool,
+    // Closing tag name while skipping the body of e.g. <script>
+    skip_until: Option<String>,
+}
+
+impl Default for MarkdownSanitizer {
+    fn default() -> Self {
+        Self {
+            pending: String::new(),
+            in_fence: false,
+            in_inline_code: false,
+            at_line_start: true,
+            skip_until: None,
+        }
+    }
+}
+
+fn has_dangerous_scheme(value: &str) -> bool {
+    let compact: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    DANGEROUS_SCHEMES.iter().any(|scheme| compact.contains(scheme))
+}
+
+// Rebuilds a tag without event handlers or script URLs
+fn clean_attributes(name: &str, closing: bool, mut rest: &str) -> String {
+    let mut tag = format!("<{}{}", if closing { "/" } else { "" }, name);
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        if let Some(after) = rest.strip_prefix('/') {
+            tag.push_str(" /");
+            rest = after;
+            continue;
+        }
+        let name_len = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        let attr_name = &rest[..name_len];
+        rest = &rest[name_len..];
+
+        let mut value = None;
+        if let Some(after_eq) = rest.trim_start().strip_prefix('=') {
+            let after_eq = after_eq.trim_start();
+            let value_len = match after_eq.chars().next() {
+                Some(quote @ ('"' | '\'')) => after_eq[1..].find(quote).map(|e| e + 2).unwrap_or(after_eq.len()),
+                _ => after_eq.find(char::is_whitespace).unwrap_or(after_eq.len()),
+            };
+            value = Some(&after_eq[..value_len]);
+            rest = &after_eq[value_len..];
+        }
+
+        let lower = attr_name.to_ascii_lowercase();
+        if lower.starts_with("on") || lower == "srcdoc" || value.is_some_and(has_dangerous_scheme) {
+            continue;
+        }
+        tag.push(' ');
+        tag.push_str(attr_name);
+        if let Some(value) = value {
+            tag.push('=');
+            tag.push_str(value);
+        }
+    }
+    tag.push('>');
+    tag
+}
+
+fn clean_tag(tag: &str) -> TagAction {
+    let inner = &tag[1..tag.len() - 1];
+    // Comments, doctypes and processing instructions have no place in a chat reply
+    if inner.starts_with('!') || inner.starts_with('?') {
+        return TagAction::Drop;
+    }
+    let closing = inner.starts_with('/');
+    let body = inner.trim_start_matches('/');
+    let name = body
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect::<String>()
+        .to_ascii_lowercase();
+
+    // "a <b" or "<3" in prose, not a tag
+    if name.is_empty() {
+        return TagAction::Keep(tag.to_string());
+    }
+    // Markdown autolinks such as <https://example.com>
+    if body[name.len()..].starts_with(':') {
+        return if has_dangerous_scheme(inner) { TagAction::Drop } else { TagAction::Keep(tag.to_string()) };
+    }
+    if DROPPED_WITH_CONTENT.contains(&name.as_str()) {
+        return if closing || inner.ends_with('/') { TagAction::Drop } else { TagAction::DropWithContent(name) };
+    }
+    if DROPPED_TAGS.contains(&name.as_str()) {
+        return TagAction::Drop;
+    }
+    TagAction::Keep(clean_attributes(&name, closing, &body[name.len()..]))
+}
+
+impl MarkdownSanitizer {
+    // Feeds raw model output and returns the part that is now safe to display
+    pub fn push(&mut self, text: &str) -> String {
+        self.pending.push_str(text);
+        let cut = self.safe_cut();
+        let ready: String = self.pending.drain(..cut).collect();
+        self.process(&ready, false)
+    }
+
+    // Releases everything still held back and closes an unterminated code fence
+    pub fn finish(&mut self) -> String {
+        let rest = std::mem::take(&mut self.pending);
+        let mut output = self.process(&rest, true);
+        if self.in_fence {
+            if !output.ends_with('\n') && !self.at_line_start {
+                output.push('\n');
+            }
+            output.push_str("```");
+        }
+        *self = Self::default();
+        output
+    }
+
+    // Byte offset up to which pending text can't change meaning with more input
+    fn safe_cut(&self) -> usize {
+        let pending = &self.pending;
+        let mut cut = pending.len();
+
+        if let Some(lt) = pending.rfind('<') {
+            if !pending[lt..].contains('>') && pending.len() - lt < MAX_TAG_LEN {
+                cut = cut.min(lt);
+            }
+        }
+        if let Some(link) = pending.rfind("](") {
+            if !pending[link..].contains(')') && pending.len() - link < MAX_LINK_PREFIX {
+                cut = cut.min(link);
+            }
+        } else if pending.ends_with(']') {
+            cut = cut.min(pending.len() - 1);
+        }
+
+        // One or two backticks at the start of a line may become a fence
+        let line_start = match pending.rfind('\n') {
+            Some(i) => i + 1,
+            None if self.at_line_start => 0,
+            None => pending.len(),
+        };
+        let tail = pending[line_start..].trim_start_matches(' ');
+        if !tail.is_empty() && tail.len() < 3 && tail.chars().all(|c| c == '`') {
+            cut = cut.min(line_start);
+        }
+        cut
+    }
+
+    fn process(&mut self, text: &str, last: bool) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut i = 0;
+
+        while i < text.len() {
+            let rest = &text[i..];
+
+            if let Some(name) = &self.skip_until {
+                let close = format!("</{}", name);
+                let lower = rest.to_ascii_lowercase();
+                match lower.find(&close).and_then(|start| lower[start..].find('>').map(|end| start + end + 1)) {
+                    Some(end) => {
+                        self.skip_until = None;
+                        i += end;
+                    }
+                    None => i = text.len(),
+                }
+                continue;
+            }
+
+            if self.at_line_start && !self.in_inline_code && rest.trim_start_matches(' ').starts_with("```") {
+                // Only the fence marker itself; an info string is passed through by the fence state
+                let indent = rest.len() - rest.trim_start_matches(' ').len();
+                let end = indent + rest[indent..].len() - rest[indent..].trim_start_matches('`').len();
+                output.push_str(&rest[..end]);
+                self.in_fence = !self.in_fence;
+                self.at_line_start = false;
+                i += end;
+                continue;
+            }
+
+            let c = rest.chars().next().unwrap_or('\0');
+            let mut consumed = c.len_utf8();
+            if self.in_fence {
+                output.push(c);
+            } else if c == '\n' {
+                self.in_inline_code = false;
+                output.push(c);
+            } else if c == '`' {
+                self.in_inline_code = !self.in_inline_code;
+                output.push(c);
+            } else if self.in_inline_code {
+                output.push(c);
+            } else if c == '<' {
+                match rest.find('>') {
+                    Some(end) => {
+                        consumed = end + 1;
+                        match clean_tag(&rest[..=end]) {
+                            TagAction::Keep(tag) => output.push_str(&tag),
+                            TagAction::Drop => {}
+                            TagAction::DropWithContent(name) => self.skip_until = Some(name),
+                        }
+                    }
+                    // Only reachable once the stream has ended, or for a '<' longer than MAX_TAG_LEN
+                    None if last || rest.len() >= MAX_TAG_LEN => output.push_str("&lt;"),
+                    None => output.push(c),
+                }
+            } else if let Some(target) = rest.strip_prefix("](") {
+                let target = target.trim_start();
+                let scheme = DANGEROUS_SCHEMES
+                    .iter()
+                    .find(|scheme| target.get(..scheme.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme)));
+                output.push_str("](");
+                consumed = rest.len() - target.len();
+                if let Some(scheme) = scheme {
+                    output.push('#');
+                    consumed += scheme.len();
+                }
+            } else {
+                output.push(c);
+            }
+            self.at_line_start = c == '\n';
+            i += consumed;
+        }
+
+        output
+    }
+}
+
+// Sanitizes a complete text in one go, e.g. a response that was rewritten after streaming
+pub fn sanitize(text: &str) -> String {
+    let mut sanitizer = MarkdownSanitizer::default();
+    let mut output = sanitizer.push(text);
+    output.push_str(&sanitizer.finish());
+    output
+}
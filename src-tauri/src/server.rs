@@ -0,0 +1,497 @@
+use axum::extract::ws::{Message as WsMessage, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path as UrlPath, Query, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use futures::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use tauri::Listener;
+use tokio::sync::mpsc;
+
+use crate::config::{self, CONFIG_CHANGED_EVENT};
+use crate::{audit, middleware};
+use crate::history::{self, with_db};
+use crate::keep_alive::MODEL_UNLOADED_EVENT;
+use crate::kiosk;
+use crate::memory::MEMORY_PRESSURE_EVENT;
+use crate::presentation;
+use crate::streaming::{self, now_ms, CHAT_STREAM_EVENT};
+
+static SERVER_RUNNING: AtomicBool = AtomicBool::new(false);
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+// App events mirrored to remote clients, matching what desktop windows listen for
+const FORWARDED_EVENTS: [&str; 4] = [CHAT_STREAM_EVENT, CONFIG_CHANGED_EVENT, MODEL_UNLOADED_EVENT, MEMORY_PRESSURE_EVENT];
+
+// Active read-only shares, keyed by their access token
+static SHARES: OnceLock<Mutex<HashMap<String, Share>>> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Share {
+    pub token: String,
+    pub conversation_id: String,
+    pub url: String,
+    pub created_at: u64,
+}
+
+fn shares() -> &'static Mutex<HashMap<String, Share>> {
+    SHARES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn router(app: tauri::AppHandle) -> Router {
+    Router::new()
+        .route("/share/:token", get(share_page))
+        .route("/ws", get(remote_socket))
+        .with_state(app)
+}
+
+// Starts the local HTTP server once; later calls are no-ops
+pub async fn ensure_started(app: &tauri::AppHandle) -> Result<SocketAddr, String> {
+    let addr: SocketAddr = config::get()
+        .server
+        .bind
+        .parse()
+        .map_err(|e| format!("Invalid server.bind: {}", e))?;
+
+    if SERVER_RUNNING.swap(true, Ordering::SeqCst) {
+        return Ok(addr);
+    }
+
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            SERVER_RUNNING.store(false, Ordering::SeqCst);
+            return Err(format!("Failed to bind local server to {}: {}", addr, e));
+        }
+    };
+
+    println!("Local server listening on http://{}", addr);
+    let router = router(app.clone());
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            println!("Warning: Local server stopped: {}", e);
+        }
+        SERVER_RUNNING.store(false, Ordering::SeqCst);
+    });
+
+    Ok(addr)
+}
+
+// Starts the server at launch when server.enabled is set in config.toml
+pub fn spawn_if_enabled(app: tauri::AppHandle) {
+    if !config::get().server.enabled {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = ensure_started(&app).await {
+            println!("Warning: {}", e);
+        }
+    });
+}
+
+// Best-effort LAN address of this machine; no packets are sent
+fn lan_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("192.168.0.1:80").ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+// Host other devices should use to reach the server, or an error if it only listens on loopback
+fn lan_host(addr: SocketAddr) -> Result<String, String> {
+    if addr.ip().is_loopback() {
+        return Err(format!(
+            "server.bind is {} which is only reachable from this machine; set server.bind = \"0.0.0.0:{}\" in config.toml to use it over the LAN",
+            addr,
+            addr.port()
+        ));
+    }
+
+    Ok(if addr.ip().is_unspecified() {
+        lan_ip().map(|ip| ip.to_string()).unwrap_or_else(|| "localhost".to_string())
+    } else {
+        addr.ip().to_string()
+    })
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn render_conversation(detail: &history::ConversationDetail) -> String {
+    let mut body = String::new();
+    for message in &detail.messages {
+        body.push_str(&format!(
+            "<div class=\"msg {role}\"><div class=\"role\">{role}</div><pre>{content}</pre></div>\n",
+            role = escape_html(&message.role),
+            content = escape_html(&message.content),
+        ));
+    }
+
+    format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+         <title>{title}</title><style>\
+         body{{font-family:system-ui,sans-serif;max-width:760px;margin:2rem auto;padding:0 1rem;color:#222}}\
+         .msg{{margin:1rem 0;padding:.75rem 1rem;border-radius:8px;background:#f4f4f5}}\
+         .msg.user{{background:#e0ecff}}.role{{font-size:.75rem;text-transform:uppercase;color:#666}}\
+         pre{{white-space:pre-wrap;font-family:inherit;margin:.25rem 0 0}}\
+         </style></head><body><h1>{title}</h1><p><em>Read-only view shared from tauri-mistral-chat</em></p>\n{body}</body></html>",
+        title = escape_html(&detail.conversation.title),
+        body = body,
+    )
+}
+
+async fn share_page(UrlPath(token): UrlPath<String>) -> Response {
+    let conversation_id = shares()
+        .lock()
+        .unwrap()
+        .get(&token)
+        .map(|share| share.conversation_id.clone());
+
+    let Some(conversation_id) = conversation_id else {
+        return (StatusCode::NOT_FOUND, Html("<h1>Share not found or revoked</h1>".to_string())).into_response();
+    };
+
+    match with_db(|conn| history::load(conn, &conversation_id)) {
+        Ok(mut detail) => {
+            // LAN requests bypass the command middleware, so shares started earlier are redacted here
+            if presentation::is_active() {
+                presentation::redact_detail(&mut detail);
+            }
+            Html(render_conversation(&detail)).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Html(escape_html(&e))).into_response(),
+    }
+}
+
+// Publishes a token-protected, read-only view of a conversation on the local network
+#[tauri::command]
+pub async fn start_lan_share(conversation_id: String, app: tauri::AppHandle) -> Result<Share, String> {
+    kiosk::ensure_unlocked("Conversation sharing")?;
+    with_db(|conn| history::load(conn, &conversation_id).map(|_| ()))?;
+
+    let addr = ensure_started(&app).await?;
+    let host = lan_host(addr)?;
+
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    let share = Share {
+        url: format!("http://{}:{}/share/{}", host, addr.port(), token),
+        token: token.clone(),
+        conversation_id,
+        created_at: now_ms(),
+    };
+    shares().lock().unwrap().insert(token, share.clone());
+
+    println!("Sharing conversation {} at {}", share.conversation_id, share.url);
+    Ok(share)
+}
+
+#[tauri::command]
+pub fn stop_lan_share(token: String) -> Result<(), String> {
+    shares()
+        .lock()
+        .unwrap()
+        .remove(&token)
+        .map(|_| ())
+        .ok_or_else(|| "Share not found".to_string())
+}
+
+#[tauri::command]
+pub fn list_lan_shares() -> Vec<Share> {
+    shares().lock().unwrap().values().cloned().collect()
+}
+
+// Frames sent to remote clients: command replies and mirrored app events
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerFrame {
+    Response {
+        id: Value,
+        ok: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        result: Option<Value>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    Event {
+        event: String,
+        payload: Value,
+    },
+}
+
+// A command invocation from a remote client, mirroring `invoke(cmd, args)`
+#[derive(Debug, Deserialize)]
+struct ClientCall {
+    id: Value,
+    cmd: String,
+    #[serde(default)]
+    args: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteControlInfo {
+    pub enabled: bool,
+    pub url: Option<String>,
+    pub token: Option<String>,
+}
+
+// Compares tokens without short-circuiting on the first differing byte
+fn tokens_match(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+async fn remote_socket(
+    ws: WebSocketUpgrade,
+    Query(query): Query<HashMap<String, String>>,
+    State(app): State<tauri::AppHandle>,
+) -> Response {
+    let server = config::get().server;
+    if !server.remote_control {
+        return (StatusCode::FORBIDDEN, "Remote control is disabled").into_response();
+    }
+
+    let authorized = match (server.remote_token.as_deref(), query.get("token")) {
+        (Some(expected), Some(given)) => tokens_match(expected, given),
+        _ => false,
+    };
+    if !authorized {
+        return (StatusCode::UNAUTHORIZED, "Invalid token").into_response();
+    }
+
+    ws.on_upgrade(move |socket| serve_client(socket, app))
+}
+
+async fn serve_client(socket: WebSocket, app: tauri::AppHandle) {
+    let client_id = NEXT_CLIENT_ID.fetch_add(1, Ordering::SeqCst);
+    let label = format!("remote-{}", client_id);
+    println!("Remote client {} connected", label);
+
+    let (mut sink, mut stream) = socket.split();
+    let (tx, mut rx) = mpsc::unbounded_channel::<ServerFrame>();
+
+    // Chat streams are only forwarded to, and resumable by, the client that started them. Ids stay
+    // for the whole connection so a finished stream can still be resumed.
+    let own_requests: Arc<Mutex<HashSet<String>>> = Arc::new(Mutex::new(HashSet::new()));
+
+    let listeners: Vec<_> = FORWARDED_EVENTS
+        .iter()
+        .map(|&name| {
+            let tx = tx.clone();
+            let own_requests = own_requests.clone();
+            app.listen_any(name, move |event| {
+                let payload: Value = serde_json::from_str(event.payload()).unwrap_or(Value::Null);
+                if name == CHAT_STREAM_EVENT {
+                    let request_id = payload.get("request_id").and_then(Value::as_str).unwrap_or_default();
+                    if !own_requests.lock().unwrap().contains(request_id) {
+                        return;
+                    }
+                }
+                let _ = tx.send(ServerFrame::Event {
+                    event: name.to_string(),
+                    payload,
+                });
+            })
+        })
+        .collect();
+
+    let writer = tauri::async_runtime::spawn(async move {
+        while let Some(frame) = rx.recv().await {
+            let Ok(text) = serde_json::to_string(&frame) else {
+                continue;
+            };
+            if sink.send(WsMessage::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(Ok(message)) = stream.next().await {
+        let text = match message {
+            WsMessage::Text(text) => text,
+            WsMessage::Close(_) => break,
+            _ => continue,
+        };
+
+        let call: ClientCall = match serde_json::from_str(&text) {
+            Ok(call) => call,
+            Err(e) => {
+                let _ = tx.send(ServerFrame::Response {
+                    id: Value::Null,
+                    ok: false,
+                    result: None,
+                    error: Some(format!("Malformed call: {}", e)),
+                });
+                continue;
+            }
+        };
+
+        // Commands run concurrently so a long generation doesn't block other calls
+        let app = app.clone();
+        let tx = tx.clone();
+        let label = label.clone();
+        let own_requests = own_requests.clone();
+        tauri::async_runtime::spawn(async move {
+            let payload_bytes = serde_json::to_string(&call.args).map_or(0, |json| json.len());
+            let source = format!("remote:{}", label);
+            let started = Instant::now();
+            let outcome = match middleware::check(&call.cmd, payload_bytes) {
+                Ok(()) => dispatch(&call.cmd, call.args, &app, &label, &own_requests).await,
+                Err(e) => Err(e),
+            };
+            // Remote calls are awaited here, so unlike IPC their entries cover the whole command
+            let status = if outcome.is_ok() { "ok" } else { "error" };
+            let elapsed_ms = started.elapsed().as_millis() as u64;
+            audit::record(&call.cmd, &source, payload_bytes, elapsed_ms, status, outcome.as_ref().err().map(String::as_str));
+            let (ok, result, error) = match outcome {
+                Ok(value) => (true, Some(value), None),
+                Err(e) => (false, None, Some(e)),
+            };
+            let _ = tx.send(ServerFrame::Response {
+                id: call.id,
+                ok,
+                result,
+                error,
+            });
+        });
+    }
+
+    for id in listeners {
+        app.unlisten(id);
+    }
+    drop(tx);
+    writer.abort();
+    println!("Remote client {} disconnected", label);
+}
+
+// Reads a camelCase argument the same way Tauri does for IPC commands
+fn arg<T: DeserializeOwned>(args: &Value, name: &str) -> Result<T, String> {
+    serde_json::from_value(args.get(name).cloned().unwrap_or(Value::Null))
+        .map_err(|e| format!("Invalid argument {}: {}", name, e))
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, String> {
+    serde_json::to_value(value).map_err(|e| e.to_string())
+}
+
+// Image paths and the clipboard are this computer's, not the remote client's; it sends image data instead
+const LOCAL_IMAGE_SOURCES: &str = "Remote clients can't attach images from files or the clipboard of this computer";
+
+// Routes a remote call to the matching Tauri command
+async fn dispatch(
+    cmd: &str,
+    args: Value,
+    app: &tauri::AppHandle,
+    label: &str,
+    own_requests: &Mutex<HashSet<String>>,
+) -> Result<Value, String> {
+    match cmd {
+        "ai_chat" => {
+            let mut chat_args: crate::ChatArgs = serde_json::from_value(args).map_err(|e| format!("Invalid arguments: {}", e))?;
+            if chat_args.image_path.is_some() || chat_args.image_from_clipboard {
+                return Err(LOCAL_IMAGE_SOURCES.to_string());
+            }
+            let request_id = chat_args.request_id.get_or_insert_with(streaming::new_request_id).clone();
+            // Reusing another request's id would subscribe this client to that stream
+            if streaming::is_buffered(&request_id) {
+                return Err(format!("Request id {} is already in use", request_id));
+            }
+            own_requests.lock().unwrap().insert(request_id);
+            to_value(crate::chat(chat_args, app.clone(), Some(label.to_string())).await?)
+        }
+        "discover_models" => to_value(crate::discover_models(app.clone()).await?),
+        "resume_stream" => {
+            let request_id: String = arg(&args, "requestId")?;
+            if !own_requests.lock().unwrap().contains(&request_id) {
+                return Err(format!("Request {} was not started by this client", request_id));
+            }
+            to_value(streaming::resume_stream(request_id, arg(&args, "fromIndex")?)?)
+        }
+        "get_metrics" => to_value(crate::metrics::get_metrics()),
+        "list_generation_profiles" => to_value(crate::sampling::list_generation_profiles()),
+        "list_slash_commands" => to_value(crate::slash_commands::list_slash_commands()),
+        "expand_slash_command" => to_value(crate::slash_commands::expand_slash_command(arg(&args, "message")?)?),
+        "list_conversations" => to_value(history::list_conversations().await?),
+        "load_conversation" => to_value(history::load_conversation(arg(&args, "conversationId")?, app.clone()).await?),
+        "create_conversation" => to_value(history::create_conversation(arg(&args, "title")?).await?),
+        "append_message" => {
+            if !args.get("imagePath").unwrap_or(&Value::Null).is_null()
+                || !args.get("clipboardRequestId").unwrap_or(&Value::Null).is_null()
+            {
+                return Err(LOCAL_IMAGE_SOURCES.to_string());
+            }
+            to_value(
+                history::append_message(
+                    arg(&args, "conversationId")?,
+                    arg(&args, "role")?,
+                    arg(&args, "content")?,
+                    arg(&args, "requestId")?,
+                    arg(&args, "imageData")?,
+                    None,
+                    None,
+                    app.clone(),
+                )
+                .await?,
+            )
+        }
+        _ => Err(format!("Unknown or unsupported remote command: {}", cmd)),
+    }
+}
+
+// Returns the WebSocket URL and token a phone browser needs to connect as a remote client
+#[tauri::command]
+pub async fn get_remote_control_info(app: tauri::AppHandle) -> Result<RemoteControlInfo, String> {
+    let server = config::get().server;
+    if !server.remote_control {
+        return Ok(RemoteControlInfo {
+            enabled: false,
+            url: None,
+            token: None,
+        });
+    }
+
+    let addr = ensure_started(&app).await?;
+    let host = lan_host(addr)?;
+    Ok(RemoteControlInfo {
+        enabled: true,
+        url: Some(format!("ws://{}:{}/ws", host, addr.port())),
+        token: server.remote_token,
+    })
+}
+
+// Turns the remote channel on or off, generating a token the first time it is enabled
+#[tauri::command]
+pub async fn set_remote_control(enabled: bool, app: tauri::AppHandle) -> Result<RemoteControlInfo, String> {
+    config::update(&app, |config| {
+        config.server.remote_control = enabled;
+        if enabled && config.server.remote_token.is_none() {
+            config.server.remote_token = Some(uuid::Uuid::new_v4().simple().to_string());
+        }
+    })?;
+    get_remote_control_info(app).await
+}
+
+// Issues a new token; clients using the old one are rejected on their next connection
+#[tauri::command]
+pub async fn rotate_remote_token(app: tauri::AppHandle) -> Result<RemoteControlInfo, String> {
+    config::update(&app, |config| {
+        config.server.remote_token = Some(uuid::Uuid::new_v4().simple().to_string());
+    })?;
+    get_remote_control_info(app).await
+}
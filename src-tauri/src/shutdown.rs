@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::{db_writer, metrics, models, safe_mode, telemetry};
+
+// How long in-flight generations may keep running once shutdown starts
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Extra time for cancelled generations to wind down and report partial output
+const CANCEL_TIMEOUT: Duration = Duration::from_secs(5);
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+static DRAINED: AtomicBool = AtomicBool::new(false);
+
+// Cancellation flags of requests currently generating, keyed by request id
+static IN_FLIGHT: OnceLock<Mutex<HashMap<String, Arc<AtomicBool>>>> = OnceLock::new();
+
+fn in_flight() -> &'static Mutex<HashMap<String, Arc<AtomicBool>>> {
+    IN_FLIGHT.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Registration of one running request; dropping it marks the request as finished
+pub struct InFlightGuard {
+    request_id: String,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl InFlightGuard {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        in_flight().lock().unwrap().remove(&self.request_id);
+    }
+}
+
+// Registers a new request, refusing it once the app has started shutting down
+pub fn begin_request(request_id: &str) -> Result<InFlightGuard, String> {
+    if SHUTTING_DOWN.load(Ordering::SeqCst) {
+        return Err("The app is shutting down and no longer accepts requests".to_string());
+    }
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    in_flight()
+        .lock()
+        .unwrap()
+        .insert(request_id.to_string(), cancelled.clone());
+
+    Ok(InFlightGuard {
+        request_id: request_id.to_string(),
+        cancelled,
+    })
+}
+
+// Asks every running generation to stop and return its partial output
+pub fn cancel_all_in_flight() -> usize {
+    let in_flight = in_flight().lock().unwrap();
+    for cancelled in in_flight.values() {
+        cancelled.store(true, Ordering::Relaxed);
+    }
+    in_flight.len()
+}
+
+// Asks one running generation to stop; false when no request with that id is generating
+pub fn cancel_request(request_id: &str) -> bool {
+    match in_flight().lock().unwrap().get(request_id) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+// Stops a streaming chat; the stream is dropped so the engine stops generating, and the partial
+// answer is handled like any other cancelled request
+#[tauri::command]
+pub fn cancel_generation(request_id: String) -> bool {
+    let found = cancel_request(&request_id);
+    if found {
+        println!("[{}] Cancellation requested", request_id);
+    }
+    found
+}
+
+fn in_flight_count() -> usize {
+    in_flight().lock().unwrap().len()
+}
+
+async fn wait_for_in_flight(timeout: Duration) -> bool {
+    let started = Instant::now();
+    while in_flight_count() > 0 {
+        if started.elapsed() >= timeout {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    true
+}
+
+// Intercepts the exit request, drains in-flight work, then exits for real
+pub fn handle_exit_requested(app: &tauri::AppHandle, api: &tauri::ExitRequestApi) {
+    if DRAINED.load(Ordering::SeqCst) {
+        return;
+    }
+
+    api.prevent_exit();
+    if SHUTTING_DOWN.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        drain(&app).await;
+        DRAINED.store(true, Ordering::SeqCst);
+        app.exit(0);
+    });
+}
+
+async fn drain(app: &tauri::AppHandle) {
+    println!("Shutting down: waiting for {} in-flight request(s)", in_flight_count());
+
+    if !wait_for_in_flight(DRAIN_TIMEOUT).await {
+        // Cancelled streams return their partial output to the frontend
+        println!("Cancelling {} request(s) still generating", cancel_all_in_flight());
+        if !wait_for_in_flight(CANCEL_TIMEOUT).await {
+            println!("Warning: {} request(s) did not stop in time", in_flight_count());
+        }
+    }
+
+    metrics::flush(app);
+    // Commits history writes still queued behind the last answers
+    if let Err(e) = tokio::task::spawn_blocking(db_writer::flush).await {
+        println!("Warning: Failed to flush history writes: {}", e);
+    }
+    // Blocks until the batch exporter has sent its spans, so keep it off the async workers
+    if let Err(e) = tokio::task::spawn_blocking(telemetry::flush).await {
+        println!("Warning: Failed to flush traces: {}", e);
+    }
+
+    // Drop models explicitly so GPU memory is released before the process exits
+    models::manager(app).clear().await;
+    safe_mode::mark_clean_exit(app);
+    println!("Shutdown complete");
+}
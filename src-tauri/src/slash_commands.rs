@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::config;
+
+// A stored prompt template triggered by "/name arg1 arg2 text..."
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SlashCommand {
+    pub description: String,
+    // Positional arguments consumed before the free text, referenced as {name} in the template
+    #[serde(default)]
+    pub args: Vec<String>,
+    // Prompt sent to the model; {input} is replaced with the remaining text
+    pub template: String,
+}
+
+// All configured commands, keyed by name (without the leading slash)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SlashCommands(pub BTreeMap<String, SlashCommand>);
+
+impl Default for SlashCommands {
+    fn default() -> Self {
+        let mut commands = BTreeMap::new();
+
+        commands.insert(
+            "summarize".to_string(),
+            SlashCommand {
+                description: "Summarize the given text".to_string(),
+                args: vec![],
+                template: "Summarize the following text in a few sentences:\n\n{input}".to_string(),
+            },
+        );
+        commands.insert(
+            "translate".to_string(),
+            SlashCommand {
+                description: "Translate text into a language, e.g. /translate fr Hello".to_string(),
+                args: vec!["language".to_string()],
+                template: "Translate the following text into the language with code \"{language}\". Reply with the translation only.\n\n{input}".to_string(),
+            },
+        );
+        commands.insert(
+            "explain".to_string(),
+            SlashCommand {
+                description: "Explain code or a concept step by step".to_string(),
+                args: vec![],
+                template: "Explain the following clearly and step by step:\n\n{input}".to_string(),
+            },
+        );
+
+        Self(commands)
+    }
+}
+
+impl SlashCommands {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        for (name, command) in &self.0 {
+            if !is_command_name(name) {
+                errors.push(format!(
+                    "slash_commands.{}: names may only contain lowercase letters, digits, '-' and '_'",
+                    name
+                ));
+            }
+            if command.template.trim().is_empty() {
+                errors.push(format!("slash_commands.{}: template must not be empty", name));
+            }
+            for arg in &command.args {
+                if arg == "input" {
+                    errors.push(format!("slash_commands.{}: \"input\" is reserved for the free text", name));
+                } else if !command.template.contains(&format!("{{{}}}", arg)) {
+                    errors.push(format!("slash_commands.{}: argument \"{}\" is never used in the template", name, arg));
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+// Result of resolving a slash command into the prompt sent to the model
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpandedPrompt {
+    pub command: Option<String>,
+    pub args: BTreeMap<String, String>,
+    pub prompt: String,
+}
+
+fn is_command_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+// Splits off the next whitespace-delimited argument, honouring "double quoted" values
+fn next_arg(text: &str) -> Option<(String, &str)> {
+    let text = text.trim_start();
+    if text.is_empty() {
+        return None;
+    }
+
+    if let Some(rest) = text.strip_prefix('"') {
+        let end = rest.find('"')?;
+        return Some((rest[..end].to_string(), &rest[end + 1..]));
+    }
+
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    Some((text[..end].to_string(), &text[end..]))
+}
+
+fn usage(name: &str, command: &SlashCommand) -> String {
+    let args: Vec<String> = command.args.iter().map(|a| format!("<{}>", a)).collect();
+    format!("Usage: /{} {} <text>", name, args.join(" ")).replace("  ", " ")
+}
+
+// Expands "/name args... text" into the stored template; plain messages pass through unchanged
+pub fn expand(message: &str) -> Result<ExpandedPrompt, String> {
+    let passthrough = || ExpandedPrompt {
+        command: None,
+        args: BTreeMap::new(),
+        prompt: message.to_string(),
+    };
+
+    let Some(rest) = message.trim_start().strip_prefix('/') else {
+        return Ok(passthrough());
+    };
+
+    let name_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    let name = &rest[..name_end];
+
+    // Things like "/usr/bin/env" are paths, not commands
+    if !is_command_name(name) {
+        return Ok(passthrough());
+    }
+
+    let commands = config::get().slash_commands.0;
+    let command = commands.get(name).ok_or_else(|| {
+        let available: Vec<String> = commands.keys().map(|k| format!("/{}", k)).collect();
+        format!("Unknown command /{}. Available commands: {}", name, available.join(", "))
+    })?;
+
+    let mut remaining = &rest[name_end..];
+    let mut args = BTreeMap::new();
+    for arg in &command.args {
+        let (value, rest) = next_arg(remaining).ok_or_else(|| usage(name, command))?;
+        args.insert(arg.clone(), value);
+        remaining = rest;
+    }
+
+    let input = remaining.trim();
+    if input.is_empty() && command.template.contains("{input}") {
+        return Err(usage(name, command));
+    }
+
+    let prompt = fill_template(&command.template, input, &args);
+
+    Ok(ExpandedPrompt {
+        command: Some(name.to_string()),
+        args,
+        prompt,
+    })
+}
+
+// Replaces {input} and the {argument} placeholders in one pass, so braces in the user's text or in
+// argument values are never read as placeholders themselves
+fn fill_template(template: &str, input: &str, args: &BTreeMap<String, String>) -> String {
+    let mut prompt = String::with_capacity(template.len() + input.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        prompt.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let value = after.find('}').and_then(|close| {
+            let name = &after[..close];
+            let value = if name == "input" { Some(input) } else { args.get(name).map(String::as_str) };
+            value.map(|value| (value, close))
+        });
+        match value {
+            Some((value, close)) => {
+                prompt.push_str(value);
+                rest = &after[close + 1..];
+            }
+            None => {
+                prompt.push('{');
+                rest = after;
+            }
+        }
+    }
+    prompt.push_str(rest);
+    prompt
+}
+
+#[tauri::command]
+pub fn list_slash_commands() -> BTreeMap<String, SlashCommand> {
+    config::get().slash_commands.0
+}
+
+// Lets frontends preview what a slash command will send before submitting it
+#[tauri::command]
+pub fn expand_slash_command(message: String) -> Result<ExpandedPrompt, String> {
+    expand(&message)
+}
+
+#[tauri::command]
+pub fn save_slash_command(name: String, command: SlashCommand, app: tauri::AppHandle) -> Result<BTreeMap<String, SlashCommand>, String> {
+    let config = config::update(&app, |config| {
+        config.slash_commands.0.insert(name, command);
+    })?;
+    Ok(config.slash_commands.0)
+}
@@ -0,0 +1,508 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::Emitter;
+
+use crate::accessibility;
+use crate::config;
+use crate::analytics::{self, UsageEvent};
+use crate::json_stream::{JsonField, JsonStream};
+use crate::metrics::{self, GenerationRecord};
+use crate::queue::WaitStatus;
+use crate::sanitize::{self, MarkdownSanitizer};
+use crate::thinking::ThinkSplitter;
+use crate::transcript::Transcript;
+use crate::watchdog::StallAction;
+
+// Event name the frontend listens on for streamed chat output
+pub const CHAT_STREAM_EVENT: &str = "chat-stream";
+
+// How long emitted events stay replayable after a stream ends
+const RESUME_GRACE_PERIOD: Duration = Duration::from_secs(5 * 60);
+
+static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Emitted events per request, kept so a reloaded webview can catch up
+static STREAM_BUFFERS: OnceLock<Mutex<HashMap<String, StreamBuffer>>> = OnceLock::new();
+
+struct StreamBuffer {
+    events: Vec<StreamEvent>,
+    finished_at: Option<Instant>,
+}
+
+// Events a client missed, plus whether the stream has already ended
+#[derive(Debug, Clone, Serialize)]
+pub struct ResumedStream {
+    pub request_id: String,
+    pub events: Vec<StreamEvent>,
+    pub finished: bool,
+}
+
+fn buffers() -> &'static Mutex<HashMap<String, StreamBuffer>> {
+    STREAM_BUFFERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Drops buffers of streams that ended longer than the grace period ago
+fn prune_buffers() {
+    buffers().lock().unwrap().retain(|_, buffer| {
+        buffer
+            .finished_at
+            .map(|finished| finished.elapsed() < RESUME_GRACE_PERIOD)
+            .unwrap_or(true)
+    });
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// Generates a unique id for requests the frontend didn't tag itself
+pub fn new_request_id() -> String {
+    let seq = REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("req-{}-{}", now_ms(), seq)
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamEventKind {
+    Accepted,
+    // Waiting on a model load or earlier requests, before prefill starts
+    Waiting,
+    Prefill,
+    FirstToken,
+    Chunk,
+    // A value of a JSON-mode answer finished streaming
+    Field,
+    // Reasoning that precedes the answer, when thinking mode is on
+    Thinking,
+    // No token for longer than the watchdog allows; content is the action taken
+    Stalled,
+    Done,
+    Cancelled,
+    Error,
+}
+
+// Payload of every chat-stream event; timestamps let the UI compute TTFT and live tok/s
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamEvent {
+    pub request_id: String,
+    pub model_id: String,
+    pub kind: StreamEventKind,
+    pub index: usize,
+    pub content: Option<String>,
+    pub timestamp_ms: u64,
+    pub elapsed_ms: u64,
+    pub ttft_ms: Option<u64>,
+    pub tokens_per_sec: Option<f64>,
+    // Estimated share of the prompt processed so far (0-100), on "prefill" events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<f64>,
+    // The completed value, on "field" events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<JsonField>,
+    // Queue position and ETA, on "waiting" events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait: Option<WaitStatus>,
+}
+
+// Long prompt being processed before the first token
+struct Prefill {
+    started_at: Instant,
+    prompt_tokens: usize,
+    // From the model's measured prefill speed; None until a long prompt has been timed
+    expected_ms: Option<f64>,
+}
+
+// Tracks timing for one streamed request and emits its lifecycle events
+pub struct StreamTracker {
+    app: tauri::AppHandle,
+    // Window that issued the request; events go only there so streams don't cross windows
+    target: Option<String>,
+    request_id: String,
+    model_id: String,
+    accepted_at: Instant,
+    first_token_at: Option<Instant>,
+    tokens: usize,
+    content: String,
+    // Set when the streamed text was replaced after the fact, so "done" must carry the new text
+    rewritten: bool,
+    // Present when generation.sanitize_markdown is on; chunks are emitted only once they are safe
+    sanitizer: Option<MarkdownSanitizer>,
+    // File the conversation's raw output is teed to, if one is configured
+    transcript: Option<Transcript>,
+    prefill: Option<Prefill>,
+    // Present for JSON-mode requests
+    json: Option<JsonStream>,
+    // Present for models that can reason before answering
+    thinking: Option<ThinkSplitter>,
+    thought: String,
+}
+
+impl StreamTracker {
+    // Emits the "accepted" event as soon as the request is received
+    pub fn start(app: &tauri::AppHandle, target: Option<&str>, request_id: &str, model_id: &str) -> Self {
+        prune_buffers();
+        buffers().lock().unwrap().insert(
+            request_id.to_string(),
+            StreamBuffer {
+                events: Vec::new(),
+                finished_at: None,
+            },
+        );
+
+        let tracker = Self {
+            app: app.clone(),
+            target: target.map(|label| label.to_string()),
+            request_id: request_id.to_string(),
+            model_id: model_id.to_string(),
+            accepted_at: Instant::now(),
+            first_token_at: None,
+            tokens: 0,
+            content: String::new(),
+            rewritten: false,
+            sanitizer: config::get().generation.sanitize_markdown.then(MarkdownSanitizer::default),
+            transcript: None,
+            prefill: None,
+            json: None,
+            thinking: None,
+            thought: String::new(),
+        };
+        tracker.emit(StreamEventKind::Accepted, None);
+        tracker
+    }
+
+    pub fn push_chunk(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        if self.first_token_at.is_none() {
+            self.first_token_at = Some(Instant::now());
+            if self.prefill.is_some() {
+                self.emit_progress(StreamEventKind::Prefill, Some(100.0));
+            }
+            self.emit(StreamEventKind::FirstToken, None);
+        }
+
+        let split = self.thinking.as_mut().map(|thinking| thinking.push(text));
+        match split {
+            Some((thought, answer)) => {
+                self.push_thought(thought);
+                self.push_answer(&answer);
+            }
+            None => self.push_answer(text),
+        }
+        self.tokens += 1;
+    }
+
+    fn push_thought(&mut self, text: String) {
+        if !text.is_empty() {
+            self.thought.push_str(&text);
+            self.emit(StreamEventKind::Thinking, Some(text));
+        }
+    }
+
+    fn push_answer(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.content.push_str(text);
+        if let Some(transcript) = &self.transcript {
+            transcript.chunk(text);
+        }
+        let safe = match self.sanitizer.as_mut() {
+            Some(sanitizer) => sanitizer.push(text),
+            None => text.to_string(),
+        };
+        // Held-back text (e.g. half a tag) goes out with a later chunk
+        if !safe.is_empty() {
+            self.emit(StreamEventKind::Chunk, Some(safe));
+        }
+        let fields = self.json.as_mut().map(|json| json.push(text)).unwrap_or_default();
+        for field in fields {
+            self.send(StreamEventKind::Field, None, None, Some(field), None);
+        }
+    }
+
+    // Sends <think>...</think> blocks as "thinking" events instead of answer chunks
+    pub fn split_thinking(&mut self) {
+        self.thinking = Some(ThinkSplitter::default());
+    }
+
+    // Releases the text the splitter held back in case it was the start of a tag
+    fn flush_thinking(&mut self) {
+        if let Some((thought, answer)) = self.thinking.as_mut().map(ThinkSplitter::finish) {
+            self.push_thought(thought);
+            self.push_answer(&answer);
+        }
+    }
+
+    // Reasoning streamed so far, without the answer
+    pub fn thought(&self) -> &str {
+        &self.thought
+    }
+
+    // Parses the answer as JSON while it streams, emitting a "field" event per completed value
+    pub fn parse_json(&mut self, schema: serde_json::Value) {
+        self.json = Some(JsonStream::new(schema));
+    }
+
+    // Starts prefill progress reporting when the prompt is long enough to cause a noticeable stall
+    pub fn start_prefill(&mut self, prompt_tokens: usize) {
+        let min_tokens = config::get().generation.prefill_progress_min_tokens;
+        if min_tokens == 0 || prompt_tokens < min_tokens {
+            return;
+        }
+        let expected_ms = metrics::prefill_tokens_per_sec(&self.model_id)
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| prompt_tokens as f64 / rate * 1000.0);
+        self.prefill = Some(Prefill {
+            started_at: Instant::now(),
+            prompt_tokens,
+            expected_ms,
+        });
+        self.emit_progress(StreamEventKind::Prefill, expected_ms.map(|_| 0.0));
+    }
+
+    pub fn is_prefilling(&self) -> bool {
+        self.prefill.is_some() && self.first_token_at.is_none()
+    }
+
+    // Emits a "prefill" event; the percentage stays below 100 until the first token arrives
+    pub fn report_prefill(&self) {
+        let Some(prefill) = &self.prefill else {
+            return;
+        };
+        let elapsed = prefill.started_at.elapsed().as_millis() as f64;
+        let progress = prefill.expected_ms.map(|expected| (elapsed / expected * 100.0).min(99.0).floor());
+        self.emit_progress(StreamEventKind::Prefill, progress);
+    }
+
+    // Emits a "waiting" event with what the request is stuck behind
+    pub fn report_wait(&self, status: WaitStatus) {
+        self.send(StreamEventKind::Waiting, None, None, None, Some(status));
+    }
+
+    // Emits a "stalled" event naming what the watchdog does about it
+    pub fn report_stall(&self, action: StallAction, idle: Duration) {
+        println!("[{}] No token for {}s; watchdog action: {}", self.request_id, idle.as_secs(), action.as_str());
+        self.emit(StreamEventKind::Stalled, Some(action.as_str().to_string()));
+    }
+
+    // Drops the text of a stalled attempt before the answer is generated again. Its chunks were
+    // already sent, so "done" carries the new text in full.
+    pub fn restart(&mut self) {
+        self.content.clear();
+        self.thought.clear();
+        self.rewritten = true;
+        self.sanitizer = config::get().generation.sanitize_markdown.then(MarkdownSanitizer::default);
+        self.json = None;
+        self.thinking = None;
+        self.prefill = None;
+    }
+
+    pub fn tee_to(&mut self, transcript: Transcript) {
+        self.transcript = Some(transcript);
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    // Swaps the streamed text for a corrected version before the stream is finished
+    pub fn replace_content(&mut self, text: String) {
+        self.content = text;
+        self.rewritten = true;
+    }
+
+    // Emits the "done" event, records metrics and returns the full response text
+    pub fn finish(mut self) -> String {
+        self.flush_thinking();
+        // With accessibility formatting on, "done" carries the rewritten text for clients to swap in
+        let formatted = accessibility::apply(&self.content).or_else(|| self.rewritten.then(|| self.content.clone()));
+        if let Some(text) = &formatted {
+            self.content = text.clone();
+        }
+        let formatted = match &self.sanitizer {
+            Some(_) => formatted.map(|text| sanitize::sanitize(&text)),
+            None => formatted,
+        };
+        self.complete(StreamEventKind::Done, formatted)
+    }
+
+    // Ends the stream early (e.g. during shutdown), returning whatever was generated so far
+    pub fn cancel(mut self) -> String {
+        self.flush_thinking();
+        self.complete(StreamEventKind::Cancelled, None)
+    }
+
+    fn complete(mut self, kind: StreamEventKind, content: Option<String>) -> String {
+        if kind == StreamEventKind::Done && self.json.as_ref().is_some_and(|json| !json.is_complete()) {
+            println!("[{}] Warning: The JSON answer ended before its closing bracket", self.request_id);
+        }
+        // Release what the sanitizer was still holding and close an open code fence
+        if let Some(rest) = self.sanitizer.as_mut().map(MarkdownSanitizer::finish) {
+            if !rest.is_empty() {
+                self.emit(StreamEventKind::Chunk, Some(rest));
+            }
+        }
+        self.emit(kind, content);
+        self.mark_finished();
+        if let Some(transcript) = &self.transcript {
+            transcript.end(kind, None);
+        }
+
+        analytics::record(match kind {
+            StreamEventKind::Done => UsageEvent::GenerationCompleted {
+                model_id: &self.model_id,
+                tokens: self.tokens,
+            },
+            _ => UsageEvent::GenerationCancelled { model_id: &self.model_id },
+        });
+
+        if kind == StreamEventKind::Done {
+            metrics::record_generation(GenerationRecord {
+                request_id: self.request_id.clone(),
+                model_id: self.model_id.clone(),
+                prompt_tokens: self.prefill.as_ref().map(|prefill| prefill.prompt_tokens),
+                ttft_ms: self.ttft_ms(),
+                completion_tokens: self.tokens,
+                duration_ms: self.accepted_at.elapsed().as_millis() as u64,
+                tokens_per_sec: self.tokens_per_sec(),
+                finished_at_ms: now_ms(),
+            });
+        }
+
+        println!(
+            "[{}] Generation {:?}: {} tokens, ttft {:?}ms, {:.1} tok/s",
+            self.request_id,
+            kind,
+            self.tokens,
+            self.ttft_ms(),
+            self.tokens_per_sec().unwrap_or(0.0)
+        );
+
+        self.content
+    }
+
+    pub fn fail(&self, error: &str) {
+        self.emit(StreamEventKind::Error, Some(error.to_string()));
+        self.mark_finished();
+        if let Some(transcript) = &self.transcript {
+            transcript.end(StreamEventKind::Error, Some(error));
+        }
+        analytics::record(UsageEvent::GenerationFailed { model_id: &self.model_id });
+    }
+
+    fn mark_finished(&self) {
+        if let Some(buffer) = buffers().lock().unwrap().get_mut(&self.request_id) {
+            buffer.finished_at = Some(Instant::now());
+        }
+    }
+
+    fn ttft_ms(&self) -> Option<u64> {
+        self.first_token_at
+            .map(|t| t.duration_since(self.accepted_at).as_millis() as u64)
+    }
+
+    // Decode throughput measured from the first token onwards
+    fn tokens_per_sec(&self) -> Option<f64> {
+        let first = self.first_token_at?;
+        let secs = first.elapsed().as_secs_f64();
+        if self.tokens < 2 || secs <= 0.0 {
+            return None;
+        }
+        Some((self.tokens - 1) as f64 / secs)
+    }
+
+    fn emit(&self, kind: StreamEventKind, content: Option<String>) {
+        self.send(kind, content, None, None, None);
+    }
+
+    fn emit_progress(&self, kind: StreamEventKind, progress: Option<f64>) {
+        self.send(kind, None, progress, None, None);
+    }
+
+    fn send(
+        &self,
+        kind: StreamEventKind,
+        content: Option<String>,
+        progress: Option<f64>,
+        field: Option<JsonField>,
+        wait: Option<WaitStatus>,
+    ) {
+        let event = StreamEvent {
+            request_id: self.request_id.clone(),
+            model_id: self.model_id.clone(),
+            kind,
+            index: self.tokens,
+            content,
+            timestamp_ms: now_ms(),
+            elapsed_ms: self.accepted_at.elapsed().as_millis() as u64,
+            ttft_ms: self.ttft_ms(),
+            tokens_per_sec: self.tokens_per_sec(),
+            progress,
+            field,
+            wait,
+        };
+
+        if let Some(buffer) = buffers().lock().unwrap().get_mut(&self.request_id) {
+            buffer.events.push(event.clone());
+        }
+
+        let result = match &self.target {
+            Some(label) => self.app.emit_to(label.as_str(), CHAT_STREAM_EVENT, &event),
+            None => self.app.emit(CHAT_STREAM_EVENT, &event),
+        };
+        if let Err(e) = result {
+            println!("Warning: Failed to emit stream event: {}", e);
+        }
+    }
+}
+
+// Whether a request has a stream buffer, live or within its grace period
+pub fn is_buffered(request_id: &str) -> bool {
+    buffers().lock().unwrap().contains_key(request_id)
+}
+
+// Every event emitted so far for a request, e.g. to record a finished stream
+pub fn buffered_events(request_id: &str) -> Vec<StreamEvent> {
+    buffers()
+        .lock()
+        .unwrap()
+        .get(request_id)
+        .map(|buffer| buffer.events.clone())
+        .unwrap_or_default()
+}
+
+// Replays chunks, reasoning and completed JSON fields from `from_index` onwards (plus the terminal
+// event) for a client that lost its stream
+#[tauri::command]
+pub fn resume_stream(request_id: String, from_index: usize) -> Result<ResumedStream, String> {
+    let buffers = buffers().lock().unwrap();
+    let buffer = buffers
+        .get(&request_id)
+        .ok_or_else(|| format!("No buffered stream for request {} (it may have expired)", request_id))?;
+
+    let events = buffer
+        .events
+        .iter()
+        .filter(|event| match event.kind {
+            StreamEventKind::Chunk | StreamEventKind::Thinking | StreamEventKind::Field => event.index >= from_index,
+            StreamEventKind::Done | StreamEventKind::Cancelled | StreamEventKind::Error => true,
+            _ => false,
+        })
+        .cloned()
+        .collect();
+
+    Ok(ResumedStream {
+        request_id,
+        events,
+        finished: buffer.finished_at.is_some(),
+    })
+}
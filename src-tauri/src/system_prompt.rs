@@ -0,0 +1,97 @@
+// Which system prompt a request runs under. From most to least specific: the kiosk prompt, the
+// conversation's own, its assistant's, the model's, the configured default, and finally the
+// built-in persona. The response language instruction is appended to whichever wins.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{assistants, config, kiosk, language, prompt_format};
+
+const BUILT_IN: &str = "You are a helpful AI assistant. Keep your responses concise and friendly.";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SystemPromptConfig {
+    // Replaces the built-in persona for models and conversations without their own prompt
+    pub default: Option<String>,
+    // Model id -> system prompt
+    pub per_model: HashMap<String, String>,
+    // Conversation id -> system prompt
+    pub per_conversation: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptSource {
+    Kiosk,
+    Conversation,
+    Assistant,
+    Model,
+    Default,
+    BuiltIn,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemPrompt {
+    pub prompt: String,
+    pub source: PromptSource,
+    // False when the model's chat template has no system role and the prompt leads the first user turn
+    pub system_role: bool,
+}
+
+fn non_empty(prompt: Option<&String>) -> Option<String> {
+    prompt.map(|p| p.trim()).filter(|p| !p.is_empty()).map(str::to_string)
+}
+
+pub fn resolve(conversation_id: Option<&str>, model_id: &str, assistant_prompt: Option<&str>) -> SystemPrompt {
+    let settings = config::get().system_prompt;
+    let (mut prompt, source) = kiosk::system_prompt()
+        .map(|prompt| (prompt, PromptSource::Kiosk))
+        .or_else(|| {
+            let id = conversation_id?;
+            non_empty(settings.per_conversation.get(id)).map(|prompt| (prompt, PromptSource::Conversation))
+        })
+        .or_else(|| assistant_prompt.map(|prompt| (prompt.to_string(), PromptSource::Assistant)))
+        .or_else(|| non_empty(settings.per_model.get(model_id)).map(|prompt| (prompt, PromptSource::Model)))
+        .or_else(|| non_empty(settings.default.as_ref()).map(|prompt| (prompt, PromptSource::Default)))
+        .unwrap_or_else(|| (BUILT_IN.to_string(), PromptSource::BuiltIn));
+    if let Some(instruction) = language::instruction() {
+        prompt = format!("{} {}", prompt, instruction);
+    }
+    SystemPrompt { prompt, source, system_role: prompt_format::supports_system_role(model_id) }
+}
+
+// Sets the prompt every conversation with a model starts from; None returns it to the default
+#[tauri::command]
+pub fn set_model_system_prompt(model_id: String, prompt: Option<String>, app: tauri::AppHandle) -> Result<(), String> {
+    config::update(&app, |config| match prompt.filter(|p| !p.trim().is_empty()) {
+        Some(prompt) => {
+            config.system_prompt.per_model.insert(model_id, prompt);
+        }
+        None => {
+            config.system_prompt.per_model.remove(&model_id);
+        }
+    })?;
+    Ok(())
+}
+
+// Sets a conversation's own prompt, which wins over its assistant's and its model's
+#[tauri::command]
+pub fn set_conversation_system_prompt(conversation_id: String, prompt: Option<String>, app: tauri::AppHandle) -> Result<(), String> {
+    config::update(&app, |config| match prompt.filter(|p| !p.trim().is_empty()) {
+        Some(prompt) => {
+            config.system_prompt.per_conversation.insert(conversation_id, prompt);
+        }
+        None => {
+            config.system_prompt.per_conversation.remove(&conversation_id);
+        }
+    })?;
+    Ok(())
+}
+
+// The prompt a message to `model_id` would be sent with, and where it comes from
+#[tauri::command]
+pub fn get_system_prompt(conversation_id: Option<String>, model_id: String) -> SystemPrompt {
+    let assistant_prompt = conversation_id.as_deref().and_then(assistants::system_prompt_for);
+    resolve(conversation_id.as_deref(), &model_id, assistant_prompt.as_deref())
+}
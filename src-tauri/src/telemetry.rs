@@ -0,0 +1,101 @@
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::TracerProvider;
+use opentelemetry_sdk::{runtime, Resource};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::config;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    // OTLP gRPC endpoint such as "http://localhost:4317"; None keeps tracing off. Read at startup.
+    pub otlp_endpoint: Option<String>,
+    pub service_name: String,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: "tauri-mistral-chat".to_string(),
+        }
+    }
+}
+
+impl TelemetryConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if let Some(endpoint) = &self.otlp_endpoint {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                errors.push(format!("telemetry.otlp_endpoint = \"{}\" must be an http(s) URL", endpoint));
+            }
+        }
+        if self.service_name.trim().is_empty() {
+            errors.push("telemetry.service_name must not be empty".to_string());
+        }
+        errors
+    }
+}
+
+fn build_provider(endpoint: &str, service_name: &str) -> Result<TracerProvider, String> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|e| e.to_string())?;
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new(vec![KeyValue::new("service.name", service_name.to_string())]))
+        .build())
+}
+
+// Exports spans (ours and mistral.rs's own) to the configured OTLP collector
+pub fn init() {
+    let settings = config::get().telemetry;
+    let Some(endpoint) = settings.otlp_endpoint else {
+        return;
+    };
+
+    // The batch exporter and its gRPC channel have to be created inside the async runtime
+    let provider = match tauri::async_runtime::block_on(async { build_provider(&endpoint, &settings.service_name) }) {
+        Ok(provider) => provider,
+        Err(e) => {
+            println!("Warning: Failed to set up OTLP export to {}: {}", endpoint, e);
+            return;
+        }
+    };
+    let tracer = provider.tracer(settings.service_name.clone());
+    opentelemetry::global::set_tracer_provider(provider);
+
+    match tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()
+    {
+        Ok(()) => {
+            INSTALLED.store(true, Ordering::SeqCst);
+            println!("Exporting traces to {}", endpoint);
+        }
+        Err(e) => println!("Warning: Failed to install the tracing subscriber: {}", e),
+    }
+}
+
+// While our subscriber is installed, model builders must skip with_logging(): mistral.rs would
+// try to install its own log subscriber on top of it
+pub fn is_enabled() -> bool {
+    INSTALLED.load(Ordering::SeqCst)
+}
+
+// Sends spans still buffered by the batch exporter
+pub fn flush() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
@@ -0,0 +1,66 @@
+// SmolLM3's hybrid reasoning. With enable_thinking its chat template lets the model reason inside
+// <think>...</think> before answering; the splitter takes that part out of the stream so it goes
+// to the UI as "thinking" events and never ends up in the answer or the history.
+
+const OPEN: &str = "<think>";
+const CLOSE: &str = "</think>";
+
+#[derive(Default)]
+pub struct ThinkSplitter {
+    in_thought: bool,
+    // Just left a thought; the whitespace the model puts before its answer is dropped
+    after_thought: bool,
+    // A tail that may be the start of a tag, held back until the next chunk decides it
+    pending: String,
+}
+
+// Length of the longest suffix of `text` that is a proper prefix of `tag`
+fn partial_tag(text: &str, tag: &str) -> usize {
+    (1..tag.len())
+        .rev()
+        .find(|&len| text.ends_with(&tag[..len]))
+        .unwrap_or(0)
+}
+
+impl ThinkSplitter {
+    // Splits a chunk into (thinking, answer) text
+    pub fn push(&mut self, chunk: &str) -> (String, String) {
+        let mut text = std::mem::take(&mut self.pending);
+        text.push_str(chunk);
+        let mut thinking = String::new();
+        let mut answer = String::new();
+        let mut rest = text.as_str();
+        loop {
+            let (tag, out) = if self.in_thought { (CLOSE, &mut thinking) } else { (OPEN, &mut answer) };
+            match rest.find(tag) {
+                Some(at) => {
+                    out.push_str(&rest[..at]);
+                    rest = &rest[at + tag.len()..];
+                    self.after_thought = self.in_thought;
+                    self.in_thought = !self.in_thought;
+                }
+                None => {
+                    let keep = partial_tag(rest, tag);
+                    out.push_str(&rest[..rest.len() - keep]);
+                    self.pending = rest[rest.len() - keep..].to_string();
+                    break;
+                }
+            }
+        }
+        if self.after_thought {
+            answer = answer.trim_start().to_string();
+            self.after_thought = answer.is_empty();
+        }
+        (thinking, answer)
+    }
+
+    // Releases a held-back tail at the end of the stream
+    pub fn finish(&mut self) -> (String, String) {
+        let rest = std::mem::take(&mut self.pending);
+        if self.in_thought {
+            (rest, String::new())
+        } else {
+            (String::new(), rest)
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::config;
+use crate::dates::{days_from_civil, format_day};
+use crate::streaming::now_ms;
+
+const DAY_MS: u64 = 86_400_000;
+
+// Walking stops after this many entries so a huge root can't stall a request
+const MAX_SCANNED_ENTRIES: usize = 20_000;
+
+const DEFAULT_FILE_LIMIT: usize = 20;
+
+// Candidate files offered to the model for a message that seems to refer to one
+const PATH_CANDIDATES: usize = 5;
+
+// Words that make a chat message pull in calendar or file data
+const CALENDAR_HINTS: [&str; 8] = ["calendar", "schedule", "meeting", "appointment", "today", "tomorrow", "this week", "agenda"];
+const FILE_HINTS: [&str; 6] = ["file", "folder", "download", "directory", "disk space", "biggest"];
+// Words that suggest a message names a particular file, e.g. "summarize my latest report"
+const PATH_HINTS: [&str; 7] = ["file", "report", "document", "notes", "latest", "recent", "spreadsheet"];
+// Words of such messages that say which file without being part of its name
+const NOT_NAME_WORDS: [&str; 12] = ["file", "files", "latest", "recent", "summarize", "about", "what", "which", "with", "from", "that", "this"];
+
+// Read-only local tools; each one stays off until the user grants it and lists what it may read
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolsConfig {
+    pub calendar_enabled: bool,
+    // .ics files to read events from
+    pub calendar_files: Vec<String>,
+    pub file_metadata_enabled: bool,
+    // Directories whose file names, sizes and dates may be listed (contents are never read)
+    pub file_roots: Vec<String>,
+}
+
+impl ToolsConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.calendar_enabled && self.calendar_files.is_empty() {
+            errors.push("tools.calendar_files must list at least one .ics file when the calendar tool is enabled".to_string());
+        }
+        if self.file_metadata_enabled && self.file_roots.is_empty() {
+            errors.push("tools.file_roots must list at least one directory when the file metadata tool is enabled".to_string());
+        }
+        errors
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CalendarEvent {
+    pub summary: String,
+    pub start_ms: u64,
+    pub end_ms: Option<u64>,
+    pub all_day: bool,
+    pub location: Option<String>,
+    pub calendar: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileMetadata {
+    pub path: String,
+    pub size: u64,
+    pub modified_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PathMatch {
+    pub path: String,
+    pub is_dir: bool,
+    pub modified_ms: Option<u64>,
+}
+
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(|home| PathBuf::from(home).join(rest))
+            .unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    }
+}
+
+// Joins folded lines (continuations start with a space or tab), per RFC 5545
+fn unfold(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.lines() {
+        match (line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", "\n")
+        .replace("\\N", "\n")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+// Parses DATE or DATE-TIME values; there is no timezone database, so TZID times are read as UTC
+fn parse_ics_time(value: &str) -> Option<(u64, bool)> {
+    let digits = |range: std::ops::Range<usize>| value.get(range)?.parse::<i64>().ok();
+    let days = days_from_civil(digits(0..4)?, digits(4..6)?, digits(6..8)?);
+    if days < 0 {
+        return None;
+    }
+    let base = days as u64 * DAY_MS;
+    if value.len() == 8 {
+        return Some((base, true));
+    }
+    let (hour, minute, second) = (digits(9..11)?, digits(11..13)?, digits(13..15)?);
+    Some((base + ((hour * 3600 + minute * 60 + second) * 1000) as u64, false))
+}
+
+// Reads VEVENTs from one .ics file; recurring events only show their first occurrence
+fn parse_calendar(path: &Path) -> Result<Vec<CalendarEvent>, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let calendar = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let mut events = Vec::new();
+    let mut current: Option<Vec<(String, String)>> = None;
+
+    for line in unfold(&contents) {
+        match line.trim_end() {
+            "BEGIN:VEVENT" => current = Some(Vec::new()),
+            "END:VEVENT" => {
+                let Some(props) = current.take() else { continue };
+                let get = |name: &str| props.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+                let Some((start_ms, all_day)) = get("DTSTART").and_then(parse_ics_time) else {
+                    continue;
+                };
+                events.push(CalendarEvent {
+                    summary: get("SUMMARY").map(unescape).unwrap_or_else(|| "(no title)".to_string()),
+                    start_ms,
+                    end_ms: get("DTEND").and_then(parse_ics_time).map(|(ms, _)| ms),
+                    all_day,
+                    location: get("LOCATION").map(unescape).filter(|l| !l.is_empty()),
+                    calendar: calendar.clone(),
+                });
+            }
+            line => {
+                if let (Some(props), Some((key, value))) = (current.as_mut(), line.split_once(':')) {
+                    // Drop parameters such as ";TZID=Europe/Paris" or ";VALUE=DATE"
+                    let name = key.split(';').next().unwrap_or(key).to_ascii_uppercase();
+                    props.push((name, value.to_string()));
+                }
+            }
+        }
+    }
+
+    Ok(events)
+}
+
+pub fn calendar_events(from_ms: u64, to_ms: u64) -> Result<Vec<CalendarEvent>, String> {
+    let tools = config::get().tools;
+    if !tools.calendar_enabled {
+        return Err("The calendar tool is disabled; grant it with set_tool_permission".to_string());
+    }
+
+    let mut events = Vec::new();
+    for file in &tools.calendar_files {
+        match parse_calendar(&expand_home(file)) {
+            Ok(parsed) => events.extend(parsed.into_iter().filter(|e| e.start_ms < to_ms && e.end_ms.unwrap_or(e.start_ms) >= from_ms)),
+            Err(e) => println!("Warning: {}", e),
+        }
+    }
+    events.sort_by_key(|e| e.start_ms);
+    Ok(events)
+}
+
+fn modified_ms(metadata: &std::fs::Metadata) -> Option<u64> {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<FileMetadata>, scanned: &mut usize) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if *scanned >= MAX_SCANNED_ENTRIES {
+            return;
+        }
+        *scanned += 1;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        // symlink_metadata keeps the walk from following links out of the granted root
+        let Ok(metadata) = entry.path().symlink_metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            collect_files(&entry.path(), files, scanned);
+        } else if metadata.is_file() {
+            files.push(FileMetadata {
+                path: entry.path().to_string_lossy().to_string(),
+                size: metadata.len(),
+                modified_ms: modified_ms(&metadata),
+            });
+        }
+    }
+}
+
+// The granted roots, resolved; errors when the file metadata tool is off
+fn granted_roots() -> Result<Vec<PathBuf>, String> {
+    let tools = config::get().tools;
+    if !tools.file_metadata_enabled {
+        return Err("The file metadata tool is disabled; grant it with set_tool_permission".to_string());
+    }
+    Ok(tools.file_roots.iter().filter_map(|allowed| expand_home(allowed).canonicalize().ok()).collect())
+}
+
+fn check_granted(path: &str) -> Result<PathBuf, String> {
+    let roots = granted_roots()?;
+    let resolved = expand_home(path)
+        .canonicalize()
+        .map_err(|e| format!("Cannot access {}: {}", path, e))?;
+    if !roots.iter().any(|allowed| resolved.starts_with(allowed)) {
+        return Err(format!("{} is not inside any of tools.file_roots", resolved.display()));
+    }
+    Ok(resolved)
+}
+
+// Lists files under a granted root, sorted by "size" (default) or "modified"
+pub fn file_metadata(root: &str, sort_by: &str, extension: Option<&str>, limit: usize) -> Result<Vec<FileMetadata>, String> {
+    let root = check_granted(root)?;
+
+    let mut files = Vec::new();
+    collect_files(&root, &mut files, &mut 0);
+    if let Some(extension) = extension {
+        let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+        files.retain(|f| {
+            Path::new(&f.path)
+                .extension()
+                .is_some_and(|e| e.to_string_lossy().to_ascii_lowercase() == extension)
+        });
+    }
+    match sort_by {
+        "modified" => files.sort_by(|a, b| b.modified_ms.cmp(&a.modified_ms)),
+        "size" => files.sort_by(|a, b| b.size.cmp(&a.size)),
+        other => return Err(format!("Unknown sort order \"{}\"; use \"size\" or \"modified\"", other)),
+    }
+    files.truncate(limit);
+    Ok(files)
+}
+
+// Entries of the directory `partial` points into whose names start with its last component, e.g.
+// "~/Documents/rep" -> "~/Documents/report.pdf". Only names and dates are read.
+fn complete_in_dir(partial: &str) -> Result<Vec<PathMatch>, String> {
+    let (dir, prefix) = match partial.rfind(['/', std::path::MAIN_SEPARATOR]) {
+        Some(at) => (&partial[..=at], &partial[at + 1..]),
+        None => (partial, ""),
+    };
+    let dir = check_granted(dir)?;
+    let prefix = prefix.to_lowercase();
+    let entries = std::fs::read_dir(&dir).map_err(|e| format!("Cannot list {}: {}", dir.display(), e))?;
+    let mut matches = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_lowercase();
+        if !name.starts_with(&prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+            continue;
+        }
+        let Ok(metadata) = entry.path().symlink_metadata() else {
+            continue;
+        };
+        matches.push(PathMatch {
+            path: entry.path().to_string_lossy().to_string(),
+            is_dir: metadata.is_dir(),
+            modified_ms: modified_ms(&metadata),
+        });
+    }
+    matches.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.path.cmp(&b.path)));
+    Ok(matches)
+}
+
+// Files anywhere under the granted roots whose names contain the most of `words`, most recently
+// modified first among equals
+fn search_roots(words: &[String]) -> Result<Vec<PathMatch>, String> {
+    let mut scored = Vec::new();
+    for root in granted_roots()? {
+        let mut files = Vec::new();
+        collect_files(&root, &mut files, &mut 0);
+        for file in files {
+            let name = Path::new(&file.path).file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+            let score = words.iter().filter(|word| name.contains(word.as_str())).count();
+            if score > 0 {
+                scored.push((score, PathMatch { path: file.path, is_dir: false, modified_ms: file.modified_ms }));
+            }
+        }
+    }
+    scored.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then_with(|| b.modified_ms.cmp(&a.modified_ms)));
+    Ok(scored.into_iter().map(|(_, path)| path).collect())
+}
+
+// A path ("~/Documents/rep") is completed in its directory; anything else ("q3 report") is looked up
+// by name under every granted root
+pub fn complete_path(partial: &str, limit: usize) -> Result<Vec<PathMatch>, String> {
+    let partial = partial.trim();
+    let mut matches = if partial.starts_with(['/', '~', '.']) || partial.contains(std::path::MAIN_SEPARATOR) {
+        complete_in_dir(partial)?
+    } else {
+        let words: Vec<String> = partial.split_whitespace().map(str::to_lowercase).collect();
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+        search_roots(&words)?
+    };
+    matches.truncate(limit);
+    Ok(matches)
+}
+
+fn format_time(ms: u64) -> String {
+    let minutes = (ms % DAY_MS) / 60_000;
+    format!("{} {:02}:{:02}", format_day((ms / DAY_MS) as i64), minutes / 60, minutes % 60)
+}
+
+fn mentions(message: &str, hints: &[&str]) -> bool {
+    let lower = message.to_lowercase();
+    hints.iter().any(|hint| lower.contains(hint))
+}
+
+// Adds calendar or file listings to questions that look like they need them, for granted tools only
+pub fn augment(message: &str) -> String {
+    let tools = config::get().tools;
+    let mut context = String::new();
+
+    if tools.calendar_enabled && mentions(message, &CALENDAR_HINTS) {
+        let today = now_ms() / DAY_MS * DAY_MS;
+        if let Ok(events) = calendar_events(today, today + 7 * DAY_MS) {
+            context.push_str(&format!("Calendar events (today is {}, times in UTC):\n", format_day((today / DAY_MS) as i64)));
+            if events.is_empty() {
+                context.push_str("- none in the next 7 days\n");
+            }
+            for event in events {
+                let when = if event.all_day { format_day((event.start_ms / DAY_MS) as i64) + " (all day)" } else { format_time(event.start_ms) };
+                let location = event.location.map(|l| format!(" @ {}", l)).unwrap_or_default();
+                context.push_str(&format!("- {}: {}{}\n", when, event.summary, location));
+            }
+            context.push('\n');
+        }
+    }
+
+    if tools.file_metadata_enabled && mentions(message, &FILE_HINTS) {
+        for root in &tools.file_roots {
+            let Ok(files) = file_metadata(root, "size", None, 10) else {
+                continue;
+            };
+            context.push_str(&format!("Largest files in {}:\n", root));
+            for file in files {
+                context.push_str(&format!("- {} ({} bytes, modified {})\n", file.path, file.size, file.modified_ms.map(format_time).unwrap_or_default()));
+            }
+            context.push('\n');
+        }
+    }
+
+    if tools.file_metadata_enabled && mentions(message, &PATH_HINTS) {
+        // Words long enough to be part of a file name, e.g. "report" from "summarize my latest report"
+        let words: Vec<String> = message
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.len() >= 4)
+            .map(str::to_lowercase)
+            .filter(|word| !NOT_NAME_WORDS.contains(&word.as_str()))
+            .collect();
+        let candidates = search_roots(&words).unwrap_or_default();
+        if !candidates.is_empty() {
+            context.push_str(
+                "Files whose names match the question, newest first. If the user means one of them, name its path and \
+                 ask them to confirm it before relying on it; never make up a path:\n",
+            );
+            for candidate in candidates.into_iter().take(PATH_CANDIDATES) {
+                context.push_str(&format!("- {} (modified {})\n", candidate.path, candidate.modified_ms.map(format_time).unwrap_or_default()));
+            }
+            context.push('\n');
+        }
+    }
+
+    if context.is_empty() {
+        message.to_string()
+    } else {
+        format!("Local data the user has allowed you to read:\n\n{}Question:\n{}", context, message)
+    }
+}
+
+#[tauri::command]
+pub fn list_calendar_events(from_ms: Option<u64>, to_ms: Option<u64>) -> Result<Vec<CalendarEvent>, String> {
+    let from_ms = from_ms.unwrap_or_else(|| now_ms() / DAY_MS * DAY_MS);
+    calendar_events(from_ms, to_ms.unwrap_or(from_ms + 7 * DAY_MS))
+}
+
+#[tauri::command]
+pub fn query_file_metadata(
+    root: String,
+    sort_by: Option<String>,
+    extension: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<FileMetadata>, String> {
+    file_metadata(&root, sort_by.as_deref().unwrap_or("size"), extension.as_deref(), limit.unwrap_or(DEFAULT_FILE_LIMIT))
+}
+
+// Completes a partial path or file name under the granted roots, for the user to confirm a file
+// the model suggested
+#[tauri::command]
+pub fn complete_file_path(partial: String, limit: Option<usize>) -> Result<Vec<PathMatch>, String> {
+    complete_path(&partial, limit.unwrap_or(DEFAULT_FILE_LIMIT))
+}
+
+// Grants or revokes a tool; paths are optional and replace the tool's current list when given
+#[tauri::command]
+pub fn set_tool_permission(tool: String, enabled: bool, paths: Option<Vec<String>>, app: tauri::AppHandle) -> Result<ToolsConfig, String> {
+    if !matches!(tool.as_str(), "calendar" | "file_metadata") {
+        return Err(format!("Unknown tool \"{}\"; expected \"calendar\" or \"file_metadata\"", tool));
+    }
+    let config = config::update(&app, |config| {
+        let (flag, list) = match tool.as_str() {
+            "calendar" => (&mut config.tools.calendar_enabled, &mut config.tools.calendar_files),
+            _ => (&mut config.tools.file_metadata_enabled, &mut config.tools.file_roots),
+        };
+        *flag = enabled;
+        if let Some(paths) = paths {
+            *list = paths;
+        }
+    })?;
+    Ok(config.tools)
+}
@@ -0,0 +1,154 @@
+// End-to-end trace of one operation. Chats, model loads and catalog downloads each run under a
+// request id that prefixes their log lines, rides along in their events and errors, and is stored
+// with the history rows they produce. The log lines are also kept in memory for a while, so
+// get_request_trace can put everything about a failing request in one place.
+
+use rusqlite::params;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::Mutex;
+
+use crate::history::{db_err, with_db};
+use crate::streaming::{self, now_ms, StreamEvent};
+
+// Traces older than this are dropped, as are the oldest ones beyond MAX_TRACES
+const TRACE_TTL_MS: u64 = 60 * 60 * 1000;
+const MAX_TRACES: usize = 200;
+
+// Later lines of a runaway trace are dropped rather than growing it without bound
+const MAX_LINES_PER_TRACE: usize = 500;
+
+static TRACES: Mutex<Option<HashMap<String, Trace>>> = Mutex::new(None);
+
+struct Trace {
+    kind: &'static str,
+    started_at: u64,
+    lines: Vec<TraceLine>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceLine {
+    pub at_ms: u64,
+    pub message: String,
+    pub error: bool,
+}
+
+// A history message written by the request
+#[derive(Debug, Clone, Serialize)]
+pub struct TracedMessage {
+    pub message_id: String,
+    pub conversation_id: String,
+    pub role: String,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestTrace {
+    pub request_id: String,
+    // "chat", "load" or "download"; None once the in-memory trace has expired
+    pub kind: Option<String>,
+    pub started_at: Option<u64>,
+    pub lines: Vec<TraceLine>,
+    // Stream events, from the resume buffer or the replay recording
+    pub events: Vec<StreamEvent>,
+    pub messages: Vec<TracedMessage>,
+}
+
+// Starts the trace of a new operation
+pub fn begin(request_id: &str, kind: &'static str) {
+    let now = now_ms();
+    let mut traces = TRACES.lock().unwrap();
+    let traces = traces.get_or_insert_with(HashMap::new);
+    traces.retain(|_, trace| now.saturating_sub(trace.started_at) < TRACE_TTL_MS);
+    while traces.len() >= MAX_TRACES {
+        let Some(oldest) = traces.iter().min_by_key(|(_, trace)| trace.started_at).map(|(id, _)| id.clone()) else {
+            break;
+        };
+        traces.remove(&oldest);
+    }
+    traces.entry(request_id.to_string()).or_insert(Trace { kind, started_at: now, lines: Vec::new() });
+}
+
+fn record(request_id: &str, message: String, error: bool) {
+    if let Some(trace) = TRACES.lock().unwrap().as_mut().and_then(|traces| traces.get_mut(request_id)) {
+        if trace.lines.len() < MAX_LINES_PER_TRACE {
+            trace.lines.push(TraceLine { at_ms: now_ms(), message, error });
+        }
+    }
+}
+
+// Prints a log line tagged with the request id and adds it to the request's trace
+pub fn log(request_id: &str, message: impl Display) {
+    let message = message.to_string();
+    println!("[{}] {}", request_id, message);
+    record(request_id, message, false);
+}
+
+// Records an operation's error and returns it tagged with the request id, for the caller to return
+pub fn fail(request_id: &str, error: &str) -> String {
+    println!("[{}] Error: {}", request_id, error);
+    record(request_id, error.to_string(), true);
+    format!("{} (request {})", error, request_id)
+}
+
+// The log lines still kept for a request, oldest first
+pub fn lines(request_id: &str) -> Vec<TraceLine> {
+    TRACES
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|traces| traces.get(request_id))
+        .map(|trace| trace.lines.clone())
+        .unwrap_or_default()
+}
+
+// Everything known about one request: its log lines, stream events and the messages it produced
+#[tauri::command]
+pub fn get_request_trace(request_id: String) -> Result<RequestTrace, String> {
+    let (kind, started_at, lines) = match TRACES.lock().unwrap().as_ref().and_then(|traces| traces.get(&request_id)) {
+        Some(trace) => (Some(trace.kind.to_string()), Some(trace.started_at), trace.lines.clone()),
+        None => (None, None, Vec::new()),
+    };
+
+    let (messages, recorded_events) = with_db(|conn| {
+        let mut stmt = conn
+            .prepare("SELECT id, conversation_id, role, created_at FROM messages WHERE request_id = ?1 ORDER BY created_at")
+            .map_err(db_err)?;
+        let messages = stmt
+            .query_map(params![request_id], |row| {
+                Ok(TracedMessage {
+                    message_id: row.get(0)?,
+                    conversation_id: row.get(1)?,
+                    role: row.get(2)?,
+                    created_at: row.get(3)?,
+                })
+            })
+            .map_err(db_err)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db_err)?;
+        let mut stmt = conn
+            .prepare("SELECT event FROM stream_recordings WHERE request_id = ?1 ORDER BY seq")
+            .map_err(db_err)?;
+        let events = stmt
+            .query_map(params![request_id], |row| row.get::<_, String>(0))
+            .map_err(db_err)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db_err)?;
+        Ok((messages, events))
+    })?;
+
+    let mut events = streaming::buffered_events(&request_id);
+    if events.is_empty() {
+        events = recorded_events.iter().filter_map(|json| serde_json::from_str(json).ok()).collect();
+    }
+
+    if kind.is_none() && events.is_empty() && messages.is_empty() {
+        return Err(format!(
+            "Nothing is known about request {}; traces are kept for {} minutes",
+            request_id,
+            TRACE_TTL_MS / 60_000
+        ));
+    }
+    Ok(RequestTrace { request_id, kind, started_at, lines, events, messages })
+}
@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use crate::dates::format_day;
+use crate::streaming::{now_ms, StreamEventKind};
+use crate::{config, kiosk};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    // Readable log: a header line per request followed by the streamed text
+    #[default]
+    Text,
+    // One JSON object per prompt, chunk and end-of-stream event, each with a timestamp
+    Jsonl,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TranscriptTarget {
+    pub path: String,
+    #[serde(default)]
+    pub format: TranscriptFormat,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TranscriptConfig {
+    // Conversation id -> file its streamed output is appended to
+    pub conversations: HashMap<String, TranscriptTarget>,
+}
+
+impl TranscriptConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (conversation_id, target) in &self.conversations {
+            if !Path::new(&target.path).is_absolute() {
+                errors.push(format!(
+                    "transcripts.conversations.{}: path \"{}\" must be absolute",
+                    conversation_id, target.path
+                ));
+            }
+        }
+        errors
+    }
+}
+
+#[derive(Serialize)]
+struct TranscriptRecord<'a> {
+    timestamp_ms: u64,
+    conversation_id: &'a str,
+    request_id: &'a str,
+    model_id: &'a str,
+    kind: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<&'a str>,
+}
+
+// Append-only tee of one request's stream; every write goes straight to the file
+pub struct Transcript {
+    file: File,
+    format: TranscriptFormat,
+    conversation_id: String,
+    request_id: String,
+    model_id: String,
+}
+
+// "YYYY-MM-DD HH:MM:SSZ" for text transcript headers
+fn format_timestamp(ms: u64) -> String {
+    let secs = ms / 1000;
+    format!(
+        "{} {:02}:{:02}:{:02}Z",
+        format_day((secs / 86_400) as i64),
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+// Opens the conversation's transcript if one is configured and records the prompt
+pub fn open(conversation_id: &str, request_id: &str, model_id: &str, prompt: &str) -> Option<Transcript> {
+    let target = config::get().transcripts.conversations.get(conversation_id).cloned()?;
+    let file = match OpenOptions::new().create(true).append(true).open(&target.path) {
+        Ok(file) => file,
+        Err(e) => {
+            println!("Warning: Failed to open transcript {}: {}", target.path, e);
+            return None;
+        }
+    };
+
+    let transcript = Transcript {
+        file,
+        format: target.format,
+        conversation_id: conversation_id.to_string(),
+        request_id: request_id.to_string(),
+        model_id: model_id.to_string(),
+    };
+    match transcript.format {
+        TranscriptFormat::Text => transcript.write(&format!(
+            "\n--- {} | {} | {} ---\n> {}\n\n",
+            format_timestamp(now_ms()),
+            model_id,
+            request_id,
+            prompt.replace('\n', "\n> ")
+        )),
+        TranscriptFormat::Jsonl => transcript.record("prompt", Some(prompt)),
+    }
+    Some(transcript)
+}
+
+impl Transcript {
+    fn write(&self, text: &str) {
+        if let Err(e) = (&self.file).write_all(text.as_bytes()) {
+            println!("Warning: Failed to write transcript for {}: {}", self.conversation_id, e);
+        }
+    }
+
+    fn record(&self, kind: &str, content: Option<&str>) {
+        let record = TranscriptRecord {
+            timestamp_ms: now_ms(),
+            conversation_id: &self.conversation_id,
+            request_id: &self.request_id,
+            model_id: &self.model_id,
+            kind,
+            content,
+        };
+        match serde_json::to_string(&record) {
+            Ok(line) => self.write(&format!("{}\n", line)),
+            Err(e) => println!("Warning: Failed to serialize transcript record: {}", e),
+        }
+    }
+
+    // Raw model output, before sanitizing or accessibility formatting
+    pub fn chunk(&self, text: &str) {
+        match self.format {
+            TranscriptFormat::Text => self.write(text),
+            TranscriptFormat::Jsonl => self.record("chunk", Some(text)),
+        }
+    }
+
+    pub fn end(&self, kind: StreamEventKind, detail: Option<&str>) {
+        let label = match kind {
+            StreamEventKind::Done => "done",
+            StreamEventKind::Cancelled => "cancelled",
+            _ => "error",
+        };
+        match self.format {
+            TranscriptFormat::Text => match (kind, detail) {
+                (StreamEventKind::Done, _) => self.write("\n"),
+                (_, Some(detail)) => self.write(&format!("\n[{}: {}]\n", label, detail)),
+                (_, None) => self.write(&format!("\n[{}]\n", label)),
+            },
+            TranscriptFormat::Jsonl => self.record(label, detail),
+        }
+    }
+}
+
+// Starts (path set) or stops (path None) teeing a conversation's streamed output to a file
+#[tauri::command]
+pub fn set_conversation_transcript(
+    conversation_id: String,
+    path: Option<String>,
+    format: Option<TranscriptFormat>,
+    app: tauri::AppHandle,
+) -> Result<Option<TranscriptTarget>, String> {
+    kiosk::ensure_unlocked("Transcripts")?;
+    let target = path.filter(|p| !p.trim().is_empty()).map(|path| TranscriptTarget {
+        path,
+        format: format.unwrap_or_default(),
+    });
+    config::update(&app, |config| match &target {
+        Some(target) => {
+            config.transcripts.conversations.insert(conversation_id.clone(), target.clone());
+        }
+        None => {
+            config.transcripts.conversations.remove(&conversation_id);
+        }
+    })?;
+    Ok(target)
+}
@@ -0,0 +1,143 @@
+// Decoded images kept per model, keyed by a hash of the uploaded data (or of an image file's path and
+// timestamp), so follow-up questions about the same image (OCR, then "what does the second line
+// mean?") skip the base64 decode and image parsing. mistral.rs doesn't expose the vision encoder's
+// output, so that part still runs.
+
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Instant, UNIX_EPOCH};
+
+use crate::config;
+use crate::image_input;
+use crate::provenance::fnv1a;
+
+static CACHE: Mutex<Option<ImageCache>> = Mutex::new(None);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VisionCacheConfig {
+    // Decoded images kept across all models; the least recently used is dropped first. 0 disables
+    pub max_images: usize,
+}
+
+impl Default for VisionCacheConfig {
+    fn default() -> Self {
+        Self { max_images: 8 }
+    }
+}
+
+struct CachedImage {
+    image: DynamicImage,
+    last_used: Instant,
+}
+
+#[derive(Default)]
+struct ImageCache {
+    // (model id, image hash) -> decoded image
+    images: HashMap<(String, String), CachedImage>,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VisionCacheStats {
+    pub images: usize,
+    pub max_images: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+fn decode(image_base64: &str) -> Result<DynamicImage, String> {
+    use base64::Engine;
+    let image_bytes = base64::engine::general_purpose::STANDARD
+        .decode(image_base64)
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    image_input::decode_bytes(&image_bytes)
+}
+
+// The decoded image for a request, reusing the one decoded for an earlier request to the same model
+pub fn image_for(model_id: &str, image_base64: &str) -> Result<DynamicImage, String> {
+    cached(model_id, fnv1a(image_base64.as_bytes()), || decode(image_base64))
+}
+
+// Same for an image file, keyed by its path, size and modification time so an edited file is read again
+pub fn file_image_for(model_id: &str, path: &Path) -> Result<DynamicImage, String> {
+    let meta = std::fs::metadata(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_millis());
+    let key = fnv1a(format!("{}:{}:{}", path.display(), meta.len(), modified).as_bytes());
+    cached(model_id, key, || {
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        image_input::decode_bytes(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+    })
+}
+
+fn cached(model_id: &str, hash: String, load: impl FnOnce() -> Result<DynamicImage, String>) -> Result<DynamicImage, String> {
+    let max_images = config::get().vision_cache.max_images;
+    if max_images == 0 {
+        return load().map(image_input::downscale);
+    }
+
+    // Images are cached downscaled, so a new size limit must not reuse them
+    let key = (model_id.to_string(), format!("{}@{}", hash, config::get().image_input.max_dimension));
+    {
+        let mut cache = CACHE.lock().unwrap();
+        let cache = cache.get_or_insert_with(ImageCache::default);
+        if let Some(cached) = cache.images.get_mut(&key) {
+            cached.last_used = Instant::now();
+            let image = cached.image.clone();
+            cache.hits += 1;
+            println!("Reusing the decoded image {} for {}", key.1, model_id);
+            return Ok(image);
+        }
+        cache.misses += 1;
+    }
+
+    // Decoding happens outside the lock so other requests aren't held up by a large image
+    let image = image_input::downscale(load()?);
+    let mut cache = CACHE.lock().unwrap();
+    let cache = cache.get_or_insert_with(ImageCache::default);
+    while cache.images.len() >= max_images {
+        let Some(oldest) = cache.images.iter().min_by_key(|(_, cached)| cached.last_used).map(|(key, _)| key.clone()) else {
+            break;
+        };
+        cache.images.remove(&oldest);
+    }
+    cache.images.insert(key, CachedImage { image: image.clone(), last_used: Instant::now() });
+    Ok(image)
+}
+
+// Drops a model's images once it is unloaded
+pub fn forget_model(model_id: &str) {
+    if let Some(cache) = CACHE.lock().unwrap().as_mut() {
+        cache.images.retain(|(cached_model, _), _| cached_model != model_id);
+    }
+}
+
+#[tauri::command]
+pub fn get_vision_cache_stats() -> VisionCacheStats {
+    let max_images = config::get().vision_cache.max_images;
+    match CACHE.lock().unwrap().as_ref() {
+        Some(cache) => VisionCacheStats { images: cache.images.len(), max_images, hits: cache.hits, misses: cache.misses },
+        None => VisionCacheStats { images: 0, max_images, hits: 0, misses: 0 },
+    }
+}
+
+// Clears one model's images, or every model's when model_id is None
+#[tauri::command]
+pub fn clear_vision_cache(model_id: Option<String>) {
+    match model_id {
+        Some(model_id) => forget_model(&model_id),
+        None => {
+            if let Some(cache) = CACHE.lock().unwrap().as_mut() {
+                cache.images.clear();
+            }
+        }
+    }
+}
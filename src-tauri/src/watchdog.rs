@@ -0,0 +1,113 @@
+// Per-request watchdog on token progress. A generation that produces nothing for too long gets a
+// "stalled" stream event instead of hanging silently, and is cancelled or restarted if configured.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::config;
+
+// How often a waiting stream is checked for a stall
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StallAction {
+    // Only report the stall and keep waiting; the frontend can cancel_generation
+    #[default]
+    Notify,
+    // Stop the request, keeping or discarding its partial answer like a cancel
+    Cancel,
+    // Drop the stalled attempt and generate the answer again, up to max_restarts times
+    Restart,
+}
+
+impl StallAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StallAction::Notify => "notify",
+            StallAction::Cancel => "cancel",
+            StallAction::Restart => "restart",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatchdogConfig {
+    // Seconds without a new token, once generation has started, before a request counts as stalled; 0 disables
+    pub stall_after_secs: u64,
+    // Seconds the first token may take after the prompt is sent; prefill of long prompts is slow; 0 disables
+    pub first_token_stall_secs: u64,
+    pub on_stall: StallAction,
+    // Restarts per request before a stall cancels it instead
+    pub max_restarts: u32,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            stall_after_secs: 60,
+            first_token_stall_secs: 300,
+            on_stall: StallAction::default(),
+            max_restarts: 1,
+        }
+    }
+}
+
+impl WatchdogConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.stall_after_secs > 0 && self.first_token_stall_secs > 0 && self.first_token_stall_secs < self.stall_after_secs {
+            errors.push("watchdog.first_token_stall_secs must be 0 or at least stall_after_secs".to_string());
+        }
+        errors
+    }
+}
+
+// Time since a stream last made progress, checked against the configured limits
+pub struct Watchdog {
+    settings: WatchdogConfig,
+    last_progress: Instant,
+    // When the current stall was last reported, so it is reported once per interval
+    reported_at: Option<Instant>,
+}
+
+impl Watchdog {
+    pub fn start() -> Self {
+        Self {
+            settings: config::get().watchdog,
+            last_progress: Instant::now(),
+            reported_at: None,
+        }
+    }
+
+    // Any response from the engine counts as progress
+    pub fn progress(&mut self) {
+        self.last_progress = Instant::now();
+        self.reported_at = None;
+    }
+
+    fn limit(&self, started: bool) -> Option<Duration> {
+        let secs = if started { self.settings.stall_after_secs } else { self.settings.first_token_stall_secs };
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+
+    // The configured action once the stream has been idle past its limit; a stall that goes on is
+    // reported again after every further limit
+    pub fn check(&mut self, started: bool) -> Option<(StallAction, Duration)> {
+        let limit = self.limit(started)?;
+        let idle = self.last_progress.elapsed();
+        let since_report = self.reported_at.map_or(idle, |at| at.elapsed());
+        if idle < limit || since_report < limit {
+            return None;
+        }
+        self.reported_at = Some(Instant::now());
+        Some((self.settings.on_stall, idle))
+    }
+}
+
+// Whether a stalled request gets another attempt, after `restarts` so far
+pub fn should_restart(restarts: u32) -> bool {
+    let settings = config::get().watchdog;
+    settings.on_stall == StallAction::Restart && restarts < settings.max_restarts
+}
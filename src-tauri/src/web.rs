@@ -0,0 +1,291 @@
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{config, kiosk};
+
+// Elements whose content is never part of the readable page
+const BOILERPLATE_TAGS: [&str; 10] = [
+    "script", "style", "noscript", "svg", "nav", "header", "footer", "aside", "form", "iframe",
+];
+
+// Tags that start a new line of text when flattened
+const BLOCK_TAGS: [&str; 18] = [
+    "p", "div", "br", "li", "ul", "ol", "tr", "table", "section", "article", "blockquote", "pre", "h1", "h2", "h3", "h4",
+    "h5", "h6",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebConfig {
+    // Hosts fetch_url may contact; subdomains of a listed domain are allowed too
+    pub allowed_domains: Vec<String>,
+    pub max_bytes: usize,
+    pub timeout_secs: u64,
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            allowed_domains: vec![
+                "wikipedia.org".to_string(),
+                "docs.rs".to_string(),
+                "doc.rust-lang.org".to_string(),
+                "developer.mozilla.org".to_string(),
+            ],
+            max_bytes: 2 * 1024 * 1024,
+            timeout_secs: 15,
+        }
+    }
+}
+
+impl WebConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.max_bytes == 0 {
+            errors.push("web.max_bytes must be greater than 0".to_string());
+        }
+        if self.timeout_secs == 0 {
+            errors.push("web.timeout_secs must be greater than 0".to_string());
+        }
+        errors
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FetchedPage {
+    pub url: String,
+    pub title: Option<String>,
+    pub text: String,
+    // True when the body was cut off at web.max_bytes
+    pub truncated: bool,
+}
+
+fn is_allowed(url: &Url, allowed_domains: &[String]) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let host = host.to_ascii_lowercase();
+    allowed_domains.iter().any(|domain| {
+        let domain = domain.trim().trim_start_matches('.').to_ascii_lowercase();
+        host == domain || host.ends_with(&format!(".{}", domain))
+    })
+}
+
+// Removes <tag ...>...</tag> spans, matching tag names case-insensitively
+fn remove_elements(html: &str, tag: &str) -> String {
+    let lower = html.to_ascii_lowercase();
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut output = String::with_capacity(html.len());
+    let mut pos = 0;
+
+    while let Some(start) = lower[pos..].find(&open).map(|i| pos + i) {
+        // Skip tags that merely share a prefix, e.g. <header> while removing <head>
+        let next = lower[start + open.len()..].chars().next();
+        if !matches!(next, Some('>') | Some(' ') | Some('\n') | Some('\t') | Some('/')) {
+            output.push_str(&html[pos..start + open.len()]);
+            pos = start + open.len();
+            continue;
+        }
+        output.push_str(&html[pos..start]);
+        pos = match lower[start..].find(&close) {
+            Some(end) => start + end + close.len(),
+            None => html.len(),
+        };
+    }
+    output.push_str(&html[pos..]);
+    output
+}
+
+// Inner HTML of the first <tag>...</tag>, if present
+fn element_content<'a>(html: &'a str, tag: &str) -> Option<&'a str> {
+    let lower = html.to_ascii_lowercase();
+    let start = lower.find(&format!("<{}", tag))?;
+    let content_start = start + lower[start..].find('>')? + 1;
+    let end = lower[content_start..].find(&format!("</{}>", tag))? + content_start;
+    Some(&html[content_start..end])
+}
+
+pub fn decode_entities(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(amp) = rest.find('&') {
+        output.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        // Bytes, not a str slice: byte 10 can fall inside a multi-byte character
+        let Some(semi) = rest.bytes().take(10).position(|b| b == b';') else {
+            output.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" | "#39" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                output.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+// Flattens markup to text, turning block-level tags into line breaks
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len() / 2);
+    let mut chars = html.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if c != '<' {
+            text.push(c);
+            continue;
+        }
+        // An unclosed tag at the end is dropped rather than shown as text
+        let Some(end) = html[i..].find('>').map(|e| i + e) else {
+            break;
+        };
+        let name: String = html[i + 1..end]
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        text.push(if BLOCK_TAGS.contains(&name.as_str()) { '\n' } else { ' ' });
+        while chars.peek().is_some_and(|(j, _)| *j <= end) {
+            chars.next();
+        }
+    }
+
+    decode_entities(&text)
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Readability-style extraction: drop boilerplate, prefer <article>/<main>, then flatten
+pub fn extract_readable(html: &str) -> (Option<String>, String) {
+    let title = element_content(html, "title").map(|t| decode_entities(t.trim())).filter(|t| !t.is_empty());
+
+    let mut cleaned = html.to_string();
+    for tag in BOILERPLATE_TAGS {
+        cleaned = remove_elements(&cleaned, tag);
+    }
+    let main = element_content(&cleaned, "article")
+        .or_else(|| element_content(&cleaned, "main"))
+        .or_else(|| element_content(&cleaned, "body"))
+        .unwrap_or(&cleaned);
+
+    (title, html_to_text(main))
+}
+
+// Response body as downloaded, before any extraction
+pub struct RawBody {
+    pub url: String,
+    pub is_html: bool,
+    pub body: String,
+    pub truncated: bool,
+}
+
+// Downloads from an allowlisted domain, enforcing the size cap and timeout
+pub async fn fetch_raw(url: &str) -> Result<RawBody, String> {
+    let web = config::get().web;
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid URL {}: {}", url, e))?;
+    if !is_allowed(&parsed, &web.allowed_domains) {
+        return Err(format!(
+            "{} is not in web.allowed_domains; add its domain to config.toml to allow fetching it",
+            parsed.host_str().unwrap_or(url)
+        ));
+    }
+
+    // Redirects must stay on allowlisted domains too
+    let allowed = web.allowed_domains.clone();
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(web.timeout_secs))
+        .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= 5 || !is_allowed(attempt.url(), &allowed) {
+                attempt.stop()
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut response = client
+        .get(parsed)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("Failed to fetch {}: HTTP {}", url, response.status()));
+    }
+    let final_url = response.url().to_string();
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.contains("html"))
+        .unwrap_or(true);
+
+    // Read incrementally so an oversized page never sits fully in memory
+    let mut body = Vec::new();
+    let mut truncated = false;
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        let room = web.max_bytes.saturating_sub(body.len());
+        if chunk.len() > room {
+            body.extend_from_slice(&chunk[..room]);
+            truncated = true;
+            break;
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(RawBody {
+        url: final_url,
+        is_html,
+        body: String::from_utf8_lossy(&body).to_string(),
+        truncated,
+    })
+}
+
+// Downloads a page from an allowlisted domain and returns its readable text
+pub async fn fetch_page(url: &str) -> Result<FetchedPage, String> {
+    let raw = fetch_raw(url).await?;
+    let (title, text) = if raw.is_html { extract_readable(&raw.body) } else { (None, raw.body) };
+    Ok(FetchedPage {
+        url: raw.url,
+        title,
+        text,
+        truncated: raw.truncated,
+    })
+}
+
+#[tauri::command]
+pub async fn fetch_url(url: String) -> Result<FetchedPage, String> {
+    kiosk::ensure_unlocked("Fetching web pages")?;
+    fetch_page(&url).await
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tauri::{WebviewUrl, WebviewWindowBuilder};
+
+// Label prefix for secondary chat windows; matched by the capability file
+const CHAT_WINDOW_PREFIX: &str = "chat-";
+
+static WINDOW_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+// Which conversation each window is bound to, keyed by window label
+static WINDOW_CONVERSATIONS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+fn bindings() -> &'static Mutex<HashMap<String, String>> {
+    WINDOW_CONVERSATIONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Drops the binding when a window is closed
+pub fn unbind(label: &str) {
+    bindings().lock().unwrap().remove(label);
+}
+
+// Opens another chat window; models and caches are shared since all windows talk to the same backend
+#[tauri::command]
+pub async fn new_chat_window(conversation_id: Option<String>, app: tauri::AppHandle) -> Result<String, String> {
+    let label = format!("{}{}", CHAT_WINDOW_PREFIX, WINDOW_COUNTER.fetch_add(1, Ordering::Relaxed));
+
+    WebviewWindowBuilder::new(&app, &label, WebviewUrl::default())
+        .title("tauri-mistral-chat")
+        .inner_size(1280.0, 800.0)
+        .build()
+        .map_err(|e| format!("Failed to create chat window: {}", e))?;
+
+    if let Some(conversation_id) = conversation_id {
+        bindings().lock().unwrap().insert(label.clone(), conversation_id);
+    }
+
+    println!("Opened chat window: {}", label);
+    Ok(label)
+}
+
+// Binds the calling window to a conversation
+#[tauri::command]
+pub fn bind_window_conversation(conversation_id: String, window: tauri::Window) {
+    bindings().lock().unwrap().insert(window.label().to_string(), conversation_id);
+}
+
+// Lets a freshly opened window find out which conversation it should show
+#[tauri::command]
+pub fn get_window_conversation(window: tauri::Window) -> Option<String> {
+    bindings().lock().unwrap().get(window.label()).cloned()
+}
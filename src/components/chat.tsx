@@ -36,6 +36,47 @@ interface ModelInfo {
   is_vision: boolean;
 }
 
+// Error returned by the chat and model commands
+interface ModelError {
+  code: string;
+  message: string;
+  hint?: string | null;
+  request_id?: string | null;
+}
+
+function describeError(error: unknown): string {
+  if (error && typeof error === "object" && "message" in error) {
+    const { message, hint } = error as ModelError;
+    return hint ? `${message}\n\n${hint}` : message;
+  }
+  return String(error);
+}
+
+// Plain-language explanation of a ModelError from explain_error
+interface Explanation {
+  explanation: string;
+  suggestions: string[];
+  model_id?: string | null;
+}
+
+async function explainError(error: unknown): Promise<string | null> {
+  if (!error || typeof error !== "object" || !("code" in error)) {
+    return null;
+  }
+  const modelError = error as ModelError;
+  try {
+    const { explanation, suggestions } = await invoke<Explanation>("explain_error", {
+      errorCode: modelError.code,
+      context: modelError,
+    });
+    const fixes = suggestions.map((fix) => `- ${fix}`).join("\n");
+    return fixes ? `${explanation}\n\n${fixes}` : explanation;
+  } catch (explainFailure) {
+    console.error("Failed to explain error:", explainFailure);
+    return null;
+  }
+}
+
 // Custom component to show file attachments
 function FileAttachmentIndicator() {
   const { requestData } = useChatUI();
@@ -361,8 +402,15 @@ export function ChatSection() {
       // Add error message using original append
       await originalAppend({
         role: "assistant",
-        content: `Error: ${error}`,
+        content: `Error: ${describeError(error)}`,
       });
+      const explanation = await explainError(error);
+      if (explanation) {
+        await originalAppend({
+          role: "assistant",
+          content: `What went wrong: ${explanation}`,
+        });
+      }
     }
 
     return result;